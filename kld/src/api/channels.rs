@@ -3,16 +3,22 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use super::payloads::{
-    ChannelFee, FundChannel, FundChannelResponse, SetChannelFee, SetChannelFeeResponse,
+    ChannelFee, ChannelProfitability, CloseChannel, FeeHistoryQuery, FeeUpdate, ForwardingPeriod,
+    ForwardingReport, ForwardingReportQuery, FundChannel, FundChannelResponse, PageParams,
+    RebalanceChannel, RebalanceResponse, SetChannelFee, SetChannelFeeResponse,
 };
 use crate::api::SocketAddress;
-use crate::database::{forward::ForwardStatus, ChannelRecord};
+use crate::database::{
+    forward::{ForwardStatus, TimeBucket},
+    ChannelRecord,
+};
 use crate::ldk::htlc_destination_to_string;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use axum::extract::Path;
 use axum::extract::Query;
 use axum::{response::IntoResponse, Extension, Json};
 use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Address, OutPoint, Txid};
 use lightning::events::HTLCDestination;
 use lightning::ln::channelmanager::ChannelDetails;
 use lightning::ln::features::ChannelTypeFeatures;
@@ -22,8 +28,11 @@ use lightning::util::config::MaxDustHTLCExposure;
 use crate::api::bad_request;
 use crate::ldk::LightningInterface;
 use crate::ldk::PeerStatus;
+use crate::ldk::{ChannelTarget, FundingOptions};
 use crate::to_string_empty;
 
+use super::macaroon_auth::AmountLimit;
+
 use super::codegen::get_kld_channel_response::GetKldChannelResponseItem;
 use super::codegen::get_v1_channel_history_response::GetV1ChannelHistoryResponseItem;
 use super::codegen::get_v1_channel_list_forwards_response::{
@@ -35,6 +44,8 @@ use super::codegen::get_v1_channel_list_peer_channels_response::{
 };
 use super::codegen::get_v1_channel_localremotebal_response::GetV1ChannelLocalremotebalResponse;
 use super::internal_server;
+use super::pagination;
+use super::time_range;
 use super::ApiError;
 
 pub(crate) async fn list_channels(
@@ -286,52 +297,106 @@ pub(crate) async fn list_peer_channels(
 
 pub(crate) async fn open_channel(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
     Json(fund_channel): Json<FundChannel>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (public_key, net_address) = match fund_channel.id.split_once('@') {
+    let mut targets = vec![
+        channel_target(
+            &lightning_interface,
+            &fund_channel.id,
+            &fund_channel.satoshis,
+            &fund_channel.push_msat,
+            fund_channel.announce,
+        )
+        .await?,
+    ];
+    for channel in &fund_channel.batch {
+        targets.push(
+            channel_target(
+                &lightning_interface,
+                &channel.id,
+                &channel.satoshis,
+                &channel.push_msat,
+                channel.announce,
+            )
+            .await?,
+        );
+    }
+    // Both the funding and the amount pushed to the peer leave the node.
+    limit.check(targets.iter().fold(0u64, |total, target| {
+        total
+            .saturating_add(target.value_sats.saturating_mul(1000))
+            .saturating_add(target.push_msat.unwrap_or_default())
+    }))?;
+    let utxos = fund_channel
+        .utxos
+        .iter()
+        .map(|utxo| OutPoint::from_str(utxo))
+        .collect::<Result<Vec<OutPoint>, _>>()
+        .map_err(bad_request)?;
+
+    let result = lightning_interface
+        .open_channels(
+            targets,
+            FundingOptions {
+                fee_rate: fund_channel.fee_rate,
+                utxos,
+                min_conf: fund_channel.min_conf,
+            },
+        )
+        .await
+        .map_err(internal_server)?;
+
+    let channel_ids: Vec<String> = result
+        .channel_ids
+        .iter()
+        .map(|id| hex::encode(id.0))
+        .collect();
+    let response = FundChannelResponse {
+        tx: result.transaction,
+        txid: result.txid.to_string(),
+        channel_id: channel_ids.first().cloned().unwrap_or_default(),
+        channel_ids,
+    };
+    Ok(Json(response))
+}
+
+async fn channel_target(
+    lightning_interface: &Arc<dyn LightningInterface + Send + Sync>,
+    id: &str,
+    satoshis: &str,
+    push_msat: &Option<String>,
+    announce: Option<bool>,
+) -> Result<ChannelTarget, ApiError> {
+    let (public_key, net_address) = match id.split_once('@') {
         Some((public_key, net_address)) => (
             PublicKey::from_str(public_key).map_err(bad_request)?,
             Some(net_address.parse::<SocketAddress>().map_err(bad_request)?),
         ),
-        None => (
-            PublicKey::from_str(&fund_channel.id).map_err(bad_request)?,
-            None,
-        ),
+        None => (PublicKey::from_str(id).map_err(bad_request)?, None),
     };
     lightning_interface
         .connect_peer(public_key, net_address)
         .await
         .map_err(internal_server)?;
 
-    let value = fund_channel.satoshis.parse::<u64>().map_err(bad_request)?;
-    let push_msat = fund_channel
-        .push_msat
+    let value = satoshis.parse::<u64>().map_err(bad_request)?;
+    let push_msat = push_msat
+        .as_ref()
         .map(|x| x.parse::<u64>())
         .transpose()
         .map_err(bad_request)?;
 
     let mut user_config = lightning_interface.user_config();
-    if let Some(announce) = fund_channel.announce {
+    if let Some(announce) = announce {
         user_config.channel_handshake_config.announced_channel = announce;
     }
-
-    let result = lightning_interface
-        .open_channel(
-            public_key,
-            value,
-            push_msat,
-            fund_channel.fee_rate,
-            Some(user_config),
-        )
-        .await
-        .map_err(internal_server)?;
-
-    let response = FundChannelResponse {
-        tx: result.transaction,
-        txid: result.txid.to_string(),
-        channel_id: hex::encode(result.channel_id.0),
-    };
-    Ok(Json(response))
+    Ok(ChannelTarget {
+        node_id: public_key,
+        value_sats: value,
+        push_msat,
+        override_config: Some(user_config),
+    })
 }
 
 pub(crate) async fn set_channel_fee(
@@ -390,38 +455,108 @@ pub(crate) async fn set_channel_fee(
     Ok(Json(SetChannelFeeResponse(updated_channels)))
 }
 
+pub(crate) async fn rebalance_channel(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
+    Json(rebalance): Json<RebalanceChannel>,
+) -> Result<impl IntoResponse, ApiError> {
+    if rebalance.amount == 0 {
+        return Err(bad_request(anyhow!("Amount must be greater than zero")));
+    }
+    let max_fee = rebalance
+        .max_fee_ppm
+        .map(|ppm| rebalance.amount.saturating_mul(ppm as u64) / 1_000_000);
+    limit.check_with_fee(rebalance.amount, max_fee)?;
+    let channels = lightning_interface.list_active_channels();
+    let find_channel = |id: &str| {
+        channels
+            .iter()
+            .find(|c| {
+                hex::encode(c.channel_id.0) == id
+                    || c.short_channel_id.unwrap_or_default().to_string() == id
+            })
+            .map(|c| c.channel_id)
+            .ok_or_else(|| ApiError::NotFound(id.to_string()))
+    };
+    let outbound_channel_id = find_channel(&rebalance.outbound_channel_id)?;
+    let inbound_channel_id = find_channel(&rebalance.inbound_channel_id)?;
+    let rebalance = lightning_interface
+        .rebalance(
+            &outbound_channel_id,
+            &inbound_channel_id,
+            rebalance.amount,
+            rebalance.max_fee_ppm,
+        )
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(RebalanceResponse {
+        id: rebalance.id.to_string(),
+        payment_id: hex::encode(rebalance.payment_id.0),
+        outbound_channel_id: hex::encode(rebalance.outbound_channel_id.0),
+        inbound_channel_id: hex::encode(rebalance.inbound_channel_id.0),
+        amount_msat: rebalance.amount,
+        fee_msat: rebalance.fee,
+        status: rebalance.status.to_string(),
+        created_at: rebalance.timestamp.unix_timestamp() as u64,
+    }))
+}
+
 pub(crate) async fn close_channel(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
     Path(channel_id): Path<String>,
+    Query(close): Query<CloseChannel>,
 ) -> Result<impl IntoResponse, ApiError> {
-    if let Some(channel) = lightning_interface.list_active_channels().iter().find(|c| {
-        hex::encode(c.channel_id.0) == channel_id
-            || c.short_channel_id.unwrap_or_default().to_string() == channel_id
-    }) {
-        lightning_interface
-            .close_channel(&channel.channel_id, &channel.counterparty.node_id, None)
-            .await
-            .map_err(internal_server)?;
-        Ok(Json(()))
-    } else {
-        Err(ApiError::NotFound(channel_id))
-    }
+    cooperative_close(lightning_interface, limit, channel_id, None, close).await
 }
 
 pub(crate) async fn close_channel_with_fee(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
-    Path(channel_id): Path<String>,
-    Path(fee_rate): Path<u32>,
+    Extension(limit): Extension<AmountLimit>,
+    Path((channel_id, fee_rate)): Path<(String, u32)>,
+    Query(close): Query<CloseChannel>,
 ) -> Result<impl IntoResponse, ApiError> {
+    cooperative_close(
+        lightning_interface,
+        limit,
+        channel_id,
+        Some(fee_rate),
+        close,
+    )
+    .await
+}
+
+async fn cooperative_close(
+    lightning_interface: Arc<dyn LightningInterface + Send + Sync>,
+    limit: AmountLimit,
+    channel_id: String,
+    fee_rate: Option<u32>,
+    close: CloseChannel,
+) -> Result<Json<()>, ApiError> {
+    let shutdown_script = close
+        .address
+        .map(|address| {
+            Address::from_str(&address)
+                .and_then(|a| a.require_network(lightning_interface.network()))
+                .map(|a| a.script_pubkey())
+        })
+        .transpose()
+        .map_err(bad_request)?;
     if let Some(channel) = lightning_interface.list_active_channels().iter().find(|c| {
         hex::encode(c.channel_id.0) == channel_id
             || c.short_channel_id.unwrap_or_default().to_string() == channel_id
     }) {
+        // Closing to an address sends our balance out of the node, like a payment would.
+        if shutdown_script.is_some() {
+            limit.check(channel.balance_msat)?;
+        }
         lightning_interface
             .close_channel(
                 &channel.channel_id,
                 &channel.counterparty.node_id,
-                Some(fee_rate),
+                fee_rate,
+                close.max_initial_fee_rate,
+                shutdown_script,
             )
             .await
             .map_err(internal_server)?;
@@ -501,6 +636,7 @@ pub struct ListForwardsQueryParams {
 pub(crate) async fn list_forwards(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListForwardsQueryParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     let status = match params.status {
         None => None,
@@ -510,7 +646,7 @@ pub(crate) async fn list_forwards(
     };
     let mut response = vec![];
     for forward in lightning_interface
-        .fetch_forwards(status)
+        .fetch_forwards(status, pagination(page)?)
         .await
         .map_err(internal_server)?
     {
@@ -558,11 +694,122 @@ pub(crate) async fn list_forwards(
     Ok(Json(response))
 }
 
+pub(crate) async fn forwarding_report(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(query): Query<ForwardingReportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let bucket = query
+        .bucket
+        .as_deref()
+        .map(TimeBucket::from_str)
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or(TimeBucket::Day);
+    let (from, to) = time_range(query.from, query.to)?;
+    let summaries = lightning_interface
+        .forward_summaries(bucket, from, to)
+        .await
+        .map_err(internal_server)?;
+    let records = lightning_interface
+        .list_channels()
+        .await
+        .map_err(internal_server)?;
+
+    // Channels opened in one batch share the fee of their funding transaction.
+    let mut batch_sizes: HashMap<Txid, u64> = HashMap::new();
+    for record in &records {
+        if let Some(detail) = record.detail.as_ref().filter(|d| d.is_outbound) {
+            if let Some(txo) = detail.funding_txo {
+                *batch_sizes.entry(txo.txid).or_default() += 1;
+            }
+        }
+    }
+    let mut channels: HashMap<ChannelId, ChannelProfitability> = HashMap::new();
+    for record in records {
+        let Some(detail) = record.detail else {
+            continue;
+        };
+        let mut channel = unknown_channel(&detail.channel_id);
+        channel.counterparty = Some(detail.counterparty.node_id.to_string());
+        channel.is_open = record.closure_reason.is_none();
+        if let Some(txo) = detail.funding_txo.filter(|_| detail.is_outbound) {
+            channel.open_cost_sat = lightning_interface
+                .funding_fee(&txo.txid)
+                .map_err(internal_server)?
+                .map(|fee| fee / batch_sizes[&txo.txid]);
+        }
+        channels.insert(detail.channel_id, channel);
+    }
+
+    let mut periods = vec![];
+    for summary in summaries {
+        let failed: u64 = summary.failures.values().sum();
+        let inbound = channels
+            .entry(summary.inbound_channel_id)
+            .or_insert_with(|| unknown_channel(&summary.inbound_channel_id));
+        inbound.volume_in_msat += summary.amount;
+        inbound.failed_in += failed;
+        if let Some(outbound_channel_id) = summary.outbound_channel_id {
+            let outbound = channels
+                .entry(outbound_channel_id)
+                .or_insert_with(|| unknown_channel(&outbound_channel_id));
+            outbound.volume_out_msat += summary.amount;
+            outbound.fees_earned_msat += summary.fee;
+            outbound.failed_out += failed;
+        }
+        periods.push(ForwardingPeriod {
+            period_start: summary.period_start.unix_timestamp() as u64,
+            in_channel: hex::encode(summary.inbound_channel_id.0),
+            out_channel: summary.outbound_channel_id.map(|id| hex::encode(id.0)),
+            forwards: summary.succeeded,
+            volume_msat: summary.amount,
+            fees_msat: summary.fee,
+            failures: summary.failures,
+        });
+    }
+
+    let mut channels: Vec<ChannelProfitability> = channels
+        .into_values()
+        .map(|mut channel| {
+            channel.profit_msat = channel.fees_earned_msat as i64
+                - channel.open_cost_sat.unwrap_or_default() as i64 * 1000;
+            channel
+        })
+        .collect();
+    channels.sort_by(|a, b| {
+        a.profit_msat
+            .cmp(&b.profit_msat)
+            .then_with(|| a.channel_id.cmp(&b.channel_id))
+    });
+    Ok(Json(ForwardingReport {
+        bucket: bucket.to_string(),
+        periods,
+        channels,
+    }))
+}
+
+// A channel that appears in the forwards but has no record in the channels table.
+fn unknown_channel(channel_id: &ChannelId) -> ChannelProfitability {
+    ChannelProfitability {
+        channel_id: hex::encode(channel_id.0),
+        counterparty: None,
+        is_open: false,
+        volume_in_msat: 0,
+        volume_out_msat: 0,
+        fees_earned_msat: 0,
+        failed_in: 0,
+        failed_out: 0,
+        open_cost_sat: None,
+        profit_msat: 0,
+    }
+}
+
 pub(crate) async fn channel_history(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     let channel_history = lightning_interface
-        .channel_history()
+        .channel_history(pagination(page)?)
         .await
         .map_err(internal_server)?;
 
@@ -599,6 +846,39 @@ pub(crate) async fn channel_history(
     Ok(Json(response))
 }
 
+pub(crate) async fn fee_history(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(query): Query<FeeHistoryQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let channel_id = query
+        .channel_id
+        .map(|id| {
+            hex::decode(&id)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(ChannelId::from_bytes)
+                .ok_or_else(|| bad_request(anyhow!("Invalid channel ID {id}")))
+        })
+        .transpose()?;
+    let response: Vec<FeeUpdate> = lightning_interface
+        .fee_history(channel_id)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|update| FeeUpdate {
+            id: update.id.to_string(),
+            channel_id: hex::encode(update.channel_id.0),
+            previous_ppm: update.previous_ppm,
+            ppm: update.ppm,
+            base_msat: update.base_msat,
+            outbound_ratio: update.outbound_ratio,
+            forwarded_msat: update.forwarded_amount,
+            timestamp: update.timestamp.unix_timestamp() as u64,
+        })
+        .collect();
+    Ok(Json(response))
+}
+
 fn format_features(channel_type: ChannelTypeFeatures) -> Vec<String> {
     channel_type
         .to_string()
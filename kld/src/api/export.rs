@@ -0,0 +1,448 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use bdk::KeychainKind;
+use serde_json::{json, Map, Value};
+
+use super::payloads::ExportQuery;
+use super::{bad_request, internal_server, ApiError};
+use crate::database::{forward::ForwardStatus, Pagination};
+use crate::ldk::{htlc_destination_to_string, LightningInterface};
+use crate::wallet::WalletInterface;
+
+/// The records that can be exported for accounting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Transactions,
+    Payments,
+    Invoices,
+    Forwards,
+    Channels,
+    Labels,
+}
+
+impl FromStr for ExportKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transactions" => Ok(ExportKind::Transactions),
+            "payments" => Ok(ExportKind::Payments),
+            "invoices" => Ok(ExportKind::Invoices),
+            "forwards" => Ok(ExportKind::Forwards),
+            "channels" => Ok(ExportKind::Channels),
+            "labels" => Ok(ExportKind::Labels),
+            _ => bail!(
+                "Unknown export {s}, must be one of transactions, payments, invoices, forwards, channels or labels"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            _ => bail!("Unknown export format {s}, must be csv or jsonl"),
+        }
+    }
+}
+
+// Rows with one value per column, rendered as CSV or as one JSON object per line.
+struct Table {
+    columns: &'static [&'static str],
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    fn new(columns: &'static [&'static str]) -> Table {
+        Table {
+            columns,
+            rows: vec![],
+        }
+    }
+
+    fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(self.columns.len(), row.len());
+        self.rows.push(row);
+    }
+
+    fn render(&self, format: ExportFormat) -> String {
+        let mut output = String::new();
+        match format {
+            ExportFormat::Csv => {
+                output.push_str(&self.columns.join(","));
+                output.push('\n');
+                for row in &self.rows {
+                    let fields: Vec<String> = row.iter().map(csv_field).collect();
+                    output.push_str(&fields.join(","));
+                    output.push('\n');
+                }
+            }
+            ExportFormat::JsonLines => {
+                for row in &self.rows {
+                    let object: Map<String, Value> = self
+                        .columns
+                        .iter()
+                        .map(|column| column.to_string())
+                        .zip(row.iter().cloned())
+                        .collect();
+                    output.push_str(&Value::Object(object).to_string());
+                    output.push('\n');
+                }
+            }
+        }
+        output
+    }
+}
+
+fn csv_field(value: &Value) -> String {
+    let field = match value {
+        Value::Null => return String::new(),
+        // Spreadsheets run text starting with these as a formula, labels and descriptions come from peers.
+        Value::String(s) if s.starts_with(['=', '+', '-', '@']) => format!("'{s}"),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+pub(crate) async fn export(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Path(kind): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let kind = ExportKind::from_str(&kind).map_err(bad_request)?;
+    let format = query
+        .format
+        .as_deref()
+        .map(ExportFormat::from_str)
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or(ExportFormat::Csv);
+    let body = match kind {
+        ExportKind::Labels => {
+            if format == ExportFormat::Csv && query.format.is_some() {
+                return Err(bad_request(anyhow!("Labels are only exported as jsonl")));
+            }
+            let labels = labels(lightning_interface.as_ref(), wallet.as_ref())
+                .await
+                .map_err(internal_server)?;
+            return Ok(([(header::CONTENT_TYPE, "application/jsonl")], labels));
+        }
+        ExportKind::Transactions => transactions(wallet.as_ref()),
+        ExportKind::Payments => payments(lightning_interface.as_ref()).await,
+        ExportKind::Invoices => invoices(lightning_interface.as_ref()).await,
+        ExportKind::Forwards => forwards(lightning_interface.as_ref()).await,
+        ExportKind::Channels => channels(lightning_interface.as_ref()).await,
+    }
+    .map_err(internal_server)?
+    .render(format);
+    let content_type = match format {
+        ExportFormat::Csv => "text/csv",
+        ExportFormat::JsonLines => "application/jsonl",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body))
+}
+
+fn transactions(wallet: &(dyn WalletInterface + Send + Sync)) -> anyhow::Result<Table> {
+    let mut table = Table::new(&[
+        "txid",
+        "confirmation_height",
+        "confirmation_time",
+        "received_sat",
+        "sent_sat",
+        "fee_sat",
+        "net_sat",
+    ]);
+    for tx in wallet.list_transactions()? {
+        table.push(vec![
+            json!(tx.txid.to_string()),
+            json!(tx.confirmation_time.as_ref().map(|time| time.height)),
+            json!(tx.confirmation_time.as_ref().map(|time| time.timestamp)),
+            json!(tx.received),
+            json!(tx.sent),
+            json!(tx.fee),
+            json!(tx.received as i64 - tx.sent as i64),
+        ]);
+    }
+    Ok(table)
+}
+
+async fn payments(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+) -> anyhow::Result<Table> {
+    let mut table = Table::new(&[
+        "id",
+        "timestamp",
+        "direction",
+        "status",
+        "payment_hash",
+        "amount_msat",
+        "fee_msat",
+        "label",
+        "bolt11",
+    ]);
+    for payment in lightning_interface
+        .list_payments(None, None, Pagination::default())
+        .await?
+    {
+        table.push(vec![
+            json!(hex::encode(payment.id.0)),
+            json!(payment.timestamp.unix_timestamp()),
+            json!(payment.direction.to_string()),
+            json!(payment.status.to_string()),
+            json!(payment.hash.map(|hash| hex::encode(hash.0))),
+            json!(payment.amount),
+            json!(payment.fee),
+            json!(payment.label),
+            json!(payment.bolt11.map(|bolt11| bolt11.to_string())),
+        ]);
+    }
+    Ok(table)
+}
+
+async fn invoices(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+) -> anyhow::Result<Table> {
+    let mut table = Table::new(&[
+        "payment_hash",
+        "timestamp",
+        "label",
+        "status",
+        "amount_msat",
+        "amount_received_msat",
+        "paid_at",
+        "bolt11",
+    ]);
+    for invoice in lightning_interface
+        .list_invoices(None, Pagination::default())
+        .await?
+    {
+        table.push(vec![
+            json!(hex::encode(invoice.payment_hash.0)),
+            json!(unix_timestamp(invoice.timestamp)),
+            json!(invoice.label),
+            json!(invoice.status.to_string()),
+            json!(invoice.amount),
+            json!(invoice.amount_received),
+            json!(invoice.paid_at.map(unix_timestamp)),
+            json!(invoice.bolt11.to_string()),
+        ]);
+    }
+    Ok(table)
+}
+
+async fn forwards(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+) -> anyhow::Result<Table> {
+    let mut table = Table::new(&[
+        "id",
+        "timestamp",
+        "status",
+        "in_channel",
+        "out_channel",
+        "amount_msat",
+        "fee_msat",
+        "failure",
+    ]);
+    for forward in lightning_interface
+        .fetch_forwards(None, Pagination::default())
+        .await?
+    {
+        table.push(vec![
+            json!(forward.id.to_string()),
+            json!(forward.timestamp.unix_timestamp()),
+            json!(match forward.status {
+                ForwardStatus::Succeeded => "succeeded",
+                ForwardStatus::Failed => "failed",
+            }),
+            json!(hex::encode(forward.inbound_channel_id.0)),
+            json!(forward.outbound_channel_id.map(|id| hex::encode(id.0))),
+            json!(forward.amount),
+            json!(forward.fee),
+            json!(forward
+                .htlc_destination
+                .as_ref()
+                .map(htlc_destination_to_string)),
+        ]);
+    }
+    Ok(table)
+}
+
+async fn channels(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+) -> anyhow::Result<Table> {
+    let mut table = Table::new(&[
+        "channel_id",
+        "counterparty",
+        "is_outbound",
+        "value_sat",
+        "funding_txo",
+        "funding_fee_sat",
+        "opened_at",
+        "closed_at",
+        "closure_reason",
+        "closing_txid",
+        "closing_fee_sat",
+    ]);
+    for record in lightning_interface.list_channels().await? {
+        let Some(detail) = record.detail else {
+            continue;
+        };
+        // The fee of a batch funding transaction is listed for each of its channels.
+        let funding_fee = match detail.funding_txo.filter(|_| detail.is_outbound) {
+            Some(txo) => lightning_interface.funding_fee(&txo.txid)?,
+            None => None,
+        };
+        let closed = record.closure_reason.is_some();
+        let closing_tx = match detail.funding_txo.filter(|_| closed) {
+            Some(txo) => lightning_interface.closing_transaction(&txo.into_bitcoin_outpoint())?,
+            None => None,
+        };
+        // The closing transaction only spends the funding output, its fee is whatever it doesn't pay out.
+        let closing_fee = closing_tx.as_ref().map(|tx| {
+            detail
+                .channel_value_satoshis
+                .saturating_sub(tx.output.iter().map(|output| output.value).sum())
+        });
+        table.push(vec![
+            json!(hex::encode(detail.channel_id.0)),
+            json!(detail.counterparty.node_id.to_string()),
+            json!(detail.is_outbound),
+            json!(detail.channel_value_satoshis),
+            json!(detail
+                .funding_txo
+                .map(|txo| txo.into_bitcoin_outpoint().to_string())),
+            json!(funding_fee),
+            json!(record.open_timestamp.unix_timestamp()),
+            json!(closed.then(|| record.update_timestamp.unix_timestamp())),
+            json!(record.closure_reason),
+            json!(closing_tx.map(|tx| tx.txid().to_string())),
+            json!(closing_fee),
+        ]);
+    }
+    Ok(table)
+}
+
+/// Labels of the wallet transactions, addresses and the outputs that belong to channels, as BIP-329 JSON lines.
+async fn labels(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+    wallet: &(dyn WalletInterface + Send + Sync),
+) -> anyhow::Result<String> {
+    let mut tx_labels: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut records = vec![];
+    for record in lightning_interface.list_channels().await? {
+        let Some(detail) = record.detail else {
+            continue;
+        };
+        let Some(txo) = detail.funding_txo else {
+            continue;
+        };
+        let counterparty = detail.counterparty.node_id;
+        if detail.is_outbound {
+            tx_labels
+                .entry(txo.txid.to_string())
+                .or_default()
+                .push(format!("Channel open with {counterparty}"));
+        }
+        if record.closure_reason.is_some() {
+            if let Some(tx) =
+                lightning_interface.closing_transaction(&txo.into_bitcoin_outpoint())?
+            {
+                tx_labels
+                    .entry(tx.txid().to_string())
+                    .or_default()
+                    .push(format!("Channel close with {counterparty}"));
+            }
+        }
+        records.push(json!({
+            "type": "output",
+            "ref": txo.into_bitcoin_outpoint().to_string(),
+            "label": format!("Channel {} with {counterparty}", hex::encode(detail.channel_id.0)),
+            "spendable": false,
+        }));
+    }
+    for sweep in lightning_interface.list_sweeps().await? {
+        tx_labels
+            .entry(sweep.txid.to_string())
+            .or_default()
+            .push("Sweep of closed channel outputs".to_string());
+    }
+    for tx in wallet.list_transactions()? {
+        let net = tx.received as i64 - tx.sent as i64;
+        tx_labels.entry(tx.txid.to_string()).or_insert_with(|| {
+            vec![if net >= 0 {
+                format!("Received {net} sats")
+            } else {
+                format!("Sent {} sats", -net)
+            }]
+        });
+    }
+    let mut output = String::new();
+    for (txid, labels) in tx_labels {
+        let label = json!({"type": "tx", "ref": txid, "label": labels.join(", ")});
+        output.push_str(&label.to_string());
+        output.push('\n');
+    }
+    for address in wallet.list_used_addresses()? {
+        let keychain = match address.keychain {
+            KeychainKind::External => "Receive",
+            KeychainKind::Internal => "Change",
+        };
+        let label = json!({
+            "type": "addr",
+            "ref": address.address.to_string(),
+            "label": format!("{keychain} address {}", address.index),
+        });
+        output.push_str(&label.to_string());
+        output.push('\n');
+    }
+    for record in records {
+        output.push_str(&record.to_string());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn unix_timestamp(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!("", csv_field(&Value::Null));
+    assert_eq!("-12", csv_field(&json!(-12)));
+    assert_eq!("label", csv_field(&json!("label")));
+    assert_eq!("\"a,\"\"b\"\"\"", csv_field(&json!("a,\"b\"")));
+    assert_eq!("'=1+1", csv_field(&json!("=1+1")));
+    assert_eq!("'@SUM(A1)", csv_field(&json!("@SUM(A1)")));
+    assert_eq!(
+        "\"'=HYPERLINK(\"\"x\"\",1)\"",
+        csv_field(&json!("=HYPERLINK(\"x\",1)"))
+    );
+}
@@ -1,16 +1,21 @@
 use std::{
+    future::Future,
     str::FromStr,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, UNIX_EPOCH},
 };
 
-use super::payloads::{GenerateInvoice, GenerateInvoiceResponse, Invoice, InvoiceStatus};
+use super::payloads::{
+    CancelInvoice, GenerateInvoice, GenerateInvoiceResponse, Invoice, InvoiceStatus, PageParams,
+    SettleInvoice,
+};
 use anyhow::anyhow;
 use axum::{
     extract::{Path, Query},
     response::IntoResponse,
     Extension, Json,
 };
+use lightning::ln::{PaymentHash, PaymentPreimage};
 use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
 
 use super::{
@@ -19,9 +24,17 @@ use super::{
     },
     empty_string_as_none,
 };
-use crate::{ldk::LightningInterface, MillisatAmount};
+use crate::{
+    database::{
+        invoice::InvoiceStatus as StoredInvoiceStatus,
+        payment::{Payment, PaymentStatus},
+        Pagination,
+    },
+    ldk::LightningInterface,
+    MillisatAmount,
+};
 
-use super::{bad_request, internal_server, ApiError};
+use super::{bad_request, internal_server, pagination, ApiError};
 
 pub(crate) async fn generate_invoice(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
@@ -30,12 +43,21 @@ pub(crate) async fn generate_invoice(
     if invoice_request.label.len() > 100 {
         return Err(bad_request(anyhow!("Label max length is 100 chars")));
     }
+    let payment_hash = invoice_request
+        .payment_hash
+        .map(|hash| {
+            hex_32_bytes(&hash)
+                .map(PaymentHash)
+                .ok_or_else(|| bad_request(anyhow!("invalid payment hash")))
+        })
+        .transpose()?;
     let invoice = lightning_interface
         .generate_invoice(
             invoice_request.label,
             Some(invoice_request.amount),
             invoice_request.description,
             invoice_request.expiry,
+            payment_hash,
         )
         .await
         .map_err(internal_server)?;
@@ -62,57 +84,171 @@ pub struct ListInvoiceParams {
 pub(crate) async fn list_invoices(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListInvoiceParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     if let Some(label) = &params.label {
         if label.len() > 100 {
             return Err(bad_request(anyhow!("Label max length is 100 chars")));
         }
     }
-    let mut response = vec![];
     let invoices = lightning_interface
-        .list_invoices(params.label)
+        .list_invoices(params.label, pagination(page)?)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(
+        invoices
+            .into_iter()
+            .map(invoice_response)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WaitInvoiceParams {
+    // Give up waiting after this many seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WaitAnyInvoiceParams {
+    #[serde(default)]
+    pub lastpay_index: Option<u64>,
+    // Give up waiting after this many seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+pub(crate) async fn wait_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(label): Path<String>,
+    Query(params): Query<WaitInvoiceParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let invoices = lightning_interface
+        .list_invoices(Some(label.clone()), Pagination::default())
         .await
         .map_err(internal_server)?;
-    for invoice in invoices {
-        let description = match invoice.bolt11.description() {
-            lightning_invoice::Bolt11InvoiceDescription::Direct(d) => d.to_string(),
-            lightning_invoice::Bolt11InvoiceDescription::Hash(h) => hex::encode(h.0),
-        };
-        let amount_received_msat = invoice
-            .payments
+    if invoices.len() > 1 {
+        return Err(bad_request(anyhow!(
+            "Label {label} matches {} invoices",
+            invoices.len()
+        )));
+    }
+    let invoice = with_timeout(
+        params.timeout,
+        lightning_interface.wait_invoice(label.clone()),
+    )
+    .await?
+    .map_err(internal_server)?
+    .ok_or_else(|| ApiError::NotFound(label))?;
+    Ok(Json(invoice_response(invoice)))
+}
+
+pub(crate) async fn wait_any_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<WaitAnyInvoiceParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let invoice = with_timeout(
+        params.timeout,
+        lightning_interface.wait_any_invoice(params.lastpay_index.unwrap_or_default()),
+    )
+    .await?
+    .map_err(internal_server)?;
+    Ok(Json(invoice_response(invoice)))
+}
+
+async fn with_timeout<T>(
+    timeout: Option<u64>,
+    future: impl Future<Output = T>,
+) -> Result<T, ApiError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(Duration::from_secs(timeout), future)
+            .await
+            .map_err(|_| ApiError::RequestTimeout(format!("Timed out after {timeout} seconds"))),
+        None => Ok(future.await),
+    }
+}
+
+fn invoice_response(invoice: crate::database::invoice::Invoice) -> Invoice {
+    let description = match invoice.bolt11.description() {
+        lightning_invoice::Bolt11InvoiceDescription::Direct(d) => d.to_string(),
+        lightning_invoice::Bolt11InvoiceDescription::Hash(h) => hex::encode(h.0),
+    };
+    // Invoices that we paid ourselves only have the outbound payments.
+    let succeeded: Vec<&Payment> = invoice
+        .payments
+        .iter()
+        .filter(|p| p.status == PaymentStatus::Succeeded)
+        .collect();
+    let status = match invoice.status {
+        StoredInvoiceStatus::Settled => InvoiceStatus::Paid,
+        StoredInvoiceStatus::Held => InvoiceStatus::Held,
+        StoredInvoiceStatus::Canceled => InvoiceStatus::Canceled,
+        StoredInvoiceStatus::Open if !succeeded.is_empty() => InvoiceStatus::Paid,
+        StoredInvoiceStatus::Open if invoice.bolt11.is_expired() => InvoiceStatus::Expired,
+        StoredInvoiceStatus::Open => InvoiceStatus::Unpaid,
+    };
+    let amount_received_msat = invoice.amount_received.or_else(|| {
+        let sum = succeeded
             .iter()
             .fold(MillisatAmount::default(), |sum, p| sum + p.amount);
-        let status = if !invoice.payments.is_empty() {
-            InvoiceStatus::Paid
-        } else if invoice.bolt11.expiry_time()
-            > SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map_err(internal_server)?
-        {
-            InvoiceStatus::Expired
-        } else {
-            InvoiceStatus::Unpaid
-        };
-        response.push(Invoice {
-            label: invoice.label,
-            bolt11: invoice.bolt11.to_string(),
-            payment_hash: hex::encode(invoice.bolt11.payment_hash()),
-            amount_msat: invoice.bolt11.amount_milli_satoshis(),
-            status,
-            amount_received_msat: if amount_received_msat > 0 {
-                Some(amount_received_msat)
-            } else {
-                None
-            },
-            paid_at: invoice
-                .payments
+        (sum > 0).then_some(sum)
+    });
+    let paid_at = invoice
+        .paid_at
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as u32)
+        .or_else(|| {
+            succeeded
                 .first()
-                .map(|p| p.timestamp.unix_timestamp() as u32),
-            description,
-            expires_at: invoice.bolt11.expires_at().map(|d| d.as_secs()),
+                .map(|p| p.timestamp.unix_timestamp() as u32)
         });
+    Invoice {
+        label: invoice.label,
+        bolt11: invoice.bolt11.to_string(),
+        payment_hash: hex::encode(invoice.bolt11.payment_hash()),
+        amount_msat: invoice.bolt11.amount_milli_satoshis(),
+        status,
+        amount_received_msat,
+        paid_at,
+        pay_index: invoice.pay_index,
+        description,
+        expires_at: invoice.bolt11.expires_at().map(|d| d.as_secs()),
     }
-    Ok(Json(response))
+}
+
+pub(crate) async fn settle_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(settle_request): Json<SettleInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let preimage = hex_32_bytes(&settle_request.preimage)
+        .map(PaymentPreimage)
+        .ok_or_else(|| bad_request(anyhow!("invalid preimage")))?;
+    lightning_interface
+        .settle_invoice(preimage)
+        .await
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
+pub(crate) async fn cancel_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(cancel_request): Json<CancelInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payment_hash = hex_32_bytes(&cancel_request.payment_hash)
+        .map(PaymentHash)
+        .ok_or_else(|| bad_request(anyhow!("invalid payment hash")))?;
+    lightning_interface
+        .cancel_invoice(payment_hash)
+        .await
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
+fn hex_32_bytes(value: &str) -> Option<[u8; 32]> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
 }
 
 pub(crate) async fn decode_invoice(
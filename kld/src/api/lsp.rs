@@ -0,0 +1,162 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+use bitcoin::secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use super::macaroon_auth::AmountLimit;
+use super::payloads::{
+    BuyChannel, GenerateInvoiceResponse, GenerateJitInvoice, LspInfo, LspOrder, Lsps1LspOptions,
+    Lsps2OpeningFee,
+};
+use super::{bad_request, empty_string_as_none, internal_server, unauthorized, ApiError};
+use crate::ldk::{self, ChannelOrder, LightningInterface, Lsps1Options};
+
+// Leased for about a month unless asked otherwise.
+const DEFAULT_CHANNEL_EXPIRY_BLOCKS: u32 = 4320;
+
+impl From<Lsps1Options> for Lsps1LspOptions {
+    fn from(options: Lsps1Options) -> Self {
+        Lsps1LspOptions {
+            min_required_channel_confirmations: options.min_required_channel_confirmations,
+            min_funding_confirms_within_blocks: options.min_funding_confirms_within_blocks,
+            supports_zero_channel_reserve: options.supports_zero_channel_reserve,
+            max_channel_expiry_blocks: options.max_channel_expiry_blocks,
+            min_initial_client_balance_sat: options.min_initial_client_balance_sat,
+            max_initial_client_balance_sat: options.max_initial_client_balance_sat,
+            min_initial_lsp_balance_sat: options.min_initial_lsp_balance_sat,
+            max_initial_lsp_balance_sat: options.max_initial_lsp_balance_sat,
+            min_channel_balance_sat: options.min_channel_balance_sat,
+            max_channel_balance_sat: options.max_channel_balance_sat,
+        }
+    }
+}
+
+impl From<ldk::LspInfo> for LspInfo {
+    fn from(info: ldk::LspInfo) -> Self {
+        LspInfo {
+            lsps1: info.lsps1_options.map(Lsps1LspOptions::from),
+            lsps2_opening_fees: info.lsps2_opening_fees.map(|fees| {
+                fees.into_iter()
+                    .map(|params| Lsps2OpeningFee {
+                        min_fee_msat: params.min_fee_msat,
+                        proportional: params.proportional,
+                        valid_until: params.valid_until.timestamp() as u64,
+                        min_lifetime: params.min_lifetime,
+                        max_client_to_self_delay: params.max_client_to_self_delay,
+                        min_payment_size_msat: params.min_payment_size_msat,
+                        max_payment_size_msat: params.max_payment_size_msat,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl From<ldk::LspOrder> for LspOrder {
+    fn from(order: ldk::LspOrder) -> Self {
+        LspOrder {
+            order_id: order.order_id,
+            order_state: order.order_state,
+            payment_state: order.payment_state,
+            lsp_balance_sat: order.lsp_balance_sat,
+            client_balance_sat: order.client_balance_sat,
+            fee_total_sat: order.fee_total_sat,
+            order_total_sat: order.order_total_sat,
+            bolt11_invoice: order.bolt11_invoice,
+            onchain_address: order.onchain_address,
+            expires_at: order.expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspInfoParams {
+    pub node_id: String,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub token: Option<String>,
+}
+
+pub(crate) async fn lsp_info(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<LspInfoParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let lsp = PublicKey::from_str(&params.node_id).map_err(bad_request)?;
+    let info = lightning_interface
+        .lsp_info(lsp, params.token)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(LspInfo::from(info)))
+}
+
+pub(crate) async fn buy_channel(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
+    Json(request): Json<BuyChannel>,
+) -> Result<impl IntoResponse, ApiError> {
+    let lsp = PublicKey::from_str(&request.node_id).map_err(bad_request)?;
+    if request.lsp_balance_sat == 0 {
+        return Err(bad_request(anyhow!("lsp_balance_sat must be positive")));
+    }
+    // The client balance is paid to the LSP together with the fee of the order.
+    limit.check(request.client_balance_sat.saturating_mul(1000))?;
+    let order = ChannelOrder {
+        lsp_balance_sat: request.lsp_balance_sat,
+        client_balance_sat: request.client_balance_sat,
+        channel_expiry_blocks: request
+            .channel_expiry_blocks
+            .unwrap_or(DEFAULT_CHANNEL_EXPIRY_BLOCKS),
+        announce_channel: request.announce_channel,
+        token: request.token,
+    };
+    let order = lightning_interface
+        .buy_channel(lsp, order)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(LspOrder::from(order)))
+}
+
+pub(crate) async fn generate_jit_invoice(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
+    Json(request): Json<GenerateJitInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    let lsp = PublicKey::from_str(&request.node_id).map_err(bad_request)?;
+    if request.label.len() > 100 {
+        return Err(bad_request(anyhow!("Label max length is 100 chars")));
+    }
+    // The opening fee is taken from the payment, so it is bounded by the invoice amount.
+    match (request.amount, limit.0) {
+        (Some(amount), _) => limit.check(amount)?,
+        (None, Some(_)) => {
+            return Err(unauthorized(anyhow!(
+                "An amount is required when the macaroon limits the amount"
+            )))
+        }
+        (None, None) => (),
+    }
+    let invoice = lightning_interface
+        .generate_jit_invoice(
+            lsp,
+            request.token,
+            request.label,
+            request.amount,
+            request.description,
+            request.expiry,
+        )
+        .await
+        .map_err(internal_server)?;
+    let response = GenerateInvoiceResponse {
+        payment_hash: hex::encode(invoice.payment_hash.0),
+        expires_at: invoice
+            .bolt11
+            .expires_at()
+            .ok_or_else(|| bad_request(anyhow!("expiry is too far in the future")))?
+            .as_secs() as u32,
+        bolt11: invoice.bolt11.to_string(),
+    };
+    Ok(Json(response))
+}
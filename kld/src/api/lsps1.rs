@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{response::IntoResponse, Extension, Json};
+
+use super::payloads::Lsps1Order;
+use super::{internal_server, ApiError};
+use crate::database::lsps1;
+use crate::ldk::LightningInterface;
+
+impl From<lsps1::Lsps1Order> for Lsps1Order {
+    fn from(order: lsps1::Lsps1Order) -> Self {
+        Lsps1Order {
+            order_id: order.order_id,
+            counterparty: order.counterparty.to_string(),
+            lsp_balance_sat: order.lsp_balance_sat,
+            client_balance_sat: order.client_balance_sat,
+            channel_expiry_blocks: order.channel_expiry_blocks,
+            token: order.token,
+            announce_channel: order.announce_channel,
+            fee_total_sat: order.fee_total_sat,
+            order_total_sat: order.order_total_sat,
+            payment_hash: hex::encode(order.payment_hash.0),
+            bolt11_invoice: order.bolt11_invoice,
+            onchain_address: order.onchain_address,
+            refund_onchain_address: order.refund_onchain_address,
+            order_state: order.order_state.to_string(),
+            payment_state: order.payment_state.to_string(),
+            funding_outpoint: order.funding_outpoint,
+            expires_at: order.expires_at.unix_timestamp() as u64,
+            funded_at: order.funded_at.map(|t| t.unix_timestamp() as u64),
+            created_at: order.created_at.unix_timestamp() as u64,
+            updated_at: order.updated_at.unix_timestamp() as u64,
+        }
+    }
+}
+
+pub(crate) async fn list_lsps1_orders(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let orders: Vec<Lsps1Order> = lightning_interface
+        .list_lsps1_orders()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(Lsps1Order::from)
+        .collect();
+    Ok(Json(orders))
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{extract::Path, response::IntoResponse, Extension, Json};
+
+use super::payloads::{CreateLsps2Token, JitChannel, Lsps2Token};
+use super::{bad_request, internal_server, ApiError};
+use crate::database::lsps2;
+use crate::ldk::LightningInterface;
+
+// The fees offered with a token can be used for 10 minutes unless configured otherwise.
+const DEFAULT_VALID_FOR_SECS: u32 = 600;
+
+impl From<lsps2::Lsps2Token> for Lsps2Token {
+    fn from(token: lsps2::Lsps2Token) -> Self {
+        Lsps2Token {
+            token: token.token,
+            min_fee_msat: token.min_fee_msat,
+            proportional: token.proportional,
+            valid_for_secs: token.valid_for_secs,
+            min_payment_size_msat: token.min_payment_size_msat,
+            max_payment_size_msat: token.max_payment_size_msat,
+            created_at: token.created_at.unix_timestamp() as u64,
+        }
+    }
+}
+
+impl From<lsps2::JitChannel> for JitChannel {
+    fn from(channel: lsps2::JitChannel) -> Self {
+        JitChannel {
+            user_channel_id: channel.user_channel_id.to_string(),
+            token: channel.token,
+            counterparty: channel.counterparty.to_string(),
+            intercept_scid: channel.intercept_scid,
+            payment_size_msat: channel.payment_size_msat,
+            opening_fee_msat: channel.opening_fee_msat,
+            channel_value_sats: channel.channel_value_sats,
+            channel_id: channel.channel_id.map(|id| hex::encode(id.0)),
+            status: channel.status.to_string(),
+            created_at: channel.created_at.unix_timestamp() as u64,
+            updated_at: channel.updated_at.unix_timestamp() as u64,
+        }
+    }
+}
+
+pub(crate) async fn list_lsps2_tokens(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tokens: Vec<Lsps2Token> = lightning_interface
+        .list_lsps2_tokens()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(Lsps2Token::from)
+        .collect();
+    Ok(Json(tokens))
+}
+
+pub(crate) async fn create_lsps2_token(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(request): Json<CreateLsps2Token>,
+) -> Result<impl IntoResponse, ApiError> {
+    if request.max_payment_size_msat < request.min_payment_size_msat {
+        return Err(bad_request(anyhow!(
+            "max_payment_size_msat is smaller than min_payment_size_msat"
+        )));
+    }
+    let token = match request.token {
+        Some(token) if token.is_empty() || token.len() > 100 => {
+            return Err(bad_request(anyhow!("Token must be 1 to 100 chars")));
+        }
+        Some(token) => token,
+        None => hex::encode(rand::random::<[u8; 16]>()),
+    };
+    let token = lsps2::Lsps2Token::new(
+        token,
+        request.min_fee_msat,
+        request.proportional,
+        request.valid_for_secs.unwrap_or(DEFAULT_VALID_FOR_SECS),
+        request.min_payment_size_msat,
+        request.max_payment_size_msat,
+    );
+    lightning_interface
+        .persist_lsps2_token(token.clone())
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(Lsps2Token::from(token)))
+}
+
+pub(crate) async fn delete_lsps2_token(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !lightning_interface
+        .delete_lsps2_token(&token)
+        .await
+        .map_err(internal_server)?
+    {
+        return Err(ApiError::NotFound(format!("Token {token}")));
+    }
+    Ok(Json(()))
+}
+
+pub(crate) async fn list_jit_channels(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let channels: Vec<JitChannel> = lightning_interface
+        .list_jit_channels()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(JitChannel::from)
+        .collect();
+    Ok(Json(channels))
+}
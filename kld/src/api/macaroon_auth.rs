@@ -1,36 +1,87 @@
 use base64::{engine::general_purpose, Engine};
 use bitcoin::hashes::hex::FromHex;
 use hyper::header;
+use std::collections::BTreeMap;
+use std::fmt;
 #[cfg(not(test))]
 use std::fs;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 #[cfg(test)]
 use test_utils::fake_fs as fs;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, Path},
     http::{request::Parts, Request, StatusCode},
     middleware::Next,
     response::IntoResponse,
-    Extension,
+    Extension, Json,
 };
-use macaroon::{ByteString, Macaroon, MacaroonKey, Verifier};
+use macaroon::{ByteString, Caveat, Macaroon, MacaroonKey, Verifier};
 
-use super::{unauthorized, ApiError};
+use super::payloads::{BakeMacaroon, BakeMacaroonResponse, RootKey};
+use super::{bad_request, internal_server, routes, unauthorized, ApiError};
+use crate::database::{
+    macaroon::{MacaroonKeyStore, MacaroonRootKey},
+    microsecond_timestamp,
+};
 
 pub struct MacaroonAuth {
-    key: MacaroonKey,
+    seed: [u8; 32],
+    data_dir: String,
+    store: Arc<dyn MacaroonKeyStore + Send + Sync>,
+    root_keys: RwLock<BTreeMap<u64, MacaroonRootKey>>,
 }
 
 impl MacaroonAuth {
-    pub fn init(seed: &[u8; 32], data_dir: &str) -> Result<MacaroonAuth> {
+    pub async fn init(
+        seed: &[u8; 32],
+        data_dir: &str,
+        store: Arc<dyn MacaroonKeyStore + Send + Sync>,
+    ) -> Result<MacaroonAuth> {
         macaroon::initialize()?;
-        let key = MacaroonKey::generate(seed);
+        let root_keys = store
+            .fetch_macaroon_root_keys()
+            .await?
+            .into_iter()
+            .map(|root_key| (root_key.id, root_key))
+            .collect();
+        let macaroon_auth = MacaroonAuth {
+            seed: *seed,
+            data_dir: data_dir.to_string(),
+            store,
+            root_keys: RwLock::new(root_keys),
+        };
+        let admin_key = macaroon_auth
+            .root_keys()?
+            .into_iter()
+            .find(|root_key| root_key.admin && !root_key.is_revoked());
+        let admin_key_id = match admin_key {
+            Some(root_key) => root_key.id,
+            None => macaroon_auth.create_root_key(None, true).await?,
+        };
+        macaroon_auth.write_macaroons(admin_key_id)?;
+        Ok(macaroon_auth)
+    }
 
-        let admin_macaroon = Self::admin_macaroon(&key)?;
-        let readonly_macaroon = Self::readonly_macaroon(&key)?;
+    // The first root key is derived from the seed alone so the macaroons from before there were more root keys stay valid.
+    fn root_key(&self, id: u64) -> MacaroonKey {
+        if id == 0 {
+            MacaroonKey::generate(&self.seed)
+        } else {
+            MacaroonKey::generate(&[&self.seed[..], &id.to_be_bytes()].concat())
+        }
+    }
+
+    // Write the admin and readonly macaroons to the data directory.
+    fn write_macaroons(&self, root_key_id: u64) -> Result<()> {
+        let data_dir = &self.data_dir;
+        let admin_macaroon = self.admin_macaroon(root_key_id)?;
+        let readonly_macaroon = self.readonly_macaroon(root_key_id)?;
 
         let mut buf = vec![];
         let base64 = admin_macaroon.serialize(macaroon::Format::V2)?;
@@ -48,69 +99,565 @@ impl MacaroonAuth {
             format!("{data_dir}/macaroons/readonly.macaroon"),
             readonly_macaroon.serialize(macaroon::Format::V2)?,
         )?;
+        Ok(())
+    }
 
-        Ok(MacaroonAuth { key })
+    /// All the root keys, including the revoked ones.
+    pub fn root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        Ok(self
+            .root_keys
+            .read()
+            .map_err(|e| anyhow!("{e}"))?
+            .values()
+            .cloned()
+            .collect())
     }
 
-    pub fn verify_admin_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
-        let mut verifier = Verifier::default();
-        verifier.satisfy_general(|caveat| verify_role(caveat, "admin"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+    // Add a root key with the id, or with the next free id if there is none.
+    async fn create_root_key(&self, id: Option<u64>, admin: bool) -> Result<u64> {
+        let root_key = {
+            let mut root_keys = self.root_keys.write().map_err(|e| anyhow!("{e}"))?;
+            let id = match id {
+                Some(id) => id,
+                None => match root_keys.last_key_value() {
+                    Some((last_id, _)) => last_id
+                        .checked_add(1)
+                        .ok_or_else(|| anyhow!("No root key id left after {last_id}"))?,
+                    None => 0,
+                },
+            };
+            if root_keys.contains_key(&id) {
+                bail!("Root key {id} already exists");
+            }
+            let root_key = MacaroonRootKey::new(id, admin);
+            root_keys.insert(id, root_key.clone());
+            root_key
+        };
+        if let Err(e) = self.store.persist_macaroon_root_key(&root_key).await {
+            if let Ok(mut root_keys) = self.root_keys.write() {
+                root_keys.remove(&root_key.id);
+            }
+            return Err(e);
+        }
+        Ok(root_key.id)
+    }
+
+    /// Revoke the root key so that none of its macaroons are accepted anymore. If the admin and
+    /// readonly macaroons in the data directory used it they are replaced with ones from a new root key.
+    /// Returns None if there is no such root key.
+    pub async fn revoke_root_key(&self, id: u64) -> Result<Option<MacaroonRootKey>> {
+        let Some(mut root_key) = self.root_keys()?.into_iter().find(|key| key.id == id) else {
+            return Ok(None);
+        };
+        if root_key.is_revoked() {
+            return Ok(Some(root_key));
+        }
+        root_key.revoked_at = Some(microsecond_timestamp());
+        self.store.persist_macaroon_root_key(&root_key).await?;
+        self.root_keys
+            .write()
+            .map_err(|e| anyhow!("{e}"))?
+            .insert(id, root_key.clone());
+        if root_key.admin {
+            let admin_key_id = self.create_root_key(None, true).await?;
+            self.write_macaroons(admin_key_id)?;
+        }
+        Ok(Some(root_key))
     }
 
-    pub fn verify_readonly_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
+    /// Check the signature of the macaroon, that its root key is not revoked and that its caveats allow the request.
+    /// Returns the largest amount the request may spend.
+    pub fn verify(&self, macaroon: &Macaroon, access: &Access) -> Result<AmountLimit> {
+        let root_key_id = root_key_id(&macaroon.identifier())?;
+        match self
+            .root_keys
+            .read()
+            .map_err(|e| anyhow!("{e}"))?
+            .get(&root_key_id)
+        {
+            Some(root_key) if root_key.is_revoked() => {
+                bail!("Root key {root_key_id} of the macaroon is revoked")
+            }
+            Some(_) => (),
+            None => bail!("Unknown root key {root_key_id}"),
+        }
         let mut verifier = Verifier::default();
-        verifier.satisfy_general(|caveat| verify_role(caveat, "readonly"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+        verifier.satisfy_general(is_known_caveat);
+        verifier.verify(macaroon, &self.root_key(root_key_id), vec![])?;
+
+        let mut granted = false;
+        let mut limit = AmountLimit(None);
+        for caveat in macaroon.first_party_caveats() {
+            let Caveat::FirstParty(caveat) = caveat else {
+                bail!("Third party caveats are not supported");
+            };
+            let predicate = caveat.predicate();
+            let predicate = std::str::from_utf8(&predicate.0)?;
+            let Some((name, value)) = predicate.split_once(" = ") else {
+                bail!("Unknown caveat {predicate}");
+            };
+            match name {
+                "roles" => {
+                    if !value
+                        .split('|')
+                        .any(|role| role_grants(role, access.permission))
+                    {
+                        bail!("Macaroon role {value} does not allow this request");
+                    }
+                    granted = true;
+                }
+                "permissions" => match access.permission {
+                    Some(permission) if value.split('|').any(|p| p == permission) => granted = true,
+                    _ => bail!("Macaroon permissions {value} do not allow this request"),
+                },
+                "time-before" => {
+                    let expiry: u64 = value.parse()?;
+                    if unix_time() >= expiry {
+                        bail!("Macaroon expired at {expiry}");
+                    }
+                }
+                "max-amount-msat" => {
+                    let max: u64 = value.parse()?;
+                    limit = AmountLimit(Some(limit.0.map_or(max, |limit| limit.min(max))));
+                }
+                "ip" => {
+                    let ranges = value
+                        .split('|')
+                        .map(IpRange::from_str)
+                        .collect::<Result<Vec<_>>>()?;
+                    match access.ip {
+                        Some(ip) if ranges.iter().any(|range| range.contains(&ip)) => (),
+                        _ => bail!("Macaroon is not valid from {:?}", access.ip),
+                    }
+                }
+                _ => bail!("Unknown caveat {predicate}"),
+            }
+        }
+        if !granted {
+            bail!("Macaroon has no roles or permissions");
+        }
+        Ok(limit)
+    }
+
+    /// Create a macaroon that only allows the given permissions, optionally limited in time, amount and client address.
+    /// The macaroon gets a new root key unless one is given, so that it can be revoked on its own.
+    pub async fn bake(&self, restrictions: &MacaroonRestrictions) -> Result<(Macaroon, u64)> {
+        self.check_restrictions(restrictions)?;
+        let existing = self.existing_root_key(restrictions.root_key_id)?;
+        let root_key_id = match existing {
+            Some(root_key) => root_key.id,
+            None => {
+                self.create_root_key(restrictions.root_key_id, false)
+                    .await?
+            }
+        };
+        let identifier = format!("{root_key_id}:{}", hex::encode(rand::random::<[u8; 16]>()));
+        let mut macaroon = Macaroon::create(None, &self.root_key(root_key_id), identifier.into())?;
+        macaroon.add_first_party_caveat(
+            format!("permissions = {}", restrictions.permissions.join("|")).into(),
+        );
+        if let Some(expires_at) = restrictions.expires_at {
+            macaroon.add_first_party_caveat(format!("time-before = {expires_at}").into());
+        }
+        if let Some(max_amount_msat) = restrictions.max_amount_msat {
+            macaroon.add_first_party_caveat(format!("max-amount-msat = {max_amount_msat}").into());
+        }
+        if !restrictions.ip_ranges.is_empty() {
+            let ranges: Vec<String> = restrictions
+                .ip_ranges
+                .iter()
+                .map(|range| range.to_string())
+                .collect();
+            macaroon.add_first_party_caveat(format!("ip = {}", ranges.join("|")).into());
+        }
+        Ok((macaroon, root_key_id))
+    }
+
+    /// Check that a macaroon can be baked with the restrictions, the errors are caused by the request.
+    pub fn check_restrictions(&self, restrictions: &MacaroonRestrictions) -> Result<()> {
+        if restrictions.permissions.is_empty() {
+            bail!("At least one permission is required");
+        }
+        for permission in &restrictions.permissions {
+            if !PERMISSIONS.contains(&permission.as_str()) {
+                bail!(
+                    "Unknown permission {permission}, must be one of {}",
+                    PERMISSIONS.join(", ")
+                );
+            }
+        }
+        match self.existing_root_key(restrictions.root_key_id)? {
+            Some(root_key) if root_key.is_revoked() => bail!("Root key {} is revoked", root_key.id),
+            Some(_) => Ok(()),
+            None => match restrictions.root_key_id {
+                Some(id) if id > MAX_ROOT_KEY_ID => {
+                    bail!("Root key id {id} is above the maximum of {MAX_ROOT_KEY_ID}")
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn existing_root_key(&self, id: Option<u64>) -> Result<Option<MacaroonRootKey>> {
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        Ok(self
+            .root_keys()?
+            .into_iter()
+            .find(|root_key| root_key.id == id))
     }
 
-    fn admin_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
-        let mut macaroon = Macaroon::create(None, key, "admin".into())?;
+    fn admin_macaroon(&self, root_key_id: u64) -> Result<Macaroon> {
+        let mut macaroon = Macaroon::create(
+            None,
+            &self.root_key(root_key_id),
+            identifier(root_key_id, "admin"),
+        )?;
         macaroon.add_first_party_caveat("roles = admin|readonly".into());
         Ok(macaroon)
     }
 
-    fn readonly_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
-        let mut macaroon = Macaroon::create(None, key, "readonly".into())?;
+    fn readonly_macaroon(&self, root_key_id: u64) -> Result<Macaroon> {
+        let mut macaroon = Macaroon::create(
+            None,
+            &self.root_key(root_key_id),
+            identifier(root_key_id, "readonly"),
+        )?;
         macaroon.add_first_party_caveat("roles = readonly".into());
         Ok(macaroon)
     }
 }
 
-fn verify_role(caveat: &ByteString, expected_role: &str) -> bool {
-    if !caveat.0.starts_with(b"roles = ") {
-        return false;
+// Identifiers are root_key_id:name, except for the admin and readonly macaroons of the first root key
+// which have kept the name only.
+fn identifier(root_key_id: u64, name: &str) -> ByteString {
+    if root_key_id == 0 {
+        name.into()
+    } else {
+        format!("{root_key_id}:{name}").into()
     }
-    let strcaveat = match std::str::from_utf8(&caveat.0) {
-        Ok(s) => s,
-        Err(_) => return false,
+}
+
+fn root_key_id(identifier: &ByteString) -> Result<u64> {
+    let identifier = std::str::from_utf8(&identifier.0)?;
+    match identifier.split_once(':') {
+        Some((id, _)) => Ok(id.parse()?),
+        None => Ok(0),
+    }
+}
+
+// Root key ids chosen by clients stay well below u64::MAX so that there are always new ids left.
+const MAX_ROOT_KEY_ID: u64 = u32::MAX as u64;
+
+/// Every permission that can be baked into a macaroon. A permission grants access to a group of endpoints,
+/// the readonly role has the permissions ending in `:read`, except ADMIN_READ_PERMISSIONS, and the admin
+/// role has every permission.
+pub const PERMISSIONS: [&str; 22] = [
+    "info:read",
+    "network:read",
+    "onchain:read",
+    "onchain:write",
+    "channels:read",
+    "channels:write",
+    "peers:read",
+    "peers:write",
+    "invoices:read",
+    "invoices:write",
+    "payments:read",
+    "payments:send",
+    "offers:read",
+    "offers:write",
+    "export:read",
+    "message:sign",
+    "events:subscribe",
+    "macaroon:read",
+    "macaroon:write",
+    "backup:read",
+    "lsps:read",
+    "lsps:write",
+];
+
+/// Read permissions that expose secrets or the whole node history, only the admin role has them.
+const ADMIN_READ_PERMISSIONS: [&str; 3] = ["export:read", "backup:read", "macaroon:read"];
+
+/// The permission needed to call a route, None for the routes that only the admin role can call.
+pub fn route_permission(route: &str) -> Option<&'static str> {
+    let permission = match route {
+        routes::ROOT | routes::GET_INFO | routes::GET_FEES | routes::DECODE_INVOICE => "info:read",
+        routes::LIST_NETWORK_NODE
+        | routes::LIST_NETWORK_NODES
+        | routes::LIST_NETWORK_CHANNEL
+        | routes::LIST_NETWORK_CHANNELS
+        | routes::FEE_RATES
+        | routes::GET_ROUTE
+        | routes::ESTIMATE_CHANNEL_LIQUIDITY
+        | routes::SCORER => "network:read",
+        routes::GET_BALANCE | routes::LIST_FUNDS | routes::LIST_SWEEPS => "onchain:read",
+        routes::NEW_ADDR | routes::WITHDRAW => "onchain:write",
+        routes::LIST_PEER_CHANNELS
+        | routes::LOCAL_REMOTE_BALANCE
+        | routes::LIST_FORWARDS
+        | routes::FORWARDING_REPORT
+        | routes::LIST_CHANNEL_HISTORY
+        | routes::CHANNEL_FEE_HISTORY
+        | routes::LIST_CHANNELS => "channels:read",
+        routes::OPEN_CHANNEL
+        | routes::SET_CHANNEL_FEE
+        | routes::REBALANCE_CHANNEL
+        | routes::CLOSE_CHANNEL
+        | routes::CLOSE_CHANNEL_WITH_FEE
+        | routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST
+        | routes::FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST => "channels:write",
+        routes::LIST_PEERS => "peers:read",
+        routes::CONNECT_PEER | routes::DISCONNECT_PEER => "peers:write",
+        routes::LIST_INVOICES | routes::WAIT_INVOICE | routes::WAIT_ANY_INVOICE => "invoices:read",
+        routes::GENERATE_INVOICE | routes::SETTLE_INVOICE | routes::CANCEL_INVOICE => {
+            "invoices:write"
+        }
+        routes::LIST_PAYMENTS | routes::PAYMENT_STATUS => "payments:read",
+        routes::KEYSEND | routes::PAY_INVOICE | routes::PAY_OFFER => "payments:send",
+        routes::LIST_OFFERS => "offers:read",
+        routes::CREATE_OFFER => "offers:write",
+        routes::EXPORT => "export:read",
+        routes::BACKUP => "backup:read",
+        routes::SIGN => "message:sign",
+        routes::WEBSOCKET => "events:subscribe",
+        routes::LIST_ROOT_KEYS => "macaroon:read",
+        routes::BAKE_MACAROON | routes::REVOKE_ROOT_KEY => "macaroon:write",
+        routes::LIST_JIT_CHANNELS | routes::LIST_LSPS1_ORDERS | routes::LSP_GET_INFO => "lsps:read",
+        // The tokens are secrets, so listing them needs write permission.
+        routes::LIST_LSPS2_TOKENS
+        | routes::CREATE_LSPS2_TOKEN
+        | routes::DELETE_LSPS2_TOKEN
+        | routes::LSP_BUY_CHANNEL
+        | routes::LSP_JIT_INVOICE => "lsps:write",
+        _ => return None,
     };
+    Some(permission)
+}
+
+fn role_grants(role: &str, permission: Option<&str>) -> bool {
+    match role {
+        "admin" => true,
+        "readonly" => {
+            permission.is_some_and(|p| p.ends_with(":read") && !ADMIN_READ_PERMISSIONS.contains(&p))
+        }
+        _ => false,
+    }
+}
 
-    strcaveat[8..].split('|').any(|r| r == expected_role)
+// The signature check accepts the caveats we know, their conditions are checked afterwards.
+fn is_known_caveat(caveat: &ByteString) -> bool {
+    [
+        "roles",
+        "permissions",
+        "time-before",
+        "max-amount-msat",
+        "ip",
+    ]
+    .iter()
+    .any(|name| caveat.0.starts_with(format!("{name} = ").as_bytes()))
 }
 
-pub async fn admin_auth<B>(
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// What a request asks the macaroon for.
+pub struct Access {
+    pub permission: Option<&'static str>,
+    pub ip: Option<IpAddr>,
+}
+
+/// Restrictions for baking a new macaroon.
+#[derive(Default)]
+pub struct MacaroonRestrictions {
+    // The root key to sign with, a new one is created if not given or if it does not exist yet.
+    pub root_key_id: Option<u64>,
+    pub permissions: Vec<String>,
+    // Unix timestamp after which the macaroon is no longer valid.
+    pub expires_at: Option<u64>,
+    pub max_amount_msat: Option<u64>,
+    pub ip_ranges: Vec<IpRange>,
+}
+
+/// The largest amount in milli satoshis a request may spend, None if the macaroon has no limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmountLimit(pub Option<u64>);
+
+impl AmountLimit {
+    pub fn check(&self, amount_msat: u64) -> Result<(), ApiError> {
+        match self.0 {
+            Some(max) if amount_msat > max => Err(unauthorized(anyhow!(
+                "Amount {amount_msat} msat is above the {max} msat allowed by the macaroon"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the amount together with the most the payment may pay in routing fees. Without a fee
+    /// cap the fees are unbounded, so a limited macaroon requires one.
+    pub fn check_with_fee(
+        &self,
+        amount_msat: u64,
+        max_fee_msat: Option<u64>,
+    ) -> Result<(), ApiError> {
+        if self.0.is_none() {
+            return Ok(());
+        }
+        let max_fee_msat = max_fee_msat.ok_or_else(|| {
+            bad_request(anyhow!(
+                "A maximum routing fee is required by the amount limit of the macaroon"
+            ))
+        })?;
+        self.check(amount_msat.saturating_add(max_fee_msat))
+    }
+}
+
+/// An IP address range in CIDR notation, e.g. 10.0.0.0/8. A single address is a range of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (IpAddr::from_str(address)?, prefix_len.parse()?),
+            None => {
+                let address = IpAddr::from_str(s)?;
+                (address, if address.is_ipv4() { 32 } else { 128 })
+            }
+        };
+        let max_len = if address.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            bail!("Invalid prefix length in {s}");
+        }
+        Ok(IpRange {
+            address,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// Authorize the request with the permission of its route and pass the amount limit on to the handler.
+pub async fn authorize<B>(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
-    request: Request<B>,
+    path: MatchedPath,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut request: Request<B>,
     next: Next<B>,
 ) -> Result<impl IntoResponse, ApiError> {
-    macaroon_auth
-        .verify_admin_macaroon(&macaroon.0)
+    let access = Access {
+        permission: route_permission(path.as_str()),
+        ip: connect_info.map(|ConnectInfo(address)| address.ip()),
+    };
+    let limit = macaroon_auth
+        .verify(&macaroon.0, &access)
         .map_err(unauthorized)?;
+    request.extensions_mut().insert(limit);
     Ok(next.run(request).await)
 }
 
-pub async fn readonly_auth<B>(
-    macaroon: KldMacaroon,
+pub(crate) async fn bake_macaroon(
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
-    request: Request<B>,
-    next: Next<B>,
+    Json(bake): Json<BakeMacaroon>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let restrictions = MacaroonRestrictions {
+        root_key_id: bake.root_key_id,
+        permissions: bake.permissions,
+        expires_at: bake.expires_at,
+        max_amount_msat: bake.max_amount_msat,
+        ip_ranges: bake
+            .ip_ranges
+            .iter()
+            .map(|range| IpRange::from_str(range))
+            .collect::<Result<_>>()
+            .map_err(bad_request)?,
+    };
     macaroon_auth
-        .verify_readonly_macaroon(&macaroon.0)
-        .map_err(unauthorized)?;
-    Ok(next.run(request).await)
+        .check_restrictions(&restrictions)
+        .map_err(bad_request)?;
+    let (macaroon, root_key_id) = macaroon_auth
+        .bake(&restrictions)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(BakeMacaroonResponse {
+        macaroon: macaroon
+            .serialize(macaroon::Format::V2)
+            .map_err(internal_server)?,
+        permissions: restrictions.permissions,
+        root_key_id,
+    }))
+}
+
+pub(crate) async fn list_root_keys(
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root_keys: Vec<RootKey> = macaroon_auth
+        .root_keys()
+        .map_err(internal_server)?
+        .into_iter()
+        .map(root_key_payload)
+        .collect();
+    Ok(Json(root_keys))
+}
+
+pub(crate) async fn revoke_root_key(
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root_key = macaroon_auth
+        .revoke_root_key(id)
+        .await
+        .map_err(internal_server)?
+        .ok_or_else(|| ApiError::NotFound(format!("Root key {id}")))?;
+    Ok(Json(root_key_payload(root_key)))
+}
+
+fn root_key_payload(root_key: MacaroonRootKey) -> RootKey {
+    RootKey {
+        id: root_key.id,
+        admin: root_key.admin,
+        created_at: root_key.created_at.unix_timestamp() as u64,
+        revoked_at: root_key
+            .revoked_at
+            .map(|revoked_at| revoked_at.unix_timestamp() as u64),
+    }
 }
 
 pub struct KldMacaroon(pub Macaroon);
@@ -157,22 +704,247 @@ where
     }
 }
 
-#[test]
-fn test_readonly_macaroon() {
-    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
-    let readonly_macaroon = MacaroonAuth::readonly_macaroon(&macaroon_auth.key).unwrap();
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryKeyStore(std::sync::Mutex<Vec<MacaroonRootKey>>);
+
+#[cfg(test)]
+#[async_trait]
+impl MacaroonKeyStore for MemoryKeyStore {
+    async fn fetch_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    async fn persist_macaroon_root_key(&self, root_key: &MacaroonRootKey) -> Result<()> {
+        let mut keys = self.0.lock().unwrap();
+        keys.retain(|key| key.id != root_key.id);
+        keys.push(root_key.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+async fn test_macaroon_auth() -> MacaroonAuth {
+    MacaroonAuth::init(&[3u8; 32], "", Arc::new(MemoryKeyStore::default()))
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+fn access(permission: &'static str) -> Access {
+    Access {
+        permission: Some(permission),
+        ip: Some("10.1.2.3".parse().unwrap()),
+    }
+}
+
+#[tokio::test]
+async fn test_readonly_macaroon() {
+    let macaroon_auth = test_macaroon_auth().await;
+    let readonly_macaroon = macaroon_auth.readonly_macaroon(0).unwrap();
 
     macaroon_auth
-        .verify_readonly_macaroon(&readonly_macaroon)
+        .verify(&readonly_macaroon, &access("invoices:read"))
         .unwrap();
+    assert!(macaroon_auth
+        .verify(&readonly_macaroon, &access("invoices:write"))
+        .is_err());
+    for permission in ADMIN_READ_PERMISSIONS {
+        assert!(macaroon_auth
+            .verify(&readonly_macaroon, &access(permission))
+            .is_err());
+    }
 }
 
-#[test]
-fn test_admin_macaroon() {
-    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
-    let admin_macaroon = MacaroonAuth::admin_macaroon(&macaroon_auth.key).unwrap();
+#[tokio::test]
+async fn test_admin_macaroon() {
+    let macaroon_auth = test_macaroon_auth().await;
+    let admin_macaroon = macaroon_auth.admin_macaroon(0).unwrap();
+
+    for permission in PERMISSIONS {
+        macaroon_auth
+            .verify(&admin_macaroon, &access(permission))
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_macaroon_from_seed() {
+    // Macaroons made before there were several root keys are signed with the seed alone.
+    let macaroon_auth = test_macaroon_auth().await;
+    let mut macaroon =
+        Macaroon::create(None, &MacaroonKey::generate(&[3u8; 32]), "admin".into()).unwrap();
+    macaroon.add_first_party_caveat("roles = admin|readonly".into());
+    macaroon_auth
+        .verify(&macaroon, &access("payments:send"))
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_baked_macaroon() {
+    let macaroon_auth = test_macaroon_auth().await;
+    let (macaroon, root_key_id) = macaroon_auth
+        .bake(&MacaroonRestrictions {
+            permissions: vec!["invoices:read".to_string(), "payments:send".to_string()],
+            expires_at: Some(unix_time() + 60),
+            max_amount_msat: Some(1000),
+            ip_ranges: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(1, root_key_id);
+
+    assert_eq!(
+        AmountLimit(Some(1000)),
+        macaroon_auth
+            .verify(&macaroon, &access("payments:send"))
+            .unwrap()
+    );
+    assert!(macaroon_auth
+        .verify(&macaroon, &access("invoices:write"))
+        .is_err());
+    let outside = Access {
+        permission: Some("invoices:read"),
+        ip: Some("192.168.0.1".parse().unwrap()),
+    };
+    assert!(macaroon_auth.verify(&macaroon, &outside).is_err());
+
+    // Caveats added by the holder can only restrict the macaroon further.
+    let mut restricted = macaroon.clone();
+    restricted.add_first_party_caveat("max-amount-msat = 10".into());
+    assert_eq!(
+        AmountLimit(Some(10)),
+        macaroon_auth
+            .verify(&restricted, &access("payments:send"))
+            .unwrap()
+    );
+    let mut escalated = macaroon.clone();
+    escalated.add_first_party_caveat("roles = admin".into());
+    assert!(macaroon_auth
+        .verify(&escalated, &access("invoices:write"))
+        .is_err());
+    let mut unknown = macaroon;
+    unknown.add_first_party_caveat("owner = me".into());
+    assert!(macaroon_auth
+        .verify(&unknown, &access("payments:send"))
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_expired_macaroon() {
+    let macaroon_auth = test_macaroon_auth().await;
+    let (macaroon, _) = macaroon_auth
+        .bake(&MacaroonRestrictions {
+            permissions: vec!["invoices:read".to_string()],
+            expires_at: Some(unix_time() - 1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(macaroon_auth
+        .verify(&macaroon, &access("invoices:read"))
+        .is_err());
+    assert!(macaroon_auth
+        .bake(&MacaroonRestrictions {
+            permissions: vec!["invoices:everything".to_string()],
+            ..Default::default()
+        })
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_root_key_id_limit() {
+    let macaroon_auth = test_macaroon_auth().await;
+    let restrictions = |root_key_id| MacaroonRestrictions {
+        root_key_id: Some(root_key_id),
+        permissions: vec!["invoices:read".to_string()],
+        ..Default::default()
+    };
+    assert!(macaroon_auth.bake(&restrictions(u64::MAX)).await.is_err());
+    let (_, root_key_id) = macaroon_auth
+        .bake(&restrictions(MAX_ROOT_KEY_ID))
+        .await
+        .unwrap();
+    assert_eq!(MAX_ROOT_KEY_ID, root_key_id);
+    let (_, root_key_id) = macaroon_auth
+        .bake(&MacaroonRestrictions {
+            permissions: vec!["invoices:read".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(MAX_ROOT_KEY_ID + 1, root_key_id);
+}
+
+#[tokio::test]
+async fn test_revoke_root_key() {
+    let store = Arc::new(MemoryKeyStore::default());
+    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "", store.clone())
+        .await
+        .unwrap();
+    let restrictions = MacaroonRestrictions {
+        root_key_id: Some(7),
+        permissions: vec!["invoices:write".to_string()],
+        ..Default::default()
+    };
+    let (first, root_key_id) = macaroon_auth.bake(&restrictions).await.unwrap();
+    assert_eq!(7, root_key_id);
+    let (second, _) = macaroon_auth.bake(&restrictions).await.unwrap();
+    macaroon_auth
+        .verify(&second, &access("invoices:write"))
+        .unwrap();
+
+    let revoked = macaroon_auth.revoke_root_key(7).await.unwrap().unwrap();
+    assert!(revoked.is_revoked());
+    assert!(macaroon_auth
+        .verify(&first, &access("invoices:write"))
+        .is_err());
+    assert!(macaroon_auth
+        .verify(&second, &access("invoices:write"))
+        .is_err());
+    assert!(macaroon_auth.bake(&restrictions).await.is_err());
+    assert_eq!(None, macaroon_auth.revoke_root_key(8).await.unwrap());
 
+    // The admin key is replaced with a new one when it is revoked.
+    let admin_macaroon = macaroon_auth.admin_macaroon(0).unwrap();
+    macaroon_auth.revoke_root_key(0).await.unwrap();
+    assert!(macaroon_auth
+        .verify(&admin_macaroon, &access("info:read"))
+        .is_err());
+    let admin_key = macaroon_auth
+        .root_keys()
+        .unwrap()
+        .into_iter()
+        .find(|key| key.admin && !key.is_revoked())
+        .unwrap();
+    assert_eq!(8, admin_key.id);
     macaroon_auth
-        .verify_admin_macaroon(&admin_macaroon)
+        .verify(
+            &macaroon_auth.admin_macaroon(admin_key.id).unwrap(),
+            &access("info:read"),
+        )
         .unwrap();
+
+    // Revocations are kept across restarts.
+    let restarted = MacaroonAuth::init(&[3u8; 32], "", store).await.unwrap();
+    assert!(restarted.verify(&first, &access("invoices:write")).is_err());
+    assert_eq!(3, restarted.root_keys().unwrap().len());
+}
+
+#[test]
+fn test_ip_range() {
+    let range = IpRange::from_str("192.168.1.0/24").unwrap();
+    assert!(range.contains(&"192.168.1.77".parse().unwrap()));
+    assert!(!range.contains(&"192.168.2.1".parse().unwrap()));
+    assert!(!range.contains(&"::1".parse().unwrap()));
+    assert_eq!(
+        "10.0.0.1/32",
+        IpRange::from_str("10.0.0.1").unwrap().to_string()
+    );
+    assert!(IpRange::from_str("0.0.0.0/0")
+        .unwrap()
+        .contains(&"8.8.8.8".parse().unwrap()));
+    assert!(IpRange::from_str("::1/129").is_err());
 }
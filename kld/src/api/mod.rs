@@ -1,7 +1,12 @@
 mod channels;
+mod export;
 mod invoices;
+mod lsp;
+mod lsps1;
+mod lsps2;
 mod macaroon_auth;
 mod network;
+mod offers;
 pub mod payloads;
 mod payments;
 mod peers;
@@ -17,32 +22,42 @@ pub use macaroon_auth::{KldMacaroon, MacaroonAuth};
 use serde::{Deserialize, Deserializer};
 use serde_json::json;
 
+use self::payloads::{PageParams, SortOrder};
 use self::utility::get_info;
 use crate::{
     api::{
         channels::{
-            channel_history, close_channel, close_channel_with_fee,
+            channel_history, close_channel, close_channel_with_fee, fee_history,
             force_close_channel_with_broadcast, force_close_channel_without_broadcast,
-            list_channels, list_forwards, list_peer_channels, local_remote_balance, open_channel,
-            set_channel_fee,
+            forwarding_report, list_channels, list_forwards, list_peer_channels,
+            local_remote_balance, open_channel, rebalance_channel, set_channel_fee,
         },
-        invoices::{decode_invoice, generate_invoice, list_invoices},
-        macaroon_auth::{admin_auth, readonly_auth},
+        export::export,
+        invoices::{
+            cancel_invoice, decode_invoice, generate_invoice, list_invoices, settle_invoice,
+            wait_any_invoice, wait_invoice,
+        },
+        lsp::{buy_channel, generate_jit_invoice, lsp_info},
+        lsps1::list_lsps1_orders,
+        lsps2::{create_lsps2_token, delete_lsps2_token, list_jit_channels, list_lsps2_tokens},
+        macaroon_auth::{authorize, bake_macaroon, list_root_keys, revoke_root_key},
         network::{
-            fee_rates, get_network_channel, get_network_node, list_network_channels,
+            fee_rates, get_network_channel, get_network_node, get_route, list_network_channels,
             list_network_nodes,
         },
-        payments::{keysend, list_payments, pay_invoice},
+        offers::{create_offer, list_offers, pay_offer},
+        payments::{keysend, list_payments, pay_invoice, payment_status},
         peers::{connect_peer, disconnect_peer, list_peers},
-        utility::{estimate_channel_liquidity_range, get_fees, score, sign},
-        wallet::{get_balance, list_funds, new_address, transfer},
+        utility::{estimate_channel_liquidity_range, get_fees, score, sign, static_channel_backup},
+        wallet::{get_balance, list_funds, list_sweeps, new_address, transfer},
         ws::ws_handler,
     },
     bitcoind::bitcoind_interface::BitcoindInterface,
+    database::Pagination,
     ldk::LightningInterface,
     wallet::WalletInterface,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use axum::{
     extract::Extension,
     middleware::from_fn,
@@ -58,6 +73,7 @@ use futures::{future::Shared, Future};
 use hyper::StatusCode;
 use log::{error, info, warn};
 use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+use time::OffsetDateTime;
 use tower_http::cors::CorsLayer;
 
 pub const API_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -97,6 +113,7 @@ impl RestApi {
             )
             .route(routes::GET_BALANCE, get(get_balance))
             .route(routes::LIST_FUNDS, get(list_funds))
+            .route(routes::LIST_SWEEPS, get(list_sweeps))
             .route(routes::LIST_PEER_CHANNELS, get(list_peer_channels))
             .route(routes::LIST_PEERS, get(list_peers))
             .route(routes::LIST_NETWORK_NODE, get(get_network_node))
@@ -104,21 +121,34 @@ impl RestApi {
             .route(routes::LIST_NETWORK_CHANNEL, get(get_network_channel))
             .route(routes::LIST_NETWORK_CHANNELS, get(list_network_channels))
             .route(routes::FEE_RATES, get(fee_rates))
+            .route(routes::GET_ROUTE, get(get_route))
             .route(routes::LIST_INVOICES, get(list_invoices))
+            .route(routes::WAIT_INVOICE, get(wait_invoice))
+            .route(routes::WAIT_ANY_INVOICE, get(wait_any_invoice))
             .route(routes::LIST_PAYMENTS, get(list_payments))
+            .route(routes::PAYMENT_STATUS, get(payment_status))
             .route(routes::LOCAL_REMOTE_BALANCE, get(local_remote_balance))
             .route(routes::GET_FEES, get(get_fees))
             .route(routes::LIST_FORWARDS, get(list_forwards))
+            .route(routes::FORWARDING_REPORT, get(forwarding_report))
             .route(routes::LIST_CHANNEL_HISTORY, get(channel_history))
+            .route(routes::CHANNEL_FEE_HISTORY, get(fee_history))
             .route(routes::LIST_CHANNELS, get(list_channels))
             .route(routes::DECODE_INVOICE, get(decode_invoice))
+            .route(routes::LIST_OFFERS, get(list_offers))
             .route(routes::SCORER, get(score))
-            .layer(from_fn(readonly_auth));
+            .route(routes::BACKUP, get(static_channel_backup))
+            .route(routes::EXPORT, get(export))
+            .route(routes::LIST_ROOT_KEYS, get(list_root_keys))
+            .route(routes::LIST_JIT_CHANNELS, get(list_jit_channels))
+            .route(routes::LIST_LSPS1_ORDERS, get(list_lsps1_orders))
+            .route(routes::LSP_GET_INFO, get(lsp_info));
 
         let admin_routes = Router::new()
             .route(routes::SIGN, post(sign))
             .route(routes::OPEN_CHANNEL, post(open_channel))
             .route(routes::SET_CHANNEL_FEE, post(set_channel_fee))
+            .route(routes::REBALANCE_CHANNEL, post(rebalance_channel))
             .route(routes::CLOSE_CHANNEL, delete(close_channel))
             .route(
                 routes::CLOSE_CHANNEL_WITH_FEE,
@@ -138,12 +168,23 @@ impl RestApi {
             .route(routes::DISCONNECT_PEER, delete(disconnect_peer))
             .route(routes::KEYSEND, post(keysend))
             .route(routes::GENERATE_INVOICE, post(generate_invoice))
+            .route(routes::SETTLE_INVOICE, post(settle_invoice))
+            .route(routes::CANCEL_INVOICE, post(cancel_invoice))
             .route(routes::PAY_INVOICE, post(pay_invoice))
+            .route(routes::CREATE_OFFER, post(create_offer))
+            .route(routes::PAY_OFFER, post(pay_offer))
             .route(routes::WEBSOCKET, get(ws_handler))
-            .layer(from_fn(admin_auth));
+            .route(routes::BAKE_MACAROON, post(bake_macaroon))
+            .route(routes::REVOKE_ROOT_KEY, delete(revoke_root_key))
+            .route(routes::LIST_LSPS2_TOKENS, get(list_lsps2_tokens))
+            .route(routes::CREATE_LSPS2_TOKEN, post(create_lsps2_token))
+            .route(routes::DELETE_LSPS2_TOKEN, delete(delete_lsps2_token))
+            .route(routes::LSP_BUY_CHANNEL, post(buy_channel))
+            .route(routes::LSP_JIT_INVOICE, post(generate_jit_invoice));
 
         let routes = readonly_routes
             .merge(admin_routes)
+            .route_layer(from_fn(authorize))
             .fallback(handler_404)
             .layer(cors)
             .layer(Extension(bitcoind_api))
@@ -186,6 +227,7 @@ async fn config(certs_dir: &str) -> Result<RustlsConfig> {
 pub enum ApiError {
     Unauthorized,
     NotFound(String),
+    RequestTimeout(String),
     BadRequest(Box<dyn std::error::Error>),
     InternalServerError(Box<dyn std::error::Error>),
 }
@@ -198,6 +240,7 @@ impl IntoResponse for ApiError {
                 "Failed to verify macaroon".to_string(),
             ),
             ApiError::NotFound(s) => build_api_error(StatusCode::NOT_FOUND, s),
+            ApiError::RequestTimeout(s) => build_api_error(StatusCode::REQUEST_TIMEOUT, s),
             ApiError::BadRequest(e) => build_api_error(StatusCode::BAD_REQUEST, e.to_string()),
             ApiError::InternalServerError(e) => {
                 build_api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
@@ -252,6 +295,38 @@ pub mod codegen {
     include!(concat!(env!("OUT_DIR"), "/mod.rs"));
 }
 
+/// Check the paging parameters of a list request.
+pub(crate) fn pagination(params: PageParams) -> Result<Pagination, ApiError> {
+    let (from, to) = time_range(params.from, params.to)?;
+    Ok(Pagination {
+        offset: params.offset.unwrap_or_default(),
+        limit: params.limit,
+        from,
+        to,
+        descending: params.sort == Some(SortOrder::Desc),
+    })
+}
+
+/// Convert a range of unix timestamps from the query of a request.
+pub(crate) fn time_range(
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<(Option<OffsetDateTime>, Option<OffsetDateTime>), ApiError> {
+    let timestamp = |seconds: Option<u64>| {
+        seconds
+            .map(|s| OffsetDateTime::from_unix_timestamp(s as i64))
+            .transpose()
+            .map_err(bad_request)
+    };
+    let (from, to) = (timestamp(from)?, timestamp(to)?);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return Err(bad_request(anyhow!("from must be before to")));
+        }
+    }
+    Ok((from, to))
+}
+
 pub(crate) fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
@@ -1,24 +1,39 @@
 use super::payloads::{
-    FeeRates, FeeRatesResponse, NetworkChannel, NetworkNode, OnChainFeeEstimates,
+    FeeRates, FeeRatesResponse, GetRouteQuery, GetRouteResponse, NetworkChannel, NetworkNode,
+    NetworkNodesQuery, OnChainFeeEstimates, RouteHop, RoutePath,
 };
 use crate::api::SocketAddress;
 use anyhow::anyhow;
-use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
 use bitcoin::secp256k1::PublicKey;
+use lightning::ln::channelmanager::MIN_FINAL_CLTV_EXPIRY_DELTA;
 use lightning::routing::gossip::{ChannelInfo, ChannelUpdateInfo, NodeId, NodeInfo};
 use std::{str::FromStr, sync::Arc};
 
-use crate::{bitcoind::bitcoind_interface::BitcoindInterface, ldk::LightningInterface};
+use crate::{
+    bitcoind::bitcoind_interface::BitcoindInterface,
+    ldk::{LightningInterface, RouteQuery},
+};
 
 use super::{bad_request, internal_server, ApiError};
 
 pub(crate) async fn list_network_nodes(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(query): Query<NetworkNodesQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let after = query
+        .after
+        .map(|id| PublicKey::from_str(&id).map(|pk| NodeId::from_pubkey(&pk)))
+        .transpose()
+        .map_err(bad_request)?;
     let nodes: Vec<NetworkNode> = lightning_interface
-        .nodes()
-        .unordered_iter()
-        .filter_map(|(node_id, announcement)| to_api_node(node_id, announcement))
+        .nodes(after, query.limit)
+        .iter()
+        .filter_map(|(node_id, node_info)| to_api_node(node_id, node_info))
         .collect();
     Ok(Json(nodes))
 }
@@ -58,6 +73,83 @@ pub(crate) async fn list_network_channels(
     Ok(Json(channels))
 }
 
+pub(crate) async fn get_route(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(query): Query<GetRouteQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let destination = PublicKey::from_str(&query.destination).map_err(bad_request)?;
+    if destination == lightning_interface.identity_pubkey() {
+        return Err(bad_request(anyhow!("Can not find a route to ourselves")));
+    }
+    if query.amount_msat == 0 {
+        return Err(bad_request(anyhow!("amountMsat must be positive")));
+    }
+    let exclude_channels = split_list(&query.exclude_channels)
+        .map(u64::from_str)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bad_request)?;
+    let exclude_nodes = split_list(&query.exclude_nodes)
+        .map(PublicKey::from_str)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bad_request)?;
+    if exclude_nodes.contains(&destination) {
+        return Err(bad_request(anyhow!("The destination can not be excluded")));
+    }
+    let estimates = lightning_interface
+        .find_route(RouteQuery {
+            destination,
+            amount: query.amount_msat,
+            max_fee_msat: query.max_fee_msat,
+            final_cltv_expiry_delta: query.cltv.unwrap_or(MIN_FINAL_CLTV_EXPIRY_DELTA as u32),
+            exclude_channels,
+            exclude_nodes,
+        })
+        .map_err(internal_server)?;
+
+    let paths: Vec<RoutePath> = estimates
+        .iter()
+        .map(|estimate| {
+            let hops = &estimate.path.hops;
+            RoutePath {
+                amount_msat: estimate.path.final_value_msat(),
+                fee_msat: estimate.path.fee_msat(),
+                total_cltv_expiry_delta: hops.iter().map(|hop| hop.cltv_expiry_delta).sum(),
+                probability: estimate.success_probability(),
+                hops: hops
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hop)| RouteHop {
+                        node_id: hop.pubkey.to_string(),
+                        alias: lightning_interface.alias_of(&hop.pubkey),
+                        short_channel_id: hop.short_channel_id,
+                        amount_msat: hops[i..].iter().map(|hop| hop.fee_msat).sum(),
+                        // The fee of the last hop is the amount that the destination receives.
+                        fee_msat: if i + 1 < hops.len() { hop.fee_msat } else { 0 },
+                        cltv_expiry_delta: hop.cltv_expiry_delta,
+                        probability: estimate.hop_probabilities.get(i).copied().flatten(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    Ok(Json(GetRouteResponse {
+        amount_msat: paths.iter().map(|path| path.amount_msat).sum(),
+        fee_msat: paths.iter().map(|path| path.fee_msat).sum(),
+        probability: paths
+            .iter()
+            .map(|path| path.probability)
+            .try_fold(1.0, |probability, p| p.map(|p| probability * p)),
+        paths,
+    }))
+}
+
+fn split_list(list: &Option<String>) -> impl Iterator<Item = &str> {
+    list.iter()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
 const CHANNEL_OPEN_VB: u32 = 152;
 const MUTUAL_CLOSE_VB: u32 = 130;
 const UNILATERAL_CLOSE_VB: u32 = 150;
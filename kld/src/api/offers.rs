@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use super::payloads::{CreateOffer, Offer, OfferStatus, PayOffer, PaymentResponse};
+use anyhow::anyhow;
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+
+use super::empty_string_as_none;
+use crate::ldk::LightningInterface;
+
+use super::macaroon_auth::AmountLimit;
+use super::{bad_request, internal_server, ApiError};
+
+impl From<crate::database::offer::Offer> for Offer {
+    fn from(offer: crate::database::offer::Offer) -> Self {
+        Offer {
+            id: hex::encode(offer.id),
+            label: offer.label.clone(),
+            bolt12: offer.bolt12.to_string(),
+            description: offer.description.clone(),
+            status: if offer.is_expired() {
+                OfferStatus::Expired
+            } else {
+                OfferStatus::Active
+            },
+            amount_msat: offer.amount,
+            expires_at: offer.expiry,
+            created_at: offer.timestamp.unix_timestamp() as u64,
+        }
+    }
+}
+
+pub(crate) async fn create_offer(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(offer_request): Json<CreateOffer>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(label) = &offer_request.label {
+        if label.len() > 100 {
+            return Err(bad_request(anyhow!("Label max length is 100 chars")));
+        }
+    }
+    let offer = lightning_interface
+        .create_offer(
+            offer_request.label,
+            offer_request.amount,
+            offer_request.description,
+            offer_request.expiry,
+        )
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(Offer::from(offer)))
+}
+
+pub(crate) async fn pay_offer(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
+    Json(pay_offer_request): Json<PayOffer>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offer: crate::database::offer::Offer =
+        pay_offer_request.offer.try_into().map_err(bad_request)?;
+    if offer.amount.is_none() && pay_offer_request.amount.is_none() {
+        return Err(bad_request(anyhow!(
+            "Amount is required for an offer without an amount"
+        )));
+    }
+    let amount = pay_offer_request.amount.or(offer.amount);
+    limit.check_with_fee(amount.unwrap_or_default(), pay_offer_request.max_fee_msat)?;
+    let destination = offer.bolt12.signing_pubkey().to_string();
+    let payment = lightning_interface
+        .pay_offer(
+            offer,
+            pay_offer_request.amount,
+            pay_offer_request.max_fee_msat,
+            pay_offer_request.payer_note,
+            pay_offer_request.label,
+        )
+        .await
+        .map_err(internal_server)?;
+    let response = PaymentResponse {
+        payment_id: hex::encode(payment.id.0),
+        destination,
+        payment_hash: payment.hash.map(|h| hex::encode(h.0)).unwrap_or_default(),
+        created_at: payment.timestamp.unix_timestamp() as u64,
+        parts: 1,
+        amount_msat: amount,
+        amount_sent_msat: payment.amount,
+        payment_preimage: payment
+            .preimage
+            .map(|i| hex::encode(i.0))
+            .unwrap_or_default(),
+        status: payment.status.to_string(),
+    };
+    Ok(Json(response))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOffersParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub label: Option<String>,
+}
+
+pub(crate) async fn list_offers(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListOffersParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let offers: Vec<Offer> = lightning_interface
+        .list_offers(params.label)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(Offer::from)
+        .collect();
+    Ok(Json(offers))
+}
@@ -1,4 +1,4 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
 use bitcoin::Transaction;
 use serde::{de::Visitor, Deserialize, Serialize};
@@ -152,6 +152,22 @@ pub struct FundChannel {
     pub request_amt: Option<String>,
     /// Compact representation of the peer's expected channel lease terms
     pub compact_lease: Option<String>,
+    /// Further channels to open with the same funding transaction
+    #[serde(default)]
+    pub batch: Vec<BatchChannel>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchChannel {
+    /// Pub key of the peer
+    pub id: String,
+    /// Amount in satoshis
+    pub satoshis: String,
+    /// Amount of millisatoshis to push to the channel peer at open
+    pub push_msat: Option<String>,
+    /// Flag to announce the channel
+    pub announce: Option<bool>,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -283,6 +299,9 @@ pub struct FundChannelResponse {
     pub txid: String,
     /// channel_id of the newly created channel (hex)
     pub channel_id: String,
+    /// channel_ids of all channels funded by the transaction, in the order of the request (hex)
+    #[serde(default)]
+    pub channel_ids: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -313,6 +332,231 @@ pub struct SetChannelFee {
 #[derive(Serialize, Deserialize)]
 pub struct SetChannelFeeResponse(pub Vec<SetChannelFee>);
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CloseChannel {
+    // Address our share of the channel funds is paid to instead of the node wallet
+    pub address: Option<String>,
+    // Refuse to start the close when the first fee rate we would propose, in sats per 1000 weight,
+    // is above this. The fee negotiated with the peer can still end up higher.
+    pub max_initial_fee_rate: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RebalanceChannel {
+    // Channel ID or short channel ID to move liquidity out of
+    pub outbound_channel_id: String,
+    // Channel ID or short channel ID to move liquidity into
+    pub inbound_channel_id: String,
+    // Amount in milli satoshis
+    pub amount: u64,
+    // Maximum total routing fee in parts per million of the amount, defaults to the node setting
+    #[serde(default)]
+    pub max_fee_ppm: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebalanceResponse {
+    pub id: String,
+    pub payment_id: String,
+    pub outbound_channel_id: String,
+    pub inbound_channel_id: String,
+    pub amount_msat: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_msat: Option<u64>,
+    pub status: String,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FeeHistoryQuery {
+    // Only return the fee changes of this channel ID
+    pub channel_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ForwardingReportQuery {
+    // Length of the periods to group forwards by: day, week or month (default day)
+    pub bucket: Option<String>,
+    // Only include forwards at or after this unix timestamp
+    pub from: Option<u64>,
+    // Only include forwards before this unix timestamp
+    pub to: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ExportQuery {
+    // csv (default) or jsonl, labels are always jsonl
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardingReport {
+    pub bucket: String,
+    pub periods: Vec<ForwardingPeriod>,
+    // Least profitable channels first.
+    pub channels: Vec<ChannelProfitability>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardingPeriod {
+    pub period_start: u64,
+    pub in_channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub out_channel: Option<String>,
+    pub forwards: u64,
+    pub volume_msat: u64,
+    pub fees_msat: u64,
+    // Failed forwards by the kind of HTLC destination they failed at.
+    pub failures: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelProfitability {
+    pub channel_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    pub is_open: bool,
+    pub volume_in_msat: u64,
+    pub volume_out_msat: u64,
+    // Fees are earned by the channel the HTLC was forwarded out of.
+    pub fees_earned_msat: u64,
+    pub failed_in: u64,
+    pub failed_out: u64,
+    // Fee of the funding transaction if we opened the channel, shared between the channels it funded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_cost_sat: Option<u64>,
+    pub profit_msat: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortOrder {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err("not a valid sort order, must be asc or desc"),
+        }
+    }
+}
+
+// Query parameters of the list endpoints that can be paged, the items are ordered by time.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct PageParams {
+    // Number of items to skip
+    pub offset: Option<u64>,
+    // Maximum number of items to return
+    pub limit: Option<u64>,
+    // Only return items at or after this unix timestamp
+    pub from: Option<u64>,
+    // Only return items before this unix timestamp
+    pub to: Option<u64>,
+    // Oldest first by default
+    pub sort: Option<SortOrder>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct NetworkNodesQuery {
+    // Only return nodes with an ID after this one, pass the last node ID of the previous page
+    pub after: Option<String>,
+    // Maximum number of nodes to return
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRouteQuery {
+    // Node ID of the payee
+    pub destination: String,
+    pub amount_msat: u64,
+    // Limit on the total routing fees
+    pub max_fee_msat: Option<u64>,
+    // CLTV expiry delta required by the destination (blocks)
+    pub cltv: Option<u32>,
+    // Comma separated short channel IDs that the route must avoid
+    pub exclude_channels: Option<String>,
+    // Comma separated node IDs that the route must avoid
+    pub exclude_nodes: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteHop {
+    pub node_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub short_channel_id: u64,
+    // Amount forwarded over the channel to this node
+    pub amount_msat: u64,
+    // Fee this node takes to forward to the next hop
+    pub fee_msat: u64,
+    pub cltv_expiry_delta: u32,
+    // Chance that the channel forwards the amount, not set if the scorer knows nothing about it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutePath {
+    pub amount_msat: u64,
+    pub fee_msat: u64,
+    pub total_cltv_expiry_delta: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    pub hops: Vec<RouteHop>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRouteResponse {
+    pub amount_msat: u64,
+    pub fee_msat: u64,
+    // Chance that every path succeeds, from the estimates of the hops the scorer knows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    // More than one if the payment is split
+    pub paths: Vec<RoutePath>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeUpdate {
+    pub id: String,
+    pub channel_id: String,
+    pub previous_ppm: u32,
+    pub ppm: u32,
+    pub base_msat: u32,
+    pub outbound_ratio: f64,
+    pub forwarded_msat: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Sweep {
+    pub txid: String,
+    pub status: String,
+    // The channel outputs spent by the sweep as txid:vout.
+    pub outputs: Vec<String>,
+    pub amount_sat: u64,
+    pub fee_rate: u32,
+    pub broadcast_height: u32,
+    pub confirmation_height: Option<u32>,
+    pub timestamp: u64,
+}
+
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct Peer {
     pub id: String,
@@ -332,6 +576,194 @@ pub struct NetworkNode {
     pub addresses: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct BakeMacaroon {
+    // Root key to sign with so that macaroons can be revoked together, a new one by default
+    pub root_key_id: Option<u64>,
+    // Permissions of the macaroon, e.g. invoices:read and invoices:write
+    pub permissions: Vec<String>,
+    // Unix timestamp after which the macaroon is no longer valid
+    pub expires_at: Option<u64>,
+    // Largest amount in milli satoshis a single payment or withdrawal may spend
+    pub max_amount_msat: Option<u64>,
+    // IP addresses or CIDR ranges the macaroon may be used from
+    #[serde(default)]
+    pub ip_ranges: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BakeMacaroonResponse {
+    // Base64 encoded, to be sent in the macaroon header
+    pub macaroon: String,
+    pub permissions: Vec<String>,
+    // Revoking this root key revokes the macaroon
+    pub root_key_id: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RootKey {
+    pub id: u64,
+    // Signs the admin and readonly macaroons in the data directory
+    pub admin: bool,
+    // Unix timestamps
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CreateLsps2Token {
+    // A random token is created if none is given, an existing token gets the new fees
+    pub token: Option<String>,
+    pub min_fee_msat: u64,
+    // Fee in millionths of the payment size, the higher of this and the minimum fee is charged
+    pub proportional: u32,
+    // How long clients can buy a channel at the offered fees, 600 seconds by default
+    pub valid_for_secs: Option<u32>,
+    pub min_payment_size_msat: u64,
+    pub max_payment_size_msat: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Lsps2Token {
+    pub token: String,
+    pub min_fee_msat: u64,
+    pub proportional: u32,
+    pub valid_for_secs: u32,
+    pub min_payment_size_msat: u64,
+    pub max_payment_size_msat: u64,
+    // Unix timestamp
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JitChannel {
+    // Above 2^63 so it is given as a string
+    pub user_channel_id: String,
+    pub token: Option<String>,
+    pub counterparty: String,
+    pub intercept_scid: u64,
+    // None for variable amount invoices
+    pub payment_size_msat: Option<u64>,
+    pub opening_fee_msat: Option<u64>,
+    pub channel_value_sats: Option<u64>,
+    pub channel_id: Option<String>,
+    // requested, opening, ready, failed or closed
+    pub status: String,
+    // Unix timestamps
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Lsps1Order {
+    pub order_id: String,
+    pub counterparty: String,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub channel_expiry_blocks: u32,
+    pub token: Option<String>,
+    pub announce_channel: bool,
+    pub fee_total_sat: u64,
+    pub order_total_sat: u64,
+    pub payment_hash: String,
+    pub bolt11_invoice: String,
+    pub onchain_address: String,
+    pub refund_onchain_address: Option<String>,
+    // created, completed or failed
+    pub order_state: String,
+    // expect_payment, paid or refunded
+    pub payment_state: String,
+    pub funding_outpoint: Option<String>,
+    // Unix timestamps
+    pub expires_at: u64,
+    pub funded_at: Option<u64>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LspInfo {
+    // None if the LSP does not sell channels up front with LSPS1
+    pub lsps1: Option<Lsps1LspOptions>,
+    // None if the LSP does not sell JIT channels with LSPS2
+    pub lsps2_opening_fees: Option<Vec<Lsps2OpeningFee>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Lsps1LspOptions {
+    pub min_required_channel_confirmations: u16,
+    pub min_funding_confirms_within_blocks: u16,
+    pub supports_zero_channel_reserve: bool,
+    pub max_channel_expiry_blocks: u32,
+    pub min_initial_client_balance_sat: u64,
+    pub max_initial_client_balance_sat: u64,
+    pub min_initial_lsp_balance_sat: u64,
+    pub max_initial_lsp_balance_sat: u64,
+    pub min_channel_balance_sat: u64,
+    pub max_channel_balance_sat: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Lsps2OpeningFee {
+    pub min_fee_msat: u64,
+    // Fee in millionths of the payment size, the higher of this and the minimum fee is charged
+    pub proportional: u32,
+    // Unix timestamp
+    pub valid_until: u64,
+    pub min_lifetime: u32,
+    pub max_client_to_self_delay: u32,
+    pub min_payment_size_msat: u64,
+    pub max_payment_size_msat: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct BuyChannel {
+    // 33 byte, hex-encoded, pubkey of the connected LSP
+    pub node_id: String,
+    // Inbound liquidity of the channel
+    pub lsp_balance_sat: u64,
+    // Paid with the order and pushed to us when the channel is opened
+    #[serde(default)]
+    pub client_balance_sat: u64,
+    // How long the LSP keeps the channel open, 4320 blocks by default
+    pub channel_expiry_blocks: Option<u32>,
+    #[serde(default)]
+    pub announce_channel: bool,
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LspOrder {
+    pub order_id: String,
+    // CREATED, COMPLETED or FAILED
+    pub order_state: String,
+    // EXPECT_PAYMENT, PAID or REFUNDED
+    pub payment_state: String,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub fee_total_sat: u64,
+    pub order_total_sat: u64,
+    // Pay either the invoice or the address before the order expires
+    pub bolt11_invoice: String,
+    pub onchain_address: String,
+    // ISO 8601 timestamp
+    pub expires_at: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct GenerateJitInvoice {
+    // 33 byte, hex-encoded, pubkey of the connected LSP that opens the channel
+    pub node_id: String,
+    pub token: Option<String>,
+    // Amount in milli satoshis, the opening fee is deducted from it. Any amount if not set.
+    pub amount: Option<u64>,
+    // Unique label for the invoice
+    pub label: String,
+    pub description: String,
+    // Expiry time period for the invoice (seconds)
+    pub expiry: Option<u32>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SignRequest {
     pub message: String,
@@ -358,11 +790,18 @@ pub struct KeysendRequest {
     pub maxdelay: Option<u64>,
     // Amount for which the maxfeepercent check is skipped
     pub exemptfee: Option<u64>,
+    // Maximum total routing fee (milli satoshis)
+    #[serde(default)]
+    pub max_fee_msat: Option<u64>,
+    // Return once the payment is sent instead of waiting for the result
+    #[serde(default)]
+    pub non_blocking: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PaymentResponse {
+    pub payment_id: String,
     pub destination: String,
     pub payment_hash: String,
     pub created_at: u64,
@@ -389,6 +828,21 @@ pub struct GenerateInvoice {
     pub fallbacks: Option<Vec<String>>,
     // 64-digit hex string to be used as payment preimage for the created invoice. IMPORTANT> if you specify the preimage, you are responsible, to ensure appropriate care for generating using a secure pseudorandom generator seeded with sufficient entropy, and keeping the preimage secret. This parameter is an advanced feature intended for use with cutting-edge cryptographic protocols and should not be used unless explicitly needed.
     pub preimage: Option<String>,
+    // 64-digit hex string of the payment hash to create a hold invoice. Payments are held until the invoice is settled with the preimage or canceled.
+    #[serde(default)]
+    pub payment_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SettleInvoice {
+    // 64-digit hex string of the preimage of the held invoice
+    pub preimage: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CancelInvoice {
+    // 64-digit hex string of the payment hash of the invoice
+    pub payment_hash: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -396,6 +850,9 @@ pub enum InvoiceStatus {
     Unpaid,
     Paid,
     Expired,
+    // A payment to a hold invoice waits to be settled or canceled.
+    Held,
+    Canceled,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -411,6 +868,9 @@ pub struct Invoice {
     pub amount_received_msat: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub paid_at: Option<u32>,
+    // Increases by one with every paid invoice, for waitAnyInvoice.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pay_index: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_at: Option<u64>,
 }
@@ -423,10 +883,235 @@ pub struct GenerateInvoiceResponse {
     pub bolt11: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct PayInvoice {
     pub invoice: String,
     pub label: Option<String>,
+    // Amount in milli satoshis, required if the invoice has no amount
+    #[serde(default)]
+    pub amount: Option<u64>,
+    // Maximum total routing fee (milli satoshis)
+    #[serde(default)]
+    pub max_fee_msat: Option<u64>,
+    // Maximum total routing fee in parts per million of the amount
+    #[serde(default)]
+    pub max_fee_ppm: Option<u32>,
+    // Keep retrying to find routes for this long (seconds)
+    #[serde(default)]
+    pub retry_for: Option<u64>,
+    // Maximum number of paths the payment can be split over
+    #[serde(default)]
+    pub max_paths: Option<u8>,
+    // Maximum total CLTV expiry delta of the route (blocks)
+    #[serde(default)]
+    pub max_cltv_expiry_delta: Option<u32>,
+    // Return once the payment is sent instead of waiting for the result
+    #[serde(default)]
+    pub non_blocking: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentHop {
+    pub node_id: String,
+    pub short_channel_id: u64,
+    pub fee_msat: u64,
+    pub cltv_expiry_delta: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentAttempt {
+    pub status: String,
+    pub hops: Vec<PaymentHop>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+    // The channel that caused the failure, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_short_channel_id: Option<u64>,
+    // The payment will not be retried after this failure
+    pub failed_permanently: bool,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentStatusResponse {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_preimage: Option<String>,
+    pub status: String,
+    pub direction: String,
+    pub amount_msat: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_msat: Option<u64>,
+    pub created_at: u64,
+    pub attempts: Vec<PaymentAttempt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CreateOffer {
+    // Amount in milli satoshis, the payer chooses the amount if not set
+    pub amount: Option<u64>,
+    // Label for the offer
+    pub label: Option<String>,
+    // Description for the offer
+    pub description: String,
+    // Expiry time period for the offer (seconds)
+    pub expiry: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PayOffer {
+    // The bech32 encoded bolt12 offer
+    pub offer: String,
+    // Amount in milli satoshis, required if the offer has no amount
+    pub amount: Option<u64>,
+    // Note to include in the invoice request
+    pub payer_note: Option<String>,
+    // Label for the payment
+    pub label: Option<String>,
+    // Maximum total routing fee (milli satoshis)
+    #[serde(default)]
+    pub max_fee_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum OfferStatus {
+    Active,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Offer {
+    pub id: String,
+    pub label: Option<String>,
+    pub bolt12: String,
+    pub description: String,
+    pub status: OfferStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_msat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    pub created_at: u64,
+}
+
+/// Groups of events that a websocket client can subscribe to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTopic {
+    Channels,
+    Payments,
+    Forwards,
+    Htlcs,
+    Outputs,
+    Blocks,
+}
+
+/// Events pushed to websocket clients.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    ChannelPending {
+        channel_id: String,
+        counterparty_node_id: String,
+        funding_txo: String,
+    },
+    ChannelReady {
+        channel_id: String,
+        counterparty_node_id: String,
+    },
+    ChannelClosed {
+        channel_id: String,
+        reason: String,
+    },
+    PaymentSent {
+        payment_id: Option<String>,
+        payment_hash: String,
+        fee_msat: Option<u64>,
+    },
+    PaymentFailed {
+        payment_id: String,
+        payment_hash: String,
+        reason: Option<String>,
+    },
+    PaymentClaimed {
+        payment_hash: String,
+        amount_msat: u64,
+    },
+    PaymentForwarded {
+        inbound_channel_id: Option<String>,
+        outbound_channel_id: Option<String>,
+        amount_msat: Option<u64>,
+        fee_msat: Option<u64>,
+    },
+    HtlcHandlingFailed {
+        inbound_channel_id: String,
+        destination: String,
+    },
+    SpendableOutputs {
+        channel_id: Option<String>,
+        outputs: usize,
+    },
+    NewBlock {
+        height: u32,
+        hash: String,
+    },
+}
+
+impl StreamEvent {
+    pub fn topic(&self) -> EventTopic {
+        match self {
+            StreamEvent::ChannelPending { .. }
+            | StreamEvent::ChannelReady { .. }
+            | StreamEvent::ChannelClosed { .. } => EventTopic::Channels,
+            StreamEvent::PaymentSent { .. }
+            | StreamEvent::PaymentFailed { .. }
+            | StreamEvent::PaymentClaimed { .. } => EventTopic::Payments,
+            StreamEvent::PaymentForwarded { .. } => EventTopic::Forwards,
+            StreamEvent::HtlcHandlingFailed { .. } => EventTopic::Htlcs,
+            StreamEvent::SpendableOutputs { .. } => EventTopic::Outputs,
+            StreamEvent::NewBlock { .. } => EventTopic::Blocks,
+        }
+    }
+}
+
+/// Message sent by a websocket client to change the topics it receives.
+#[derive(Serialize, Deserialize, Default)]
+pub struct EventSubscription {
+    #[serde(default)]
+    pub subscribe: Vec<EventTopic>,
+    #[serde(default)]
+    pub unsubscribe: Vec<EventTopic>,
+}
+
+/// Reply to an `EventSubscription` with all the topics the client is now subscribed to.
+#[derive(Serialize, Deserialize)]
+pub struct EventSubscriptionResponse {
+    pub topics: Vec<EventTopic>,
+}
+
+#[test]
+fn test_stream_event() -> Result<(), serde_json::Error> {
+    let event = StreamEvent::NewBlock {
+        height: 100,
+        hash: "abcd".to_string(),
+    };
+    assert_eq!(EventTopic::Blocks, event.topic());
+    assert_eq!(
+        r#"{"type":"new_block","height":100,"hash":"abcd"}"#,
+        serde_json::to_string(&event)?
+    );
+    let subscription: EventSubscription =
+        serde_json::from_str(r#"{"subscribe":["payments","forwards"]}"#)?;
+    assert_eq!(
+        vec![EventTopic::Payments, EventTopic::Forwards],
+        subscription.subscribe
+    );
+    assert!(subscription.unsubscribe.is_empty());
+    Ok(())
 }
 
 #[test]
@@ -1,16 +1,23 @@
 use std::{str::FromStr, sync::Arc};
 
-use super::payloads::{KeysendRequest, PayInvoice, PaymentResponse};
+use super::payloads::{
+    KeysendRequest, PageParams, PayInvoice, PaymentAttempt, PaymentHop, PaymentResponse,
+    PaymentStatusResponse,
+};
 use anyhow::{anyhow, Context};
-use axum::{extract::Query, response::IntoResponse, Extension, Json};
-use lightning::routing::gossip::NodeId;
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use lightning::{ln::channelmanager::PaymentId, routing::gossip::NodeId};
 
 use crate::{
     database::{
         invoice::Invoice,
         payment::{PaymentDirection, PaymentStatus},
     },
-    ldk::LightningInterface,
+    ldk::{path_failure_to_string, LightningInterface, PaymentOptions},
 };
 
 use super::{
@@ -19,20 +26,30 @@ use super::{
         GetV1PayListPaymentsResponse, GetV1PayListPaymentsResponsePaymentsItem,
         GetV1PayListPaymentsResponsePaymentsItemStatus,
     },
-    empty_string_as_none, internal_server, ApiError,
+    empty_string_as_none, internal_server,
+    macaroon_auth::AmountLimit,
+    pagination, ApiError,
 };
 
 pub(crate) async fn keysend(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
     Json(keysend_request): Json<KeysendRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
+    limit.check_with_fee(keysend_request.amount, keysend_request.max_fee_msat)?;
     let node_id = NodeId::from_str(&keysend_request.pubkey)
         .map_err(|_| bad_request(anyhow!("node id decode error")))?;
     let payment = lightning_interface
-        .keysend_payment(node_id, keysend_request.amount)
+        .keysend_payment(
+            node_id,
+            keysend_request.amount,
+            keysend_request.max_fee_msat,
+            keysend_request.non_blocking.unwrap_or_default(),
+        )
         .await
         .map_err(internal_server)?;
     let response = PaymentResponse {
+        payment_id: hex::encode(payment.id.0),
         destination: keysend_request.pubkey,
         payment_hash: hex::encode(
             payment
@@ -56,19 +73,50 @@ pub(crate) async fn keysend(
 
 pub(crate) async fn pay_invoice(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
     Json(pay_invoice_request): Json<PayInvoice>,
 ) -> Result<impl IntoResponse, ApiError> {
     let invoice: Invoice = pay_invoice_request
         .invoice
         .try_into()
         .map_err(bad_request)?;
+    if invoice.amount.is_none() && pay_invoice_request.amount.is_none() {
+        return Err(bad_request(anyhow!(
+            "Amount is required for an invoice without an amount"
+        )));
+    }
+    if let (Some(invoice_amount), Some(amount)) = (invoice.amount, pay_invoice_request.amount) {
+        if amount < invoice_amount {
+            return Err(bad_request(anyhow!(
+                "Amount {amount} is less than the invoice amount {invoice_amount}"
+            )));
+        }
+    }
+    if pay_invoice_request.max_paths == Some(0) {
+        return Err(bad_request(anyhow!("Max paths must be at least 1")));
+    }
     let destination = invoice.payee_pub_key.to_string();
     let amount = invoice.amount;
+    let options = PaymentOptions {
+        amount: pay_invoice_request.amount,
+        max_fee_msat: pay_invoice_request.max_fee_msat,
+        max_fee_ppm: pay_invoice_request.max_fee_ppm,
+        retry_for: pay_invoice_request.retry_for,
+        max_paths: pay_invoice_request.max_paths,
+        max_cltv_expiry_delta: pay_invoice_request.max_cltv_expiry_delta,
+        non_blocking: pay_invoice_request.non_blocking.unwrap_or_default(),
+    };
+    let pay_amount = pay_invoice_request
+        .amount
+        .or(invoice.amount)
+        .unwrap_or_default();
+    limit.check_with_fee(pay_amount, options.max_total_routing_fee_msat(pay_amount))?;
     let payment = lightning_interface
-        .pay_invoice(invoice, pay_invoice_request.label)
+        .pay_invoice(invoice, pay_invoice_request.label, options)
         .await
         .map_err(internal_server)?;
     let response = PaymentResponse {
+        payment_id: hex::encode(payment.id.0),
         destination,
         payment_hash: hex::encode(
             payment
@@ -102,6 +150,7 @@ pub struct ListPaysParams {
 pub(crate) async fn list_payments(
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     Query(params): Query<ListPaysParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     let invoice = params
         .invoice
@@ -114,7 +163,7 @@ pub(crate) async fn list_payments(
         .transpose()
         .map_err(bad_request)?;
     let payments: Vec<GetV1PayListPaymentsResponsePaymentsItem> = lightning_interface
-        .list_payments(invoice, direction)
+        .list_payments(invoice, direction, pagination(page)?)
         .await
         .map_err(internal_server)?
         .into_iter()
@@ -141,3 +190,55 @@ pub(crate) async fn list_payments(
         .collect();
     Ok(Json(GetV1PayListPaymentsResponse { payments }))
 }
+
+pub(crate) async fn payment_status(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let payment_id = PaymentId(
+        hex::decode(&id)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| bad_request(anyhow!("invalid payment id")))?,
+    );
+    let payment = lightning_interface
+        .get_payment(payment_id)
+        .await
+        .map_err(internal_server)?
+        .ok_or_else(|| ApiError::NotFound(id.clone()))?;
+    let attempts = lightning_interface
+        .list_payment_attempts(payment_id)
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|attempt| PaymentAttempt {
+            status: attempt.status.to_string(),
+            hops: attempt
+                .path
+                .hops
+                .iter()
+                .map(|hop| PaymentHop {
+                    node_id: hop.pubkey.to_string(),
+                    short_channel_id: hop.short_channel_id,
+                    fee_msat: hop.fee_msat,
+                    cltv_expiry_delta: hop.cltv_expiry_delta,
+                })
+                .collect(),
+            failure: attempt.failure.as_ref().map(path_failure_to_string),
+            failed_short_channel_id: attempt.short_channel_id,
+            failed_permanently: attempt.payment_failed_permanently,
+            created_at: attempt.timestamp.unix_timestamp() as u64,
+        })
+        .collect();
+    Ok(Json(PaymentStatusResponse {
+        id,
+        payment_hash: payment.hash.map(|h| hex::encode(h.0)),
+        payment_preimage: payment.preimage.map(|p| hex::encode(p.0)),
+        status: payment.status.to_string(),
+        direction: payment.direction.to_string(),
+        amount_msat: payment.amount,
+        fee_msat: payment.fee,
+        created_at: payment.timestamp.unix_timestamp() as u64,
+        attempts,
+    }))
+}
@@ -8,11 +8,15 @@ pub const GET_INFO: &str = "/v1/getinfo";
 pub const GET_FEES: &str = "/v1/getFees";
 /// Estimate channel liquidity range to a particular node.
 pub const ESTIMATE_CHANNEL_LIQUIDITY: &str = "/v1/estimateChannelLiquidity";
-/// Websocket
+/// Websocket streaming node events for the topics the client subscribes to.
 pub const WEBSOCKET: &str = "/v1/ws";
 
 /// List on chain and channel funds
 pub const LIST_FUNDS: &str = "/v1/listFunds";
+/// Encrypted static channel backup to recover channel funds if the database is lost.
+pub const BACKUP: &str = "/v1/backup";
+/// Export transactions, payments, invoices, forwards or channels as csv or jsonl, or the wallet labels in BIP-329 format.
+pub const EXPORT: &str = "/v1/export/:kind";
 
 /// --- Peers ---
 /// Connect with a network peer.
@@ -38,17 +42,24 @@ pub const FORCE_CLOSE_CHANNEL_WITH_BROADCAST: &str =
     "/v1/channel/forceCloseChannelWithBoradCast/:id";
 pub const FORCE_CLOSE_CHANNEL_WITHOUT_BROADCAST: &str =
     "/v1/channel/forceCloseChannelWithoutBoradCast/:id";
+/// Move liquidity between two of our channels with a circular payment.
+pub const REBALANCE_CHANNEL: &str = "/v1/channel/rebalance";
 /// Fetch aggregate channel local and remote balances.
 pub const LOCAL_REMOTE_BALANCE: &str = "/v1/channel/localremotebal";
-/// Fetch the list of the forwarded htlcs.
+/// Fetch the list of the forwarded htlcs, paged with offset, limit, from, to and sort.
 pub const LIST_FORWARDS: &str = "/v1/channel/listForwards";
-/// Fetch our channel history.
+/// Volume, fees and failures of the forwards per period and channel, with the profitability of each channel.
+/// Group by bucket (day, week, month) and restrict the forwards with from and to.
+pub const FORWARDING_REPORT: &str = "/v1/channel/forwardingReport";
+/// Fetch our channel history, paged by the time the channels closed.
 pub const LIST_CHANNEL_HISTORY: &str = "/v1/channel/history";
+/// Fetch the fee changes made by the automatic fee adjustment.
+pub const CHANNEL_FEE_HISTORY: &str = "/v1/channel/feeHistory";
 
 /// --- Network ---
 /// Look up a node on the network.
 pub const LIST_NETWORK_NODE: &str = "/v1/network/listNode/:id";
-/// Return list of all nodes on the network
+/// Return list of the nodes on the network in node ID order. Page with limit and after (the last node ID of the previous page).
 pub const LIST_NETWORK_NODES: &str = "/v1/network/listNode";
 /// Look up a channel on the network
 pub const LIST_NETWORK_CHANNEL: &str = "/v1/network/listChannel/:id";
@@ -56,6 +67,8 @@ pub const LIST_NETWORK_CHANNEL: &str = "/v1/network/listChannel/:id";
 pub const LIST_NETWORK_CHANNELS: &str = "/v1/network/listChannel";
 /// Return feerate estimates, either satoshi-per-kw or satoshi-per-kb
 pub const FEE_RATES: &str = "/v1/network/feeRates/:style";
+/// Find the route a payment would take, with its fees and chance of success, without paying.
+pub const GET_ROUTE: &str = "/v1/network/getRoute";
 
 /// --- On chain wallet ---
 /// Returns total, confirmed and unconfirmed on-chain balances.
@@ -64,23 +77,73 @@ pub const GET_BALANCE: &str = "/v1/getBalance";
 pub const NEW_ADDR: &str = "/v1/newaddr";
 /// Withdraw on-chain funds to an address.
 pub const WITHDRAW: &str = "/v1/withdraw";
+/// List the transactions sweeping funds from closed channels into the wallet.
+pub const LIST_SWEEPS: &str = "/v1/listSweeps";
 
 /// --- Payments ---
 /// Send funds to a node without an invoice.
 pub const KEYSEND: &str = "/v1/pay/keysend";
 /// Pay a  bolt11 invoice.
 pub const PAY_INVOICE: &str = "/v1/pay";
-/// List payments.
+/// List payments, paged with offset, limit, from, to and sort.
 pub const LIST_PAYMENTS: &str = "/v1/pay/listPayments";
+/// Status of a payment and the paths that were attempted.
+pub const PAYMENT_STATUS: &str = "/v1/pay/status/:id";
 
 /// --- Invoices ---
 /// Generate a bolt11 invoice.
 pub const GENERATE_INVOICE: &str = "/v1/invoice/genInvoice";
-/// List the invoices on the node
+/// List the invoices on the node, paged with offset, limit, from, to and sort.
 pub const LIST_INVOICES: &str = "/v1/invoice/listInvoices";
+/// Wait until an invoice is paid, canceled or expired.
+pub const WAIT_INVOICE: &str = "/v1/invoice/waitInvoice/:label";
+/// Wait for the next paid invoice after the pay index given as lastpay_index.
+pub const WAIT_ANY_INVOICE: &str = "/v1/invoice/waitAnyInvoice";
+/// Claim the held payment of a hold invoice with its preimage.
+pub const SETTLE_INVOICE: &str = "/v1/invoice/settle";
+/// Fail the held payment of a hold invoice back to the payer.
+pub const CANCEL_INVOICE: &str = "/v1/invoice/cancel";
 /// Decode invoice
 pub const DECODE_INVOICE: &str = "/v1/utility/decode/:invoice";
 
+/// --- Offers ---
+/// Create a bolt12 offer.
+pub const CREATE_OFFER: &str = "/v1/offers/create";
+/// Request an invoice for a bolt12 offer and pay it.
+pub const PAY_OFFER: &str = "/v1/offers/pay";
+/// List the offers created by this node.
+pub const LIST_OFFERS: &str = "/v1/offers/listOffers";
+
+/// --- Macaroons ---
+/// Create a macaroon with a subset of the permissions, optionally expiring, with an amount limit or for some IP ranges only.
+pub const BAKE_MACAROON: &str = "/v1/macaroon/bake";
+/// List the root keys that macaroons are signed with.
+pub const LIST_ROOT_KEYS: &str = "/v1/macaroon/rootKeys";
+/// Revoke a root key and with it every macaroon signed with it.
+pub const REVOKE_ROOT_KEY: &str = "/v1/macaroon/rootKeys/:id";
+
+/// --- LSPS2 JIT channels ---
+/// List the tokens that clients can buy JIT channels with.
+pub const LIST_LSPS2_TOKENS: &str = "/v1/lsps2/listTokens";
+/// Create a token with the fees it offers, or change the fees of an existing token.
+pub const CREATE_LSPS2_TOKEN: &str = "/v1/lsps2/tokens";
+/// Delete a token, clients can no longer buy channels with it.
+pub const DELETE_LSPS2_TOKEN: &str = "/v1/lsps2/tokens/:token";
+/// List the JIT channels sold to clients and the fees they paid.
+pub const LIST_JIT_CHANNELS: &str = "/v1/lsps2/listJitChannels";
+
+/// --- LSPS1 channel orders ---
+/// List the channels ordered by clients and the state of their payments.
+pub const LIST_LSPS1_ORDERS: &str = "/v1/lsps1/listOrders";
+
+/// --- Buying channels from LSPs ---
+/// The channels a connected LSP sells with LSPS1 and the JIT channel fees it offers with LSPS2.
+pub const LSP_GET_INFO: &str = "/v1/lsp/getInfo";
+/// Order a channel from an LSP with LSPS1, it is opened once the order is paid.
+pub const LSP_BUY_CHANNEL: &str = "/v1/lsp/buyChannel";
+/// Generate an invoice that is paid through a JIT channel from an LSP.
+pub const LSP_JIT_INVOICE: &str = "/v1/lsp/jitInvoice";
+
 /// --- Kuutamo Apis ---
 pub const SCORER: &str = "/kld/scorer";
 pub const LIST_CHANNELS: &str = "/kld/channels";
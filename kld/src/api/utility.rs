@@ -120,3 +120,13 @@ pub(crate) async fn score(
         .map_err(internal_server)?;
     Ok(score)
 }
+
+pub(crate) async fn static_channel_backup(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let backup = lightning_interface
+        .static_channel_backup()
+        .await
+        .map_err(internal_server)?;
+    Ok(backup)
+}
@@ -1,5 +1,5 @@
 use super::payloads::{
-    ChannelState, ListFunds, ListFundsChannel, ListFundsOutput, OutputStatus, WalletBalance,
+    ChannelState, ListFunds, ListFundsChannel, ListFundsOutput, OutputStatus, Sweep, WalletBalance,
     WalletTransfer, WalletTransferResponse,
 };
 use anyhow::anyhow;
@@ -16,6 +16,7 @@ use crate::to_string_empty;
 use crate::wallet::WalletInterface;
 
 use super::codegen::get_v1_newaddr_response::GetV1NewaddrResponse;
+use super::macaroon_auth::AmountLimit;
 use super::{bad_request, internal_server, ApiError};
 
 pub(crate) async fn get_balance(
@@ -54,6 +55,7 @@ pub(crate) async fn new_address(
 
 pub(crate) async fn transfer(
     Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Extension(limit): Extension<AmountLimit>,
     Json(wallet_transfer): Json<WalletTransfer>,
 ) -> Result<impl IntoResponse, ApiError> {
     let address = Address::from_str(&wallet_transfer.address).map_err(bad_request)?;
@@ -63,6 +65,7 @@ pub(crate) async fn transfer(
     } else {
         u64::from_str(&wallet_transfer.satoshis).map_err(bad_request)?
     };
+    limit.check(amount.saturating_mul(1000))?;
     let (tx, tx_details) = wallet
         .transfer(address, amount, wallet_transfer.fee_rate, None, vec![])
         .await
@@ -132,3 +135,25 @@ pub(crate) async fn list_funds(
     let response = ListFunds { outputs, channels };
     Ok(Json(response))
 }
+
+pub(crate) async fn list_sweeps(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let response: Vec<Sweep> = lightning_interface
+        .list_sweeps()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|sweep| Sweep {
+            txid: sweep.txid.to_string(),
+            status: sweep.status.to_string(),
+            outputs: sweep.outputs().iter().map(|o| o.to_string()).collect(),
+            amount_sat: sweep.transaction.output.iter().map(|o| o.value).sum(),
+            fee_rate: sweep.fee_rate,
+            broadcast_height: sweep.broadcast_height,
+            confirmation_height: sweep.confirmation_height,
+            timestamp: sweep.timestamp.unix_timestamp() as u64,
+        })
+        .collect();
+    Ok(Json(response))
+}
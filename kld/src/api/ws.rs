@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, ops::ControlFlow};
+use std::{collections::BTreeSet, net::SocketAddr, ops::ControlFlow, sync::Arc};
 
 use axum::{
     extract::{
@@ -7,14 +7,16 @@ use axum::{
     },
     headers::UserAgent,
     response::IntoResponse,
-    TypedHeader,
+    Extension, TypedHeader,
 };
-use futures::StreamExt;
-use log::{debug, error, info};
+use log::{debug, info, warn};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
 
-use super::ApiError;
-
-/// This is WIP. Just connects and checks macaroon at the moment.
+use super::{
+    payloads::{EventSubscription, EventSubscriptionResponse, EventTopic, StreamEvent},
+    ApiError,
+};
+use crate::ldk::LightningInterface;
 
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
 /// of websocket negotiation. After this completes, the actual switching from HTTP to
@@ -22,6 +24,7 @@ use super::ApiError;
 /// This is the last point where we can extract TCP/IP metadata such as IP address of the client
 /// as well as things from HTTP headers such as user-agent of the browser etc.
 pub async fn ws_handler(
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
@@ -31,15 +34,18 @@ pub async fn ws_handler(
         .unwrap_or_else(|| "Unknown client".to_string());
 
     info!("`{}` at {} connected.", user_agent, addr.to_string());
+    // Subscribe before the upgrade so that no events are missed while it completes.
+    let events = lightning_interface.subscribe_events();
     // finalize the upgrade process by returning upgrade callback.
     // we can customize the callback by sending additional info such as address.
     Ok(ws
         .protocols(["hex"])
-        .on_upgrade(move |socket| handle_socket(socket, addr)))
+        .on_upgrade(move |socket| handle_socket(socket, addr, events)))
 }
 
 /// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
+/// Clients receive nothing until they send an `EventSubscription` naming the topics they want.
+async fn handle_socket(mut socket: WebSocket, who: SocketAddr, mut events: Receiver<StreamEvent>) {
     //send a ping (unsupported by some browsers) just to kick things off and get a response
     if socket.send(Message::Ping(vec![])).await.is_ok() {
         debug!("Pinged {}...", who);
@@ -50,51 +56,77 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
         return;
     }
 
-    // Since each client gets individual statemachine, we can pause handling
-    // when necessary to wait for some external event (in this case illustrated by sleeping).
-    // Waiting for this client to finish getting his greetings does not prevent other clients form
-    // connecting to server and receiving their greetings.
-
-    // By splitting socket we can send and receive at the same time. In this example we will send
-    // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
-    let (mut _sender, mut receiver) = socket.split();
-
-    /* To close connection.
-        debug!("Sending close to {}...", who);
-        if let Err(e) = sender
-            .send(Message::Close(Some(CloseFrame {
-                code: axum::extract::ws::close_code::NORMAL,
-                reason: Cow::from("Goodbye"),
-            })))
-            .await
-        {
-            debug!("Could not send Close due to {}, probably it is ok?", e);
-        }
-    });*/
-
-    // This second task will receive messages from client and print them on server console
-    let recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
-            // print message and break if instructed to do so
-            if process_message(msg, who).is_break() {
-                break;
+    let mut topics = BTreeSet::new();
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let Some(Ok(msg)) = msg else {
+                    break;
+                };
+                let reply = match process_message(msg, who, &mut topics) {
+                    ControlFlow::Break(()) => break,
+                    ControlFlow::Continue(reply) => reply,
+                };
+                if let Some(reply) = reply {
+                    if socket.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Websocket client {who} is too slow, {skipped} events were dropped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !topics.contains(&event.topic()) {
+                    continue;
+                }
+                match serde_json::to_string(&event) {
+                    Ok(text) => {
+                        if socket.send(Message::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Could not serialize event {event:?}: {e}"),
+                }
             }
         }
-    });
-
-    if let Err(e) = recv_task.await {
-        error!("{e}")
-    };
+    }
 
     // returning from the handler closes the websocket connection
     info!("Websocket context {} destroyed", who);
 }
 
-/// helper to print contents of messages to stdout. Has special treatment for Close.
-fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
+/// Handles subscription changes from the client, returning the reply to send if there is one.
+/// Has special treatment for Close.
+fn process_message(
+    msg: Message,
+    who: SocketAddr,
+    topics: &mut BTreeSet<EventTopic>,
+) -> ControlFlow<(), Option<String>> {
     match msg {
         Message::Text(t) => {
-            info!(">>> {} sent str: {:?}", who, t);
+            debug!(">>> {} sent str: {:?}", who, t);
+            let reply = match serde_json::from_str::<EventSubscription>(&t) {
+                Ok(subscription) => {
+                    topics.extend(subscription.subscribe);
+                    for topic in subscription.unsubscribe {
+                        topics.remove(&topic);
+                    }
+                    serde_json::to_string(&EventSubscriptionResponse {
+                        topics: topics.iter().copied().collect(),
+                    })
+                }
+                Err(e) => serde_json::to_string(&super::payloads::Error {
+                    status: "Bad Request".to_string(),
+                    detail: format!("Invalid subscription: {e}"),
+                }),
+            };
+            return ControlFlow::Continue(reply.ok());
         }
         Message::Binary(d) => {
             info!(">>> {} sent {} bytes: {:?}", who, d.len(), d);
@@ -112,14 +144,14 @@ fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
         }
 
         Message::Pong(v) => {
-            info!(">>> {} sent pong with {:?}", who, v);
+            debug!(">>> {} sent pong with {:?}", who, v);
         }
         // You should never need to manually handle Message::Ping, as axum's websocket library
         // will do so for you automagically by replying with Pong and copying the v according to
         // spec. But if you need the contents of the pings you can see them here.
         Message::Ping(v) => {
-            info!(">>> {} sent ping with {:?}", who, v);
+            debug!(">>> {} sent ping with {:?}", who, v);
         }
     }
-    ControlFlow::Continue(())
+    ControlFlow::Continue(None)
 }
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
@@ -9,18 +10,20 @@ use std::{
 use anyhow::{anyhow, bail, Context, Result};
 
 use crate::settings::Network;
-use crate::settings::Settings;
+use crate::settings::{target_name, FeeSource, Settings, TargetFeeRate, CONFIRMATION_TARGETS};
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine};
 use bitcoin::{consensus::encode, Address, BlockHash, Transaction, Txid};
 use bitcoincore_rpc_json::{EstimateMode, EstimateSmartFeeResult, GetBlockchainInfoResult};
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::chain::chaininterface::{
+    BroadcasterInterface, ConfirmationTarget, FeeEstimator, FEERATE_FLOOR_SATS_PER_KW,
+};
 use lightning_block_sync::{
     http::{HttpEndpoint, JsonResponse},
     rpc::RpcClient,
     AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use tokio::runtime::Handle;
@@ -46,7 +49,7 @@ impl BitcoindClient {
             RpcClient::new(&credentials, http_endpoint).context("failed to create rpc client")?,
         );
 
-        let priorities = Arc::new(Priorities::new());
+        let priorities = Arc::new(Priorities::new(settings));
         let bitcoind_client = BitcoindClient {
             client,
             priorities,
@@ -117,45 +120,103 @@ impl BitcoindClient {
         let client = self.client.clone();
         let priorities = self.priorities.clone();
         tokio::spawn(async move {
+            let http_client = reqwest::Client::new();
             loop {
-                BitcoindClient::estimate_fee(priorities.clone(), client.clone()).await;
-                tokio::time::sleep(Duration::from_secs(60)).await;
+                BitcoindClient::estimate_fee(&priorities, &client, &http_client).await;
+                tokio::time::sleep(priorities.poll_interval).await;
             }
         });
     }
 
-    async fn estimate_fee(priorities: Arc<Priorities>, client: Arc<RpcClient>) {
-        for class in priorities.list_class() {
-            match client
-                .call_method::<JsonString>(
-                    "estimatesmartfee",
-                    &[json!(class.n_blocks), json!(class.estimate_mode)],
-                )
-                .await
-                .map(|r| serde_json::from_str::<EstimateSmartFeeResult>(&r.0))
-            {
-                Ok(Ok(result)) => {
-                    // Bitcoind returns fee in BTC/kB.
-                    // So convert to sats and divide by 4 to get sats per 1000 weight units.
-                    let fee = ((result
-                        .fee_rate
-                        .map(|amount| amount.to_sat())
-                        .unwrap_or(class.default_fee_rate as u64)
-                        / 4) as u32)
-                        .max(MIN_FEERATE);
-                    Priorities::store(class, fee);
+    async fn estimate_fee(
+        priorities: &Priorities,
+        client: &RpcClient,
+        http_client: &reqwest::Client,
+    ) {
+        // Each source is asked at most once per round, failures included.
+        let mut bitcoind_estimates: HashMap<u16, Option<u32>> = HashMap::new();
+        let mut esplora_estimates = None;
+        for class in &priorities.classes {
+            let mut fee_rate = None;
+            for source in &priorities.sources {
+                fee_rate = match source {
+                    FeeSource::Bitcoind => match bitcoind_estimates.get(&class.n_blocks) {
+                        Some(estimate) => *estimate,
+                        None => {
+                            let estimate = BitcoindClient::estimate_smart_fee(client, class)
+                                .await
+                                .map_err(|e| {
+                                    warn!("Could not fetch fee estimate from bitcoind: {e}")
+                                })
+                                .ok();
+                            bitcoind_estimates.insert(class.n_blocks, estimate);
+                            estimate
+                        }
+                    },
+                    FeeSource::Esplora => {
+                        if esplora_estimates.is_none() {
+                            esplora_estimates = Some(
+                                BitcoindClient::fetch_esplora_estimates(priorities, http_client)
+                                    .await
+                                    .map_err(|e| {
+                                        warn!("Could not fetch fee estimates from esplora: {e}")
+                                    })
+                                    .ok(),
+                            );
+                        }
+                        esplora_estimates
+                            .as_ref()
+                            .and_then(|estimates| estimates.as_ref())
+                            .and_then(|estimates| esplora_fee_rate(estimates, class.n_blocks))
+                    }
+                    FeeSource::Static => Some(class.static_fee_rate),
+                };
+                if fee_rate.is_some() {
+                    break;
                 }
-                Ok(Err(e)) => error!("Could not fetch fee estimate: {}", e),
-                Err(e) => error!("Could not fetch fee estimate: {}", e),
-            };
+            }
+            match fee_rate {
+                Some(fee_rate) => Priorities::store(class, fee_rate),
+                None => error!(
+                    "No fee source could estimate {}, keeping the previous fee rate",
+                    target_name(&class.target)
+                ),
+            }
         }
     }
 
-    /// To set up the lowest fee base for a testing environment
-    pub fn set_lowest_fee_estimates(&self) {
-        for class in self.priorities.list_class() {
-            Priorities::store(class, 253);
-        }
+    async fn estimate_smart_fee(client: &RpcClient, class: &PriorityClass) -> Result<u32> {
+        let result = client
+            .call_method::<JsonString>(
+                "estimatesmartfee",
+                &[json!(class.n_blocks), json!(class.estimate_mode)],
+            )
+            .await?
+            .deserialize::<EstimateSmartFeeResult>()?;
+        // Bitcoind returns fee in BTC/kB.
+        // So convert to sats and divide by 4 to get sats per 1000 weight units.
+        result
+            .fee_rate
+            .map(|amount| (amount.to_sat() / 4) as u32)
+            .with_context(|| format!("{:?}", result.errors.unwrap_or_default()))
+    }
+
+    async fn fetch_esplora_estimates(
+        priorities: &Priorities,
+        http_client: &reqwest::Client,
+    ) -> Result<HashMap<String, f64>> {
+        let url = priorities
+            .esplora_url
+            .as_ref()
+            .context("KLD_FEE_ESPLORA_URL is not set")?;
+        Ok(http_client
+            .get(format!("{url}/fee-estimates"))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
     }
 }
 
@@ -318,76 +379,119 @@ impl BlockSource for BitcoindClient {
 }
 
 struct PriorityClass {
+    target: ConfirmationTarget,
     // sats per 1000 weight unit
     fee_rate: AtomicU32,
-    default_fee_rate: u32,
+    static_fee_rate: u32,
+    min_fee_rate: u32,
+    max_fee_rate: u32,
     n_blocks: u16,
     estimate_mode: EstimateMode,
 }
 
+/// The current fee rate of every confirmation target and where to estimate them from.
 struct Priorities {
-    background: Arc<PriorityClass>,
-    normal: Arc<PriorityClass>,
-    high: Arc<PriorityClass>,
+    classes: Vec<PriorityClass>,
+    sources: Vec<FeeSource>,
+    esplora_url: Option<String>,
+    poll_interval: Duration,
 }
 
 impl Priorities {
-    fn new() -> Priorities {
+    fn new(settings: &Settings) -> Priorities {
+        let configured = |rates: &[TargetFeeRate], target: ConfirmationTarget| {
+            rates
+                .iter()
+                .find(|rate| rate.target == target)
+                .map(|rate| rate.fee_rate)
+        };
+        let classes = CONFIRMATION_TARGETS
+            .into_iter()
+            .map(|target| {
+                let (n_blocks, default_fee_rate) = match target {
+                    ConfirmationTarget::OnChainSweep => (6, 10000),
+                    ConfirmationTarget::NonAnchorChannelFee => (18, 5000),
+                    _ => (72, MIN_FEERATE),
+                };
+                let min_fee_rate = configured(&settings.fee_min_rates, target)
+                    .unwrap_or(FEERATE_FLOOR_SATS_PER_KW)
+                    .max(FEERATE_FLOOR_SATS_PER_KW);
+                let max_fee_rate = configured(&settings.fee_max_rates, target)
+                    .unwrap_or(u32::MAX)
+                    .max(min_fee_rate);
+                let static_fee_rate = configured(&settings.fee_static_rates, target)
+                    .unwrap_or(default_fee_rate)
+                    .clamp(min_fee_rate, max_fee_rate);
+                PriorityClass {
+                    target,
+                    fee_rate: AtomicU32::new(static_fee_rate),
+                    static_fee_rate,
+                    min_fee_rate,
+                    max_fee_rate,
+                    n_blocks,
+                    estimate_mode: EstimateMode::Conservative,
+                }
+            })
+            .collect();
         Priorities {
-            background: Arc::new(PriorityClass {
-                fee_rate: AtomicU32::new(MIN_FEERATE),
-                default_fee_rate: MIN_FEERATE,
-                n_blocks: 72,
-                estimate_mode: EstimateMode::Conservative,
-            }),
-            normal: Arc::new(PriorityClass {
-                fee_rate: AtomicU32::new(5000),
-                default_fee_rate: 5000,
-                n_blocks: 18,
-                estimate_mode: EstimateMode::Conservative,
-            }),
-            high: Arc::new(PriorityClass {
-                fee_rate: AtomicU32::new(10000),
-                default_fee_rate: 10000,
-                n_blocks: 6,
-                estimate_mode: EstimateMode::Conservative,
-            }),
-        }
-    }
-
-    /// Return a base class and a mulipler
-    fn priority_of(&self, conf_target: &ConfirmationTarget) -> (Arc<PriorityClass>, Option<f32>) {
-        match conf_target {
-            ConfirmationTarget::OnChainSweep => (self.high.clone(), None),
-            ConfirmationTarget::NonAnchorChannelFee => (self.normal.clone(), None),
-            ConfirmationTarget::ChannelCloseMinimum
-            | ConfirmationTarget::AnchorChannelFee
-            | ConfirmationTarget::MinAllowedAnchorChannelRemoteFee
-            | ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee => {
-                (self.background.clone(), None)
-            }
+            classes,
+            sources: settings.fee_sources.clone(),
+            esplora_url: settings
+                .fee_esplora_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            poll_interval: Duration::from_secs(settings.fee_poll_interval.max(1)),
         }
     }
 
     fn get(&self, conf_target: &ConfirmationTarget) -> u32 {
-        let (priority, multiplier) = self.priority_of(conf_target);
-        let base = priority.fee_rate.load(Ordering::Acquire);
-        if let Some(multiplier) = multiplier {
-            ((base as f32) * multiplier) as u32
-        } else {
-            base
-        }
+        self.classes
+            .iter()
+            .find(|class| class.target == *conf_target)
+            .map(|class| class.fee_rate.load(Ordering::Acquire))
+            .unwrap_or(FEERATE_FLOOR_SATS_PER_KW)
     }
 
-    fn store(class: Arc<PriorityClass>, fee: u32) {
-        class.fee_rate.store(fee, Ordering::Release);
+    fn store(class: &PriorityClass, fee: u32) {
+        class.fee_rate.store(
+            fee.clamp(class.min_fee_rate, class.max_fee_rate),
+            Ordering::Release,
+        );
     }
+}
+
+/// Pick the esplora estimate for the closest block target that is not slower than the one asked for.
+/// Esplora returns sats per vbyte keyed by the number of blocks.
+fn esplora_fee_rate(estimates: &HashMap<String, f64>, n_blocks: u16) -> Option<u32> {
+    let mut estimates: Vec<(u16, f64)> = estimates
+        .iter()
+        .filter_map(|(blocks, fee_rate)| Some((blocks.parse().ok()?, *fee_rate)))
+        .collect();
+    estimates.sort_by_key(|(blocks, _)| *blocks);
+    estimates
+        .iter()
+        .rev()
+        .find(|(blocks, _)| *blocks <= n_blocks)
+        .or(estimates.first())
+        .map(|(_, fee_rate)| (fee_rate * 250.0).ceil() as u32)
+}
 
-    fn list_class(&self) -> Vec<Arc<PriorityClass>> {
-        vec![
-            self.background.clone(),
-            self.normal.clone(),
-            self.high.clone(),
-        ]
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::esplora_fee_rate;
+
+    #[test]
+    fn test_esplora_fee_rate() {
+        let estimates: HashMap<String, f64> = [("1", 20.5), ("6", 10.0), ("144", 1.2)]
+            .into_iter()
+            .map(|(blocks, fee_rate)| (blocks.to_string(), fee_rate))
+            .collect();
+        assert_eq!(Some(5125), esplora_fee_rate(&estimates, 1));
+        assert_eq!(Some(2500), esplora_fee_rate(&estimates, 6));
+        assert_eq!(Some(2500), esplora_fee_rate(&estimates, 72));
+        assert_eq!(Some(300), esplora_fee_rate(&estimates, 1008));
+        assert_eq!(None, esplora_fee_rate(&HashMap::new(), 6));
     }
 }
@@ -22,10 +22,15 @@ use kld::api::codegen::{
     post_v1_peer_connect_response::PostV1PeerConnectResponse,
 };
 use kld::api::payloads::{
-    ChannelFee, FeeRate, FeeRatesResponse, FundChannel, FundChannelResponse, GenerateInvoice,
-    GenerateInvoiceResponse, GetInfo, Invoice, KeysendRequest, ListFunds, NetworkChannel,
-    NetworkNode, PayInvoice, PaymentResponse, Peer, SetChannelFeeResponse, SignRequest,
-    SignResponse, WalletBalance, WalletTransfer, WalletTransferResponse,
+    BakeMacaroon, BakeMacaroonResponse, BuyChannel, CancelInvoice, ChannelFee, CloseChannel,
+    CreateLsps2Token, CreateOffer, ExportQuery, FeeRate, FeeRatesResponse, FeeUpdate,
+    ForwardingReport, ForwardingReportQuery, FundChannel, FundChannelResponse, GenerateInvoice,
+    GenerateInvoiceResponse, GenerateJitInvoice, GetInfo, GetRouteQuery, GetRouteResponse, Invoice,
+    JitChannel, KeysendRequest, ListFunds, LspInfo, LspOrder, Lsps1Order, Lsps2Token,
+    NetworkChannel, NetworkNode, NetworkNodesQuery, Offer, PageParams, PayInvoice, PayOffer,
+    PaymentResponse, PaymentStatusResponse, Peer, RebalanceChannel, RebalanceResponse, RootKey,
+    SetChannelFeeResponse, SettleInvoice, SignRequest, SignResponse, Sweep, WalletBalance,
+    WalletTransfer, WalletTransferResponse,
 };
 use kld::api::routes;
 use reqwest::{
@@ -105,6 +110,11 @@ impl Api {
         deserialize::<ListFunds>(response)
     }
 
+    pub fn list_sweeps(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::LIST_SWEEPS).send()?;
+        deserialize::<Vec<Sweep>>(response)
+    }
+
     pub fn list_channels(&self) -> Result<String> {
         let response = self.request(Method::GET, routes::LIST_CHANNELS).send()?;
         deserialize::<Vec<GetKldChannelResponseItem>>(response)
@@ -137,26 +147,7 @@ impl Api {
         deserialize::<()>(response)
     }
 
-    pub fn open_channel(
-        &self,
-        id: String,
-        satoshis: String,
-        push_msat: Option<String>,
-        announce: Option<bool>,
-        fee_rate: Option<String>,
-    ) -> Result<String> {
-        let open_channel = FundChannel {
-            id,
-            satoshis,
-            fee_rate: fee_rate.map(|f| FeeRate::from_str(&f)).transpose()?,
-            announce,
-            min_conf: None,
-            utxos: vec![],
-            push_msat,
-            close_to: None,
-            request_amt: None,
-            compact_lease: None,
-        };
+    pub fn open_channel(&self, open_channel: FundChannel) -> Result<String> {
         let response = self
             .request_with_body(Method::POST, routes::OPEN_CHANNEL, open_channel)
             .send()?;
@@ -176,19 +167,27 @@ impl Api {
         deserialize::<SetChannelFeeResponse>(response)
     }
 
-    pub fn close_channel(&self, id: String, fee_rate: Option<u32>) -> Result<String> {
-        let response = if let Some(fee_rate) = fee_rate {
-            self.request(
-                Method::DELETE,
-                &routes::CLOSE_CHANNEL_WITH_FEE
-                    .replace(":id", &id)
-                    .replace(":fee_rate", &fee_rate.to_string()),
-            )
-            .send()?
+    pub fn rebalance(&self, body: RebalanceChannel) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::REBALANCE_CHANNEL, body)
+            .send()?;
+        deserialize::<RebalanceResponse>(response)
+    }
+
+    pub fn close_channel(
+        &self,
+        id: String,
+        fee_rate: Option<u32>,
+        close: CloseChannel,
+    ) -> Result<String> {
+        let route = if let Some(fee_rate) = fee_rate {
+            routes::CLOSE_CHANNEL_WITH_FEE
+                .replace(":id", &id)
+                .replace(":fee_rate", &fee_rate.to_string())
         } else {
-            self.request(Method::DELETE, &routes::CLOSE_CHANNEL.replace(":id", &id))
-                .send()?
+            routes::CLOSE_CHANNEL.replace(":id", &id)
         };
+        let response = self.request(Method::DELETE, &route).query(&close).send()?;
         deserialize::<()>(response)
     }
 
@@ -209,12 +208,18 @@ impl Api {
         deserialize::<()>(response)
     }
 
-    pub fn list_network_nodes(&self, id: Option<String>) -> Result<String> {
+    pub fn list_network_nodes(
+        &self,
+        id: Option<String>,
+        after: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String> {
         let response = if let Some(id) = id {
             self.request(Method::GET, &routes::LIST_NETWORK_NODE.replace(":id", &id))
                 .send()?
         } else {
             self.request(Method::GET, routes::LIST_NETWORK_NODES)
+                .query(&NetworkNodesQuery { after, limit })
                 .send()?
         };
         deserialize::<Vec<NetworkNode>>(response)
@@ -244,7 +249,21 @@ impl Api {
         deserialize::<FeeRatesResponse>(response)
     }
 
-    pub fn keysend(&self, public_key: String, amount: u64) -> Result<String> {
+    pub fn get_route(&self, query: GetRouteQuery) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::GET_ROUTE)
+            .query(&query)
+            .send()?;
+        deserialize::<GetRouteResponse>(response)
+    }
+
+    pub fn keysend(
+        &self,
+        public_key: String,
+        amount: u64,
+        max_fee_msat: Option<u64>,
+        non_blocking: bool,
+    ) -> Result<String> {
         let body = KeysendRequest {
             pubkey: public_key,
             amount,
@@ -253,6 +272,8 @@ impl Api {
             retry_for: None,
             maxdelay: None,
             exemptfee: None,
+            max_fee_msat,
+            non_blocking: non_blocking.then_some(true),
         };
         let response = self
             .request_with_body(Method::POST, routes::KEYSEND, body)
@@ -266,12 +287,14 @@ impl Api {
         label: String,
         description: String,
         expiry: Option<u32>,
+        payment_hash: Option<String>,
     ) -> Result<String> {
         let body = GenerateInvoice {
             amount,
             label,
             description,
             expiry,
+            payment_hash,
             ..Default::default()
         };
         let response = self
@@ -280,21 +303,73 @@ impl Api {
         deserialize::<GenerateInvoiceResponse>(response)
     }
 
-    pub fn list_invoices(&self, label: Option<String>) -> Result<String> {
-        let route = if let Some(label) = label {
-            format!("{}?{label}", routes::LIST_INVOICES)
-        } else {
-            routes::LIST_INVOICES.to_string()
-        };
-        let response = self.request(Method::GET, &route).send()?;
+    pub fn settle_invoice(&self, preimage: String) -> Result<String> {
+        let response = self
+            .request_with_body(
+                Method::POST,
+                routes::SETTLE_INVOICE,
+                SettleInvoice { preimage },
+            )
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn cancel_invoice(&self, payment_hash: String) -> Result<String> {
+        let response = self
+            .request_with_body(
+                Method::POST,
+                routes::CANCEL_INVOICE,
+                CancelInvoice { payment_hash },
+            )
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn list_invoices(&self, label: Option<String>, page: PageParams) -> Result<String> {
+        let mut params = vec![];
+        if let Some(label) = label {
+            params.push(("label", label));
+        }
+        let response = self
+            .request(Method::GET, routes::LIST_INVOICES)
+            .query(&params)
+            .query(&page)
+            .send()?;
         deserialize::<Vec<Invoice>>(response)
     }
 
-    pub fn pay_invoice(&self, bolt11: String, label: Option<String>) -> Result<String> {
-        let body = PayInvoice {
-            invoice: bolt11,
-            label,
-        };
+    pub fn wait_invoice(&self, label: String, timeout: Option<u64>) -> Result<String> {
+        let mut params = vec![];
+        if let Some(timeout) = timeout {
+            params.push(("timeout", timeout));
+        }
+        let response = self
+            .request(Method::GET, &routes::WAIT_INVOICE.replace(":label", &label))
+            .query(&params)
+            .send()?;
+        deserialize::<Invoice>(response)
+    }
+
+    pub fn wait_any_invoice(
+        &self,
+        lastpay_index: Option<u64>,
+        timeout: Option<u64>,
+    ) -> Result<String> {
+        let mut params = vec![];
+        if let Some(lastpay_index) = lastpay_index {
+            params.push(("lastpay_index", lastpay_index));
+        }
+        if let Some(timeout) = timeout {
+            params.push(("timeout", timeout));
+        }
+        let response = self
+            .request(Method::GET, routes::WAIT_ANY_INVOICE)
+            .query(&params)
+            .send()?;
+        deserialize::<Invoice>(response)
+    }
+
+    pub fn pay_invoice(&self, body: PayInvoice) -> Result<String> {
         let response = self
             .request_with_body(Method::POST, routes::PAY_INVOICE, body)
             .send()?;
@@ -305,6 +380,7 @@ impl Api {
         &self,
         bolt11: Option<String>,
         direction: Option<String>,
+        page: PageParams,
     ) -> Result<String> {
         let mut params = vec![];
         if let Some(bolt11) = bolt11 {
@@ -316,10 +392,70 @@ impl Api {
         let response = self
             .request(Method::GET, routes::LIST_PAYMENTS)
             .query(&params)
+            .query(&page)
             .send()?;
         deserialize::<GetV1PayListPaymentsResponse>(response)
     }
 
+    pub fn payment_status(&self, id: String) -> Result<String> {
+        let response = self
+            .request(Method::GET, &routes::PAYMENT_STATUS.replace(":id", &id))
+            .send()?;
+        deserialize::<PaymentStatusResponse>(response)
+    }
+
+    pub fn create_offer(
+        &self,
+        description: String,
+        amount: Option<u64>,
+        label: Option<String>,
+        expiry: Option<u64>,
+    ) -> Result<String> {
+        let body = CreateOffer {
+            amount,
+            label,
+            description,
+            expiry,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::CREATE_OFFER, body)
+            .send()?;
+        deserialize::<Offer>(response)
+    }
+
+    pub fn pay_offer(
+        &self,
+        offer: String,
+        amount: Option<u64>,
+        max_fee_msat: Option<u64>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<String> {
+        let body = PayOffer {
+            offer,
+            amount,
+            payer_note,
+            label,
+            max_fee_msat,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::PAY_OFFER, body)
+            .send()?;
+        deserialize::<PaymentResponse>(response)
+    }
+
+    pub fn list_offers(&self, label: Option<String>) -> Result<String> {
+        let mut params = vec![];
+        if let Some(label) = label {
+            params.push(("label", label));
+        }
+        let response = self
+            .request(Method::GET, routes::LIST_OFFERS)
+            .query(&params)
+            .send()?;
+        deserialize::<Vec<Offer>>(response)
+    }
+
     pub fn estimate_channel_liquidity(&self, scid: u64, target: String) -> Result<String> {
         let body = GetV1EstimateChannelLiquidityBody { scid, target };
         let response = self
@@ -340,7 +476,7 @@ impl Api {
         deserialize::<GetV1GetFeesResponse>(response)
     }
 
-    pub fn list_forwards(&self, status: Option<String>) -> Result<String> {
+    pub fn list_forwards(&self, status: Option<String>, page: PageParams) -> Result<String> {
         let mut params = vec![];
         if let Some(status) = status {
             params.push(("status", status));
@@ -348,17 +484,44 @@ impl Api {
         let response = self
             .request(Method::GET, routes::LIST_FORWARDS)
             .query(&params)
+            .query(&page)
             .send()?;
         deserialize::<Vec<GetV1ChannelListForwardsResponseItem>>(response)
     }
 
-    pub fn channel_history(&self) -> Result<String> {
+    pub fn forwarding_report(
+        &self,
+        bucket: Option<String>,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::FORWARDING_REPORT)
+            .query(&ForwardingReportQuery { bucket, from, to })
+            .send()?;
+        deserialize::<ForwardingReport>(response)
+    }
+
+    pub fn channel_history(&self, page: PageParams) -> Result<String> {
         let response = self
             .request(Method::GET, routes::LIST_CHANNEL_HISTORY)
+            .query(&page)
             .send()?;
         deserialize::<Vec<GetV1ChannelHistoryResponseItem>>(response)
     }
 
+    pub fn fee_history(&self, channel_id: Option<String>) -> Result<String> {
+        let mut params = vec![];
+        if let Some(channel_id) = channel_id {
+            params.push(("channel_id", channel_id));
+        }
+        let response = self
+            .request(Method::GET, routes::CHANNEL_FEE_HISTORY)
+            .query(&params)
+            .send()?;
+        deserialize::<Vec<FeeUpdate>>(response)
+    }
+
     pub fn decode(&self, invoice: String) -> Result<String> {
         let response = self
             .request(
@@ -377,6 +540,114 @@ impl Api {
         Ok(format!("scorer save in {}", path.display()))
     }
 
+    pub fn backup(&self, path: PathBuf) -> Result<String> {
+        let backup = self.request(Method::GET, routes::BACKUP).send()?.bytes()?;
+        let mut f = File::create(&path)?;
+        f.write_all(&backup)?;
+
+        Ok(format!("channel backup save in {}", path.display()))
+    }
+
+    pub fn bake_macaroon(&self, bake: BakeMacaroon) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::BAKE_MACAROON, bake)
+            .send()?;
+        deserialize::<BakeMacaroonResponse>(response)
+    }
+
+    pub fn list_root_keys(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::LIST_ROOT_KEYS).send()?;
+        deserialize::<Vec<RootKey>>(response)
+    }
+
+    pub fn revoke_root_key(&self, id: u64) -> Result<String> {
+        let response = self
+            .request(
+                Method::DELETE,
+                &routes::REVOKE_ROOT_KEY.replace(":id", &id.to_string()),
+            )
+            .send()?;
+        deserialize::<RootKey>(response)
+    }
+
+    pub fn list_lsps2_tokens(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_LSPS2_TOKENS)
+            .send()?;
+        deserialize::<Vec<Lsps2Token>>(response)
+    }
+
+    pub fn create_lsps2_token(&self, token: CreateLsps2Token) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::CREATE_LSPS2_TOKEN, token)
+            .send()?;
+        deserialize::<Lsps2Token>(response)
+    }
+
+    pub fn delete_lsps2_token(&self, token: String) -> Result<String> {
+        let response = self
+            .request(
+                Method::DELETE,
+                &routes::DELETE_LSPS2_TOKEN.replace(":token", &token),
+            )
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn list_jit_channels(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_JIT_CHANNELS)
+            .send()?;
+        deserialize::<Vec<JitChannel>>(response)
+    }
+
+    pub fn list_lsps1_orders(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_LSPS1_ORDERS)
+            .send()?;
+        deserialize::<Vec<Lsps1Order>>(response)
+    }
+
+    pub fn lsp_info(&self, node_id: String, token: Option<String>) -> Result<String> {
+        let mut params = vec![("nodeId", node_id)];
+        if let Some(token) = token {
+            params.push(("token", token));
+        }
+        let response = self
+            .request(Method::GET, routes::LSP_GET_INFO)
+            .query(&params)
+            .send()?;
+        deserialize::<LspInfo>(response)
+    }
+
+    pub fn buy_channel(&self, order: BuyChannel) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::LSP_BUY_CHANNEL, order)
+            .send()?;
+        deserialize::<LspOrder>(response)
+    }
+
+    pub fn generate_jit_invoice(&self, invoice: GenerateJitInvoice) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::LSP_JIT_INVOICE, invoice)
+            .send()?;
+        deserialize::<GenerateInvoiceResponse>(response)
+    }
+
+    pub fn export(&self, kind: String, format: Option<String>) -> Result<String> {
+        let response = self
+            .request(Method::GET, &routes::EXPORT.replace(":kind", &kind))
+            .query(&ExportQuery { format })
+            .send()?;
+        if response.status().is_success() {
+            Ok(response.text()?.trim_end().to_string())
+        } else {
+            Ok(to_string_pretty(
+                &response.json::<kld::api::payloads::Error>()?,
+            )?)
+        }
+    }
+
     fn request_builder(&self, method: Method, route: &str) -> RequestBuilder {
         self.client
             .request(method, format!("https://{}{}", self.host, route))
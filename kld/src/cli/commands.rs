@@ -1,6 +1,7 @@
 use std::{net::SocketAddr, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use kld::api::payloads::{PageParams, SortOrder};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -47,6 +48,8 @@ pub enum KldCliSubCommand {
     },
     /// Show available funds from the internal wallet.
     ListFunds,
+    /// Show the transactions sweeping funds from closed channels into the internal wallet.
+    ListSweeps,
     /// Fetch a list of this nodes peers.
     ListPeers,
     /// Connect with a network peer.
@@ -82,6 +85,15 @@ pub enum KldCliSubCommand {
         /// Fee rate [urgent/normal/slow/<sats>perkw/<sats>perkb]
         #[arg(short, long)]
         fee_rate: Option<String>,
+        /// Only fund the channel from these outputs [txid:vout,...].
+        #[arg(long, value_delimiter = ',')]
+        utxos: Vec<String>,
+        /// Minimum number of confirmations of the outputs used to fund the channel.
+        #[arg(long)]
+        min_conf: Option<u8>,
+        /// Open another channel in the same funding transaction [id:sats or id@host:port:sats], can be repeated.
+        #[arg(long)]
+        batch: Vec<String>,
     },
     /// Set channel fees.
     SetChannelFee {
@@ -95,6 +107,21 @@ pub enum KldCliSubCommand {
         #[arg(short, long)]
         ppm_fee: Option<u32>,
     },
+    /// Move liquidity from one channel to another by paying ourselves in a circle.
+    Rebalance {
+        /// Channel ID or short channel ID to move liquidity out of.
+        #[arg()]
+        outbound_channel_id: String,
+        /// Channel ID or short channel ID to move liquidity into.
+        #[arg()]
+        inbound_channel_id: String,
+        /// Amount to move in millisatoshis.
+        #[arg()]
+        amount: u64,
+        /// Maximum total routing fee in parts per million of the amount, defaults to the node setting.
+        #[arg(long)]
+        max_fee_ppm: Option<u32>,
+    },
     /// Close a channel.
     CloseChannel {
         /// Channel ID or short channel ID to close.
@@ -106,6 +133,17 @@ pub enum KldCliSubCommand {
         #[arg(short, long)]
         fee_rate: Option<u32>,
 
+        /// Send our funds to this address instead of the node wallet.
+        /// This option do not work with `force_close` option
+        #[arg(long)]
+        address: Option<String>,
+
+        /// Refuse to start the close if the first fee rate we would propose in sats per 1000 weight is higher.
+        /// The fee negotiated with the peer can still end up higher.
+        /// This option do not work with `force_close` option
+        #[arg(long)]
+        max_initial_fee_rate: Option<u32>,
+
         /// Force closes a channel with or without broadcasting the latest local transaction(s) .
         /// If `broadcast-flag` is `broadcast`, it will immediately broadcasting the latest local transaction(s) and rejecting new HTLCs on the given channel.
         /// If `broadcast-flag` is `no-broadcast`, it will rejecting new HTLCs on the given channel but skips broadcasting the latest local transaction(s).
@@ -117,6 +155,12 @@ pub enum KldCliSubCommand {
         /// Provide Node ID to get info about a single node.
         #[arg(short, long)]
         id: Option<String>,
+        /// Only list nodes with an ID after this one (the last node ID of the previous page).
+        #[arg(long)]
+        after: Option<String>,
+        /// Maximum number of nodes to list.
+        #[arg(long)]
+        limit: Option<usize>,
     },
     /// Get channel information from the network graph.
     NetworkChannels {
@@ -130,6 +174,27 @@ pub enum KldCliSubCommand {
         #[arg(short, long)]
         style: Option<String>,
     },
+    /// Find the route a payment would take, with its fees and chance of success, without paying.
+    GetRoute {
+        /// Node ID of the payee.
+        #[arg()]
+        destination: String,
+        /// Amount to pay in millisats.
+        #[arg()]
+        amount: u64,
+        /// Limit on the total routing fees in millisats.
+        #[arg(long)]
+        max_fee: Option<u64>,
+        /// CLTV expiry delta required by the payee (blocks).
+        #[arg(long)]
+        cltv: Option<u32>,
+        /// Short channel IDs that the route must avoid [scid,...].
+        #[arg(long, value_delimiter = ',')]
+        exclude_channels: Vec<String>,
+        /// Node IDs that the route must avoid [id,...].
+        #[arg(long, value_delimiter = ',')]
+        exclude_nodes: Vec<String>,
+    },
     /// Pay a node without an invoice.
     Keysend {
         /// Node ID of the payee.
@@ -138,6 +203,12 @@ pub enum KldCliSubCommand {
         /// Amount to pay in millisats.
         #[arg()]
         amount: u64,
+        /// Maximum total routing fee in millisats
+        #[arg(long)]
+        max_fee_msat: Option<u64>,
+        /// Return once the payment is sent instead of waiting for the result.
+        #[arg(long)]
+        non_blocking: bool,
     },
     /// Generate a bolt11 invoice for receiving a payment.
     GenerateInvoice {
@@ -153,12 +224,47 @@ pub enum KldCliSubCommand {
         /// Expiry time period for the invoice (seconds)
         #[arg(short, long)]
         expiry: Option<u32>,
+        /// Create a hold invoice with this payment hash (hex), payments wait for settle-invoice or cancel-invoice
+        #[arg(long)]
+        payment_hash: Option<String>,
+    },
+    /// Claim the held payment of a hold invoice
+    SettleInvoice {
+        /// Preimage of the payment hash of the hold invoice (hex)
+        #[arg()]
+        preimage: String,
+    },
+    /// Fail the held payment of a hold invoice back to the payer
+    CancelInvoice {
+        /// Payment hash of the hold invoice (hex)
+        #[arg()]
+        payment_hash: String,
     },
     /// List all invoices
     ListInvoices {
         /// Label of the invoice
         #[arg(short, long)]
         label: Option<String>,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Wait until the invoice with the label is paid, canceled or expired
+    WaitInvoice {
+        /// Label of the invoice
+        #[arg()]
+        label: String,
+        /// Give up waiting after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Wait for the next invoice that is paid
+    WaitAnyInvoice {
+        /// Pay index of the last invoice seen, returns the invoice paid after it
+        #[arg(long)]
+        lastpay_index: Option<u64>,
+        /// Give up waiting after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
     },
     /// Pay an invoice
     PayInvoice {
@@ -168,6 +274,27 @@ pub enum KldCliSubCommand {
         /// Label for the payment
         #[arg(short, long)]
         label: Option<String>,
+        /// Amount in millisats, required if the invoice has no amount
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Maximum total routing fee in millisats
+        #[arg(long)]
+        max_fee_msat: Option<u64>,
+        /// Maximum total routing fee in parts per million of the amount
+        #[arg(long)]
+        max_fee_ppm: Option<u32>,
+        /// Keep retrying to find routes for this long (seconds)
+        #[arg(long)]
+        retry_for: Option<u64>,
+        /// Maximum number of paths the payment can be split over
+        #[arg(long)]
+        max_paths: Option<u8>,
+        /// Maximum total CLTV expiry delta of the route (blocks)
+        #[arg(long)]
+        max_cltv_expiry_delta: Option<u32>,
+        /// Return once the payment is sent instead of waiting for the result
+        #[arg(long)]
+        non_blocking: bool,
     },
     /// List all payments
     ListPayments {
@@ -177,6 +304,53 @@ pub enum KldCliSubCommand {
         /// Direction (inbound/outbound)
         #[arg(short, long)]
         direction: Option<String>,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Fetch the status of a payment and the paths that were attempted
+    PaymentStatus {
+        /// ID of the payment
+        #[arg()]
+        id: String,
+    },
+    /// Create a bolt12 offer for receiving payments.
+    CreateOffer {
+        /// Description for the offer
+        #[arg()]
+        description: String,
+        /// Amount in millisats, if not set the payer chooses the amount
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Label for the offer
+        #[arg(short, long)]
+        label: Option<String>,
+        /// Expiry time period for the offer (seconds)
+        #[arg(short, long)]
+        expiry: Option<u64>,
+    },
+    /// Pay a bolt12 offer
+    PayOffer {
+        /// The offer to pay
+        #[arg()]
+        offer: String,
+        /// Amount in millisats, required if the offer has no amount
+        #[arg(short, long)]
+        amount: Option<u64>,
+        /// Maximum total routing fee in millisats
+        #[arg(long)]
+        max_fee_msat: Option<u64>,
+        /// Note to the recipient included in the invoice request
+        #[arg(short, long)]
+        payer_note: Option<String>,
+        /// Label for the payment
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+    /// List all offers
+    ListOffers {
+        /// Label of the offer
+        #[arg(short, long)]
+        label: Option<String>,
     },
     /// Estimate channel liquidity to a target node
     EstimateChannelLiquidity {
@@ -196,12 +370,191 @@ pub enum KldCliSubCommand {
         /// The status of the forwards (succeeded, failed)
         #[arg(short, long)]
         status: Option<String>,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Report the forwarding volume, fees and failures per period and the profitability of each channel
+    ForwardingReport {
+        /// Length of the periods to group forwards by (day/week/month)
+        #[arg(short, long)]
+        bucket: Option<String>,
+        /// Only include forwards at or after this unix timestamp
+        #[arg(long)]
+        from: Option<u64>,
+        /// Only include forwards before this unix timestamp
+        #[arg(long)]
+        to: Option<u64>,
     },
     /// Fetch a list of historic (closed) channels
-    ListChannelHistory,
+    ListChannelHistory {
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Fetch the fee changes made by the automatic fee adjustment
+    FeeHistory {
+        /// Only show the fee changes of this channel ID
+        #[arg(short, long)]
+        channel_id: Option<String>,
+    },
     /// Decode invoice
     Decode { invoice: String },
 
     /// Download scorer to the path, if unspecific, will use `scorer.bin` as default
     Scorer { path: Option<PathBuf> },
+
+    /// Download the encrypted static channel backup to the path, if unspecific, will use `channel_backup.scb` as default
+    Backup { path: Option<PathBuf> },
+
+    /// Create a macaroon with only some permissions, e.g. an invoice-only macaroon for a web shop
+    BakeMacaroon {
+        /// Permissions to grant, comma separated (e.g. invoices:read,invoices:write)
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        permissions: Vec<String>,
+        /// Unix timestamp after which the macaroon is no longer valid
+        #[arg(long)]
+        expires_at: Option<u64>,
+        /// Largest amount in milli satoshis a single payment or withdrawal may spend
+        #[arg(long)]
+        max_amount_msat: Option<u64>,
+        /// IP addresses or CIDR ranges the macaroon may be used from, comma separated
+        #[arg(long, value_delimiter = ',')]
+        ip_ranges: Vec<String>,
+        /// Root key to sign the macaroon with, so that macaroons can be revoked together (default a new root key)
+        #[arg(long)]
+        root_key_id: Option<u64>,
+    },
+    /// List the root keys that macaroons are signed with
+    ListRootKeys,
+    /// Revoke a root key, every macaroon signed with it stops working
+    RevokeRootKey { id: u64 },
+
+    /// List the tokens that LSPS2 clients can buy JIT channels with
+    ListLsps2Tokens,
+    /// Create a token for LSPS2 clients to buy JIT channels with, or change the fees of an existing token
+    CreateLsps2Token {
+        /// The token to give to clients (default random)
+        #[arg(long)]
+        token: Option<String>,
+        /// Smallest opening fee in milli satoshis
+        #[arg(long, default_value = "0")]
+        min_fee_msat: u64,
+        /// Opening fee in millionths of the payment size
+        #[arg(long, default_value = "0")]
+        proportional: u32,
+        /// How long the offered fees can be used to buy a channel (default 600 seconds)
+        #[arg(long)]
+        valid_for_secs: Option<u32>,
+        /// Smallest payment in milli satoshis to open a channel for
+        #[arg(long, default_value = "0")]
+        min_payment_size_msat: u64,
+        /// Largest payment in milli satoshis to open a channel for
+        #[arg(long)]
+        max_payment_size_msat: u64,
+    },
+    /// Delete an LSPS2 token, clients can no longer buy channels with it
+    DeleteLsps2Token { token: String },
+    /// List the JIT channels sold to LSPS2 clients
+    ListJitChannels,
+    /// List the channels ordered by LSPS1 clients
+    ListLsps1Orders,
+    /// Buy inbound liquidity from an LSP
+    Lsp {
+        #[clap(subcommand)]
+        command: LspSubCommand,
+    },
+
+    /// Export records for accounting (transactions/payments/invoices/forwards/channels) or the wallet labels in BIP-329 format (labels)
+    Export {
+        kind: String,
+        /// Output format (csv/jsonl), labels are always jsonl
+        #[arg(short, long)]
+        format: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LspSubCommand {
+    /// The channels a connected LSP sells up front (LSPS1) and its JIT channel fees (LSPS2)
+    GetInfo {
+        /// Public key of the LSP
+        #[arg()]
+        node_id: String,
+        /// Token from the LSP for its fees
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Order a channel from a connected LSP (LSPS1), it is opened once the order is paid
+    BuyChannel {
+        /// Public key of the LSP
+        #[arg()]
+        node_id: String,
+        /// Inbound liquidity in sats
+        #[arg()]
+        lsp_balance_sat: u64,
+        /// Outbound liquidity in sats, paid with the order
+        #[arg(long, default_value = "0")]
+        client_balance_sat: u64,
+        /// How long the LSP keeps the channel open (default 4320 blocks)
+        #[arg(long)]
+        channel_expiry_blocks: Option<u32>,
+        /// Announce the channel to the network
+        #[arg(long, default_value = "false")]
+        announce_channel: bool,
+        /// Token from the LSP for its fees
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Generate an invoice paid through a JIT channel from a connected LSP (LSPS2), minus the opening fee
+    JitInvoice {
+        /// Public key of the LSP
+        #[arg()]
+        node_id: String,
+        /// Unique label for the invoice
+        #[arg()]
+        label: String,
+        /// Description for the invoice
+        #[arg()]
+        description: String,
+        /// Amount in millisats (default any amount)
+        #[arg(long)]
+        amount: Option<u64>,
+        /// Expiry time period for the invoice (seconds)
+        #[arg(short, long)]
+        expiry: Option<u32>,
+        /// Token from the LSP for its fees
+        #[arg(long)]
+        token: Option<String>,
+    },
+}
+
+/// Which part of a list to fetch, the items are ordered by time.
+#[derive(Args, Debug)]
+pub struct Page {
+    /// Number of items to skip.
+    #[arg(long)]
+    pub offset: Option<u64>,
+    /// Maximum number of items to fetch.
+    #[arg(long)]
+    pub limit: Option<u64>,
+    /// Only items at or after this unix timestamp.
+    #[arg(long)]
+    pub from: Option<u64>,
+    /// Only items before this unix timestamp.
+    #[arg(long)]
+    pub to: Option<u64>,
+    /// Order by time (asc/desc).
+    #[arg(long)]
+    pub sort: Option<SortOrder>,
+}
+
+impl From<Page> for PageParams {
+    fn from(page: Page) -> Self {
+        PageParams {
+            offset: page.offset,
+            limit: page.limit,
+            from: page.from,
+            to: page.to,
+            sort: page.sort,
+        }
+    }
 }
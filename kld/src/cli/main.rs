@@ -4,7 +4,12 @@ mod commands;
 use crate::client::Api;
 use anyhow::{bail, Result};
 use clap::Parser;
-use commands::{KldCliCommand, KldCliSubCommand};
+use commands::{KldCliCommand, KldCliSubCommand, LspSubCommand};
+use kld::api::payloads::{
+    BakeMacaroon, BatchChannel, BuyChannel, CloseChannel, CreateLsps2Token, FeeRate, FundChannel,
+    GenerateJitInvoice, GetRouteQuery, PayInvoice, RebalanceChannel,
+};
+use std::str::FromStr;
 
 fn main() {
     let args = KldCliCommand::parse();
@@ -29,6 +34,7 @@ fn run_command(args: KldCliCommand) -> Result<()> {
             fee_rate,
         } => api.withdraw(address, satoshis, fee_rate)?,
         KldCliSubCommand::ListFunds => api.list_funds()?,
+        KldCliSubCommand::ListSweeps => api.list_sweeps()?,
         KldCliSubCommand::ListPeerChannels => api.list_peer_channels()?,
         KldCliSubCommand::ListPeers => api.list_peers()?,
         KldCliSubCommand::ConnectPeer { public_key } => api.connect_peer(public_key)?,
@@ -39,17 +45,63 @@ fn run_command(args: KldCliCommand) -> Result<()> {
             push_msat,
             announce,
             fee_rate,
-        } => api.open_channel(public_key, satoshis, push_msat, announce, fee_rate)?,
+            utxos,
+            min_conf,
+            batch,
+        } => api.open_channel(FundChannel {
+            id: public_key,
+            satoshis,
+            fee_rate: fee_rate.map(|f| FeeRate::from_str(&f)).transpose()?,
+            announce,
+            min_conf,
+            utxos,
+            push_msat,
+            batch: batch
+                .iter()
+                .map(|channel| match channel.rsplit_once(':') {
+                    Some((id, satoshis)) => Ok(BatchChannel {
+                        id: id.to_string(),
+                        satoshis: satoshis.to_string(),
+                        push_msat: None,
+                        announce,
+                    }),
+                    None => {
+                        bail!("Batch channel must be formatted as id:sats or id@host:port:sats")
+                    }
+                })
+                .collect::<Result<_>>()?,
+            ..Default::default()
+        })?,
         KldCliSubCommand::SetChannelFee {
             id,
             base_fee,
             ppm_fee,
         } => api.set_channel_fee(id, base_fee, ppm_fee)?,
+        KldCliSubCommand::Rebalance {
+            outbound_channel_id,
+            inbound_channel_id,
+            amount,
+            max_fee_ppm,
+        } => api.rebalance(RebalanceChannel {
+            outbound_channel_id,
+            inbound_channel_id,
+            amount,
+            max_fee_ppm,
+        })?,
         KldCliSubCommand::CloseChannel {
             id,
             force_close: None,
             fee_rate,
-        } => api.close_channel(id, fee_rate)?,
+            address,
+            max_initial_fee_rate,
+        } => api.close_channel(
+            id,
+            fee_rate,
+            CloseChannel {
+                address,
+                max_initial_fee_rate,
+            },
+        )?,
         KldCliSubCommand::CloseChannel {
             id,
             force_close: Some(broadcast_flag),
@@ -62,30 +114,174 @@ fn run_command(args: KldCliCommand) -> Result<()> {
             };
             api.force_close_channel(id, need_broadcast)?
         }
-        KldCliSubCommand::NetworkNodes { id } => api.list_network_nodes(id)?,
+        KldCliSubCommand::NetworkNodes { id, after, limit } => {
+            api.list_network_nodes(id, after, limit)?
+        }
         KldCliSubCommand::NetworkChannels { id } => api.list_network_channels(id)?,
         KldCliSubCommand::FeeRates { style } => api.fee_rates(style)?,
-        KldCliSubCommand::Keysend { public_key, amount } => api.keysend(public_key, amount)?,
+        KldCliSubCommand::GetRoute {
+            destination,
+            amount,
+            max_fee,
+            cltv,
+            exclude_channels,
+            exclude_nodes,
+        } => api.get_route(GetRouteQuery {
+            destination,
+            amount_msat: amount,
+            max_fee_msat: max_fee,
+            cltv,
+            exclude_channels: (!exclude_channels.is_empty()).then(|| exclude_channels.join(",")),
+            exclude_nodes: (!exclude_nodes.is_empty()).then(|| exclude_nodes.join(",")),
+        })?,
+        KldCliSubCommand::Keysend {
+            public_key,
+            amount,
+            max_fee_msat,
+            non_blocking,
+        } => api.keysend(public_key, amount, max_fee_msat, non_blocking)?,
         KldCliSubCommand::GenerateInvoice {
             amount,
             label,
             description,
             expiry,
-        } => api.generate_invoice(amount, label, description, expiry)?,
-        KldCliSubCommand::ListInvoices { label } => api.list_invoices(label)?,
-        KldCliSubCommand::PayInvoice { bolt11, label } => api.pay_invoice(bolt11, label)?,
-        KldCliSubCommand::ListPayments { bolt11, direction } => {
-            api.list_payments(bolt11, direction)?
-        }
+            payment_hash,
+        } => api.generate_invoice(amount, label, description, expiry, payment_hash)?,
+        KldCliSubCommand::SettleInvoice { preimage } => api.settle_invoice(preimage)?,
+        KldCliSubCommand::CancelInvoice { payment_hash } => api.cancel_invoice(payment_hash)?,
+        KldCliSubCommand::ListInvoices { label, page } => api.list_invoices(label, page.into())?,
+        KldCliSubCommand::WaitInvoice { label, timeout } => api.wait_invoice(label, timeout)?,
+        KldCliSubCommand::WaitAnyInvoice {
+            lastpay_index,
+            timeout,
+        } => api.wait_any_invoice(lastpay_index, timeout)?,
+        KldCliSubCommand::PayInvoice {
+            bolt11,
+            label,
+            amount,
+            max_fee_msat,
+            max_fee_ppm,
+            retry_for,
+            max_paths,
+            max_cltv_expiry_delta,
+            non_blocking,
+        } => api.pay_invoice(PayInvoice {
+            invoice: bolt11,
+            label,
+            amount,
+            max_fee_msat,
+            max_fee_ppm,
+            retry_for,
+            max_paths,
+            max_cltv_expiry_delta,
+            non_blocking: non_blocking.then_some(true),
+        })?,
+        KldCliSubCommand::ListPayments {
+            bolt11,
+            direction,
+            page,
+        } => api.list_payments(bolt11, direction, page.into())?,
+        KldCliSubCommand::PaymentStatus { id } => api.payment_status(id)?,
+        KldCliSubCommand::CreateOffer {
+            description,
+            amount,
+            label,
+            expiry,
+        } => api.create_offer(description, amount, label, expiry)?,
+        KldCliSubCommand::PayOffer {
+            offer,
+            amount,
+            max_fee_msat,
+            payer_note,
+            label,
+        } => api.pay_offer(offer, amount, max_fee_msat, payer_note, label)?,
+        KldCliSubCommand::ListOffers { label } => api.list_offers(label)?,
         KldCliSubCommand::EstimateChannelLiquidity { scid, target } => {
             api.estimate_channel_liquidity(scid, target)?
         }
         KldCliSubCommand::LocalRemoteBalance => api.local_remote_balance()?,
         KldCliSubCommand::GetFees => api.get_fees()?,
-        KldCliSubCommand::ListForwards { status } => api.list_forwards(status)?,
-        KldCliSubCommand::ListChannelHistory => api.channel_history()?,
+        KldCliSubCommand::ListForwards { status, page } => {
+            api.list_forwards(status, page.into())?
+        }
+        KldCliSubCommand::ForwardingReport { bucket, from, to } => {
+            api.forwarding_report(bucket, from, to)?
+        }
+        KldCliSubCommand::ListChannelHistory { page } => api.channel_history(page.into())?,
+        KldCliSubCommand::FeeHistory { channel_id } => api.fee_history(channel_id)?,
         KldCliSubCommand::Decode { invoice } => api.decode(invoice)?,
         KldCliSubCommand::Scorer { path } => api.scorer(path.unwrap_or("scorer.bin".into()))?,
+        KldCliSubCommand::Backup { path } => {
+            api.backup(path.unwrap_or("channel_backup.scb".into()))?
+        }
+        KldCliSubCommand::BakeMacaroon {
+            permissions,
+            expires_at,
+            max_amount_msat,
+            ip_ranges,
+            root_key_id,
+        } => api.bake_macaroon(BakeMacaroon {
+            root_key_id,
+            permissions,
+            expires_at,
+            max_amount_msat,
+            ip_ranges,
+        })?,
+        KldCliSubCommand::ListRootKeys => api.list_root_keys()?,
+        KldCliSubCommand::RevokeRootKey { id } => api.revoke_root_key(id)?,
+        KldCliSubCommand::ListLsps2Tokens => api.list_lsps2_tokens()?,
+        KldCliSubCommand::CreateLsps2Token {
+            token,
+            min_fee_msat,
+            proportional,
+            valid_for_secs,
+            min_payment_size_msat,
+            max_payment_size_msat,
+        } => api.create_lsps2_token(CreateLsps2Token {
+            token,
+            min_fee_msat,
+            proportional,
+            valid_for_secs,
+            min_payment_size_msat,
+            max_payment_size_msat,
+        })?,
+        KldCliSubCommand::DeleteLsps2Token { token } => api.delete_lsps2_token(token)?,
+        KldCliSubCommand::ListJitChannels => api.list_jit_channels()?,
+        KldCliSubCommand::ListLsps1Orders => api.list_lsps1_orders()?,
+        KldCliSubCommand::Lsp { command } => match command {
+            LspSubCommand::GetInfo { node_id, token } => api.lsp_info(node_id, token)?,
+            LspSubCommand::BuyChannel {
+                node_id,
+                lsp_balance_sat,
+                client_balance_sat,
+                channel_expiry_blocks,
+                announce_channel,
+                token,
+            } => api.buy_channel(BuyChannel {
+                node_id,
+                lsp_balance_sat,
+                client_balance_sat,
+                channel_expiry_blocks,
+                announce_channel,
+                token,
+            })?,
+            LspSubCommand::JitInvoice {
+                node_id,
+                label,
+                description,
+                amount,
+                expiry,
+                token,
+            } => api.generate_jit_invoice(GenerateJitInvoice {
+                node_id,
+                token,
+                amount,
+                label,
+                description,
+                expiry,
+            })?,
+        },
+        KldCliSubCommand::Export { kind, format } => api.export(kind, format)?,
         KldCliSubCommand::ListChannels => api.list_channels()?,
     };
     if output != "null" {
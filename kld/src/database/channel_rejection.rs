@@ -0,0 +1,57 @@
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::ChannelId;
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// An inbound channel request that did not pass the inbound channel policy.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChannelRejection {
+    pub id: Uuid,
+    pub temporary_channel_id: ChannelId,
+    pub counterparty_node_id: PublicKey,
+    pub funding_satoshis: u64,
+    pub push_msat: u64,
+    pub reason: String,
+    pub timestamp: OffsetDateTime,
+}
+
+impl ChannelRejection {
+    pub fn new(
+        temporary_channel_id: ChannelId,
+        counterparty_node_id: PublicKey,
+        funding_satoshis: u64,
+        push_msat: u64,
+        reason: String,
+    ) -> ChannelRejection {
+        ChannelRejection {
+            id: Uuid::new_v4(),
+            temporary_channel_id,
+            counterparty_node_id,
+            funding_satoshis,
+            push_msat,
+            reason,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<Row> for ChannelRejection {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(ChannelRejection {
+            id: row.get("id"),
+            temporary_channel_id: ChannelId::from_bytes(
+                row.get::<&str, &[u8]>("temporary_channel_id").try_into()?,
+            ),
+            counterparty_node_id: PublicKey::from_slice(row.get("counterparty_node_id"))?,
+            funding_satoshis: row.get::<&str, i64>("funding_satoshis") as u64,
+            push_msat: row.get::<&str, i64>("push_msat") as u64,
+            reason: row.get("reason"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use lightning::ln::ChannelId;
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::MillisatAmount;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// A change of the proportional forwarding fee of a channel made by the automatic fee adjustment.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChannelFeeUpdate {
+    pub id: Uuid,
+    pub channel_id: ChannelId,
+    pub previous_ppm: u32,
+    pub ppm: u32,
+    pub base_msat: u32,
+    // Share of the channel capacity on our side when the fee was changed.
+    pub outbound_ratio: f64,
+    // Amount forwarded out through the channel during the lookback window.
+    pub forwarded_amount: MillisatAmount,
+    pub timestamp: OffsetDateTime,
+}
+
+impl ChannelFeeUpdate {
+    pub fn new(
+        channel_id: ChannelId,
+        previous_ppm: u32,
+        ppm: u32,
+        base_msat: u32,
+        outbound_ratio: f64,
+        forwarded_amount: MillisatAmount,
+    ) -> ChannelFeeUpdate {
+        ChannelFeeUpdate {
+            id: Uuid::new_v4(),
+            channel_id,
+            previous_ppm,
+            ppm,
+            base_msat,
+            outbound_ratio,
+            forwarded_amount,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<Row> for ChannelFeeUpdate {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(ChannelFeeUpdate {
+            id: row.get("id"),
+            channel_id: ChannelId::from_bytes(row.get::<&str, &[u8]>("channel_id").try_into()?),
+            previous_ppm: row.get::<&str, i64>("previous_ppm") as u32,
+            ppm: row.get::<&str, i64>("ppm") as u32,
+            base_msat: row.get::<&str, i64>("base_msat") as u32,
+            outbound_ratio: row.get("outbound_ratio"),
+            forwarded_amount: row.get::<&str, i64>("forwarded_amount") as MillisatAmount,
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
@@ -1,5 +1,10 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
 use crate::MillisatAmount;
 
+use anyhow::bail;
 use lightning::events::HTLCDestination;
 use lightning::ln::ChannelId;
 use postgres_types::{FromSql, ToSql};
@@ -105,3 +110,75 @@ pub struct TotalForwards {
     pub amount: MillisatAmount,
     pub fee: MillisatAmount,
 }
+
+/// The length of the periods that the forwarding report groups forwards by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl Display for TimeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeBucket::Day => f.write_str("day"),
+            TimeBucket::Week => f.write_str("week"),
+            TimeBucket::Month => f.write_str("month"),
+        }
+    }
+}
+
+impl FromStr for TimeBucket {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(TimeBucket::Day),
+            "week" => Ok(TimeBucket::Week),
+            "month" => Ok(TimeBucket::Month),
+            _ => bail!("Unknown time bucket {s}, must be day, week or month"),
+        }
+    }
+}
+
+/// The forwards between two channels in one period.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ForwardSummary {
+    pub period_start: OffsetDateTime,
+    pub inbound_channel_id: ChannelId,
+    // Unknown for failures that never reached a next hop channel.
+    pub outbound_channel_id: Option<ChannelId>,
+    pub succeeded: u64,
+    pub amount: MillisatAmount,
+    pub fee: MillisatAmount,
+    // Number of failed forwards by the kind of HTLCDestination they failed at.
+    pub failures: BTreeMap<String, u64>,
+}
+
+impl ForwardSummary {
+    pub fn new(
+        period_start: OffsetDateTime,
+        inbound_channel_id: ChannelId,
+        outbound_channel_id: Option<ChannelId>,
+    ) -> ForwardSummary {
+        ForwardSummary {
+            period_start,
+            inbound_channel_id,
+            outbound_channel_id,
+            succeeded: 0,
+            amount: 0,
+            fee: 0,
+            failures: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn failure_kind(destination: &HTLCDestination) -> &'static str {
+    match destination {
+        HTLCDestination::NextHopChannel { .. } => "NextHopChannel",
+        HTLCDestination::UnknownNextHop { .. } => "UnknownNextHop",
+        HTLCDestination::InvalidForward { .. } => "InvalidForward",
+        HTLCDestination::FailedPayment { .. } => "FailedPayment",
+    }
+}
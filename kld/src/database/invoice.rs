@@ -1,13 +1,44 @@
-use std::{str::FromStr, time::SystemTime};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    time::SystemTime,
+};
 
 use anyhow::{anyhow, Result};
 use bitcoin::{hashes::Hash, secp256k1::PublicKey};
 use lightning::ln::PaymentHash;
+use postgres_types::{FromSql, ToSql};
+use tokio_postgres::Row;
 
 use crate::MillisatAmount;
 
 use super::payment::Payment;
 
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "invoice_status")]
+pub enum InvoiceStatus {
+    #[postgres(name = "open")]
+    Open,
+    // A payment to a hold invoice arrived and waits to be settled or canceled.
+    #[postgres(name = "held")]
+    Held,
+    #[postgres(name = "settled")]
+    Settled,
+    #[postgres(name = "canceled")]
+    Canceled,
+}
+
+impl Display for InvoiceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvoiceStatus::Open => f.write_str("open"),
+            InvoiceStatus::Held => f.write_str("held"),
+            InvoiceStatus::Settled => f.write_str("settled"),
+            InvoiceStatus::Canceled => f.write_str("canceled"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Invoice {
     pub payment_hash: PaymentHash,
@@ -20,6 +51,13 @@ pub struct Invoice {
     pub amount: Option<MillisatAmount>,
     // The time that the invoice was generated.
     pub timestamp: SystemTime,
+    pub status: InvoiceStatus,
+    // Block height until which a held payment can be settled, it is canceled automatically before.
+    pub claim_deadline: Option<u32>,
+    pub amount_received: Option<MillisatAmount>,
+    pub paid_at: Option<SystemTime>,
+    // Position of the invoice in the order invoices were paid in, starting at 1.
+    pub pay_index: Option<u64>,
     // Payments with the payment_hash of the bolt11 invoice.
     pub payments: Vec<Payment>,
 }
@@ -54,27 +92,51 @@ impl Invoice {
             expiry,
             amount,
             timestamp,
+            status: InvoiceStatus::Open,
+            claim_deadline: None,
+            amount_received: None,
+            paid_at: None,
+            pay_index: None,
             payments: vec![],
         })
     }
 
-    pub fn deserialize(
-        payment_hash: PaymentHash,
-        label: Option<String>,
-        bolt11: String,
-        payee_pub_key: Vec<u8>,
-        expiry: Option<u64>,
-        amount: Option<i64>,
-        timestamp: SystemTime,
-    ) -> Result<Self> {
+    /// Nothing happens to the invoice anymore once it is settled, canceled or expired without a held payment.
+    pub fn is_final(&self) -> bool {
+        match self.status {
+            InvoiceStatus::Settled | InvoiceStatus::Canceled => true,
+            InvoiceStatus::Held => false,
+            InvoiceStatus::Open => self.bolt11.is_expired(),
+        }
+    }
+}
+
+impl TryFrom<&Row> for Invoice {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> std::result::Result<Self, Self::Error> {
+        let payment_hash: Vec<u8> = row.get("payment_hash");
+        let bolt11: String = row.get("bolt11");
+        let payee_pub_key: Vec<u8> = row.get("payee_pub_key");
         Ok(Invoice {
-            payment_hash,
-            label,
+            payment_hash: PaymentHash(payment_hash.as_slice().try_into()?),
+            label: row.get("invoice_label"),
             bolt11: lightning_invoice::Bolt11Invoice::from_str(&bolt11)?,
             payee_pub_key: PublicKey::from_slice(&payee_pub_key)?,
-            expiry,
-            amount: amount.map(|a| a as u64),
-            timestamp,
+            expiry: row.get::<&str, Option<i64>>("expiry").map(|e| e as u64),
+            amount: row
+                .get::<&str, Option<i64>>("invoice_amount")
+                .map(|a| a as u64),
+            timestamp: row.get("invoice_timestamp"),
+            status: row.get("invoice_status"),
+            claim_deadline: row
+                .get::<&str, Option<i64>>("claim_deadline")
+                .map(|h| h as u32),
+            amount_received: row
+                .get::<&str, Option<i64>>("amount_received")
+                .map(|a| a as u64),
+            paid_at: row.get("paid_at"),
+            pay_index: row.get::<&str, Option<i64>>("pay_index").map(|i| i as u64),
             payments: vec![],
         })
     }
@@ -2,14 +2,24 @@ use crate::database::{microsecond_timestamp, to_primitive, RowExt};
 use crate::ldk::{ldk_error, ChainMonitor};
 use crate::logger::KldLogger;
 use crate::settings::Settings;
+use crate::MillisatAmount;
 use bitcoin_hashes::Hash;
 
-use super::forward::{Forward, ForwardStatus, TotalForwards};
-use super::invoice::Invoice;
-use super::payment::{Payment, PaymentDirection};
-use super::{DurableConnection, Params};
+use super::channel_rejection::ChannelRejection;
+use super::fee_history::ChannelFeeUpdate;
+use super::forward::{
+    failure_kind, Forward, ForwardStatus, ForwardSummary, TimeBucket, TotalForwards,
+};
+use super::invoice::{Invoice, InvoiceStatus};
+use super::lsps1::{Lsps1Order, OrderState};
+use super::lsps2::{JitChannel, JitInvoice, Lsps2Offer, Lsps2Token};
+use super::macaroon::{MacaroonKeyStore, MacaroonRootKey};
+use super::offer::Offer;
+use super::payment::{Payment, PaymentAttempt, PaymentDirection};
+use super::{DurableConnection, Pagination, Params};
 use anyhow::bail;
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::BlockHash;
 use bitcoin::Txid;
@@ -18,12 +28,15 @@ use lightning::chain::chainmonitor::MonitorUpdateId;
 use lightning::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate};
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::{self, ChannelMonitorUpdateStatus, Watch};
-use lightning::ln::channelmanager::{ChannelDetails, ChannelManager, ChannelManagerReadArgs};
+use lightning::events::HTLCDestination;
+use lightning::ln::channelmanager::{
+    ChannelDetails, ChannelManager, ChannelManagerReadArgs, PaymentId,
+};
 use lightning::ln::msgs::SocketAddress;
 use lightning::ln::ChannelId;
 use lightning::ln::PaymentHash;
 use lightning::routing::gossip::{NetworkGraph, NodeId};
-use lightning::routing::router::Router;
+use lightning::routing::router::{Route, Router};
 use lightning::routing::scoring::{
     ProbabilisticScorer, ProbabilisticScoringDecayParameters, WriteableScore,
 };
@@ -38,15 +51,20 @@ use lightning::util::ser::Writeable;
 use log::{debug, error};
 
 use super::peer::Peer;
+use super::rebalance::Rebalance;
+use super::sweep::{OutputSweep, SweepStatus};
 use super::{ChannelRecord, SpendableOutputRecord};
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{AsRef, TryInto};
 use std::io::Cursor;
 use std::ops::Deref;
 use std::sync::{Arc, OnceLock};
 use std::time::SystemTime;
 use std::{fs, io};
+use time::OffsetDateTime;
 use tokio::runtime::Handle;
+use tokio_postgres::Row;
 
 pub struct LdkDatabase {
     settings: Arc<Settings>,
@@ -334,22 +352,28 @@ impl LdkDatabase {
         Ok(())
     }
 
-    pub async fn fetch_channel_history(&self) -> Result<Vec<ChannelRecord>> {
+    /// Closed channels, paged by the time they were closed.
+    pub async fn fetch_channel_history(
+        &self,
+        pagination: &Pagination,
+    ) -> Result<Vec<ChannelRecord>> {
+        let mut params = Params::default();
+        let mut query = "
+            SELECT
+                data,
+                open_timestamp,
+                update_timestamp,
+                closure_reason
+            FROM
+                channels
+            WHERE is_usable = false"
+            .to_string();
+        pagination.append_to(&mut query, &mut params, "update_timestamp", "channel_id");
         let rows = self
             .durable_connection
             .get()
             .await
-            .query(
-                "SELECT
-                    data,
-                    open_timestamp,
-                    update_timestamp,
-                    closure_reason
-            FROM
-                channels
-            WHERE is_usable = false",
-                &[],
-            )
+            .query(&query, &params.to_params())
             .await?;
 
         let mut outputs = vec![];
@@ -506,6 +530,85 @@ impl LdkDatabase {
         Ok(outputs)
     }
 
+    pub async fn mark_spendable_outputs_spent(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Result<()> {
+        let client = self.durable_connection.get().await;
+        for outpoint in outpoints {
+            debug!("Spendable output {outpoint} is spent");
+            let txid: &[u8] = outpoint.txid.as_ref();
+            client
+                .execute(
+                    r#"UPDATE spendable_outputs SET is_spent = true WHERE txid = $1 AND "index" = $2"#,
+                    &[&txid, &(outpoint.vout as i16)],
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn persist_output_sweep(&self, sweep: &OutputSweep) -> Result<()> {
+        debug!("Persist output sweep {}", sweep.txid);
+        let txid: &[u8] = sweep.txid.as_ref();
+        let mut transaction = vec![];
+        sweep.transaction.write(&mut transaction)?;
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO output_sweeps (
+                    txid,
+                    transaction,
+                    fee_rate,
+                    broadcast_height,
+                    confirmation_height,
+                    status,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &txid,
+                    &transaction,
+                    &(sweep.fee_rate as i64),
+                    &(sweep.broadcast_height as i64),
+                    &sweep.confirmation_height.map(|h| h as i64),
+                    &sweep.status,
+                    &to_primitive(&sweep.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_output_sweeps(
+        &self,
+        status: Option<SweepStatus>,
+    ) -> Result<Vec<OutputSweep>> {
+        let mut params = Params::default();
+        let mut query = "SELECT
+                transaction,
+                fee_rate,
+                broadcast_height,
+                confirmation_height,
+                status,
+                timestamp
+            FROM
+                output_sweeps"
+            .to_string();
+        if let Some(status) = status {
+            params.push(status);
+            query.push_str(&format!(" WHERE status = ${}", params.count()));
+        }
+        query.push_str(" ORDER BY timestamp ASC");
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(&query, &params.to_params())
+            .await?;
+        rows.into_iter().map(OutputSweep::try_from).collect()
+    }
+
     pub async fn persist_invoice(&self, invoice: &Invoice) -> Result<()> {
         debug!(
             "Persist invoice with hash: {}",
@@ -524,8 +627,13 @@ impl LdkDatabase {
                     payee_pub_key,
                     expiry,
                     amount,
-                    timestamp
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    timestamp,
+                    status,
+                    claim_deadline,
+                    amount_received,
+                    paid_at,
+                    pay_index
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
                 &[
                     &payment_hash,
                     &invoice.label,
@@ -534,17 +642,117 @@ impl LdkDatabase {
                     &(invoice.bolt11.expiry_time().as_secs() as i64),
                     &invoice.amount.map(|a| a as i64),
                     &invoice.timestamp,
+                    &invoice.status,
+                    &invoice.claim_deadline.map(|h| h as i64),
+                    &invoice.amount_received.map(|a| a as i64),
+                    &invoice.paid_at,
+                    &invoice.pay_index.map(|i| i as i64),
                 ],
             )
             .await?;
         Ok(())
     }
 
-    pub async fn fetch_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>> {
+    pub async fn update_invoice_status(
+        &self,
+        payment_hash: &PaymentHash,
+        status: InvoiceStatus,
+        claim_deadline: Option<u32>,
+    ) -> Result<()> {
+        debug!(
+            "Update status of invoice with hash {} to {status}",
+            hex::encode(payment_hash.0)
+        );
+        let payment_hash: &[u8] = payment_hash.0.as_ref();
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPDATE invoices SET status = $1, claim_deadline = $2 WHERE payment_hash = $3",
+                &[&status, &claim_deadline.map(|h| h as i64), &payment_hash],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_invoice_status(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<InvoiceStatus>> {
+        let payment_hash: &[u8] = payment_hash.0.as_ref();
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT status FROM invoices WHERE payment_hash = $1",
+                &[&payment_hash],
+            )
+            .await?
+            .map(|row| row.get("status")))
+    }
+
+    /// The payment hashes and claim deadlines of the invoices with a payment waiting to be settled or canceled.
+    pub async fn fetch_held_invoices(&self) -> Result<Vec<(PaymentHash, Option<u32>)>> {
+        let mut held = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT payment_hash, claim_deadline FROM invoices WHERE status = $1",
+                &[&InvoiceStatus::Held],
+            )
+            .await?
+        {
+            let payment_hash: Vec<u8> = row.get("payment_hash");
+            let claim_deadline: Option<i64> = row.get("claim_deadline");
+            held.push((
+                PaymentHash(payment_hash.as_slice().try_into()?),
+                claim_deadline.map(|h| h as u32),
+            ));
+        }
+        Ok(held)
+    }
+
+    pub async fn fetch_invoices(
+        &self,
+        label: Option<String>,
+        pagination: &Pagination,
+    ) -> Result<Vec<Invoice>> {
+        let mut params = Params::default();
+        let mut filter = "WHERE 1 = 1".to_string();
+        if let Some(label) = label {
+            params.push(label);
+            filter.push_str(&format!("\nAND label = ${}", params.count()));
+        }
+        self.query_invoices(filter, pagination, params).await
+    }
+
+    /// The first invoice paid after the one with the given pay index.
+    pub async fn fetch_next_paid_invoice(&self, lastpay_index: u64) -> Result<Option<Invoice>> {
+        let mut params = Params::default();
+        params.push(lastpay_index as i64);
+        let filter = "WHERE pay_index = (SELECT min(pay_index) FROM invoices WHERE pay_index > $1)"
+            .to_string();
+        Ok(self
+            .query_invoices(filter, &Pagination::default(), params)
+            .await?
+            .pop())
+    }
+
+    // The page is taken from the invoices before joining their payments, so the limit counts invoices.
+    async fn query_invoices(
+        &self,
+        mut filter: String,
+        pagination: &Pagination,
+        mut params: Params<'_>,
+    ) -> Result<Vec<Invoice>> {
         debug!("Fetching invoices from database");
         let connection = self.durable_connection.get().await;
-        let mut params = Params::default();
-        let mut query = "
+        pagination.append_to(&mut filter, &mut params, "timestamp", "payment_hash");
+        let query = format!(
+            "
             SELECT
                 i.label as invoice_label,
                 i.payment_hash,
@@ -553,6 +761,11 @@ impl LdkDatabase {
                 i.amount as invoice_amount,
                 i.payee_pub_key,
                 i.timestamp as invoice_timestamp,
+                i.status as invoice_status,
+                i.claim_deadline,
+                i.amount_received,
+                i.paid_at,
+                i.pay_index,
                 p.id,
                 p.hash,
                 p.preimage,
@@ -563,14 +776,13 @@ impl LdkDatabase {
                 p.direction,
                 p.timestamp,
                 p.label
-            FROM invoices i
-            LEFT OUTER JOIN payments p ON i.payment_hash = p.hash"
-            .to_string();
-        if let Some(label) = &label {
-            params.push(label);
-            query.push_str(&format!("\nWHERE i.label = ${}", params.count()));
-        }
-        let mut invoices: HashMap<PaymentHash, Invoice> = HashMap::new();
+            FROM (SELECT * FROM invoices {filter}) i
+            LEFT OUTER JOIN payments p ON i.payment_hash = p.hash
+            {}",
+            pagination.order_by("i.timestamp", "i.payment_hash")
+        );
+        let mut invoices: Vec<Invoice> = vec![];
+        let mut positions: HashMap<PaymentHash, usize> = HashMap::new();
         for row in connection.query(&query, &params.to_params()).await? {
             let payment_hash: Vec<u8> = row.get("payment_hash");
             let payment_hash = PaymentHash(payment_hash.as_slice().try_into()?);
@@ -579,33 +791,46 @@ impl LdkDatabase {
             } else {
                 None
             };
-            if let Some(invoice) = invoices.get_mut(&payment_hash) {
-                if let Some(payment) = payment {
-                    invoice.payments.push(payment);
-                }
-            } else {
-                let label: Option<String> = row.get("invoice_label");
-                let bolt11: String = row.get("bolt11");
-                let expiry: Option<i64> = row.get("expiry");
-                let payee_pub_key: Vec<u8> = row.get("payee_pub_key");
-                let amount: Option<i64> = row.get("invoice_amount");
-                let timestamp: SystemTime = row.get("invoice_timestamp");
-                let mut invoice = Invoice::deserialize(
-                    payment_hash,
-                    label,
-                    bolt11,
-                    payee_pub_key,
-                    expiry.map(|i| i as u64),
-                    amount,
-                    timestamp,
-                )?;
-                if let Some(payment) = payment {
-                    invoice.payments.push(payment);
+            let position = match positions.entry(payment_hash) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    invoices.push(Invoice::try_from(&row)?);
+                    *entry.insert(invoices.len() - 1)
                 }
-                invoices.insert(invoice.payment_hash, invoice);
+            };
+            if let Some(payment) = payment {
+                invoices[position].payments.push(payment);
             }
         }
-        Ok(Vec::from_iter(invoices.into_values()))
+        Ok(invoices)
+    }
+
+    /// Record the payment of an invoice and give it the next pay index.
+    pub async fn persist_invoice_payment(
+        &self,
+        payment_hash: &PaymentHash,
+        amount_received: MillisatAmount,
+    ) -> Result<()> {
+        let payment_hash: &[u8] = payment_hash.0.as_ref();
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPDATE invoices SET
+                    status = $1,
+                    claim_deadline = NULL,
+                    amount_received = $2,
+                    paid_at = current_timestamp(),
+                    pay_index = nextval('invoice_pay_index')
+                WHERE payment_hash = $3 AND pay_index IS NULL",
+                &[
+                    &InvoiceStatus::Settled,
+                    &(amount_received as i64),
+                    &payment_hash,
+                ],
+            )
+            .await?;
+        Ok(())
     }
 
     pub async fn persist_payment(&self, payment: &Payment) -> Result<()> {
@@ -647,6 +872,7 @@ impl LdkDatabase {
         &self,
         payment_hash: Option<PaymentHash>,
         direction: Option<PaymentDirection>,
+        pagination: &Pagination,
     ) -> Result<Vec<Payment>> {
         let connection = self.durable_connection.get().await;
         let mut payments = vec![];
@@ -670,12 +896,13 @@ impl LdkDatabase {
             .to_string();
         if let Some(hash) = &payment_hash {
             params.push(hash.0.to_vec());
-            query.push_str(&format!("AND p.hash = ${}", params.count()));
+            query.push_str(&format!("\nAND p.hash = ${}", params.count()));
         }
         if let Some(direction) = direction {
             params.push(direction);
-            query.push_str(&format!("AND p.direction = ${}", params.count()));
+            query.push_str(&format!("\nAND p.direction = ${}", params.count()));
         }
+        pagination.append_to(&mut query, &mut params, "p.timestamp", "p.id");
         for row in connection
             .query(&query.to_string(), &params.to_params())
             .await?
@@ -685,6 +912,175 @@ impl LdkDatabase {
         Ok(payments)
     }
 
+    pub async fn fetch_payment(&self, id: &PaymentId) -> Result<Option<Payment>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT
+                    p.id,
+                    p.hash,
+                    p.preimage,
+                    p.secret,
+                    p.label,
+                    p.status,
+                    p.amount,
+                    p.fee,
+                    p.direction,
+                    p.timestamp,
+                    i.bolt11
+                FROM payments as p
+                LEFT OUTER JOIN invoices i ON p.hash = i.payment_hash
+                WHERE p.id = $1",
+                &[&id.0.to_vec()],
+            )
+            .await?
+            .map(|row| Payment::try_from(&row))
+            .transpose()
+    }
+
+    pub async fn persist_payment_attempt(&self, attempt: &PaymentAttempt) -> Result<()> {
+        debug!(
+            "Persist attempt {} for payment id: {}",
+            attempt.id,
+            hex::encode(attempt.payment_id.0)
+        );
+        let route = Route {
+            paths: vec![attempt.path.clone()],
+            route_params: None,
+        };
+        let failure = if let Some(failure) = &attempt.failure {
+            let mut bytes = vec![];
+            failure.write(&mut bytes)?;
+            Some(bytes)
+        } else {
+            None
+        };
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO payment_attempts (
+                    id,
+                    payment_id,
+                    path,
+                    status,
+                    failure,
+                    short_channel_id,
+                    payment_failed_permanently,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &attempt.id,
+                    &attempt.payment_id.0.to_vec(),
+                    &route.encode(),
+                    &attempt.status,
+                    &failure,
+                    &attempt.short_channel_id.map(|x| x as i64),
+                    &attempt.payment_failed_permanently,
+                    &to_primitive(&attempt.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_payment_attempts(
+        &self,
+        payment_id: &PaymentId,
+    ) -> Result<Vec<PaymentAttempt>> {
+        let mut attempts = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT
+                    id,
+                    payment_id,
+                    path,
+                    status,
+                    failure,
+                    short_channel_id,
+                    payment_failed_permanently,
+                    timestamp
+                FROM payment_attempts
+                WHERE payment_id = $1
+                ORDER BY timestamp ASC",
+                &[&payment_id.0.to_vec()],
+            )
+            .await?
+        {
+            attempts.push(PaymentAttempt::try_from(&row)?);
+        }
+        Ok(attempts)
+    }
+
+    pub async fn persist_offer(&self, offer: &Offer) -> Result<()> {
+        debug!("Persist offer with id: {}", hex::encode(offer.id));
+        let expiry = offer
+            .expiry
+            .map(|e| OffsetDateTime::from_unix_timestamp(e as i64))
+            .transpose()?;
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO offers (
+                    id,
+                    label,
+                    bolt12,
+                    amount,
+                    description,
+                    expiry,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &offer.id.to_vec(),
+                    &offer.label,
+                    &offer.bolt12.to_string(),
+                    &offer.amount.map(|a| a as i64),
+                    &offer.description,
+                    &expiry.as_ref().map(to_primitive),
+                    &to_primitive(&offer.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_offers(&self, label: Option<String>) -> Result<Vec<Offer>> {
+        debug!("Fetching offers from database");
+        let mut params = Params::default();
+        let mut query = "
+            SELECT
+                id,
+                label,
+                bolt12,
+                amount,
+                description,
+                expiry,
+                timestamp
+            FROM offers"
+            .to_string();
+        if let Some(label) = &label {
+            params.push(label);
+            query.push_str(&format!("\nWHERE label = ${}", params.count()));
+        }
+        query.push_str("\nORDER BY timestamp ASC");
+        let mut offers = vec![];
+        for row in self
+            .durable_connection
+            .get()
+            .await
+            .query(&query, &params.to_params())
+            .await?
+        {
+            offers.push(Offer::try_from(&row)?);
+        }
+        Ok(offers)
+    }
+
     pub async fn persist_forward(&self, forward: Forward) -> Result<()> {
         debug!("Persist forward with ID {}", forward.id);
 
@@ -724,7 +1120,11 @@ impl LdkDatabase {
         Ok(())
     }
 
-    pub async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
+    pub async fn fetch_forwards(
+        &self,
+        status: Option<ForwardStatus>,
+        pagination: &Pagination,
+    ) -> Result<Vec<Forward>> {
         let mut statement = "
             SELECT
                 id,
@@ -737,14 +1137,14 @@ impl LdkDatabase {
                 timestamp
             FROM
                 forwards
-            "
-        .to_string();
+            WHERE 1 = 1"
+            .to_string();
         let mut params = Params::default();
         if let Some(status) = status {
-            statement.push_str("WHERE status = $1");
             params.push(status);
+            statement.push_str(&format!("\nAND status = ${}", params.count()));
         }
-        statement.push_str("ORDER BY timestamp ASC");
+        pagination.append_to(&mut statement, &mut params, "timestamp", "id");
         let mut forwards = vec![];
         let rows = self
             .durable_connection
@@ -759,15 +1159,125 @@ impl LdkDatabase {
         Ok(forwards)
     }
 
-    pub async fn fetch_total_forwards(&self) -> Result<TotalForwards> {
-        Ok(self
+    pub async fn persist_channel_rejection(&self, rejection: &ChannelRejection) -> Result<()> {
+        debug!(
+            "Persist rejection of channel {}",
+            hex::encode(rejection.temporary_channel_id.0)
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "INSERT INTO channel_rejections (
+                    id,
+                    temporary_channel_id,
+                    counterparty_node_id,
+                    funding_satoshis,
+                    push_msat,
+                    reason,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &rejection.id,
+                    &rejection.temporary_channel_id.0.to_vec(),
+                    &rejection.counterparty_node_id.encode(),
+                    &(rejection.funding_satoshis as i64),
+                    &(rejection.push_msat as i64),
+                    &rejection.reason,
+                    &to_primitive(&rejection.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_channel_rejections(&self) -> Result<Vec<ChannelRejection>> {
+        let rows = self
             .durable_connection
             .get()
             .await
-            .query_one(
+            .query(
                 "SELECT
-                    count(*) AS count,
-                    COALESCE(CAST(sum(amount) AS INT), 0) AS amount,
+                    id,
+                    temporary_channel_id,
+                    counterparty_node_id,
+                    funding_satoshis,
+                    push_msat,
+                    reason,
+                    timestamp
+                FROM
+                    channel_rejections
+                ORDER BY timestamp ASC",
+                &[],
+            )
+            .await?;
+        rows.into_iter().map(ChannelRejection::try_from).collect()
+    }
+
+    pub async fn persist_rebalance(&self, rebalance: &Rebalance) -> Result<()> {
+        debug!("Persist rebalance with ID {}", rebalance.id);
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO rebalances (
+                    id,
+                    payment_id,
+                    outbound_channel_id,
+                    inbound_channel_id,
+                    amount,
+                    fee,
+                    status,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &rebalance.id,
+                    &rebalance.payment_id.0.to_vec(),
+                    &rebalance.outbound_channel_id.0.to_vec(),
+                    &rebalance.inbound_channel_id.0.to_vec(),
+                    &(rebalance.amount as i64),
+                    &rebalance.fee.map(|x| x as i64),
+                    &rebalance.status,
+                    &to_primitive(&rebalance.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_rebalances(&self) -> Result<Vec<Rebalance>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT
+                    id,
+                    payment_id,
+                    outbound_channel_id,
+                    inbound_channel_id,
+                    amount,
+                    fee,
+                    status,
+                    timestamp
+                FROM
+                    rebalances
+                ORDER BY timestamp ASC",
+                &[],
+            )
+            .await?;
+        rows.into_iter().map(Rebalance::try_from).collect()
+    }
+
+    pub async fn fetch_total_forwards(&self) -> Result<TotalForwards> {
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .query_one(
+                "SELECT
+                    count(*) AS count,
+                    COALESCE(CAST(sum(amount) AS INT), 0) AS amount,
                     COALESCE(CAST(sum(fee) AS INT), 0) AS fee
                 FROM forwards
                 WHERE status = 'succeeded';",
@@ -777,6 +1287,196 @@ impl LdkDatabase {
             .into())
     }
 
+    /// The amount of successful forwards per outbound channel since the given time.
+    pub async fn fetch_forwarded_amounts(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<HashMap<ChannelId, MillisatAmount>> {
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT
+                    outbound_channel_id,
+                    COALESCE(CAST(sum(amount) AS INT), 0) AS amount
+                FROM forwards
+                WHERE status = 'succeeded'
+                AND outbound_channel_id IS NOT NULL
+                AND timestamp >= $1
+                GROUP BY outbound_channel_id",
+                &[&to_primitive(&since)],
+            )
+            .await?;
+        let mut amounts = HashMap::new();
+        for row in rows {
+            let channel_id =
+                ChannelId::from_bytes(row.get::<&str, &[u8]>("outbound_channel_id").try_into()?);
+            amounts.insert(channel_id, row.get::<&str, i64>("amount") as MillisatAmount);
+        }
+        Ok(amounts)
+    }
+
+    /// Forwards grouped by the period they happened in and the channels they went through.
+    pub async fn fetch_forward_summaries(
+        &self,
+        bucket: TimeBucket,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Result<Vec<ForwardSummary>> {
+        let mut params = Params::default();
+        let mut range = String::new();
+        if let Some(from) = from {
+            params.push(to_primitive(&from));
+            range.push_str(&format!("\nAND timestamp >= ${}", params.count()));
+        }
+        if let Some(to) = to {
+            params.push(to_primitive(&to));
+            range.push_str(&format!("\nAND timestamp < ${}", params.count()));
+        }
+        let connection = self.durable_connection.get().await;
+        type Key = (OffsetDateTime, [u8; 32], Option<[u8; 32]>);
+        fn summary<'a>(
+            summaries: &'a mut BTreeMap<Key, ForwardSummary>,
+            row: &Row,
+            outbound: Option<[u8; 32]>,
+        ) -> Result<&'a mut ForwardSummary> {
+            let period_start = row.get_timestamp("period_start");
+            let inbound: [u8; 32] = row.get::<&str, &[u8]>("inbound_channel_id").try_into()?;
+            Ok(summaries
+                .entry((period_start, inbound, outbound))
+                .or_insert_with(|| {
+                    ForwardSummary::new(
+                        period_start,
+                        ChannelId::from_bytes(inbound),
+                        outbound.map(ChannelId::from_bytes),
+                    )
+                }))
+        }
+        let mut summaries: BTreeMap<Key, ForwardSummary> = BTreeMap::new();
+
+        let rows = connection
+            .query(
+                &format!(
+                    "SELECT
+                        date_trunc('{bucket}', timestamp) AS period_start,
+                        inbound_channel_id,
+                        outbound_channel_id,
+                        count(*) AS count,
+                        COALESCE(CAST(sum(amount) AS INT), 0) AS amount,
+                        COALESCE(CAST(sum(fee) AS INT), 0) AS fee
+                    FROM forwards
+                    WHERE status = 'succeeded'{range}
+                    GROUP BY period_start, inbound_channel_id, outbound_channel_id"
+                ),
+                &params.to_params(),
+            )
+            .await?;
+        for row in rows {
+            let outbound: Option<[u8; 32]> = row
+                .get::<&str, Option<&[u8]>>("outbound_channel_id")
+                .map(|x| x.try_into())
+                .transpose()?;
+            let summary = summary(&mut summaries, &row, outbound)?;
+            summary.succeeded = row.get::<&str, i64>("count") as u64;
+            summary.amount = row.get::<&str, i64>("amount") as MillisatAmount;
+            summary.fee = row.get::<&str, i64>("fee") as MillisatAmount;
+        }
+
+        // The kind of failure is only known once the HTLC destination is deserialized.
+        let rows = connection
+            .query(
+                &format!(
+                    "SELECT
+                        date_trunc('{bucket}', timestamp) AS period_start,
+                        inbound_channel_id,
+                        htlc_destination
+                    FROM forwards
+                    WHERE status = 'failed'{range}"
+                ),
+                &params.to_params(),
+            )
+            .await?;
+        for row in rows {
+            let destination: Option<HTLCDestination> = row.read_optional("htlc_destination")?;
+            let outbound = match destination {
+                Some(HTLCDestination::NextHopChannel { channel_id, .. }) => Some(channel_id.0),
+                _ => None,
+            };
+            let summary = summary(&mut summaries, &row, outbound)?;
+            let kind = destination.as_ref().map_or("Unknown", failure_kind);
+            *summary.failures.entry(kind.to_string()).or_default() += 1;
+        }
+        Ok(summaries.into_values().collect())
+    }
+
+    pub async fn persist_channel_fee_update(&self, update: &ChannelFeeUpdate) -> Result<()> {
+        debug!(
+            "Persist fee update of channel {}",
+            hex::encode(update.channel_id.0)
+        );
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO channel_fee_history (
+                    id,
+                    channel_id,
+                    previous_ppm,
+                    ppm,
+                    base_msat,
+                    outbound_ratio,
+                    forwarded_amount,
+                    timestamp
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &update.id,
+                    &update.channel_id.0.to_vec(),
+                    &(update.previous_ppm as i64),
+                    &(update.ppm as i64),
+                    &(update.base_msat as i64),
+                    &update.outbound_ratio,
+                    &(update.forwarded_amount as i64),
+                    &to_primitive(&update.timestamp),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_channel_fee_history(
+        &self,
+        channel_id: Option<ChannelId>,
+    ) -> Result<Vec<ChannelFeeUpdate>> {
+        let mut statement = "
+            SELECT
+                id,
+                channel_id,
+                previous_ppm,
+                ppm,
+                base_msat,
+                outbound_ratio,
+                forwarded_amount,
+                timestamp
+            FROM
+                channel_fee_history
+            "
+        .to_string();
+        let mut params = Params::default();
+        if let Some(channel_id) = channel_id {
+            statement.push_str("WHERE channel_id = $1 ");
+            params.push(channel_id.0.to_vec());
+        }
+        statement.push_str("ORDER BY timestamp ASC");
+        let rows = self
+            .durable_connection
+            .get()
+            .await
+            .query(&statement, &params.to_params())
+            .await?;
+        rows.into_iter().map(ChannelFeeUpdate::try_from).collect()
+    }
+
     pub async fn fetch_channel_monitors<T: EntropySource + SignerProvider>(
         &self,
         source: &T,
@@ -919,6 +1619,368 @@ impl LdkDatabase {
             .await?;
         Ok(row.get("scorer"))
     }
+
+    pub async fn persist_lsps2_token(&self, token: &Lsps2Token) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO lsps2_tokens (
+                    token,
+                    min_fee_msat,
+                    proportional,
+                    valid_for_secs,
+                    min_payment_size_msat,
+                    max_payment_size_msat,
+                    created_at
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &token.token,
+                    &(token.min_fee_msat as i64),
+                    &(token.proportional as i64),
+                    &(token.valid_for_secs as i64),
+                    &(token.min_payment_size_msat as i64),
+                    &(token.max_payment_size_msat as i64),
+                    &to_primitive(&token.created_at),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_lsps2_token(&self, token: &str) -> Result<Option<Lsps2Token>> {
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .query_opt("SELECT * FROM lsps2_tokens WHERE token = $1", &[&token])
+            .await?
+            .map(Lsps2Token::from))
+    }
+
+    pub async fn fetch_lsps2_tokens(&self) -> Result<Vec<Lsps2Token>> {
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .query("SELECT * FROM lsps2_tokens ORDER BY created_at", &[])
+            .await?
+            .into_iter()
+            .map(Lsps2Token::from)
+            .collect())
+    }
+
+    /// Returns false if there was no such token.
+    pub async fn delete_lsps2_token(&self, token: &str) -> Result<bool> {
+        let deleted = self
+            .durable_connection
+            .get()
+            .await
+            .execute("DELETE FROM lsps2_tokens WHERE token = $1", &[&token])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Keep the offer until the client buys a channel with it, the expired offers are removed.
+    pub async fn persist_lsps2_offer(&self, offer: &Lsps2Offer) -> Result<()> {
+        let connection = self.durable_connection.get().await;
+        connection
+            .execute(
+                "DELETE FROM lsps2_offers WHERE valid_until < $1",
+                &[&to_primitive(&microsecond_timestamp())],
+            )
+            .await?;
+        connection
+            .execute(
+                "UPSERT INTO lsps2_offers (
+                    counterparty,
+                    token,
+                    min_fee_msat,
+                    proportional,
+                    valid_until
+                ) VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &offer.counterparty.encode(),
+                    &offer.token,
+                    &(offer.min_fee_msat as i64),
+                    &(offer.proportional as i64),
+                    &to_primitive(&offer.valid_until),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The offers made to the client that are valid until the given time.
+    pub async fn fetch_lsps2_offers(
+        &self,
+        counterparty: &PublicKey,
+        valid_until: &OffsetDateTime,
+    ) -> Result<Vec<Lsps2Offer>> {
+        self.durable_connection
+            .get()
+            .await
+            .query(
+                "SELECT * FROM lsps2_offers WHERE counterparty = $1 AND valid_until = $2",
+                &[&counterparty.encode(), &to_primitive(valid_until)],
+            )
+            .await?
+            .into_iter()
+            .map(Lsps2Offer::try_from)
+            .collect()
+    }
+
+    pub async fn persist_jit_channel(&self, channel: &JitChannel) -> Result<()> {
+        debug!("Persist JIT channel {}", channel.user_channel_id);
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO jit_channels (
+                    user_channel_id,
+                    token,
+                    counterparty,
+                    intercept_scid,
+                    payment_size_msat,
+                    opening_fee_msat,
+                    channel_value_sats,
+                    channel_id,
+                    status,
+                    created_at,
+                    updated_at
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+                &[
+                    &(channel.user_channel_id as u64 as i64),
+                    &channel.token,
+                    &channel.counterparty.encode(),
+                    &(channel.intercept_scid as i64),
+                    &channel.payment_size_msat.map(|x| x as i64),
+                    &channel.opening_fee_msat.map(|x| x as i64),
+                    &channel.channel_value_sats.map(|x| x as i64),
+                    &channel.channel_id.map(|id| id.0.to_vec()),
+                    &channel.status,
+                    &to_primitive(&channel.created_at),
+                    &to_primitive(&channel.updated_at),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_jit_channel(&self, user_channel_id: u128) -> Result<Option<JitChannel>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT * FROM jit_channels WHERE user_channel_id = $1",
+                &[&(user_channel_id as u64 as i64)],
+            )
+            .await?
+            .map(JitChannel::try_from)
+            .transpose()
+    }
+
+    pub async fn fetch_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        self.durable_connection
+            .get()
+            .await
+            .query("SELECT * FROM jit_channels ORDER BY created_at", &[])
+            .await?
+            .into_iter()
+            .map(JitChannel::try_from)
+            .collect()
+    }
+
+    pub async fn persist_jit_invoice(&self, invoice: &JitInvoice) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO jit_invoices (
+                    payment_hash,
+                    lsp,
+                    intercept_scid,
+                    min_fee_msat,
+                    proportional,
+                    created_at
+                ) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &invoice.payment_hash.0.as_ref(),
+                    &invoice.lsp.encode(),
+                    &(invoice.intercept_scid as i64),
+                    &(invoice.min_fee_msat as i64),
+                    &(invoice.proportional as i64),
+                    &to_primitive(&invoice.created_at),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_jit_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<JitInvoice>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT * FROM jit_invoices WHERE payment_hash = $1",
+                &[&payment_hash.0.as_ref()],
+            )
+            .await?
+            .map(JitInvoice::try_from)
+            .transpose()
+    }
+
+    /// The number of JIT invoices we generated with this LSP that are still open, the LSP opens a channel to us for each one that is paid.
+    pub async fn open_jit_invoice_count(&self, lsp: &PublicKey) -> Result<i64> {
+        let row = self
+            .durable_connection
+            .get()
+            .await
+            .query_one(
+                "SELECT COUNT(*) FROM jit_invoices j
+                JOIN invoices i ON i.payment_hash = j.payment_hash
+                WHERE j.lsp = $1
+                AND i.status = 'open'
+                AND i.timestamp + i.expiry * INTERVAL '1 second' > $2",
+                &[&lsp.encode(), &to_primitive(&microsecond_timestamp())],
+            )
+            .await?;
+        let count: i64 = row.get("count");
+        Ok(count)
+    }
+
+    pub async fn persist_lsps1_order(&self, order: &Lsps1Order) -> Result<()> {
+        debug!("Persist LSPS1 order {}", order.order_id);
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO lsps1_orders (
+                    order_id,
+                    counterparty,
+                    lsp_balance_sat,
+                    client_balance_sat,
+                    required_channel_confirmations,
+                    funding_confirms_within_blocks,
+                    channel_expiry_blocks,
+                    token,
+                    announce_channel,
+                    fee_total_sat,
+                    order_total_sat,
+                    payment_hash,
+                    bolt11_invoice,
+                    onchain_address,
+                    min_onchain_payment_confirmations,
+                    refund_onchain_address,
+                    expires_at,
+                    order_state,
+                    payment_state,
+                    user_channel_id,
+                    funding_outpoint,
+                    funded_at,
+                    created_at,
+                    updated_at
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)",
+                &[
+                    &order.order_id,
+                    &order.counterparty.encode(),
+                    &(order.lsp_balance_sat as i64),
+                    &(order.client_balance_sat as i64),
+                    &(order.required_channel_confirmations as i64),
+                    &(order.funding_confirms_within_blocks as i64),
+                    &(order.channel_expiry_blocks as i64),
+                    &order.token,
+                    &order.announce_channel,
+                    &(order.fee_total_sat as i64),
+                    &(order.order_total_sat as i64),
+                    &order.payment_hash.0.as_ref(),
+                    &order.bolt11_invoice,
+                    &order.onchain_address,
+                    &(order.min_onchain_payment_confirmations as i64),
+                    &order.refund_onchain_address,
+                    &to_primitive(&order.expires_at),
+                    &order.order_state,
+                    &order.payment_state,
+                    &order.user_channel_id.map(|x| x as i64),
+                    &order.funding_outpoint,
+                    &order.funded_at.as_ref().map(to_primitive),
+                    &to_primitive(&order.created_at),
+                    &to_primitive(&order.updated_at),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_lsps1_order(&self, order_id: &str) -> Result<Option<Lsps1Order>> {
+        self.durable_connection
+            .get()
+            .await
+            .query_opt(
+                "SELECT * FROM lsps1_orders WHERE order_id = $1",
+                &[&order_id],
+            )
+            .await?
+            .map(Lsps1Order::try_from)
+            .transpose()
+    }
+
+    /// All orders, or only the ones in this state.
+    pub async fn fetch_lsps1_orders(&self, state: Option<OrderState>) -> Result<Vec<Lsps1Order>> {
+        let mut params = Params::default();
+        let mut filter = "WHERE 1 = 1".to_string();
+        if let Some(state) = state {
+            params.push(state);
+            filter.push_str(&format!("\nAND order_state = ${}", params.count()));
+        }
+        self.durable_connection
+            .get()
+            .await
+            .query(
+                &format!("SELECT * FROM lsps1_orders {filter} ORDER BY created_at"),
+                &params.to_params(),
+            )
+            .await?
+            .into_iter()
+            .map(Lsps1Order::try_from)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl MacaroonKeyStore for LdkDatabase {
+    async fn fetch_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        Ok(self
+            .durable_connection
+            .get()
+            .await
+            .query("SELECT * FROM macaroon_root_keys ORDER BY id", &[])
+            .await?
+            .into_iter()
+            .map(MacaroonRootKey::from)
+            .collect())
+    }
+
+    async fn persist_macaroon_root_key(&self, root_key: &MacaroonRootKey) -> Result<()> {
+        self.durable_connection
+            .get()
+            .await
+            .execute(
+                "UPSERT INTO macaroon_root_keys (id, admin, created_at, revoked_at) \
+                VALUES ($1, $2, $3, $4)",
+                &[
+                    &(root_key.id as i64),
+                    &root_key.admin,
+                    &to_primitive(&root_key.created_at),
+                    &root_key.revoked_at.as_ref().map(to_primitive),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, S>
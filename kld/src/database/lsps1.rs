@@ -0,0 +1,124 @@
+use std::fmt::{self, Display};
+
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::PaymentHash;
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use super::RowExt;
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "lsps1_order_state")]
+pub enum OrderState {
+    #[postgres(name = "created")]
+    Created,
+    // The channel is funded.
+    #[postgres(name = "completed")]
+    Completed,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+impl Display for OrderState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderState::Created => f.write_str("created"),
+            OrderState::Completed => f.write_str("completed"),
+            OrderState::Failed => f.write_str("failed"),
+        }
+    }
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "lsps1_payment_state")]
+pub enum PaymentState {
+    #[postgres(name = "expect_payment")]
+    ExpectPayment,
+    #[postgres(name = "paid")]
+    Paid,
+    // Refunds are made by the operator, they are only recorded here.
+    #[postgres(name = "refunded")]
+    Refunded,
+}
+
+impl Display for PaymentState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentState::ExpectPayment => f.write_str("expect_payment"),
+            PaymentState::Paid => f.write_str("paid"),
+            PaymentState::Refunded => f.write_str("refunded"),
+        }
+    }
+}
+
+/// A channel an LSPS1 client bought up front, opened once the order is paid.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lsps1Order {
+    pub order_id: String,
+    pub counterparty: PublicKey,
+    pub lsp_balance_sat: u64,
+    // Pushed to the client when the channel is opened.
+    pub client_balance_sat: u64,
+    pub required_channel_confirmations: u16,
+    pub funding_confirms_within_blocks: u16,
+    pub channel_expiry_blocks: u32,
+    pub token: Option<String>,
+    pub announce_channel: bool,
+    pub fee_total_sat: u64,
+    // The fee plus the client balance.
+    pub order_total_sat: u64,
+    pub payment_hash: PaymentHash,
+    pub bolt11_invoice: String,
+    pub onchain_address: String,
+    pub min_onchain_payment_confirmations: u16,
+    pub refund_onchain_address: Option<String>,
+    // Payments are no longer accepted after this.
+    pub expires_at: OffsetDateTime,
+    pub order_state: OrderState,
+    pub payment_state: PaymentState,
+    pub user_channel_id: Option<u64>,
+    pub funding_outpoint: Option<String>,
+    pub funded_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl TryFrom<Row> for Lsps1Order {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(Lsps1Order {
+            order_id: row.get("order_id"),
+            counterparty: PublicKey::from_slice(row.get("counterparty"))?,
+            lsp_balance_sat: row.get::<&str, i64>("lsp_balance_sat") as u64,
+            client_balance_sat: row.get::<&str, i64>("client_balance_sat") as u64,
+            required_channel_confirmations: row.get::<&str, i64>("required_channel_confirmations")
+                as u16,
+            funding_confirms_within_blocks: row.get::<&str, i64>("funding_confirms_within_blocks")
+                as u16,
+            channel_expiry_blocks: row.get::<&str, i64>("channel_expiry_blocks") as u32,
+            token: row.get("token"),
+            announce_channel: row.get("announce_channel"),
+            fee_total_sat: row.get::<&str, i64>("fee_total_sat") as u64,
+            order_total_sat: row.get::<&str, i64>("order_total_sat") as u64,
+            payment_hash: PaymentHash(row.get::<&str, &[u8]>("payment_hash").try_into()?),
+            bolt11_invoice: row.get("bolt11_invoice"),
+            onchain_address: row.get("onchain_address"),
+            min_onchain_payment_confirmations: row
+                .get::<&str, i64>("min_onchain_payment_confirmations")
+                as u16,
+            refund_onchain_address: row.get("refund_onchain_address"),
+            expires_at: row.get_timestamp("expires_at"),
+            order_state: row.get("order_state"),
+            payment_state: row.get("payment_state"),
+            user_channel_id: row
+                .get::<&str, Option<i64>>("user_channel_id")
+                .map(|x| x as u64),
+            funding_outpoint: row.get("funding_outpoint"),
+            funded_at: row.get_timestamp_optional("funded_at"),
+            created_at: row.get_timestamp("created_at"),
+            updated_at: row.get_timestamp("updated_at"),
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt::{self, Display};
+
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::{ChannelId, PaymentHash};
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use crate::MillisatAmount;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// The user channel ids of JIT channels start here, all other channels use smaller ids.
+pub const JIT_USER_CHANNEL_ID_START: u128 = 1 << 63;
+
+/// A token that LSPS2 clients present to buy JIT channels, with the fees they are offered for it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lsps2Token {
+    pub token: String,
+    pub min_fee_msat: MillisatAmount,
+    // Millionths of the payment size.
+    pub proportional: u32,
+    // How long the offered fees can be used to buy a channel.
+    pub valid_for_secs: u32,
+    pub min_payment_size_msat: MillisatAmount,
+    pub max_payment_size_msat: MillisatAmount,
+    pub created_at: OffsetDateTime,
+}
+
+impl Lsps2Token {
+    pub fn new(
+        token: String,
+        min_fee_msat: MillisatAmount,
+        proportional: u32,
+        valid_for_secs: u32,
+        min_payment_size_msat: MillisatAmount,
+        max_payment_size_msat: MillisatAmount,
+    ) -> Lsps2Token {
+        Lsps2Token {
+            token,
+            min_fee_msat,
+            proportional,
+            valid_for_secs,
+            min_payment_size_msat,
+            max_payment_size_msat,
+            created_at: microsecond_timestamp(),
+        }
+    }
+}
+
+impl From<Row> for Lsps2Token {
+    fn from(row: Row) -> Self {
+        Lsps2Token {
+            token: row.get("token"),
+            min_fee_msat: row.get::<&str, i64>("min_fee_msat") as MillisatAmount,
+            proportional: row.get::<&str, i64>("proportional") as u32,
+            valid_for_secs: row.get::<&str, i64>("valid_for_secs") as u32,
+            min_payment_size_msat: row.get::<&str, i64>("min_payment_size_msat") as MillisatAmount,
+            max_payment_size_msat: row.get::<&str, i64>("max_payment_size_msat") as MillisatAmount,
+            created_at: row.get_timestamp("created_at"),
+        }
+    }
+}
+
+/// The fees offered to an LSPS2 client for its token, until it buys a channel with them or they expire.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lsps2Offer {
+    pub counterparty: PublicKey,
+    pub token: String,
+    pub min_fee_msat: MillisatAmount,
+    // Millionths of the payment size.
+    pub proportional: u32,
+    pub valid_until: OffsetDateTime,
+}
+
+impl TryFrom<Row> for Lsps2Offer {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(Lsps2Offer {
+            counterparty: PublicKey::from_slice(row.get("counterparty"))?,
+            token: row.get("token"),
+            min_fee_msat: row.get::<&str, i64>("min_fee_msat") as MillisatAmount,
+            proportional: row.get::<&str, i64>("proportional") as u32,
+            valid_until: row.get_timestamp("valid_until"),
+        })
+    }
+}
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "jit_channel_status")]
+pub enum JitChannelStatus {
+    // The client got an invoice scid and no payment arrived yet.
+    #[postgres(name = "requested")]
+    Requested,
+    #[postgres(name = "opening")]
+    Opening,
+    #[postgres(name = "ready")]
+    Ready,
+    #[postgres(name = "failed")]
+    Failed,
+    // The channel was ready and is closed now.
+    #[postgres(name = "closed")]
+    Closed,
+}
+
+impl Display for JitChannelStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JitChannelStatus::Requested => f.write_str("requested"),
+            JitChannelStatus::Opening => f.write_str("opening"),
+            JitChannelStatus::Ready => f.write_str("ready"),
+            JitChannelStatus::Failed => f.write_str("failed"),
+            JitChannelStatus::Closed => f.write_str("closed"),
+        }
+    }
+}
+
+/// A channel sold to an LSPS2 client, opened when the first payment to the client arrives.
+#[derive(Debug, PartialEq, Clone)]
+pub struct JitChannel {
+    pub user_channel_id: u128,
+    // The token the client bought the channel with.
+    pub token: Option<String>,
+    pub counterparty: PublicKey,
+    pub intercept_scid: u64,
+    // None when the client bought a channel for a variable amount invoice.
+    pub payment_size_msat: Option<MillisatAmount>,
+    // Skimmed from the first payment once the channel is opened.
+    pub opening_fee_msat: Option<MillisatAmount>,
+    pub channel_value_sats: Option<u64>,
+    pub channel_id: Option<ChannelId>,
+    pub status: JitChannelStatus,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl JitChannel {
+    pub fn new(
+        user_channel_id: u128,
+        token: Option<String>,
+        counterparty: PublicKey,
+        intercept_scid: u64,
+        payment_size_msat: Option<MillisatAmount>,
+    ) -> JitChannel {
+        let timestamp = microsecond_timestamp();
+        JitChannel {
+            user_channel_id,
+            token,
+            counterparty,
+            intercept_scid,
+            payment_size_msat,
+            opening_fee_msat: None,
+            channel_value_sats: None,
+            channel_id: None,
+            status: JitChannelStatus::Requested,
+            created_at: timestamp,
+            updated_at: timestamp,
+        }
+    }
+}
+
+impl TryFrom<Row> for JitChannel {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(JitChannel {
+            user_channel_id: row.get::<&str, i64>("user_channel_id") as u64 as u128,
+            token: row.get("token"),
+            counterparty: PublicKey::from_slice(row.get("counterparty"))?,
+            intercept_scid: row.get::<&str, i64>("intercept_scid") as u64,
+            payment_size_msat: row
+                .get::<&str, Option<i64>>("payment_size_msat")
+                .map(|x| x as MillisatAmount),
+            opening_fee_msat: row
+                .get::<&str, Option<i64>>("opening_fee_msat")
+                .map(|x| x as MillisatAmount),
+            channel_value_sats: row
+                .get::<&str, Option<i64>>("channel_value_sats")
+                .map(|x| x as u64),
+            channel_id: row
+                .get::<&str, Option<&[u8]>>("channel_id")
+                .map(|id| id.try_into().map(ChannelId::from_bytes))
+                .transpose()?,
+            status: row.get("status"),
+            created_at: row.get_timestamp("created_at"),
+            updated_at: row.get_timestamp("updated_at"),
+        })
+    }
+}
+
+/// An invoice we generated with the intercept scid of an LSP, the LSP opens a channel to us when it is paid.
+#[derive(Debug, PartialEq, Clone)]
+pub struct JitInvoice {
+    pub payment_hash: PaymentHash,
+    pub lsp: PublicKey,
+    pub intercept_scid: u64,
+    pub min_fee_msat: MillisatAmount,
+    // Millionths of the payment size.
+    pub proportional: u32,
+    pub created_at: OffsetDateTime,
+}
+
+impl JitInvoice {
+    pub fn new(
+        payment_hash: PaymentHash,
+        lsp: PublicKey,
+        intercept_scid: u64,
+        min_fee_msat: MillisatAmount,
+        proportional: u32,
+    ) -> JitInvoice {
+        JitInvoice {
+            payment_hash,
+            lsp,
+            intercept_scid,
+            min_fee_msat,
+            proportional,
+            created_at: microsecond_timestamp(),
+        }
+    }
+
+    /// The most the LSP may skim from a payment of this size, as agreed when buying the channel.
+    pub fn opening_fee_msat(&self, payment_size_msat: MillisatAmount) -> MillisatAmount {
+        let proportional_fee =
+            (payment_size_msat as u128 * self.proportional as u128 + 999_999) / 1_000_000;
+        self.min_fee_msat.max(proportional_fee as MillisatAmount)
+    }
+}
+
+impl TryFrom<Row> for JitInvoice {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(JitInvoice {
+            payment_hash: PaymentHash(row.get::<&str, &[u8]>("payment_hash").try_into()?),
+            lsp: PublicKey::from_slice(row.get("lsp"))?,
+            intercept_scid: row.get::<&str, i64>("intercept_scid") as u64,
+            min_fee_msat: row.get::<&str, i64>("min_fee_msat") as MillisatAmount,
+            proportional: row.get::<&str, i64>("proportional") as u32,
+            created_at: row.get_timestamp("created_at"),
+        })
+    }
+}
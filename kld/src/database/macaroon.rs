@@ -0,0 +1,49 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use super::{microsecond_timestamp, RowExt};
+
+/// The id of a root key that macaroons are signed with. Revoking the root key invalidates all its macaroons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacaroonRootKey {
+    pub id: u64,
+    // The key of the admin and readonly macaroons in the data directory.
+    pub admin: bool,
+    pub created_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl MacaroonRootKey {
+    pub fn new(id: u64, admin: bool) -> MacaroonRootKey {
+        MacaroonRootKey {
+            id,
+            admin,
+            created_at: microsecond_timestamp(),
+            revoked_at: None,
+        }
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+impl From<Row> for MacaroonRootKey {
+    fn from(row: Row) -> Self {
+        MacaroonRootKey {
+            id: row.get::<&str, i64>("id") as u64,
+            admin: row.get("admin"),
+            created_at: row.get_timestamp("created_at"),
+            revoked_at: row.get_timestamp_optional("revoked_at"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait MacaroonKeyStore {
+    async fn fetch_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>>;
+
+    async fn persist_macaroon_root_key(&self, root_key: &MacaroonRootKey) -> Result<()>;
+}
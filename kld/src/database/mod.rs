@@ -1,8 +1,16 @@
+pub mod channel_rejection;
+pub mod fee_history;
 pub mod forward;
 pub mod invoice;
 mod ldk_database;
+pub mod lsps1;
+pub mod lsps2;
+pub mod macaroon;
+pub mod offer;
 pub mod payment;
 pub mod peer;
+pub mod rebalance;
+pub mod sweep;
 mod wallet_database;
 
 use std::{
@@ -243,6 +251,52 @@ impl<'a> Params<'a> {
     }
 }
 
+/// One page of a list ordered by time. The time range includes `from` and excludes `to`.
+#[derive(Debug, Clone, Default)]
+pub struct Pagination {
+    pub offset: u64,
+    pub limit: Option<u64>,
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+    pub descending: bool,
+}
+
+impl Pagination {
+    /// Append the time range to the WHERE clause that ends the query, then the ordering and the page bounds.
+    /// Rows with the same timestamp are ordered by the key column so that pages never overlap.
+    pub fn append_to(
+        &self,
+        query: &mut String,
+        params: &mut Params,
+        timestamp_column: &str,
+        key_column: &str,
+    ) {
+        if let Some(from) = &self.from {
+            params.push(to_primitive(from));
+            query.push_str(&format!("\nAND {timestamp_column} >= ${}", params.count()));
+        }
+        if let Some(to) = &self.to {
+            params.push(to_primitive(to));
+            query.push_str(&format!("\nAND {timestamp_column} < ${}", params.count()));
+        }
+        query.push('\n');
+        query.push_str(&self.order_by(timestamp_column, key_column));
+        if let Some(limit) = self.limit {
+            params.push(limit as i64);
+            query.push_str(&format!("\nLIMIT ${}", params.count()));
+        }
+        if self.offset > 0 {
+            params.push(self.offset as i64);
+            query.push_str(&format!("\nOFFSET ${}", params.count()));
+        }
+    }
+
+    pub fn order_by(&self, timestamp_column: &str, key_column: &str) -> String {
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        format!("ORDER BY {timestamp_column} {direction}, {key_column} {direction}")
+    }
+}
+
 pub fn microsecond_timestamp() -> OffsetDateTime {
     let timestamp = OffsetDateTime::now_utc();
     timestamp
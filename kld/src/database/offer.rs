@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use bitcoin::hashes::{sha256, Hash};
+use lightning::offers::offer::Amount;
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use crate::MillisatAmount;
+
+use super::{microsecond_timestamp, RowExt};
+
+#[derive(Clone, Debug)]
+pub struct Offer {
+    // Hash of the bech32 encoded offer.
+    pub id: [u8; 32],
+    // User generated id for the offer.
+    pub label: Option<String>,
+    pub bolt12: lightning::offers::offer::Offer,
+    // None if the payer chooses the amount.
+    pub amount: Option<MillisatAmount>,
+    pub description: String,
+    // Seconds since the unix epoch after which the offer should no longer be paid.
+    pub expiry: Option<u64>,
+    // The time that the offer was created.
+    pub timestamp: OffsetDateTime,
+}
+
+impl PartialEq for Offer {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.label == other.label
+            && self.amount == other.amount
+            && self.description == other.description
+            && self.expiry == other.expiry
+            && self.timestamp == other.timestamp
+    }
+}
+
+impl TryFrom<String> for Offer {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let bolt12 = lightning::offers::offer::Offer::from_str(&value)
+            .map_err(|e| anyhow!("Offer could not be decoded: {e:?}"))?;
+        Ok(Offer::new(None, bolt12))
+    }
+}
+
+impl Offer {
+    pub fn new(label: Option<String>, bolt12: lightning::offers::offer::Offer) -> Self {
+        let amount = match bolt12.amount() {
+            Some(Amount::Bitcoin { amount_msats }) => Some(*amount_msats),
+            _ => None,
+        };
+        Offer {
+            id: sha256::Hash::hash(bolt12.to_string().as_bytes()).to_byte_array(),
+            label,
+            amount,
+            description: bolt12.description().to_string(),
+            expiry: bolt12.absolute_expiry().map(|d| d.as_secs()),
+            bolt12,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.bolt12.is_expired()
+    }
+}
+
+impl TryFrom<&Row> for Offer {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> std::result::Result<Self, Self::Error> {
+        let id: &[u8] = row.get("id");
+        let bolt12: String = row.get("bolt12");
+        let bolt12 = lightning::offers::offer::Offer::from_str(&bolt12)
+            .map_err(|e| anyhow!("Offer could not be decoded: {e:?}"))?;
+        Ok(Offer {
+            id: id.try_into().context("bad ID")?,
+            label: row.get("label"),
+            bolt12,
+            amount: row
+                .get::<&str, Option<i64>>("amount")
+                .map(|a| a as MillisatAmount),
+            description: row.get("description"),
+            expiry: row
+                .get_timestamp_optional("expiry")
+                .map(|t| t.unix_timestamp() as u64),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
@@ -1,8 +1,9 @@
 use anyhow::{Context, Result};
 use bitcoin::hashes::Hash;
 use lightning::{
-    events::PaymentFailureReason,
+    events::{PathFailure, PaymentFailureReason},
     ln::{channelmanager::PaymentId, PaymentHash, PaymentPreimage, PaymentSecret},
+    routing::router::{Path, Route},
 };
 use lightning_invoice::Bolt11Invoice;
 use postgres_types::{FromSql, ToSql};
@@ -14,6 +15,7 @@ use std::{
 use thiserror::Error;
 use time::OffsetDateTime;
 use tokio_postgres::Row;
+use uuid::Uuid;
 
 use crate::MillisatAmount;
 
@@ -188,6 +190,22 @@ impl Payment {
         }
     }
 
+    pub fn of_offer_outbound(id: PaymentId, amount: MillisatAmount, label: Option<String>) -> Self {
+        Payment {
+            id,
+            hash: None,
+            preimage: None,
+            secret: None,
+            label,
+            status: PaymentStatus::Pending,
+            amount,
+            fee: None,
+            direction: PaymentDirection::Outbound,
+            timestamp: microsecond_timestamp(),
+            bolt11: None,
+        }
+    }
+
     pub fn succeeded(
         &mut self,
         hash: PaymentHash,
@@ -252,3 +270,91 @@ impl TryFrom<&Row> for Payment {
         })
     }
 }
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "payment_attempt_status")]
+pub enum PaymentAttemptStatus {
+    #[postgres(name = "succeeded")]
+    Succeeded,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+impl Display for PaymentAttemptStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentAttemptStatus::Succeeded => f.write_str("succeeded"),
+            PaymentAttemptStatus::Failed => f.write_str("failed"),
+        }
+    }
+}
+
+/// A single path that was tried for an outbound payment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentAttempt {
+    pub id: Uuid,
+    pub payment_id: PaymentId,
+    pub path: Path,
+    pub status: PaymentAttemptStatus,
+    pub failure: Option<PathFailure>,
+    // The channel that caused the failure, if it is known.
+    pub short_channel_id: Option<u64>,
+    pub payment_failed_permanently: bool,
+    pub timestamp: OffsetDateTime,
+}
+
+impl PaymentAttempt {
+    pub fn succeeded(payment_id: PaymentId, path: Path) -> Self {
+        PaymentAttempt {
+            id: Uuid::new_v4(),
+            payment_id,
+            path,
+            status: PaymentAttemptStatus::Succeeded,
+            failure: None,
+            short_channel_id: None,
+            payment_failed_permanently: false,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+
+    pub fn failed(
+        payment_id: PaymentId,
+        path: Path,
+        failure: PathFailure,
+        short_channel_id: Option<u64>,
+        payment_failed_permanently: bool,
+    ) -> Self {
+        PaymentAttempt {
+            id: Uuid::new_v4(),
+            payment_id,
+            path,
+            status: PaymentAttemptStatus::Failed,
+            failure: Some(failure),
+            short_channel_id,
+            payment_failed_permanently,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<&Row> for PaymentAttempt {
+    type Error = anyhow::Error;
+
+    fn try_from(row: &Row) -> std::result::Result<Self, Self::Error> {
+        let payment_id: &[u8] = row.get("payment_id");
+        // The path is stored as a single path route as LDK can serialize that.
+        let route: Route = row.read("path")?;
+        Ok(PaymentAttempt {
+            id: row.get("id"),
+            payment_id: PaymentId(payment_id.try_into().context("bad payment ID")?),
+            path: route.paths.into_iter().next().context("missing path")?,
+            status: row.get("status"),
+            failure: row.read_optional("failure")?,
+            short_channel_id: row
+                .get::<&str, Option<i64>>("short_channel_id")
+                .map(|x| x as u64),
+            payment_failed_permanently: row.get("payment_failed_permanently"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
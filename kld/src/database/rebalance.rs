@@ -0,0 +1,67 @@
+use lightning::ln::{channelmanager::PaymentId, ChannelId};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::MillisatAmount;
+
+use super::{microsecond_timestamp, payment::PaymentStatus, RowExt};
+
+/// A circular payment moving liquidity from one of our channels to another.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rebalance {
+    pub id: Uuid,
+    pub payment_id: PaymentId,
+    // The channel the payment leaves through.
+    pub outbound_channel_id: ChannelId,
+    // The channel the payment comes back in through.
+    pub inbound_channel_id: ChannelId,
+    pub amount: MillisatAmount,
+    // The routing fee paid to move the amount, known once the payment succeeds.
+    pub fee: Option<MillisatAmount>,
+    pub status: PaymentStatus,
+    pub timestamp: OffsetDateTime,
+}
+
+impl Rebalance {
+    pub fn new(
+        payment_id: PaymentId,
+        outbound_channel_id: ChannelId,
+        inbound_channel_id: ChannelId,
+        amount: MillisatAmount,
+    ) -> Rebalance {
+        Rebalance {
+            id: Uuid::new_v4(),
+            payment_id,
+            outbound_channel_id,
+            inbound_channel_id,
+            amount,
+            fee: None,
+            status: PaymentStatus::Pending,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+}
+
+impl TryFrom<Row> for Rebalance {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        Ok(Rebalance {
+            id: row.get("id"),
+            payment_id: PaymentId(row.get::<&str, &[u8]>("payment_id").try_into()?),
+            outbound_channel_id: ChannelId::from_bytes(
+                row.get::<&str, &[u8]>("outbound_channel_id").try_into()?,
+            ),
+            inbound_channel_id: ChannelId::from_bytes(
+                row.get::<&str, &[u8]>("inbound_channel_id").try_into()?,
+            ),
+            amount: row.get::<&str, i64>("amount") as MillisatAmount,
+            fee: row
+                .get::<&str, Option<i64>>("fee")
+                .map(|x| x as MillisatAmount),
+            status: row.get("status"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
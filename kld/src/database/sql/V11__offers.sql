@@ -0,0 +1,11 @@
+CREATE TABLE offers (
+    id              BYTES NOT NULL,
+    label           VARCHAR,
+    bolt12          VARCHAR NOT NULL,
+    amount          INT,
+    description     VARCHAR NOT NULL,
+    expiry          TIMESTAMP,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( id ),
+    INDEX ( label )
+);
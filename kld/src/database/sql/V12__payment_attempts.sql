@@ -0,0 +1,14 @@
+CREATE TYPE payment_attempt_status AS ENUM ('succeeded', 'failed');
+
+CREATE TABLE payment_attempts (
+    id                          UUID NOT NULL,
+    payment_id                  BYTES NOT NULL,
+    path                        BYTES NOT NULL,
+    status                      payment_attempt_status NOT NULL,
+    failure                     BYTES,
+    short_channel_id            INT,
+    payment_failed_permanently  BOOLEAN NOT NULL DEFAULT false,
+    timestamp                   TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( id ),
+    INDEX ( payment_id )
+);
@@ -0,0 +1,10 @@
+CREATE TABLE channel_rejections (
+    id                    UUID NOT NULL,
+    temporary_channel_id  BYTES NOT NULL,
+    counterparty_node_id  BYTES NOT NULL,
+    funding_satoshis      INT NOT NULL,
+    push_msat             INT NOT NULL,
+    reason                STRING NOT NULL,
+    timestamp             TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( id )
+);
@@ -0,0 +1,11 @@
+CREATE TABLE rebalances (
+    id                   UUID NOT NULL,
+    payment_id           BYTES NOT NULL,
+    outbound_channel_id  BYTES NOT NULL,
+    inbound_channel_id   BYTES NOT NULL,
+    amount               INT NOT NULL,
+    fee                  INT,
+    status               payment_status NOT NULL,
+    timestamp            TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( id )
+);
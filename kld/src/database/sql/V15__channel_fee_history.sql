@@ -0,0 +1,11 @@
+CREATE TABLE channel_fee_history (
+    id                   UUID NOT NULL,
+    channel_id           BYTES NOT NULL,
+    previous_ppm         INT NOT NULL,
+    ppm                  INT NOT NULL,
+    base_msat            INT NOT NULL,
+    outbound_ratio       FLOAT8 NOT NULL,
+    forwarded_amount     INT NOT NULL,
+    timestamp            TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( id )
+);
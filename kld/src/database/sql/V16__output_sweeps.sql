@@ -0,0 +1,13 @@
+CREATE TYPE sweep_status AS ENUM ('pending', 'confirmed', 'replaced');
+
+CREATE TABLE output_sweeps (
+    txid                 BYTES NOT NULL,
+    /* The signed sweep transaction, kept for re-broadcasting */
+    transaction          BYTES NOT NULL,
+    fee_rate             INT NOT NULL,
+    broadcast_height     INT NOT NULL,
+    confirmation_height  INT,
+    status               sweep_status NOT NULL,
+    timestamp            TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( txid )
+);
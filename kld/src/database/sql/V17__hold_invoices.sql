@@ -0,0 +1,5 @@
+CREATE TYPE invoice_status AS ENUM ('open', 'held', 'settled', 'canceled');
+
+ALTER TABLE invoices ADD COLUMN status invoice_status NOT NULL DEFAULT 'open';
+/* Block height at which the HTLCs of a held invoice must be settled or canceled */
+ALTER TABLE invoices ADD COLUMN claim_deadline INT;
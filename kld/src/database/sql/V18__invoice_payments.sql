@@ -0,0 +1,4 @@
+ALTER TABLE invoices ADD COLUMN amount_received INT;
+ALTER TABLE invoices ADD COLUMN paid_at TIMESTAMP;
+/* Increases by one with every paid invoice, so clients can wait for the next payment */
+ALTER TABLE invoices ADD COLUMN pay_index INT UNIQUE;
@@ -0,0 +1,6 @@
+/* The list endpoints filter and page by time */
+CREATE INDEX ON payments ( timestamp );
+CREATE INDEX ON invoices ( timestamp );
+CREATE INDEX ON forwards ( timestamp );
+CREATE INDEX ON forwards ( status, timestamp );
+CREATE INDEX ON channels ( is_usable, update_timestamp );
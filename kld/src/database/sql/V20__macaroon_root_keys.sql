@@ -0,0 +1,9 @@
+/* Only the ids are stored, the keys are derived from the macaroon seed and the id */
+CREATE TABLE macaroon_root_keys (
+    id          INT NOT NULL,
+    /* Signs the admin and readonly macaroons in the data directory */
+    admin       BOOLEAN NOT NULL DEFAULT false,
+    created_at  TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    revoked_at  TIMESTAMP,
+    PRIMARY KEY ( id )
+);
@@ -0,0 +1,29 @@
+CREATE TABLE lsps2_tokens (
+    token                  STRING NOT NULL,
+    min_fee_msat           INT NOT NULL,
+    /* Millionths of the payment size */
+    proportional           INT NOT NULL,
+    valid_for_secs         INT NOT NULL,
+    min_payment_size_msat  INT NOT NULL,
+    max_payment_size_msat  INT NOT NULL,
+    created_at             TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( token )
+);
+
+CREATE TYPE jit_channel_status AS ENUM ('requested', 'opening', 'ready', 'failed');
+
+CREATE TABLE jit_channels (
+    /* The user channel id is above 2^63, it is stored with the same bits as a signed INT */
+    user_channel_id        INT NOT NULL,
+    token                  STRING,
+    counterparty           BYTES NOT NULL,
+    intercept_scid         INT NOT NULL,
+    payment_size_msat      INT,
+    opening_fee_msat       INT,
+    channel_value_sats     INT,
+    channel_id             BYTES,
+    status                 jit_channel_status NOT NULL,
+    created_at             TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    updated_at             TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( user_channel_id )
+);
@@ -0,0 +1,33 @@
+CREATE TYPE lsps1_order_state AS ENUM ('created', 'completed', 'failed');
+
+CREATE TYPE lsps1_payment_state AS ENUM ('expect_payment', 'paid', 'refunded');
+
+CREATE TABLE lsps1_orders (
+    order_id                        STRING NOT NULL,
+    counterparty                    BYTES NOT NULL,
+    lsp_balance_sat                 INT NOT NULL,
+    client_balance_sat              INT NOT NULL,
+    required_channel_confirmations  INT NOT NULL,
+    funding_confirms_within_blocks  INT NOT NULL,
+    channel_expiry_blocks           INT NOT NULL,
+    token                           STRING,
+    announce_channel                BOOL NOT NULL,
+    fee_total_sat                   INT NOT NULL,
+    order_total_sat                 INT NOT NULL,
+    /* Both the invoice and the on-chain address pay for the order until it expires */
+    payment_hash                    BYTES NOT NULL,
+    bolt11_invoice                  STRING NOT NULL,
+    onchain_address                 STRING NOT NULL,
+    min_onchain_payment_confirmations INT NOT NULL,
+    refund_onchain_address          STRING,
+    expires_at                      TIMESTAMP NOT NULL,
+    order_state                     lsps1_order_state NOT NULL,
+    payment_state                   lsps1_payment_state NOT NULL,
+    /* Set once the channel is created, so that it is opened only once */
+    user_channel_id                 INT,
+    funding_outpoint                STRING,
+    funded_at                       TIMESTAMP,
+    created_at                      TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    updated_at                      TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( order_id )
+);
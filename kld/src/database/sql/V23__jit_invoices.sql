@@ -0,0 +1,11 @@
+/* Invoices we generated as an LSPS2 client, paid into a JIT channel from the LSP */
+CREATE TABLE jit_invoices (
+    payment_hash           BYTES NOT NULL,
+    lsp                    BYTES NOT NULL,
+    intercept_scid         INT NOT NULL,
+    /* The opening fee the LSP may skim from the payment */
+    min_fee_msat           INT NOT NULL,
+    proportional           INT NOT NULL,
+    created_at             TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( payment_hash )
+);
@@ -0,0 +1,3 @@
+/* Concurrent payments can't get the same pay index from a sequence, unlike from max(pay_index) + 1 */
+CREATE SEQUENCE invoice_pay_index;
+SELECT setval('invoice_pay_index', (SELECT coalesce(max(pay_index), 0) + 1 FROM invoices), false);
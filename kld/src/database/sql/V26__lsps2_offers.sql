@@ -0,0 +1,12 @@
+/* A JIT channel that was ready and closed later, unlike failed ones which never opened */
+ALTER TYPE jit_channel_status ADD VALUE 'closed';
+
+/* The fees offered to an LSPS2 client for its token, the channel it buys with them is accounted to the token */
+CREATE TABLE lsps2_offers (
+    counterparty           BYTES NOT NULL,
+    token                  STRING NOT NULL,
+    min_fee_msat           INT NOT NULL,
+    proportional           INT NOT NULL,
+    valid_until            TIMESTAMP NOT NULL,
+    PRIMARY KEY ( counterparty, token )
+);
@@ -0,0 +1,84 @@
+use std::fmt::{self, Display};
+
+use bitcoin::{OutPoint, Transaction, Txid};
+use postgres_types::{FromSql, ToSql};
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use super::{microsecond_timestamp, RowExt};
+
+#[derive(Debug, ToSql, FromSql, PartialEq, Clone, Copy)]
+#[postgres(name = "sweep_status")]
+pub enum SweepStatus {
+    #[postgres(name = "pending")]
+    Pending,
+    #[postgres(name = "confirmed")]
+    Confirmed,
+    #[postgres(name = "replaced")]
+    Replaced,
+}
+
+impl Display for SweepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SweepStatus::Pending => f.write_str("pending"),
+            SweepStatus::Confirmed => f.write_str("confirmed"),
+            SweepStatus::Replaced => f.write_str("replaced"),
+        }
+    }
+}
+
+/// A transaction sending spendable outputs from closed channels back to the on-chain wallet.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutputSweep {
+    pub txid: Txid,
+    pub transaction: Transaction,
+    // Sats per 1000 weight.
+    pub fee_rate: u32,
+    pub broadcast_height: u32,
+    // Height of the block the sweep was mined in, it is only confirmed after enough blocks on top of that.
+    pub confirmation_height: Option<u32>,
+    pub status: SweepStatus,
+    pub timestamp: OffsetDateTime,
+}
+
+impl OutputSweep {
+    pub fn new(transaction: Transaction, fee_rate: u32, broadcast_height: u32) -> OutputSweep {
+        OutputSweep {
+            txid: transaction.txid(),
+            transaction,
+            fee_rate,
+            broadcast_height,
+            confirmation_height: None,
+            status: SweepStatus::Pending,
+            timestamp: microsecond_timestamp(),
+        }
+    }
+
+    pub fn outputs(&self) -> Vec<OutPoint> {
+        self.transaction
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect()
+    }
+}
+
+impl TryFrom<Row> for OutputSweep {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
+        let transaction: Transaction = row.read("transaction")?;
+        Ok(OutputSweep {
+            txid: transaction.txid(),
+            transaction,
+            fee_rate: row.get::<&str, i64>("fee_rate") as u32,
+            broadcast_height: row.get::<&str, i64>("broadcast_height") as u32,
+            confirmation_height: row
+                .get::<&str, Option<i64>>("confirmation_height")
+                .map(|h| h as u32),
+            status: row.get("status"),
+            timestamp: row.get_timestamp("timestamp"),
+        })
+    }
+}
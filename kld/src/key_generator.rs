@@ -50,6 +50,10 @@ impl KeyGenerator {
         self.generate_key("promise_seed")
     }
 
+    pub fn backup_seed(&self) -> [u8; 32] {
+        self.generate_key("backup/0")
+    }
+
     fn generate_key(&self, extra_input: &str) -> [u8; 32] {
         let mut engine = sha256::HashEngine::default();
         engine.input(&self.mnemonic.to_seed(""));
@@ -65,12 +69,15 @@ fn test_key_generator() -> Result<()> {
     let wallet_seed = key_generator.wallet_seed();
     let lightning_seed = key_generator.lightning_seed();
     let macaroon_seed = key_generator.macaroon_seed();
+    let backup_seed = key_generator.backup_seed();
 
     assert_eq!(wallet_seed, key_generator.wallet_seed());
     assert_eq!(lightning_seed, key_generator.lightning_seed());
     assert_eq!(macaroon_seed, key_generator.macaroon_seed());
+    assert_eq!(backup_seed, key_generator.backup_seed());
 
     assert_ne!(wallet_seed, lightning_seed);
     assert_ne!(lightning_seed, macaroon_seed);
+    assert_ne!(macaroon_seed, backup_seed);
     Ok(())
 }
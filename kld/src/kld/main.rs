@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 use futures::FutureExt;
 use kld::api::{bind_api_server, MacaroonAuth};
 use kld::bitcoind::BitcoindClient;
-use kld::database::{DurableConnection, WalletDatabase};
+use kld::database::{DurableConnection, LdkDatabase, WalletDatabase};
 use kld::key_generator::KeyGenerator;
 use kld::ldk::Controller;
 use kld::logger::KldLogger;
@@ -63,9 +63,7 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
 
     let bitcoind_client = Arc::new(BitcoindClient::new(&settings).await?);
 
-    // Hot fix the fee rate, due to Signet
-    // bitcoind_client.poll_for_fee_estimates();
-    bitcoind_client.set_lowest_fee_estimates();
+    bitcoind_client.poll_for_fee_estimates();
 
     let wallet = Arc::new(
         Wallet::new(
@@ -94,10 +92,17 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
     .context("Failed to start ldk controller")?;
     let controller = Arc::new(controller);
 
-    let macaroon_auth = Arc::new(MacaroonAuth::init(
-        &key_generator.macaroon_seed(),
-        &settings.data_dir,
-    )?);
+    let macaroon_auth = Arc::new(
+        MacaroonAuth::init(
+            &key_generator.macaroon_seed(),
+            &settings.data_dir,
+            Arc::new(LdkDatabase::new(
+                settings.clone(),
+                durable_connection.clone(),
+            )),
+        )
+        .await?,
+    );
 
     let server = bind_api_server(
         settings.rest_api_address.clone(),
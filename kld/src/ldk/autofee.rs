@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use lightning::ln::channelmanager::ChannelDetails;
+use log::{info, warn};
+
+use crate::database::fee_history::ChannelFeeUpdate;
+use crate::database::{microsecond_timestamp, LdkDatabase};
+use crate::settings::Settings;
+
+use super::{ldk_error, ChannelManager};
+
+// Fee changes smaller than this percentage are skipped so that we don't spam the network with channel updates.
+const MIN_FEE_CHANGE_PERCENT: u32 = 5;
+
+/// Adjusts the proportional forwarding fee of every usable channel within the configured bounds.
+/// Channels with little liquidity left on our side get more expensive and channels with
+/// a lot of recent outgoing forwards get a premium on top.
+pub(crate) struct AutoFee {
+    min_ppm: u32,
+    max_ppm: u32,
+    lookback: Duration,
+}
+
+impl AutoFee {
+    pub fn new(settings: &Settings) -> AutoFee {
+        AutoFee {
+            min_ppm: settings.autofee_min_ppm,
+            max_ppm: settings.autofee_max_ppm.max(settings.autofee_min_ppm),
+            lookback: Duration::from_secs(settings.autofee_lookback_hours * 3600),
+        }
+    }
+
+    /// The fee rate for a channel with the given share of its capacity on our side and
+    /// forwarded volume in proportion to its capacity.
+    pub fn target_ppm(&self, outbound_ratio: f64, volume_ratio: f64) -> u32 {
+        let range = (self.max_ppm - self.min_ppm) as f64;
+        let liquidity_ppm = self.min_ppm as f64 + range * (1.0 - outbound_ratio.clamp(0.0, 1.0));
+        let demand = 1.0 + volume_ratio.clamp(0.0, 1.0);
+        ((liquidity_ppm * demand).round() as u32).clamp(self.min_ppm, self.max_ppm)
+    }
+
+    pub fn should_update(current_ppm: u32, target_ppm: u32) -> bool {
+        current_ppm.abs_diff(target_ppm) * 100 >= current_ppm.max(1) * MIN_FEE_CHANGE_PERCENT
+    }
+
+    pub async fn adjust_fees(
+        &self,
+        channel_manager: &Arc<ChannelManager>,
+        database: &Arc<LdkDatabase>,
+    ) -> Result<()> {
+        let since = microsecond_timestamp() - self.lookback;
+        let forwarded_amounts = database.fetch_forwarded_amounts(since).await?;
+        for channel in channel_manager.list_usable_channels() {
+            let forwarded_amount = forwarded_amounts
+                .get(&channel.channel_id)
+                .copied()
+                .unwrap_or_default();
+            // One failing channel should not hold back the fees of all the others.
+            if let Err(e) = self
+                .adjust_channel_fee(channel_manager, database, &channel, forwarded_amount)
+                .await
+            {
+                warn!(
+                    "Failed to adjust fee of channel {}: {e}",
+                    hex::encode(channel.channel_id.0)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn adjust_channel_fee(
+        &self,
+        channel_manager: &Arc<ChannelManager>,
+        database: &Arc<LdkDatabase>,
+        channel: &ChannelDetails,
+        forwarded_amount: u64,
+    ) -> Result<()> {
+        let Some(mut config) = channel.config else {
+            return Ok(());
+        };
+        let outbound_ratio = outbound_ratio(channel);
+        let volume_ratio =
+            forwarded_amount as f64 / (channel.channel_value_satoshis * 1000).max(1) as f64;
+        let previous_ppm = config.forwarding_fee_proportional_millionths;
+        let ppm = self.target_ppm(outbound_ratio, volume_ratio);
+        if !AutoFee::should_update(previous_ppm, ppm) {
+            return Ok(());
+        }
+        config.forwarding_fee_proportional_millionths = ppm;
+        channel_manager
+            .update_channel_config(
+                &channel.counterparty.node_id,
+                &[channel.channel_id],
+                &config,
+            )
+            .map_err(ldk_error)?;
+        info!(
+            "Adjusted fee of channel {} from {previous_ppm} to {ppm} ppm",
+            hex::encode(channel.channel_id.0)
+        );
+        database
+            .persist_channel_fee_update(&ChannelFeeUpdate::new(
+                channel.channel_id,
+                previous_ppm,
+                ppm,
+                config.forwarding_fee_base_msat,
+                outbound_ratio,
+                forwarded_amount,
+            ))
+            .await
+    }
+}
+
+fn outbound_ratio(channel: &ChannelDetails) -> f64 {
+    let capacity_msat = channel.channel_value_satoshis * 1000;
+    if capacity_msat == 0 {
+        return 0.0;
+    }
+    channel.outbound_capacity_msat as f64 / capacity_msat as f64
+}
+
+#[cfg(test)]
+mod test {
+    use crate::settings::Settings;
+
+    use super::AutoFee;
+
+    #[test]
+    fn test_target_ppm() {
+        let settings = Settings {
+            autofee_min_ppm: 100,
+            autofee_max_ppm: 1000,
+            ..Settings::default()
+        };
+        let autofee = AutoFee::new(&settings);
+
+        assert_eq!(1000, autofee.target_ppm(0.0, 0.0));
+        assert_eq!(100, autofee.target_ppm(1.0, 0.0));
+        assert_eq!(550, autofee.target_ppm(0.5, 0.0));
+        assert_eq!(825, autofee.target_ppm(0.5, 0.5));
+        assert_eq!(1000, autofee.target_ppm(0.5, 3.0));
+        assert_eq!(200, autofee.target_ppm(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_should_update() {
+        assert!(!AutoFee::should_update(1000, 1000));
+        assert!(!AutoFee::should_update(1000, 1040));
+        assert!(AutoFee::should_update(1000, 1050));
+        assert!(AutoFee::should_update(1000, 900));
+        assert!(AutoFee::should_update(0, 1));
+    }
+}
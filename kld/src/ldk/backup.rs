@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::channelmanager::ChannelDetails;
+use log::{info, warn};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+
+use crate::api::SocketAddress;
+use crate::database::LdkDatabase;
+
+use super::peer_manager::{KuutamoPeerManger, PeerManager};
+use super::ChannelManager;
+
+/// The name of the backup file that is kept up to date in the data directory.
+pub const BACKUP_FILE_NAME: &str = "channel_backup.scb";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// How often recovery retries peers that could not be reached yet.
+const RECOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The channels of the node and where to reach their counterparties.
+/// It holds no channel state so it can't be used to operate the channels, only to ask our peers
+/// to force close them after the database is lost. Our balance is then paid to the static payment key
+/// of the node, which is not part of the on-chain wallet and has to be swept manually.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StaticChannelBackup {
+    pub node_id: String,
+    // Seconds since the unix epoch.
+    pub timestamp: u64,
+    pub channels: Vec<ChannelBackup>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelBackup {
+    pub channel_id: String,
+    pub counterparty: String,
+    // txid:vout, not known before the funding transaction is created.
+    pub funding_txo: Option<String>,
+    pub channel_value_sat: u64,
+    pub peer_address: Option<SocketAddress>,
+}
+
+impl StaticChannelBackup {
+    pub fn new(
+        node_id: PublicKey,
+        channels: &[ChannelDetails],
+        peers: &HashMap<PublicKey, lightning::ln::msgs::SocketAddress>,
+    ) -> StaticChannelBackup {
+        StaticChannelBackup {
+            node_id: node_id.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            channels: channels
+                .iter()
+                .map(|channel| ChannelBackup {
+                    channel_id: hex::encode(channel.channel_id.0),
+                    counterparty: channel.counterparty.node_id.to_string(),
+                    funding_txo: channel
+                        .funding_txo
+                        .map(|txo| txo.into_bitcoin_outpoint().to_string()),
+                    channel_value_sat: channel.channel_value_satoshis,
+                    peer_address: peers
+                        .get(&channel.counterparty.node_id)
+                        .map(|address| address.clone().into()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Encrypt with AES-256-GCM, the result is nonce | ciphertext | tag.
+    pub fn encrypt(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(&nonce),
+            &[],
+            &serde_json::to_vec(self)?,
+            &mut tag,
+        )?;
+        Ok([&nonce[..], &ciphertext, &tag].concat())
+    }
+
+    pub fn decrypt(data: &[u8], key: &[u8; 32]) -> Result<StaticChannelBackup> {
+        if data.len() < NONCE_LEN + TAG_LEN {
+            bail!("Channel backup is too short");
+        }
+        let (nonce, rest) = data.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            &[],
+            ciphertext,
+            tag,
+        )
+        .map_err(|_| anyhow!("Channel backup can't be decrypted with the key of this node"))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Creates encrypted backups of the current channels and keeps the backup file in the data directory up to date.
+pub(crate) struct BackupExporter {
+    key: [u8; 32],
+    path: PathBuf,
+    channel_manager: Arc<ChannelManager>,
+    database: Arc<LdkDatabase>,
+}
+
+impl BackupExporter {
+    pub fn new(
+        key: [u8; 32],
+        data_dir: &str,
+        channel_manager: Arc<ChannelManager>,
+        database: Arc<LdkDatabase>,
+    ) -> BackupExporter {
+        BackupExporter {
+            key,
+            path: PathBuf::from(data_dir).join(BACKUP_FILE_NAME),
+            channel_manager,
+            database,
+        }
+    }
+
+    pub async fn export(&self) -> Result<Vec<u8>> {
+        let peers = self.database.fetch_peers().await?;
+        StaticChannelBackup::new(
+            self.channel_manager.get_our_node_id(),
+            &self.channel_manager.list_channels(),
+            &peers,
+        )
+        .encrypt(&self.key)
+    }
+
+    pub async fn write_file(&self) -> Result<()> {
+        let backup = self.export().await?;
+        // Write to a temporary file first so a crash never leaves a truncated backup behind.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, backup)
+            .with_context(|| format!("Cannot write to {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Cannot write to {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Connect to the counterparties of every channel in the backup. LDK answers their channel_reestablish
+/// for the channels it doesn't know with an invalid one, which makes the peers force close the channels.
+/// Without the channel state LDK can't claim the to_remote outputs of those closes, they have to be swept
+/// manually with the static payment key derived from the node seed.
+pub(crate) async fn recover_from_backup(
+    backup_path: &str,
+    key: &[u8; 32],
+    node_id: PublicKey,
+    peer_manager: Arc<PeerManager>,
+    database: Arc<LdkDatabase>,
+) -> Result<()> {
+    let data = fs::read(backup_path).with_context(|| format!("Cannot read {backup_path}"))?;
+    let backup = StaticChannelBackup::decrypt(&data, key)?;
+    if backup.node_id != node_id.to_string() {
+        bail!("Channel backup belongs to node {}", backup.node_id);
+    }
+    info!(
+        "Recovering {} channels from backup {backup_path}",
+        backup.channels.len()
+    );
+    let mut addresses: HashMap<PublicKey, Option<SocketAddress>> = HashMap::new();
+    for channel in &backup.channels {
+        info!(
+            "Requesting force close of channel {} with {} (funding {})",
+            channel.channel_id,
+            channel.counterparty,
+            channel.funding_txo.as_deref().unwrap_or("unknown")
+        );
+        let counterparty = PublicKey::from_str(&channel.counterparty)?;
+        let address = addresses.entry(counterparty).or_default();
+        if address.is_none() {
+            *address = channel.peer_address.clone();
+        }
+    }
+    let mut pending: HashMap<PublicKey, SocketAddress> = HashMap::new();
+    for (public_key, address) in addresses {
+        match address {
+            Some(address) => {
+                pending.insert(public_key, address);
+            }
+            None => warn!(
+                "No address to reach {public_key}, its channels are only force closed if it connects to us"
+            ),
+        }
+    }
+    while !pending.is_empty() {
+        let mut connected = vec![];
+        for (public_key, address) in &pending {
+            match peer_manager
+                .connect_peer(database.clone(), *public_key, address.clone())
+                .await
+            {
+                Ok(()) => connected.push(*public_key),
+                Err(e) => warn!("Could not connect to {public_key}@{address}: {e}"),
+            }
+        }
+        for public_key in connected {
+            info!("Reconnected to {public_key}, it will force close our channels");
+            pending.remove(&public_key);
+        }
+        if !pending.is_empty() {
+            tokio::time::sleep(RECOVERY_RETRY_INTERVAL).await;
+        }
+    }
+    info!("Recovery done, our channel balances are paid to the static payment key once the force closes confirm");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChannelBackup, StaticChannelBackup};
+
+    #[test]
+    fn test_encrypt_backup() -> anyhow::Result<()> {
+        let backup = StaticChannelBackup {
+            node_id: "02a4dbe5b2bf8b2e3b2ea6b5b3a4c2e38bd2b6a2e1bbf2c0a3c3f0cd0b6f1a8e7c"
+                .to_string(),
+            timestamp: 1700000000,
+            channels: vec![ChannelBackup {
+                channel_id: "11".repeat(32),
+                counterparty: "02".to_string() + &"33".repeat(32),
+                funding_txo: Some(format!("{}:1", "44".repeat(32))),
+                channel_value_sat: 1_000_000,
+                peer_address: Some("127.0.0.1:9735".parse()?),
+            }],
+        };
+        let encrypted = backup.encrypt(&[1; 32])?;
+        assert_ne!(encrypted, backup.encrypt(&[1; 32])?);
+        assert_eq!(backup, StaticChannelBackup::decrypt(&encrypted, &[1; 32])?);
+        assert!(StaticChannelBackup::decrypt(&encrypted, &[2; 32]).is_err());
+        assert!(StaticChannelBackup::decrypt(&encrypted[..20], &[1; 32]).is_err());
+        Ok(())
+    }
+}
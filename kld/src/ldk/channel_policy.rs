@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::settings::Settings;
+
+/// The outcome of checking an inbound channel request against the policy.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum InboundChannelDecision {
+    Accept,
+    AcceptZeroConf,
+    Reject(String),
+}
+
+/// Rules for inbound channels. Unless manual acceptance is configured every channel is accepted,
+/// only zero conf is restricted to trusted peers.
+/// Whether the channel is announced is enforced by LDK through the handshake limits.
+pub(crate) struct InboundChannelPolicy {
+    enforced: bool,
+    min_funding_sats: u64,
+    max_funding_sats: Option<u64>,
+    allow_list: HashSet<PublicKey>,
+    deny_list: HashSet<PublicKey>,
+    max_pending_per_peer: Option<usize>,
+    trusted_zero_conf_peers: HashSet<PublicKey>,
+}
+
+impl InboundChannelPolicy {
+    pub fn new(settings: &Settings) -> InboundChannelPolicy {
+        InboundChannelPolicy {
+            enforced: settings.manually_accept_inbound_channels,
+            min_funding_sats: settings.inbound_channel_min_sats,
+            max_funding_sats: settings.inbound_channel_max_sats,
+            allow_list: settings
+                .inbound_channel_allow_list
+                .iter()
+                .copied()
+                .collect(),
+            deny_list: settings.inbound_channel_deny_list.iter().copied().collect(),
+            max_pending_per_peer: settings.inbound_channel_max_pending_per_peer,
+            trusted_zero_conf_peers: settings.trusted_zero_conf_peers.iter().copied().collect(),
+        }
+    }
+
+    /// Whether inbound channels are checked against the rules at all.
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    /// Decide on a channel request given the number of channels with the peer that are not ready yet.
+    pub fn evaluate(
+        &self,
+        counterparty_node_id: &PublicKey,
+        funding_satoshis: u64,
+        requires_zero_conf: bool,
+        pending_channels: usize,
+    ) -> InboundChannelDecision {
+        if let Err(reason) =
+            self.check_rules(counterparty_node_id, funding_satoshis, pending_channels)
+        {
+            return InboundChannelDecision::Reject(reason);
+        }
+        if self.trusted_zero_conf_peers.contains(counterparty_node_id) {
+            InboundChannelDecision::AcceptZeroConf
+        } else if requires_zero_conf {
+            InboundChannelDecision::Reject(
+                "Zero conf channels are only accepted from trusted peers".to_string(),
+            )
+        } else {
+            InboundChannelDecision::Accept
+        }
+    }
+
+    fn check_rules(
+        &self,
+        counterparty_node_id: &PublicKey,
+        funding_satoshis: u64,
+        pending_channels: usize,
+    ) -> Result<(), String> {
+        if !self.enforced {
+            return Ok(());
+        }
+        if self.deny_list.contains(counterparty_node_id) {
+            return Err("Peer is on the deny list".to_string());
+        }
+        if !self.allow_list.is_empty() && !self.allow_list.contains(counterparty_node_id) {
+            return Err("Peer is not on the allow list".to_string());
+        }
+        if funding_satoshis < self.min_funding_sats {
+            return Err(format!(
+                "Channel size {funding_satoshis} sats is below the minimum of {} sats",
+                self.min_funding_sats
+            ));
+        }
+        if let Some(max_funding_sats) = self.max_funding_sats {
+            if funding_satoshis > max_funding_sats {
+                return Err(format!(
+                    "Channel size {funding_satoshis} sats is above the maximum of {max_funding_sats} sats"
+                ));
+            }
+        }
+        if let Some(max_pending) = self.max_pending_per_peer {
+            if pending_channels >= max_pending {
+                return Err(format!(
+                    "Peer already has {pending_channels} pending channels"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::PublicKey;
+
+    use crate::settings::Settings;
+
+    use super::{InboundChannelDecision, InboundChannelPolicy};
+
+    const PEER: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const OTHER_PEER: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    #[test]
+    fn test_inbound_channel_policy() {
+        let peer = PublicKey::from_str(PEER).unwrap();
+        let other_peer = PublicKey::from_str(OTHER_PEER).unwrap();
+        let settings = Settings {
+            manually_accept_inbound_channels: true,
+            inbound_channel_min_sats: 100_000,
+            inbound_channel_max_sats: Some(1_000_000),
+            inbound_channel_deny_list: vec![other_peer],
+            inbound_channel_max_pending_per_peer: Some(2),
+            ..Settings::default()
+        };
+        let policy = InboundChannelPolicy::new(&settings);
+
+        assert_eq!(
+            InboundChannelDecision::Accept,
+            policy.evaluate(&peer, 500_000, false, 1)
+        );
+        assert!(matches!(
+            policy.evaluate(&other_peer, 500_000, false, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+        assert!(matches!(
+            policy.evaluate(&peer, 50_000, false, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+        assert!(matches!(
+            policy.evaluate(&peer, 5_000_000, false, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+        assert!(matches!(
+            policy.evaluate(&peer, 500_000, false, 2),
+            InboundChannelDecision::Reject(_)
+        ));
+        assert!(matches!(
+            policy.evaluate(&peer, 500_000, true, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+
+        let settings = Settings {
+            manually_accept_inbound_channels: true,
+            inbound_channel_allow_list: vec![peer],
+            trusted_zero_conf_peers: vec![peer],
+            ..Settings::default()
+        };
+        let policy = InboundChannelPolicy::new(&settings);
+        assert_eq!(
+            InboundChannelDecision::AcceptZeroConf,
+            policy.evaluate(&peer, 500_000, true, 0)
+        );
+        assert!(matches!(
+            policy.evaluate(&other_peer, 500_000, false, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+
+        let settings = Settings {
+            inbound_channel_deny_list: vec![other_peer],
+            ..Settings::default()
+        };
+        let policy = InboundChannelPolicy::new(&settings);
+        assert_eq!(
+            InboundChannelDecision::Accept,
+            policy.evaluate(&other_peer, 500_000, false, 0)
+        );
+        assert!(matches!(
+            policy.evaluate(&other_peer, 500_000, true, 0),
+            InboundChannelDecision::Reject(_)
+        ));
+    }
+}
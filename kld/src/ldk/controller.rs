@@ -1,21 +1,29 @@
 use crate::bitcoind::bitcoind_interface::BitcoindInterface;
 use crate::bitcoind::{BitcoindClient, BitcoindUtxoLookup};
-use crate::database::forward::{Forward, ForwardStatus, TotalForwards};
-use crate::database::invoice::Invoice;
-use crate::database::payment::{Payment, PaymentDirection};
-use crate::database::ChannelRecord;
+use crate::database::fee_history::ChannelFeeUpdate;
+use crate::database::forward::{Forward, ForwardStatus, ForwardSummary, TimeBucket, TotalForwards};
+use crate::database::invoice::{Invoice, InvoiceStatus};
+use crate::database::lsps1::Lsps1Order;
+use crate::database::lsps2::{JitChannel, JitInvoice, Lsps2Token};
+use crate::database::offer::Offer;
+use crate::database::payment::{Payment, PaymentAttempt, PaymentDirection};
+use crate::database::rebalance::Rebalance;
+use crate::database::sweep::OutputSweep;
+use crate::database::{ChannelRecord, Pagination};
 use crate::key_generator::KeyGenerator;
 use crate::wallet::{Wallet, WalletInterface};
 use crate::{log_error, MillisatAmount, Service};
 
-use crate::api::payloads::FeeRate;
+use crate::api::payloads::StreamEvent;
 use crate::api::SocketAddress;
 use crate::database::{DurableConnection, LdkDatabase, WalletDatabase};
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use bitcoin::secp256k1::PublicKey;
-use bitcoin::{BlockHash, Network, Transaction};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::{BlockHash, Network, OutPoint, ScriptBuf, Transaction, Txid};
 use lightning::chain;
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
 use lightning::chain::channelmonitor::ChannelMonitor;
 use lightning::chain::BestBlock;
 use lightning::chain::Watch;
@@ -23,13 +31,18 @@ use lightning::ln::channelmanager::ChainParameters;
 use lightning::ln::channelmanager::ChannelManagerReadArgs;
 use lightning::ln::channelmanager::{
     self, ChannelDetails, PaymentId, PaymentSendFailure, RecipientOnionFields,
+    MIN_FINAL_CLTV_EXPIRY_DELTA,
 };
+use lightning::ln::features::ChannelFeatures;
+use lightning::ln::msgs::ChannelMessageHandler;
 use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler};
-use lightning::ln::ChannelId;
+use lightning::ln::script::ShutdownScript;
+use lightning::ln::{ChannelId, PaymentHash, PaymentPreimage};
+use lightning::routing::gossip::RoutingFees;
 use lightning::routing::gossip::{ChannelInfo, NodeId, NodeInfo, P2PGossipSync};
 use lightning::routing::router::{
-    DefaultRouter, Path, PaymentParameters, RouteParameters, Router,
-    ScorerAccountingForInFlightHtlcs,
+    DefaultRouter, Path, PaymentParameters, RouteHint, RouteHintHop, RouteHop, RouteParameters,
+    Router, ScorerAccountingForInFlightHtlcs,
 };
 use lightning::routing::scoring::ScoreUpdate;
 use lightning::routing::scoring::{
@@ -42,41 +55,59 @@ use lightning::util::errors::APIError;
 use crate::ldk::peer_manager::KuutamoPeerManger;
 use crate::logger::KldLogger;
 use crate::settings::Settings;
-use chrono::DateTime;
 use lightning::util::indexed_map::IndexedMap;
 use lightning_background_processor::{process_events_async, GossipSync};
 use lightning_block_sync::poll;
 use lightning_block_sync::SpvClient;
 use lightning_block_sync::UnboundedCache;
 use lightning_block_sync::{init, BlockSourceResult};
-use lightning_invoice::DEFAULT_EXPIRY_TIME;
-use lightning_liquidity::events::Event::LSPS2Service;
-use lightning_liquidity::lsps2::event::LSPS2ServiceEvent;
-use lightning_liquidity::lsps2::msgs::RawOpeningFeeParams;
+use lightning_invoice::{InvoiceBuilder, DEFAULT_EXPIRY_TIME};
+use lightning_liquidity::lsps0::ser::RequestId;
+use lightning_liquidity::lsps2::client::LSPS2ClientConfig;
+use lightning_liquidity::lsps2::msgs::OpeningFeeParams;
 use lightning_liquidity::lsps2::service::LSPS2ServiceConfig;
-use lightning_liquidity::LiquidityServiceConfig;
+use lightning_liquidity::{LiquidityClientConfig, LiquidityServiceConfig};
 use log::{debug, error, info, trace, warn};
 use prometheus::IntCounter;
 use rand::random;
 use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
 use std::sync::Arc;
 use std::sync::OnceLock;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
 
 use futures::{future::Shared, Future};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 use tokio::sync::oneshot::{self, Receiver, Sender};
 use tokio::sync::RwLock;
 
+use super::autofee::AutoFee;
+use super::backup::{recover_from_backup, BackupExporter};
 use super::event_handler::EventHandler;
+use super::lsp_client::LspClient;
+use super::lsps1::{supported_protocols, Lsps1Messages, Lsps1Service};
+use super::lsps2::Lsps2Service;
 use super::peer_manager::PeerManager;
+use super::rapid_gossip_sync::{GossipSnapshots, RapidGossipSync, SnapshotSource};
+use super::sweeper::OutputSweeper;
 use super::{
-    ldk_error, lightning_error, payment_send_failure, retryable_send_failure,
-    sign_or_creation_error, ChainMonitor, ChannelManager, KldRouter, KuutamoCustomMessageHandler,
-    LightningInterface, LiquidityManager, NetworkGraph, OnionMessenger, OpenChannelResult, Peer,
-    PeerStatus, Scorer,
+    bolt12_semantic_error, ldk_error, lightning_error, payment_send_failure,
+    retryable_send_failure, sign_or_creation_error, ChainMonitor, ChannelManager, ChannelOrder,
+    ChannelTarget, FundingOptions, KldRouter, KuutamoCustomMessageHandler, LightningInterface,
+    LiquidityManager, LspInfo, LspOrder, NetworkGraph, OnionMessenger, OpenChannelResult,
+    PathEstimate, PaymentOptions, Peer, PeerStatus, RouteQuery, Scorer,
 };
 
+// Events buffered per subscriber before slow websocket clients start missing them.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+// Held payments are canceled this many blocks before their claim deadline.
+const HOLD_INVOICE_CANCEL_BLOCKS: u32 = 3;
+// How often waiting for an invoice checks it again without a payment event.
+const INVOICE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
 #[async_trait]
 impl LightningInterface for Controller {
     fn identity_pubkey(&self) -> PublicKey {
@@ -159,56 +190,88 @@ impl LightningInterface for Controller {
         self.database.fetch_channels().await
     }
 
-    async fn open_channel(
+    async fn open_channels(
         &self,
-        their_network_key: PublicKey,
-        channel_value_satoshis: u64,
-        push_msat: Option<u64>,
-        fee_rate: Option<FeeRate>,
-        override_config: Option<UserConfig>,
+        targets: Vec<ChannelTarget>,
+        funding: FundingOptions,
     ) -> Result<OpenChannelResult> {
+        if targets.is_empty() {
+            bail!("No channels to open")
+        }
         if !self.bitcoind_client.is_synchronised().await {
             bail!("Bitcoind is synchronising blockchain")
         }
-        if !self.peer_manager.is_connected(&their_network_key) {
-            return Err(anyhow!("Peer not connected"));
+        for target in &targets {
+            if !self.peer_manager.is_connected(&target.node_id) {
+                return Err(anyhow!("Peer {} not connected", target.node_id));
+            }
         }
-        let user_channel_id: u64 = random::<u64>() / 2; // To fit into the database INT
-        let is_public = override_config
-            .map(|c| c.channel_handshake_config.announced_channel)
-            .unwrap_or_default();
-        let counterparty = their_network_key;
-        let channel_id = self
-            .channel_manager
-            .create_channel(
-                their_network_key,
-                channel_value_satoshis,
-                push_msat.unwrap_or_default(),
-                user_channel_id as u128,
-                None,
-                override_config,
-            )
-            .map_err(ldk_error)?;
-        let receiver = self
-            .async_api_requests
-            .funding_transactions
-            .insert(user_channel_id, fee_rate.unwrap_or_default())
-            .await;
-        let transaction = receiver.await??;
+        let mut channels = vec![];
+        for target in &targets {
+            let user_channel_id: u64 = random::<u64>() / 2; // To fit into the database INT
+            match self
+                .channel_manager
+                .create_channel(
+                    target.node_id,
+                    target.value_sats,
+                    target.push_msat.unwrap_or_default(),
+                    user_channel_id as u128,
+                    None,
+                    target.override_config,
+                )
+                .map_err(ldk_error)
+            {
+                Ok(channel_id) => channels.push((user_channel_id, channel_id, target.node_id)),
+                Err(e) => {
+                    // Nothing is funded yet, so the channels created so far can simply be dropped.
+                    for (_, channel_id, node_id) in &channels {
+                        if let Err(e) = self
+                            .channel_manager
+                            .force_close_without_broadcasting_txn(channel_id, node_id)
+                        {
+                            warn!("Failed to drop channel of failed batch: {e:?}");
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let batch = Arc::new(FundingBatch::new(funding, channels.clone()));
+        let mut receivers = vec![];
+        for (user_channel_id, _, _) in &channels {
+            receivers.push(
+                self.async_api_requests
+                    .funding_transactions
+                    .insert(*user_channel_id, batch.clone())
+                    .await,
+            );
+        }
+        // Every channel in the batch is answered with the same funding transaction.
+        let mut transaction = None;
+        for receiver in receivers {
+            transaction = Some(receiver.await??);
+        }
+        let transaction = transaction.context("Missing funding transaction")?;
         let txid = transaction.txid();
-        if let Err(e) = self
-            .database
-            .persist_initializing_channel(&channel_id, is_public, &counterparty, &txid)
-            .await
-        {
-            // This failure should not cause issues, the channel detail update will be retried later,
-            // triggered on the next event, so we do not retry and only log the error but not raise it here.
-            log_error(&e);
+        for ((_, channel_id, counterparty), target) in channels.iter().zip(&targets) {
+            let is_public = target
+                .override_config
+                .map(|c| c.channel_handshake_config.announced_channel)
+                .unwrap_or_default();
+            if let Err(e) = self
+                .database
+                .persist_initializing_channel(channel_id, is_public, counterparty, &txid)
+                .await
+            {
+                // This failure should not cause issues, the channel detail update will be retried later,
+                // triggered on the next event, so we do not retry and only log the error but not raise it here.
+                log_error(&e);
+            }
         }
         Ok(OpenChannelResult {
             transaction,
             txid,
-            channel_id,
+            channel_ids: channels.into_iter().map(|(_, id, _)| id).collect(),
         })
     }
 
@@ -217,17 +280,34 @@ impl LightningInterface for Controller {
         channel_id: &ChannelId,
         counterparty_node_id: &PublicKey,
         fee_rate: Option<u32>,
+        max_initial_fee_rate: Option<u32>,
+        shutdown_script: Option<ScriptBuf>,
     ) -> Result<()> {
         if !self.bitcoind_client.is_synchronised().await {
             bail!("Bitcoind is synchronising blockchain")
         }
-        if fee_rate.is_some() {
+        if let Some(max_initial_fee_rate) = max_initial_fee_rate {
+            // LDK never proposes less than the close minimum estimate, so refuse to start the close rather
+            // than propose more. The peer can still negotiate a higher fee.
+            let proposed_fee_rate = self
+                .bitcoind_client
+                .get_est_sat_per_1000_weight(ConfirmationTarget::ChannelCloseMinimum)
+                .max(fee_rate.unwrap_or_default());
+            if proposed_fee_rate > max_initial_fee_rate {
+                bail!("Initial closing fee rate {proposed_fee_rate} sats/kw is above the maximum of {max_initial_fee_rate} sats/kw")
+            }
+        }
+        let shutdown_script = shutdown_script
+            .map(ShutdownScript::try_from)
+            .transpose()
+            .map_err(|_| anyhow!("The address is not a supported shutdown script"))?;
+        if fee_rate.is_some() || shutdown_script.is_some() {
             self.channel_manager
                 .close_channel_with_feerate_and_script(
                     channel_id,
                     counterparty_node_id,
                     fee_rate,
-                    None,
+                    shutdown_script,
                 )
                 .map_err(ldk_error)
         } else {
@@ -256,6 +336,139 @@ impl LightningInterface for Controller {
         .map_err(ldk_error)
     }
 
+    async fn rebalance(
+        &self,
+        outbound_channel_id: &ChannelId,
+        inbound_channel_id: &ChannelId,
+        amount: MillisatAmount,
+        max_fee_ppm: Option<u32>,
+    ) -> Result<Rebalance> {
+        if outbound_channel_id == inbound_channel_id {
+            bail!("Outbound and inbound channels must be different");
+        }
+        let channels = self.channel_manager.list_usable_channels();
+        let find_channel = |channel_id: &ChannelId| {
+            channels
+                .iter()
+                .find(|c| c.channel_id == *channel_id)
+                .with_context(|| format!("Channel {} is not usable", hex::encode(channel_id.0)))
+        };
+        let outbound_channel = find_channel(outbound_channel_id)?;
+        let inbound_channel = find_channel(inbound_channel_id)?;
+        let inbound_scid = inbound_channel
+            .get_inbound_payment_scid()
+            .context("Inbound channel has no short channel ID")?;
+        let forwarding_info = inbound_channel
+            .counterparty
+            .forwarding_info
+            .as_ref()
+            .context("Inbound channel peer has not shared its forwarding fees")?;
+
+        // The peer on the inbound channel charges us for forwarding back to ourselves,
+        // so the route to that peer has to carry the amount plus its fee.
+        let inbound_fee = (forwarding_info.fee_base_msat as u64).saturating_add(
+            amount.saturating_mul(forwarding_info.fee_proportional_millionths as u64) / 1_000_000,
+        );
+        let max_fee_ppm = max_fee_ppm.unwrap_or(self.settings.rebalance_max_fee_ppm);
+        let max_fee = amount.saturating_mul(max_fee_ppm as u64) / 1_000_000;
+        let max_total_routing_fee_msat =
+            Some(max_fee.checked_sub(inbound_fee).context(format!(
+            "Inbound channel fee of {inbound_fee} msat exceeds the maximum fee of {max_fee} msat"
+        ))?);
+        let route_params = RouteParameters {
+            payment_params: PaymentParameters::from_node_id(
+                inbound_channel.counterparty.node_id,
+                MIN_FINAL_CLTV_EXPIRY_DELTA as u32,
+            )
+            .with_max_path_count(1)
+            .map_err(|()| anyhow!("Invalid max path count"))?,
+            final_value_msat: amount
+                .checked_add(inbound_fee)
+                .context("Rebalance amount overflows")?,
+            max_total_routing_fee_msat,
+        };
+        let mut route = self
+            .router
+            .find_route(
+                &self.identity_pubkey(),
+                &route_params,
+                Some(&[outbound_channel]),
+                self.channel_manager.compute_inflight_htlcs(),
+            )
+            .map_err(lightning_error)?;
+        // The route was found for a payment to the peer, retries would use those parameters.
+        route.route_params = None;
+        let path = route
+            .paths
+            .first_mut()
+            .context("Router returned an empty route")?;
+        let last_hop = path
+            .hops
+            .last_mut()
+            .context("Router returned an empty path")?;
+        last_hop.fee_msat = inbound_fee;
+        last_hop.cltv_expiry_delta = forwarding_info.cltv_expiry_delta as u32;
+        path.hops.push(RouteHop {
+            pubkey: self.identity_pubkey(),
+            node_features: self.channel_manager.provided_node_features(),
+            short_channel_id: inbound_scid,
+            channel_features: ChannelFeatures::empty(),
+            fee_msat: amount,
+            cltv_expiry_delta: MIN_FINAL_CLTV_EXPIRY_DELTA as u32,
+            maybe_announced_channel: inbound_channel.is_public,
+        });
+
+        let (payment_hash, payment_secret) = self
+            .channel_manager
+            .create_inbound_payment(Some(amount), 3600, None)
+            .map_err(|()| anyhow!("Failed to create payment hash"))?;
+        let payment_id = Payment::new_id();
+        let mut rebalance = Rebalance::new(
+            payment_id,
+            *outbound_channel_id,
+            *inbound_channel_id,
+            amount,
+        );
+        let mut payment = Payment::spontaneous_outbound(payment_id, amount);
+        payment.hash = Some(payment_hash);
+        payment.secret = Some(payment_secret);
+        payment.label = Some(format!("rebalance {}", rebalance.id));
+        self.database.persist_payment(&payment).await?;
+        let receiver = self
+            .async_api_requests
+            .payments
+            .insert(payment_id, payment)
+            .await;
+        if let Err(e) = self.channel_manager.send_payment_with_route(
+            &route,
+            payment_hash,
+            RecipientOnionFields::secret_only(payment_secret),
+            payment_id,
+        ) {
+            if !only_monitor_update_in_progress(&e) {
+                if let Some((mut payment, _)) =
+                    self.async_api_requests.payments.get(&payment_id).await
+                {
+                    payment.failed(None);
+                    self.database.persist_payment(&payment).await?;
+                }
+                return Err(payment_send_failure(e));
+            }
+        }
+        info!(
+            "Initiated rebalance {} of {amount} msat from channel {} to {}",
+            rebalance.id,
+            hex::encode(outbound_channel_id.0),
+            hex::encode(inbound_channel_id.0)
+        );
+        self.database.persist_rebalance(&rebalance).await?;
+        let payment = receiver.await??;
+        rebalance.status = payment.status;
+        rebalance.fee = payment.fee;
+        self.database.persist_rebalance(&rebalance).await?;
+        Ok(rebalance)
+    }
+
     fn set_channel_fee(
         &self,
         counterparty_node_id: &PublicKey,
@@ -376,8 +589,16 @@ impl LightningInterface for Controller {
         self.network_graph.read_only().node(node_id).cloned()
     }
 
-    fn nodes(&self) -> IndexedMap<NodeId, NodeInfo> {
-        self.network_graph.read_only().nodes().clone()
+    fn nodes(&self, after: Option<NodeId>, limit: Option<usize>) -> Vec<(NodeId, NodeInfo)> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        self.network_graph
+            .read_only()
+            .nodes()
+            .range((start, Bound::Unbounded))
+            .filter(|(_, node_info)| node_info.announcement_info.is_some())
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(node_id, node_info)| (*node_id, node_info.clone()))
+            .collect()
     }
 
     fn get_channel(&self, channel_id: u64) -> Option<ChannelInfo> {
@@ -388,6 +609,97 @@ impl LightningInterface for Controller {
         self.network_graph.read_only().channels().clone()
     }
 
+    fn find_route(&self, query: RouteQuery) -> Result<Vec<PathEstimate>> {
+        let mut excluded_channels = query.exclude_channels.clone();
+        {
+            let graph = self.network_graph.read_only();
+            for node_id in &query.exclude_nodes {
+                if let Some(node) = graph.node(&NodeId::from_pubkey(node_id)) {
+                    excluded_channels.extend(node.channels.iter());
+                }
+            }
+        }
+        let usable_channels = self.channel_manager.list_usable_channels();
+        let first_hops: Vec<&ChannelDetails> = usable_channels
+            .iter()
+            .filter(|channel| {
+                !query.exclude_nodes.contains(&channel.counterparty.node_id)
+                    && !channel
+                        .get_outbound_payment_scid()
+                        .is_some_and(|scid| excluded_channels.contains(&scid))
+            })
+            .collect();
+        let mut payment_params =
+            PaymentParameters::from_node_id(query.destination, query.final_cltv_expiry_delta);
+        payment_params.previously_failed_channels = excluded_channels.clone();
+        let route_params = RouteParameters {
+            payment_params,
+            final_value_msat: query.amount,
+            max_total_routing_fee_msat: query.max_fee_msat,
+        };
+        let route = self
+            .router
+            .find_route(
+                &self.identity_pubkey(),
+                &route_params,
+                Some(&first_hops),
+                self.channel_manager.compute_inflight_htlcs(),
+            )
+            .map_err(lightning_error)?;
+        if route.paths.iter().flat_map(|path| &path.hops).any(|hop| {
+            excluded_channels.contains(&hop.short_channel_id)
+                || (hop.pubkey != query.destination && query.exclude_nodes.contains(&hop.pubkey))
+        }) {
+            bail!("Failed to find a route avoiding the excluded channels and nodes");
+        }
+
+        let scorer = self
+            .scorer
+            .read()
+            .map_err(|e| anyhow!("failed to acquire lock on scorer {}", e))?;
+        let score_params = ProbabilisticScoringFeeParameters::default();
+        Ok(route
+            .paths
+            .into_iter()
+            .map(|path| {
+                let hop_probabilities = path
+                    .hops
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hop)| {
+                        // The router only sends over our own channels what they can forward.
+                        if i == 0 {
+                            return Some(1.0);
+                        }
+                        let amount: u64 = path.hops[i..].iter().map(|hop| hop.fee_msat).sum();
+                        let target = NodeId::from_pubkey(&hop.pubkey);
+                        scorer
+                            .historical_estimated_payment_success_probability(
+                                hop.short_channel_id,
+                                &target,
+                                amount,
+                                &score_params,
+                            )
+                            .or_else(|| {
+                                scorer
+                                    .estimated_channel_liquidity_range(
+                                        hop.short_channel_id,
+                                        &target,
+                                    )
+                                    .map(|(min, max)| {
+                                        liquidity_success_probability(amount, min, max)
+                                    })
+                            })
+                    })
+                    .collect();
+                PathEstimate {
+                    path,
+                    hop_probabilities,
+                }
+            })
+            .collect())
+    }
+
     // Use this to override the default/startup config.
     fn user_config(&self) -> UserConfig {
         *self.channel_manager.get_current_default_configuration()
@@ -399,132 +711,341 @@ impl LightningInterface for Controller {
         amount: Option<u64>,
         description: String,
         expiry: Option<u32>,
+        payment_hash: Option<PaymentHash>,
     ) -> Result<Invoice> {
-        let bolt11 = lightning_invoice::utils::create_invoice_from_channelmanager(
-            &self.channel_manager,
-            self.keys_manager.clone(),
-            KldLogger::global(),
-            self.network().into(),
-            amount,
-            description,
-            expiry.unwrap_or(DEFAULT_EXPIRY_TIME as u32),
-            None,
-        )
+        let expiry = expiry.unwrap_or(DEFAULT_EXPIRY_TIME as u32);
+        let bolt11 = match payment_hash {
+            Some(payment_hash) => lightning_invoice::utils::create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash(
+                &self.channel_manager,
+                self.keys_manager.clone(),
+                KldLogger::global(),
+                self.network().into(),
+                amount,
+                description,
+                SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?,
+                expiry,
+                payment_hash,
+                None,
+            ),
+            None => lightning_invoice::utils::create_invoice_from_channelmanager(
+                &self.channel_manager,
+                self.keys_manager.clone(),
+                KldLogger::global(),
+                self.network().into(),
+                amount,
+                description,
+                expiry,
+                None,
+            ),
+        }
         .map_err(sign_or_creation_error)?;
         let invoice = Invoice::new(Some(label), bolt11)?;
         info!(
-            "Generated invoice with payment hash {}",
+            "Generated {}invoice with payment hash {}",
+            if payment_hash.is_some() { "hold " } else { "" },
             hex::encode(invoice.payment_hash.0)
         );
         self.database.persist_invoice(&invoice).await?;
         Ok(invoice)
     }
 
-    async fn list_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>> {
-        self.database.fetch_invoices(label).await
+    async fn settle_invoice(&self, preimage: PaymentPreimage) -> Result<()> {
+        let payment_hash = PaymentHash(sha256::Hash::hash(&preimage.0).to_byte_array());
+        match self.database.fetch_invoice_status(&payment_hash).await? {
+            Some(InvoiceStatus::Held) => {
+                info!("Settling hold invoice {}", hex::encode(payment_hash.0));
+                self.channel_manager.claim_funds(preimage);
+                Ok(())
+            }
+            Some(status) => bail!("Invoice is {status}, only held invoices can be settled"),
+            None => bail!(
+                "No invoice with payment hash {}",
+                hex::encode(payment_hash.0)
+            ),
+        }
+    }
+
+    async fn cancel_invoice(&self, payment_hash: PaymentHash) -> Result<()> {
+        match self.database.fetch_invoice_status(&payment_hash).await? {
+            Some(InvoiceStatus::Open) | Some(InvoiceStatus::Held) => {
+                info!("Canceling hold invoice {}", hex::encode(payment_hash.0));
+                self.channel_manager.fail_htlc_backwards(&payment_hash);
+                self.database
+                    .update_invoice_status(&payment_hash, InvoiceStatus::Canceled, None)
+                    .await
+            }
+            Some(status) => bail!("Invoice is {status} and can't be canceled"),
+            None => bail!(
+                "No invoice with payment hash {}",
+                hex::encode(payment_hash.0)
+            ),
+        }
+    }
+
+    async fn list_invoices(
+        &self,
+        label: Option<String>,
+        pagination: Pagination,
+    ) -> Result<Vec<Invoice>> {
+        self.database.fetch_invoices(label, &pagination).await
     }
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment> {
-        let payment = Payment::of_invoice_outbound(&invoice, label);
+    async fn wait_invoice(&self, label: String) -> Result<Option<Invoice>> {
+        // Subscribe before reading so that no payment is missed in between.
+        let mut events = self.event_sender.subscribe();
+        loop {
+            let mut invoices = self
+                .database
+                .fetch_invoices(Some(label.clone()), &Pagination::default())
+                .await?;
+            if invoices.len() > 1 {
+                bail!("Label {label} matches {} invoices", invoices.len());
+            }
+            let Some(invoice) = invoices.pop() else {
+                return Ok(None);
+            };
+            if invoice.is_final() {
+                return Ok(Some(invoice));
+            }
+            wait_for_payment_claimed(&mut events).await;
+        }
+    }
 
+    async fn wait_any_invoice(&self, lastpay_index: u64) -> Result<Invoice> {
+        let mut events = self.event_sender.subscribe();
+        loop {
+            if let Some(invoice) = self.database.fetch_next_paid_invoice(lastpay_index).await? {
+                return Ok(invoice);
+            }
+            wait_for_payment_claimed(&mut events).await;
+        }
+    }
+
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        options: PaymentOptions,
+    ) -> Result<Payment> {
+        let amount = match (invoice.amount, options.amount) {
+            (Some(invoice_amount), Some(amount)) if amount < invoice_amount => {
+                bail!("amount {amount} is less than the invoice amount {invoice_amount}")
+            }
+            (_, Some(amount)) => amount,
+            (Some(invoice_amount), None) => invoice_amount,
+            (None, None) => bail!("amount missing from invoice"),
+        };
+        let mut payment = Payment::of_invoice_outbound(&invoice, label);
+        payment.amount = amount;
+
+        let mut payment_params = PaymentParameters::from_node_id(
+            invoice.payee_pub_key,
+            invoice.bolt11.min_final_cltv_expiry_delta() as u32,
+        )
+        .with_route_hints(invoice.bolt11.route_hints())
+        .map_err(|_| anyhow!("invalid route hints in invoice"))?;
+        if let Some(features) = invoice.bolt11.features() {
+            payment_params = payment_params
+                .with_bolt11_features(features.clone())
+                .map_err(|_| anyhow!("invalid features in invoice"))?;
+        }
+        if let Some(max_paths) = options.max_paths {
+            payment_params = payment_params.with_max_path_count(max_paths);
+        }
+        if let Some(max_cltv_expiry_delta) = options.max_cltv_expiry_delta {
+            payment_params = payment_params.with_max_total_cltv_expiry_delta(max_cltv_expiry_delta);
+        }
         let route_params = RouteParameters {
-            payment_params: PaymentParameters::from_node_id(invoice.payee_pub_key, 40),
-            final_value_msat: invoice.amount.context("amount missing from invoice")?,
-            // TODO: configurable, when opening a channel or starting kld
-            max_total_routing_fee_msat: None,
+            payment_params,
+            final_value_msat: amount,
+            max_total_routing_fee_msat: options.max_total_routing_fee_msat(amount),
         };
-        self.channel_manager
-            .send_payment(
-                payment.hash.context("expected payment hash")?,
-                RecipientOnionFields::secret_only(*invoice.bolt11.payment_secret()),
-                payment.id,
-                route_params,
-                channelmanager::Retry::Timeout(Duration::from_secs(60)),
+        let retry = Duration::from_secs(options.retry_for.unwrap_or(60));
+        // The payment events can arrive as soon as it is sent, they need the payment in place.
+        self.database.persist_invoice(&invoice).await?;
+        self.database.persist_payment(&payment).await?;
+        let receiver = if options.non_blocking {
+            None
+        } else {
+            Some(
+                self.async_api_requests
+                    .payments
+                    .insert(payment.id, payment.clone())
+                    .await,
             )
-            .map_err(retryable_send_failure)?;
+        };
+        if let Err(e) = self.channel_manager.send_payment(
+            payment.hash.context("expected payment hash")?,
+            RecipientOnionFields::secret_only(*invoice.bolt11.payment_secret()),
+            payment.id,
+            route_params,
+            channelmanager::Retry::Timeout(retry),
+        ) {
+            self.fail_unsent_payment(payment).await?;
+            return Err(retryable_send_failure(e));
+        }
         info!(
             "Initiated payment of invoice with hash {}",
             hex::encode(invoice.payment_hash.0)
         );
-        self.database.persist_invoice(&invoice).await?;
-        self.database.persist_payment(&payment).await?;
-        let receiver = self
-            .async_api_requests
-            .payments
-            .insert(payment.id, payment)
-            .await;
-        let payment = receiver.await??;
-        self.database.persist_payment(&payment).await?;
-        Ok(payment)
+        match receiver {
+            Some(receiver) => receiver.await?,
+            None => Ok(payment),
+        }
     }
 
-    async fn keysend_payment(&self, payee: NodeId, amount: MillisatAmount) -> Result<Payment> {
+    async fn keysend_payment(
+        &self,
+        payee: NodeId,
+        amount: MillisatAmount,
+        max_fee_msat: Option<MillisatAmount>,
+        non_blocking: bool,
+    ) -> Result<Payment> {
         let payment_id = Payment::new_id();
         let inflight_htlcs = self.channel_manager.compute_inflight_htlcs();
         let route_params = RouteParameters {
             payment_params: PaymentParameters::for_keysend(payee.as_pubkey()?, 40, false),
             final_value_msat: amount,
-            // TODO: configurable, when opening a channel or starting kld
-            max_total_routing_fee_msat: None,
+            max_total_routing_fee_msat: max_fee_msat,
         };
         let route = self
             .router
             .find_route(&self.identity_pubkey(), &route_params, None, inflight_htlcs)
             .map_err(lightning_error)?;
+        // Choose the preimage here so that the payment is stored with its hash before it is sent.
+        let preimage = PaymentPreimage(random());
+        let mut payment = Payment::spontaneous_outbound(payment_id, amount);
+        payment.hash = Some(PaymentHash(sha256::Hash::hash(&preimage.0).to_byte_array()));
+        self.database.persist_payment(&payment).await?;
+        let receiver = if non_blocking {
+            None
+        } else {
+            Some(
+                self.async_api_requests
+                    .payments
+                    .insert(payment_id, payment.clone())
+                    .await,
+            )
+        };
         match self.channel_manager.send_spontaneous_payment(
             &route,
-            None,
+            Some(preimage),
             RecipientOnionFields::spontaneous_empty(),
             payment_id,
         ) {
-            Ok(_hash) => (),
+            Ok(_) => {}
+            Err(e) if only_monitor_update_in_progress(&e) => {}
             Err(e) => {
-                match &e {
-                    PaymentSendFailure::PartialFailure {
-                        results,
-                        failed_paths_retry: _,
-                        payment_id: _,
-                    } => {
-                        // Monitor updates are persisted async so continue if MonitorUpdateInProgress is the only "error" we get.
-                        if !results.iter().all(|result| {
-                            result.is_ok()
-                                || result
-                                    .as_ref()
-                                    .is_err_and(|f| matches!(f, APIError::MonitorUpdateInProgress))
-                        }) {
-                            return Err(payment_send_failure(e));
-                        }
-                    }
-                    _ => return Err(payment_send_failure(e)),
-                };
+                self.fail_unsent_payment(payment).await?;
+                return Err(payment_send_failure(e));
             }
         };
-        let payment = Payment::spontaneous_outbound(payment_id, amount);
         info!(
             "Initiated keysend payment with id {}",
             hex::encode(payment_id.0)
         );
-        self.database.persist_payment(&payment).await?;
-        let receiver = self
-            .async_api_requests
-            .payments
-            .insert(payment_id, payment)
-            .await;
-        let payment = receiver.await??;
-        self.database.persist_payment(&payment).await?;
-        Ok(payment)
+        match receiver {
+            Some(receiver) => receiver.await?,
+            None => Ok(payment),
+        }
     }
 
     async fn list_payments(
         &self,
         invoice: Option<Invoice>,
         direction: Option<PaymentDirection>,
+        pagination: Pagination,
     ) -> Result<Vec<Payment>> {
         self.database
-            .fetch_payments(invoice.map(|i| i.payment_hash), direction)
+            .fetch_payments(invoice.map(|i| i.payment_hash), direction, &pagination)
             .await
     }
 
+    async fn get_payment(&self, payment_id: PaymentId) -> Result<Option<Payment>> {
+        self.database.fetch_payment(&payment_id).await
+    }
+
+    async fn list_payment_attempts(&self, payment_id: PaymentId) -> Result<Vec<PaymentAttempt>> {
+        self.database.fetch_payment_attempts(&payment_id).await
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<StreamEvent> {
+        self.event_sender.subscribe()
+    }
+
+    async fn create_offer(
+        &self,
+        label: Option<String>,
+        amount: Option<MillisatAmount>,
+        description: String,
+        expiry: Option<u64>,
+    ) -> Result<Offer> {
+        let mut builder = self
+            .channel_manager
+            .create_offer_builder(description)
+            .map_err(bolt12_semantic_error)?;
+        if let Some(amount) = amount {
+            builder = builder.amount_msats(amount);
+        }
+        if let Some(expiry) = expiry {
+            builder = builder.absolute_expiry(
+                SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?
+                    + Duration::from_secs(expiry),
+            );
+        }
+        let offer = Offer::new(label, builder.build().map_err(bolt12_semantic_error)?);
+        info!("Created offer with id {}", hex::encode(offer.id));
+        self.database.persist_offer(&offer).await?;
+        Ok(offer)
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: Offer,
+        amount: Option<MillisatAmount>,
+        max_fee_msat: Option<MillisatAmount>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment> {
+        if offer.is_expired() {
+            bail!("Offer has expired");
+        }
+        let payment = Payment::of_offer_outbound(
+            Payment::new_id(),
+            amount
+                .or(offer.amount)
+                .context("amount missing from offer")?,
+            label,
+        );
+        self.database.persist_payment(&payment).await?;
+        let receiver = self
+            .async_api_requests
+            .payments
+            .insert(payment.id, payment.clone())
+            .await;
+        if let Err(e) = self.channel_manager.pay_for_offer(
+            &offer.bolt12,
+            None,
+            amount,
+            payer_note,
+            payment.id,
+            channelmanager::Retry::Timeout(Duration::from_secs(60)),
+            max_fee_msat,
+        ) {
+            self.fail_unsent_payment(payment).await?;
+            return Err(bolt12_semantic_error(e));
+        }
+        info!(
+            "Initiated payment of offer with id {}",
+            hex::encode(offer.id)
+        );
+        receiver.await?
+    }
+
+    async fn list_offers(&self, label: Option<String>) -> Result<Vec<Offer>> {
+        self.database.fetch_offers(label).await
+    }
+
     async fn estimated_channel_liquidity_range(
         &self,
         scid: u64,
@@ -541,18 +1062,142 @@ impl LightningInterface for Controller {
         self.database.fetch_total_forwards().await
     }
 
-    async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
-        self.database.fetch_forwards(status).await
+    async fn fetch_forwards(
+        &self,
+        status: Option<ForwardStatus>,
+        pagination: Pagination,
+    ) -> Result<Vec<Forward>> {
+        self.database.fetch_forwards(status, &pagination).await
+    }
+
+    async fn forward_summaries(
+        &self,
+        bucket: TimeBucket,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Result<Vec<ForwardSummary>> {
+        self.database
+            .fetch_forward_summaries(bucket, from, to)
+            .await
+    }
+
+    fn funding_fee(&self, txid: &Txid) -> Result<Option<u64>> {
+        self.wallet.transaction_fee(txid)
+    }
+
+    fn closing_transaction(&self, funding_txo: &OutPoint) -> Result<Option<Transaction>> {
+        self.wallet.spending_transaction(funding_txo)
+    }
+
+    async fn channel_history(&self, pagination: Pagination) -> Result<Vec<ChannelRecord>> {
+        self.database.fetch_channel_history(&pagination).await
     }
 
-    async fn channel_history(&self) -> Result<Vec<ChannelRecord>> {
-        self.database.fetch_channel_history().await
+    async fn fee_history(&self, channel_id: Option<ChannelId>) -> Result<Vec<ChannelFeeUpdate>> {
+        self.database.fetch_channel_fee_history(channel_id).await
+    }
+
+    async fn list_sweeps(&self) -> Result<Vec<OutputSweep>> {
+        self.database.fetch_output_sweeps(None).await
+    }
+
+    async fn list_lsps2_tokens(&self) -> Result<Vec<Lsps2Token>> {
+        self.database.fetch_lsps2_tokens().await
+    }
+
+    async fn persist_lsps2_token(&self, token: Lsps2Token) -> Result<()> {
+        self.database.persist_lsps2_token(&token).await
+    }
+
+    async fn delete_lsps2_token(&self, token: &str) -> Result<bool> {
+        self.database.delete_lsps2_token(token).await
+    }
+
+    async fn list_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        self.database.fetch_jit_channels().await
+    }
+
+    async fn list_lsps1_orders(&self) -> Result<Vec<Lsps1Order>> {
+        self.database.fetch_lsps1_orders(None).await
+    }
+
+    async fn lsp_info(&self, lsp: PublicKey, token: Option<String>) -> Result<LspInfo> {
+        self.lsp_client.get_info(lsp, token).await
+    }
+
+    async fn buy_channel(&self, lsp: PublicKey, order: ChannelOrder) -> Result<LspOrder> {
+        self.lsp_client.buy_channel(lsp, order).await
+    }
+
+    async fn generate_jit_invoice(
+        &self,
+        lsp: PublicKey,
+        token: Option<String>,
+        label: String,
+        amount: Option<u64>,
+        description: String,
+        expiry: Option<u32>,
+    ) -> Result<Invoice> {
+        let expiry = expiry.unwrap_or(DEFAULT_EXPIRY_TIME as u32);
+        let jit_channel = self.lsp_client.buy_jit_channel(lsp, token, amount).await?;
+        let (payment_hash, payment_secret) = self
+            .channel_manager
+            .create_inbound_payment(amount, expiry, None)
+            .map_err(|()| anyhow!("Failed to create inbound payment"))?;
+        // The LSP forwards the payment over the new channel, minus the opening fee, instead of routing it.
+        let route_hint = RouteHint(vec![RouteHintHop {
+            src_node_id: lsp,
+            short_channel_id: jit_channel.intercept_scid,
+            fees: RoutingFees {
+                base_msat: 0,
+                proportional_millionths: 0,
+            },
+            cltv_expiry_delta: jit_channel.cltv_expiry_delta as u16,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None,
+        }]);
+        let builder = InvoiceBuilder::new(self.network().into())
+            .description(description)
+            .payment_hash(sha256::Hash::from_slice(&payment_hash.0)?)
+            .payment_secret(payment_secret)
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(MIN_FINAL_CLTV_EXPIRY_DELTA.into())
+            .expiry_time(Duration::from_secs(expiry.into()))
+            .private_route(route_hint);
+        let builder = match amount {
+            Some(amount) => builder.amount_milli_satoshis(amount),
+            None => builder,
+        };
+        let node_secret_key = self.keys_manager.get_node_secret_key();
+        let bolt11 = builder
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &node_secret_key))
+            .map_err(|e| anyhow!("Error creating invoice: {e}"))?;
+        let invoice = Invoice::new(Some(label), bolt11)?;
+        self.database.persist_invoice(&invoice).await?;
+        self.database
+            .persist_jit_invoice(&JitInvoice::new(
+                invoice.payment_hash,
+                lsp,
+                jit_channel.intercept_scid,
+                jit_channel.opening_fee_params.min_fee_msat,
+                jit_channel.opening_fee_params.proportional,
+            ))
+            .await?;
+        info!(
+            "Generated JIT invoice with payment hash {} through LSP {lsp}",
+            hex::encode(invoice.payment_hash.0)
+        );
+        Ok(invoice)
     }
 
     async fn scorer(&self) -> Result<Vec<u8>> {
         self.database.fetch_scorer_binary().await
     }
 
+    async fn static_channel_backup(&self) -> Result<Vec<u8>> {
+        self.backup_exporter.export().await
+    }
+
     async fn update_channels(&self, channels: &[ChannelDetails]) {
         for channel in channels {
             if let Err(e) = self.database.persist_channel(channel).await {
@@ -564,9 +1209,63 @@ impl LightningInterface for Controller {
     }
 }
 
+/// Returns when a payment is claimed, or after a while because canceled and expiring invoices don't publish events.
+async fn wait_for_payment_claimed(events: &mut broadcast::Receiver<StreamEvent>) {
+    let timeout = tokio::time::sleep(INVOICE_POLL_INTERVAL);
+    tokio::pin!(timeout);
+    loop {
+        tokio::select! {
+            _ = &mut timeout => return,
+            event = events.recv() => match event {
+                Ok(StreamEvent::PaymentClaimed { .. }) | Err(_) => return,
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+/// Channels that are opened together and funded by one transaction once every peer has accepted.
+pub(crate) struct FundingBatch {
+    pub funding: FundingOptions,
+    // The user channel ID, temporary channel ID and counterparty of each channel.
+    pub channels: Vec<(u64, ChannelId, PublicKey)>,
+    outputs: std::sync::Mutex<HashMap<u64, (ScriptBuf, u64)>>,
+}
+
+impl FundingBatch {
+    pub fn new(
+        funding: FundingOptions,
+        channels: Vec<(u64, ChannelId, PublicKey)>,
+    ) -> FundingBatch {
+        FundingBatch {
+            funding,
+            channels,
+            outputs: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record the funding output of one channel, returns the outputs of all channels once the last one is added.
+    pub fn add_output(
+        &self,
+        user_channel_id: u64,
+        output_script: ScriptBuf,
+        value_sats: u64,
+    ) -> Option<Vec<(ScriptBuf, u64)>> {
+        let mut outputs = self.outputs.lock().unwrap();
+        outputs.insert(user_channel_id, (output_script, value_sats));
+        self.channels
+            .iter()
+            .map(|(id, _, _)| outputs.get(id).cloned())
+            .collect()
+    }
+}
+
 pub(crate) struct AsyncAPIRequests {
-    pub funding_transactions: AsyncSenders<u64, FeeRate, Result<Transaction>>,
+    pub funding_transactions: AsyncSenders<u64, Arc<FundingBatch>, Result<Transaction>>,
     pub payments: AsyncSenders<PaymentId, Payment, Result<Payment>>,
+    // The answers of the LSPs to our LSPS2 requests, by the id lightning-liquidity gave the request.
+    pub lsps2_opening_params: AsyncSenders<RequestId, (), Vec<OpeningFeeParams>>,
+    pub lsps2_invoice_params: AsyncSenders<RequestId, (), (u64, u32)>,
 }
 
 impl AsyncAPIRequests {
@@ -574,6 +1273,8 @@ impl AsyncAPIRequests {
         AsyncAPIRequests {
             funding_transactions: AsyncSenders::new(),
             payments: AsyncSenders::new(),
+            lsps2_opening_params: AsyncSenders::new(),
+            lsps2_invoice_params: AsyncSenders::new(),
         }
     }
 }
@@ -589,7 +1290,7 @@ impl<K: Eq + std::hash::Hash, V: Clone, RV> AsyncSenders<K, V, RV> {
         }
     }
 
-    async fn insert(&self, k: K, v: V) -> Receiver<RV> {
+    pub async fn insert(&self, k: K, v: V) -> Receiver<RV> {
         let (tx, rx) = oneshot::channel::<RV>();
         self.senders.write().await.insert(k, (v, tx));
         rx
@@ -607,6 +1308,14 @@ impl<K: Eq + std::hash::Hash, V: Clone, RV> AsyncSenders<K, V, RV> {
         None
     }
 
+    pub async fn peek(&self, k: &K) -> Option<V> {
+        self.senders.read().await.get(k).map(|(v, _)| v.clone())
+    }
+
+    pub async fn remove(&self, k: &K) {
+        self.senders.write().await.remove(k);
+    }
+
     pub async fn respond(&self, k: &K, rv: RV) {
         if let Some((_, tx)) = self.senders.write().await.remove(k) {
             if tx.send(rv).is_err() {
@@ -628,6 +1337,9 @@ pub struct Controller {
     scorer: Arc<std::sync::RwLock<Scorer>>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
     async_api_requests: Arc<AsyncAPIRequests>,
+    event_sender: broadcast::Sender<StreamEvent>,
+    backup_exporter: Arc<BackupExporter>,
+    lsp_client: Arc<LspClient>,
 }
 
 impl Controller {
@@ -695,6 +1407,12 @@ impl Controller {
                 .context("Could not query network graph from database")?
                 .unwrap_or_else(|| NetworkGraph::new(network, KldLogger::global())),
         );
+        let rapid_gossip_sync = settings.rgs_source.as_ref().map(|_| {
+            Arc::new(RapidGossipSync::new(
+                network_graph.clone(),
+                KldLogger::global(),
+            ))
+        });
         let scorer = Arc::new(std::sync::RwLock::new(
             database
                 .fetch_scorer(
@@ -735,7 +1453,12 @@ impl Controller {
         user_config.channel_handshake_limits.max_funding_satoshis = u64::MAX;
         user_config
             .channel_handshake_limits
-            .force_announced_channel_preference = false;
+            .force_announced_channel_preference = settings.inbound_channel_require_announced;
+        user_config
+            .channel_handshake_config
+            .negotiate_anchors_zero_fee_htlc_tx = true;
+        // Required for zero conf and anchor channels, the event handler applies the inbound channel policy.
+        user_config.manually_accept_inbound_channels = true;
         user_config.accept_intercept_htlcs = true;
 
         let getinfo_resp = bitcoind_client.get_blockchain_info().await?;
@@ -791,9 +1514,11 @@ impl Controller {
                 lsps2_service_config: Some(LSPS2ServiceConfig {
                     promise_secret: key_generator.promise_seed(),
                 }),
-                advertise_service: false,
+                advertise_service: settings.lsps2_advertise_service || settings.lsps1_enable,
+            }),
+            Some(LiquidityClientConfig {
+                lsps2_client_config: Some(LSPS2ClientConfig::default()),
             }),
-            None,
         );
 
         let gossip_sync = Arc::new_cyclic(|gossip| {
@@ -822,7 +1547,14 @@ impl Controller {
             channel_manager.clone(),
             IgnoringMessageHandler {},
         ));
-        let kuutamo_handler = Arc::new(KuutamoCustomMessageHandler { liquidity_manager });
+        let (lsps1_sender, lsps1_receiver) = mpsc::unbounded_channel();
+        let kuutamo_handler = Arc::new(KuutamoCustomMessageHandler {
+            liquidity_manager,
+            lsps1_messages: Lsps1Messages::new(
+                settings.lsps1_enable.then_some(lsps1_sender),
+                supported_protocols(&settings),
+            ),
+        });
         let ephemeral_bytes: [u8; 32] = random();
         let lightning_msg_handler = MessageHandler {
             chan_handler: channel_manager.clone(),
@@ -842,7 +1574,12 @@ impl Controller {
         kuutamo_handler
             .liquidity_manager
             .set_process_msgs_callback(process_msgs_callback);
+        let pm_for_lsps1_messages = peer_manager.clone();
+        kuutamo_handler
+            .lsps1_messages
+            .set_process_msgs_callback(move || pm_for_lsps1_messages.process_events());
         let async_api_requests = Arc::new(AsyncAPIRequests::new());
+        let (event_sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
 
         let event_handler = EventHandler::new(
             channel_manager.clone(),
@@ -855,111 +1592,38 @@ impl Controller {
             async_api_requests.clone(),
             settings.clone(),
             kuutamo_handler.clone(),
+            event_sender.clone(),
         );
-        let channel_manager_cloned = channel_manager.clone();
 
-        tokio::spawn(async move {
-            loop {
-                let (result, msg_prefix) =
-                    match kuutamo_handler.liquidity_manager.next_event_async().await {
-                        LSPS2Service(LSPS2ServiceEvent::GetInfo {
-                            request_id,
-                            counterparty_node_id,
-                            token,
-                        }) => {
-                            debug!("Response LSPS2 GetInfo to {}", counterparty_node_id);
-                            let current_time = SystemTime::now()
-                                .duration_since(SystemTime::UNIX_EPOCH)
-                                .expect("Fail to fetch current time");
-                            if token == Some("kuutamo".to_string()) {
-                                // It will be happy to anyone to use this now in a small mount less than
-                                // 1_000_000, when using a token "kuutamo"
-                                (
-                                    kuutamo_handler
-                                        .liquidity_manager
-                                        .lsps2_service_handler()
-                                        .expect("lsps2 handler should be set")
-                                        .opening_fee_params_generated(
-                                            &counterparty_node_id,
-                                            request_id,
-                                            vec![RawOpeningFeeParams {
-                                                min_fee_msat: 0,
-                                                proportional: 0,
-                                                valid_until: DateTime::from_timestamp(
-                                                    current_time.as_secs() as i64 + 600,
-                                                    0,
-                                                )
-                                                .unwrap_or_default(),
-                                                min_lifetime: u32::MAX,
-                                                max_client_to_self_delay: 3600,
-                                                min_payment_size_msat: 0,
-                                                max_payment_size_msat: 1_000_001,
-                                            }],
-                                        ),
-                                    Some("Opening Generated Fee with kuutamo token"),
-                                )
-                            } else {
-                                // A bad token here
-                                (
-                                    kuutamo_handler
-                                        .liquidity_manager
-                                        .lsps2_service_handler()
-                                        .expect("lsps handler is not proper set")
-                                        .invalid_token_provided(&counterparty_node_id, request_id),
-                                    Some("Opening Generated Fee without kuutamo token"),
-                                )
-                            }
-                        }
-                        LSPS2Service(LSPS2ServiceEvent::BuyRequest {
-                            request_id,
-                            counterparty_node_id,
-                            opening_fee_params: _,
-                            payment_size_msat,
-                        }) => {
-                            debug!("Response LSPS2 GetInfo to {}", counterparty_node_id);
-                            if payment_size_msat <= Some(1_000_001) {
-                                let intercept_scid = channel_manager_cloned.get_intercept_scid();
-                                // Based on Bolt#11 we use 9 for cltv_expiry_delta
-                                let cltv_expiry_delta = 9;
-                                let client_trusts_lsp = true;
-                                // The JIT channel will use the user channel id after than 9223372036854775808
-                                let user_channel_id =
-                                    (random::<u64>() / 2 + 9223372036854775808) as u128;
-
-                                (
-                                    kuutamo_handler
-                                        .liquidity_manager
-                                        .lsps2_service_handler()
-                                        .expect("lsps2 handler should be set")
-                                        .invoice_parameters_generated(
-                                            &counterparty_node_id,
-                                            request_id,
-                                            intercept_scid,
-                                            cltv_expiry_delta,
-                                            client_trusts_lsp,
-                                            user_channel_id,
-                                        ),
-                                    Some("Generate Invoice Parameters"),
-                                )
-                            } else {
-                                // Swallow the request because the payment is higher than
-                                // expectation
-                                error!("Generate Invoice with too big payment");
-                                (Ok(()), None)
-                            }
-                        }
-                        _ => (Ok(()), None),
-                    };
-
-                if let Some(prefix) = msg_prefix {
-                    if let Err(e) = result {
-                        error!("{} Error: {:?}", prefix, e);
-                    } else {
-                        info!("{} Done", prefix);
-                    }
-                }
-            }
-        });
+        let lsps2_service = Lsps2Service::new(
+            settings.clone(),
+            database.clone(),
+            channel_manager.clone(),
+            kuutamo_handler.clone(),
+            async_api_requests.clone(),
+        );
+        tokio::spawn(lsps2_service.run());
+
+        if settings.lsps1_enable {
+            info!("Start selling LSPS1 channels");
+            let lsps1_service = Lsps1Service::new(
+                settings.clone(),
+                database.clone(),
+                channel_manager.clone(),
+                keys_manager.clone(),
+                peer_manager.clone(),
+                wallet.clone(),
+                kuutamo_handler.clone(),
+                async_api_requests.clone(),
+            );
+            tokio::spawn(lsps1_service.run(lsps1_receiver));
+        }
+        let lsp_client = Arc::new(LspClient::new(
+            kuutamo_handler.clone(),
+            peer_manager.clone(),
+            async_api_requests.clone(),
+            wallet.clone(),
+        ));
 
         if settings.probe_interval > 0 && settings.probe_amt_msat > 0 {
             info!(
@@ -1043,6 +1707,145 @@ impl Controller {
             }
         }
 
+        if settings.autofee_interval > 0 {
+            info!(
+                "Start adjusting channel fees between {} and {} ppm every {} secs",
+                settings.autofee_min_ppm, settings.autofee_max_ppm, settings.autofee_interval
+            );
+            let autofee = AutoFee::new(&settings);
+            let autofee_cm = channel_manager.clone();
+            let autofee_database = database.clone();
+            let interval = settings.autofee_interval;
+            let autofee_quit_signal = quit_signal.clone();
+            tokio::spawn(async move {
+                let mut interval_timer = tokio::time::interval(Duration::from_secs(interval));
+                interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    tokio::select! (
+                        _ = autofee_quit_signal.clone() => break,
+                        _ = interval_timer.tick() => {
+                            if let Err(e) = autofee.adjust_fees(&autofee_cm, &autofee_database).await {
+                                error!("Failed to adjust channel fees: {e}");
+                            }
+                        }
+                    );
+                }
+            });
+        }
+
+        if let (Some(source), Some(rapid_gossip_sync)) = (&settings.rgs_source, &rapid_gossip_sync)
+        {
+            info!("Start rapid gossip sync from {source}");
+            let snapshots = GossipSnapshots::new(
+                SnapshotSource::parse(source),
+                rapid_gossip_sync.clone(),
+                network_graph.clone(),
+            )?;
+            let interval = settings.rgs_interval.max(1);
+            let rgs_quit_signal = quit_signal.clone();
+            tokio::spawn(async move {
+                let mut interval_timer = tokio::time::interval(Duration::from_secs(interval));
+                interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    tokio::select! (
+                        _ = rgs_quit_signal.clone() => break,
+                        _ = interval_timer.tick() => match snapshots.sync().await {
+                            Ok(_) if !snapshots.is_refreshed() => break,
+                            Ok(_) => {}
+                            Err(e) => warn!("Rapid gossip sync failed, only P2P gossip updates the network graph: {e}"),
+                        }
+                    );
+                }
+            });
+        }
+
+        let sweeper = OutputSweeper::new(
+            database.clone(),
+            keys_manager.clone(),
+            bitcoind_client.clone(),
+            wallet.clone(),
+        );
+        let mut block_events = event_sender.subscribe();
+        let sweeper_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! (
+                    _ = sweeper_quit_signal.clone() => break,
+                    event = block_events.recv() => match event {
+                        Ok(StreamEvent::NewBlock { height, .. }) => {
+                            if let Err(e) = sweeper.on_new_block(height).await {
+                                error!("Failed to sweep spendable outputs: {e}");
+                            }
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                );
+            }
+        });
+
+        let hold_invoice_cm = channel_manager.clone();
+        let hold_invoice_database = database.clone();
+        let mut hold_invoice_events = event_sender.subscribe();
+        let hold_invoice_quit_signal = quit_signal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! (
+                    _ = hold_invoice_quit_signal.clone() => break,
+                    event = hold_invoice_events.recv() => match event {
+                        Ok(StreamEvent::NewBlock { height, .. }) => {
+                            if let Err(e) = Controller::cancel_expiring_hold_invoices(
+                                &hold_invoice_cm,
+                                &hold_invoice_database,
+                                height,
+                            )
+                            .await
+                            {
+                                error!("Failed to cancel expiring hold invoices: {e}");
+                            }
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                );
+            }
+        });
+
+        let backup_exporter = Arc::new(BackupExporter::new(
+            key_generator.backup_seed(),
+            &settings.data_dir,
+            channel_manager.clone(),
+            database.clone(),
+        ));
+        // In recovery mode the channel manager is empty, an export would overwrite the last good backup.
+        if settings.recover_from_backup.is_none() {
+            let exporter = backup_exporter.clone();
+            let mut channel_events = event_sender.subscribe();
+            let backup_quit_signal = quit_signal.clone();
+            tokio::spawn(async move {
+                if let Err(e) = exporter.write_file().await {
+                    error!("Failed to write channel backup: {e}");
+                }
+                loop {
+                    tokio::select! (
+                        _ = backup_quit_signal.clone() => break,
+                        event = channel_events.recv() => match event {
+                            Ok(StreamEvent::ChannelPending { .. })
+                            | Ok(StreamEvent::ChannelReady { .. })
+                            | Ok(StreamEvent::ChannelClosed { .. })
+                            | Err(broadcast::error::RecvError::Lagged(_)) => {
+                                if let Err(e) = exporter.write_file().await {
+                                    error!("Failed to write channel backup: {e}");
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        }
+                    );
+                }
+            });
+        }
+
         let bitcoind_client_clone = bitcoind_client.clone();
         let peer_manager_clone = peer_manager.clone();
         let wallet_clone = wallet.clone();
@@ -1052,6 +1855,11 @@ impl Controller {
         let chain_monitor_clone = chain_monitor.clone();
         let scorer_clone = scorer.clone();
         let settings_clone = settings.clone();
+        let event_sender_clone = event_sender.clone();
+        let backup_seed = key_generator.backup_seed();
+        // P2P gossip keeps updating the graph with rapid gossip sync too. With GossipSync::Rapid the background
+        // processor would not prune the graph before the first snapshot, which never comes if the server is down.
+        let background_gossip_sync = GossipSync::P2P(gossip_sync);
         tokio::spawn(async move {
             bitcoind_client_clone
                 .wait_for_blockchain_synchronisation()
@@ -1063,6 +1871,7 @@ impl Controller {
                 channel_manager_blockhash,
                 channel_manager_clone.clone(),
                 channel_monitors,
+                event_sender_clone,
             )
             .await
             {
@@ -1079,6 +1888,24 @@ impl Controller {
                 database_clone.clone(),
                 channel_manager_clone.clone(),
             );
+            if let Some(backup_path) = settings_clone.recover_from_backup.clone() {
+                let node_id = channel_manager_clone.get_our_node_id();
+                let peer_manager = peer_manager_clone.clone();
+                let database = database_clone.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = recover_from_backup(
+                        &backup_path,
+                        &backup_seed,
+                        node_id,
+                        peer_manager,
+                        database,
+                    )
+                    .await
+                    {
+                        error!("Failed to recover from channel backup: {e}");
+                    }
+                });
+            }
 
             // hourly broadcast our node to the network
             let peer_manager_clone2 = peer_manager_clone.clone();
@@ -1099,7 +1926,7 @@ impl Controller {
                     },
                     chain_monitor_clone,
                     channel_manager_clone,
-                    GossipSync::p2p(gossip_sync),
+                    background_gossip_sync,
                     peer_manager_clone,
                     KldLogger::global(),
                     Some(scorer_clone),
@@ -1141,9 +1968,43 @@ impl Controller {
             scorer,
             wallet,
             async_api_requests,
+            event_sender,
+            backup_exporter,
+            lsp_client,
         })
     }
 
+    /// Record a payment that LDK refused to send, stored beforehand for its events.
+    async fn fail_unsent_payment(&self, mut payment: Payment) -> Result<()> {
+        self.async_api_requests.payments.remove(&payment.id).await;
+        payment.failed(None);
+        self.database.persist_payment(&payment).await
+    }
+
+    /// Cancel held payments shortly before their claim deadline, LDK would fail them back at the deadline anyway.
+    async fn cancel_expiring_hold_invoices(
+        channel_manager: &ChannelManager,
+        database: &LdkDatabase,
+        height: u32,
+    ) -> Result<()> {
+        for (payment_hash, claim_deadline) in database.fetch_held_invoices().await? {
+            let Some(claim_deadline) = claim_deadline else {
+                continue;
+            };
+            if height + HOLD_INVOICE_CANCEL_BLOCKS >= claim_deadline {
+                info!(
+                    "Canceling hold invoice {} because its claim deadline {claim_deadline} is close",
+                    hex::encode(payment_hash.0)
+                );
+                channel_manager.fail_htlc_backwards(&payment_hash);
+                database
+                    .update_invoice_status(&payment_hash, InvoiceStatus::Canceled, None)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn sync_to_chain_tip(
         network: Network,
         bitcoind_client: Arc<BitcoindClient>,
@@ -1151,6 +2012,7 @@ impl Controller {
         channel_manager_blockhash: BlockHash,
         channel_manager: Arc<ChannelManager>,
         channel_monitors: Vec<(BlockHash, ChannelMonitor<InMemorySigner>)>,
+        event_sender: broadcast::Sender<StreamEvent>,
     ) -> BlockSourceResult<()> {
         info!(
             "Syncing ChannelManager and {} ChannelMonitors to chain tip",
@@ -1205,10 +2067,20 @@ impl Controller {
             let chain_listener = (chain_monitor, channel_manager);
             let mut spv_client =
                 SpvClient::new(chain_tip, chain_poller, &mut cache, &chain_listener);
+            let mut best_block = chain_listener.1.current_best_block();
             loop {
                 if let Err(e) = spv_client.poll_best_tip().await {
                     error!("{}", e.into_inner())
                 }
+                let current_best_block = chain_listener.1.current_best_block();
+                if current_best_block.block_hash() != best_block.block_hash() {
+                    // Nobody may be listening, which is fine.
+                    let _ = event_sender.send(StreamEvent::NewBlock {
+                        height: current_best_block.height(),
+                        hash: current_best_block.block_hash().to_string(),
+                    });
+                    best_block = current_best_block;
+                }
                 tokio::time::sleep(Duration::from_secs(1)).await;
             }
         });
@@ -1223,6 +2095,30 @@ impl Drop for Controller {
     }
 }
 
+// Monitor updates are persisted async so continue if MonitorUpdateInProgress is the only "error" we get.
+fn only_monitor_update_in_progress(e: &PaymentSendFailure) -> bool {
+    match e {
+        PaymentSendFailure::PartialFailure { results, .. } => results.iter().all(|result| {
+            result.is_ok()
+                || result
+                    .as_ref()
+                    .is_err_and(|f| matches!(f, APIError::MonitorUpdateInProgress))
+        }),
+        _ => false,
+    }
+}
+
+// Every liquidity within the scorer's estimated range is taken to be equally likely.
+fn liquidity_success_probability(amount: u64, min_liquidity: u64, max_liquidity: u64) -> f64 {
+    if amount <= min_liquidity {
+        1.0
+    } else if amount >= max_liquidity {
+        0.0
+    } else {
+        (max_liquidity - amount) as f64 / (max_liquidity - min_liquidity) as f64
+    }
+}
+
 async fn send_probe(
     channel_manager: &ChannelManager,
     recipient: &PublicKey,
@@ -3,45 +3,54 @@ use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, Context, Result};
 
-use bitcoin::blockdata::locktime::absolute::LockTime;
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::secp256k1::PublicKey;
 
-use crate::bitcoind::bitcoind_interface::BitcoindInterface;
+use crate::api::payloads::StreamEvent;
+use crate::database::channel_rejection::ChannelRejection;
 use crate::database::forward::Forward;
-use crate::database::payment::Payment;
-use crate::database::{LdkDatabase, WalletDatabase};
+use crate::database::invoice::InvoiceStatus;
+use crate::database::lsps2::{JitChannelStatus, JIT_USER_CHANNEL_ID_START};
+use crate::database::payment::{Payment, PaymentAttempt};
+use crate::database::{microsecond_timestamp, LdkDatabase, WalletDatabase};
 use crate::ldk::peer_manager::KuutamoPeerManger;
 use crate::log_error;
+use crate::logger::KldLogger;
 use crate::settings::Settings;
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::events::bump_transaction::{
+    self, BumpTransactionEvent, BumpTransactionEventHandler,
+};
 use lightning::events::{Event, PathFailure, PaymentPurpose};
-use lightning::ln::ChannelId;
+use lightning::ln::channelmanager::PaymentId;
+use lightning::ln::{ChannelId, PaymentHash, PaymentPreimage};
 use lightning::routing::gossip::NodeId;
 use lightning::sign::{KeysManager, SpendableOutputDescriptor};
 use log::{error, info, trace, warn};
 use rand::{thread_rng, Rng};
 use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 
 use crate::bitcoind::BitcoindClient;
 use crate::ldk::{htlc_destination_to_string, ldk_error};
 use crate::wallet::{Wallet, WalletInterface};
 
+use super::channel_policy::{InboundChannelDecision, InboundChannelPolicy};
 use super::controller::AsyncAPIRequests;
 use super::peer_manager::PeerManager;
-use super::{ChannelManager, KuutamoCustomMessageHandler, NetworkGraph};
+use super::{BumpTxEventHandler, ChannelManager, KuutamoCustomMessageHandler, NetworkGraph};
 
 pub(crate) struct EventHandler {
     channel_manager: Arc<ChannelManager>,
-    bitcoind_client: Arc<BitcoindClient>,
-    keys_manager: Arc<KeysManager>,
     network_graph: Arc<NetworkGraph>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
     ldk_database: Arc<LdkDatabase>,
     peer_manager: Arc<PeerManager>,
     async_api_requests: Arc<AsyncAPIRequests>,
     settings: Arc<Settings>,
+    inbound_channel_policy: InboundChannelPolicy,
+    bump_tx_event_handler: BumpTxEventHandler,
     runtime_handle: Handle,
     kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+    event_sender: broadcast::Sender<StreamEvent>,
 }
 
 impl EventHandler {
@@ -57,19 +66,30 @@ impl EventHandler {
         async_api_requests: Arc<AsyncAPIRequests>,
         settings: Arc<Settings>,
         kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+        event_sender: broadcast::Sender<StreamEvent>,
     ) -> EventHandler {
-        EventHandler {
-            channel_manager,
+        let bump_tx_event_handler = BumpTransactionEventHandler::new(
             bitcoind_client,
+            Arc::new(bump_transaction::Wallet::new(
+                wallet.clone(),
+                KldLogger::global(),
+            )),
             keys_manager,
+            KldLogger::global(),
+        );
+        EventHandler {
+            channel_manager,
             network_graph,
             wallet,
             ldk_database: database,
             peer_manager,
             async_api_requests,
+            inbound_channel_policy: InboundChannelPolicy::new(&settings),
+            bump_tx_event_handler,
             settings,
             runtime_handle: Handle::current(),
             kuutamo_handler,
+            event_sender,
         }
     }
 }
@@ -78,61 +98,78 @@ impl EventHandler {
     pub async fn handle_event_async(&self, event: lightning::events::Event) -> Result<()> {
         match event {
             Event::FundingGenerationReady {
-                temporary_channel_id,
-                counterparty_node_id,
                 channel_value_satoshis,
                 output_script,
                 user_channel_id,
+                ..
             } => {
-                let (fee_rate, respond) = self
+                let batch = self
                     .async_api_requests
                     .funding_transactions
-                    .get(&(user_channel_id as u64))
+                    .peek(&(user_channel_id as u64))
                     .await
                     .context(format!(
                         "Can't find funding transaction for user_channel_id {user_channel_id}"
                     ))?;
+                let Some(outputs) = batch.add_output(
+                    user_channel_id as u64,
+                    output_script,
+                    channel_value_satoshis,
+                ) else {
+                    info!("EVENT: Channel with user channel id {user_channel_id} is waiting for the rest of its batch");
+                    return Ok(());
+                };
 
-                let funding_tx =
-                    match self
-                        .wallet
-                        .fund_tx(&output_script, &channel_value_satoshis, fee_rate)
-                    {
-                        Ok(tx) => tx,
-                        Err(e) => {
-                            respond(Err(anyhow!("Failed funding transaction: {e}")));
-                            return Err(anyhow!("Failed funding transaction: {e}"));
+                let funding_tx = match self.wallet.fund_tx(&outputs, &batch.funding) {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        for (id, _, _) in &batch.channels {
+                            self.async_api_requests
+                                .funding_transactions
+                                .respond(id, Err(anyhow!("Failed funding transaction: {e}")))
+                                .await;
                         }
-                    };
+                        return Err(anyhow!("Failed funding transaction: {e}"));
+                    }
+                };
 
-                // Give the funding transaction back to LDK for opening the channel.
+                // Give the funding transaction back to LDK for opening the channels.
+                let temporary_channels: Vec<(&ChannelId, &PublicKey)> = batch
+                    .channels
+                    .iter()
+                    .map(|(_, channel_id, node_id)| (channel_id, node_id))
+                    .collect();
                 if let Err(e) = self
                     .channel_manager
-                    .funding_transaction_generated(
-                        &temporary_channel_id,
-                        &counterparty_node_id,
-                        funding_tx.clone(),
-                    )
+                    .batch_funding_transaction_generated(&temporary_channels, funding_tx.clone())
                     .map_err(ldk_error)
                 {
-                    respond(Err(anyhow!("Failed opening channel: {e}")));
+                    for (id, _, _) in &batch.channels {
+                        self.async_api_requests
+                            .funding_transactions
+                            .respond(id, Err(anyhow!("Failed opening channel: {e}")))
+                            .await;
+                    }
                     bail!(e);
                 }
-                info!("EVENT: Channel with user channel id {user_channel_id} has been funded");
-                if let Err(e) = self
-                    .ldk_database
-                    .update_initializing_channel(
-                        &temporary_channel_id,
-                        None,
-                        Some(format!(
-                            "Channel with user channel id {user_channel_id} has been funded"
-                        )),
-                    )
-                    .await
-                {
-                    warn!("Fail to update initial channel funded status: {e}");
+                for (id, temporary_channel_id, _) in &batch.channels {
+                    info!("EVENT: Channel with user channel id {id} has been funded");
+                    if let Err(e) = self
+                        .ldk_database
+                        .update_initializing_channel(
+                            temporary_channel_id,
+                            None,
+                            Some(format!("Channel with user channel id {id} has been funded")),
+                        )
+                        .await
+                    {
+                        warn!("Fail to update initial channel funded status: {e}");
+                    }
+                    self.async_api_requests
+                        .funding_transactions
+                        .respond(id, Ok(funding_tx.clone()))
+                        .await;
                 }
-                respond(Ok(funding_tx));
             }
             Event::ChannelPending {
                 channel_id,
@@ -168,6 +205,11 @@ impl EventHandler {
                         .create_channel(&channel_id, true, &counterparty_node_id)
                         .await?;
                 }
+                self.publish(StreamEvent::ChannelPending {
+                    channel_id: hex::encode(channel_id.0),
+                    counterparty_node_id: counterparty_node_id.to_string(),
+                    funding_txo: funding_txo.to_string(),
+                });
             }
             Event::ChannelReady {
                 channel_id,
@@ -176,7 +218,7 @@ impl EventHandler {
                 channel_type: _,
             } => {
                 // JIT Channels
-                if user_channel_id > 9223372036854775808 {
+                if user_channel_id >= JIT_USER_CHANNEL_ID_START {
                     if let Err(e) = self
                         .kuutamo_handler
                         .liquidity_manager
@@ -186,9 +228,14 @@ impl EventHandler {
                     {
                         error!("JIT Channel ready fail: {e:?}");
                     }
+                    self.update_jit_channel(
+                        user_channel_id,
+                        Some(channel_id),
+                        JitChannelStatus::Ready,
+                    )
+                    .await;
                 }
 
-                // LSPS2ServiceHandler::channel_ready
                 info!(
                     "EVENT: Channel {} - {user_channel_id} with counterparty {counterparty_node_id} is ready to use.",
                     hex::encode(channel_id.0),
@@ -208,6 +255,10 @@ impl EventHandler {
                         )
                         .await?;
                 }
+                self.publish(StreamEvent::ChannelReady {
+                    channel_id: hex::encode(channel_id.0),
+                    counterparty_node_id: counterparty_node_id.to_string(),
+                });
                 info!("Broadcasting node announcement message");
                 self.peer_manager
                     .broadcast_node_announcement_from_settings(self.settings.clone());
@@ -219,16 +270,40 @@ impl EventHandler {
                 ..
             } => {
                 info!("EVENT: Channel {}: {reason}.", hex::encode(channel_id.0));
-                self.async_api_requests
+                if user_channel_id >= JIT_USER_CHANNEL_ID_START {
+                    self.update_jit_channel(user_channel_id, None, JitChannelStatus::Closed)
+                        .await;
+                }
+                let user_channel_id = user_channel_id as u64;
+                if let Some(batch) = self
+                    .async_api_requests
                     .funding_transactions
-                    .respond(
-                        &(user_channel_id as u64),
-                        Err(anyhow!("Channel closed due to {reason}")),
-                    )
-                    .await;
+                    .peek(&user_channel_id)
+                    .await
+                {
+                    // An unfunded batch can't be funded without this channel, so drop the rest of it too.
+                    for (id, temporary_channel_id, node_id) in &batch.channels {
+                        self.async_api_requests
+                            .funding_transactions
+                            .respond(id, Err(anyhow!("Channel closed due to {reason}")))
+                            .await;
+                        if *id != user_channel_id {
+                            if let Err(e) = self
+                                .channel_manager
+                                .force_close_without_broadcasting_txn(temporary_channel_id, node_id)
+                            {
+                                warn!("Failed to drop channel of failed batch: {e:?}");
+                            }
+                        }
+                    }
+                }
                 self.ldk_database
                     .close_channel(&channel_id, format!("{reason}"))
                     .await?;
+                self.publish(StreamEvent::ChannelClosed {
+                    channel_id: hex::encode(channel_id.0),
+                    reason: reason.to_string(),
+                });
             }
             Event::DiscardFunding {
                 channel_id,
@@ -253,10 +328,115 @@ impl EventHandler {
                     error!("Fail to close channel which funding is discarded: {e}");
                 }
             }
-            Event::OpenChannelRequest { .. } => {
-                unreachable!(
-                    "This event will not fire as we do not manually accept inbound channels."
-                )
+            Event::OpenChannelRequest {
+                temporary_channel_id,
+                counterparty_node_id,
+                funding_satoshis,
+                push_msat,
+                channel_type,
+            } => {
+                info!(
+                    "EVENT: Inbound channel {} of {funding_satoshis} sats requested by {counterparty_node_id}",
+                    hex::encode(temporary_channel_id.0)
+                );
+                let pending_channels: Vec<_> = self
+                    .channel_manager
+                    .list_channels_with_counterparty(&counterparty_node_id)
+                    .into_iter()
+                    .filter(|c| !c.is_channel_ready)
+                    .collect();
+                let pending_zero_conf_channels = pending_channels
+                    .iter()
+                    .filter(|c| c.confirmations_required == Some(0))
+                    .count();
+                let user_channel_id = (thread_rng().gen::<u64>() / 2) as u128; // To fit into the database INT
+                                                                               // The LSP opens a channel to forward the payment of each open JIT invoice.
+                let jit_channel = self
+                    .ldk_database
+                    .open_jit_invoice_count(&counterparty_node_id)
+                    .await?
+                    > pending_zero_conf_channels as i64;
+
+                // Fee bumping anchor channels needs confirmed on-chain funds.
+                let anchor_rejection = if channel_type.requires_anchors_zero_fee_htlc_tx() {
+                    match self.wallet.balance() {
+                        Ok(balance) if balance.confirmed > 0 => None,
+                        Ok(_) => Some(
+                            "No confirmed on-chain funds to fee bump an anchor channel".to_string(),
+                        ),
+                        Err(e) => Some(format!(
+                            "Failed to check the on-chain funds to fee bump an anchor channel: {e}"
+                        )),
+                    }
+                } else {
+                    None
+                };
+                let decision = if let Some(reason) = anchor_rejection {
+                    InboundChannelDecision::Reject(reason)
+                } else {
+                    match self.inbound_channel_policy.evaluate(
+                        &counterparty_node_id,
+                        funding_satoshis,
+                        channel_type.requires_zero_conf() && !jit_channel,
+                        pending_channels.len(),
+                    ) {
+                        // The LSP forwards the payment of our JIT invoice without waiting for confirmations.
+                        InboundChannelDecision::Accept if jit_channel => {
+                            InboundChannelDecision::AcceptZeroConf
+                        }
+                        decision => decision,
+                    }
+                };
+                let result = match decision {
+                    InboundChannelDecision::Accept => self
+                        .channel_manager
+                        .accept_inbound_channel(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                            user_channel_id,
+                        )
+                        .map_err(|e| ldk_error(e).to_string()),
+                    InboundChannelDecision::AcceptZeroConf => self
+                        .channel_manager
+                        .accept_inbound_channel_from_trusted_peer_0conf(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                            user_channel_id,
+                        )
+                        .map_err(|e| ldk_error(e).to_string()),
+                    InboundChannelDecision::Reject(reason) => {
+                        if let Err(e) = self.channel_manager.force_close_without_broadcasting_txn(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                        ) {
+                            warn!("Failed to reject inbound channel: {}", ldk_error(e));
+                        }
+                        Err(reason)
+                    }
+                };
+                if result.is_ok() && jit_channel {
+                    self.accept_opening_fee(&temporary_channel_id, &counterparty_node_id);
+                }
+                if let Err(reason) = result {
+                    info!(
+                        "Rejected inbound channel {}: {reason}",
+                        hex::encode(temporary_channel_id.0)
+                    );
+                    let rejection = ChannelRejection::new(
+                        temporary_channel_id,
+                        counterparty_node_id,
+                        funding_satoshis,
+                        push_msat,
+                        reason,
+                    );
+                    if let Err(e) = self
+                        .ldk_database
+                        .persist_channel_rejection(&rejection)
+                        .await
+                    {
+                        log_error(&e);
+                    }
+                }
             }
             Event::PaymentClaimable {
                 payment_hash,
@@ -267,6 +447,7 @@ impl EventHandler {
                 via_user_channel_id: _,
                 onion_fields: _,
                 claim_deadline,
+                counterparty_skimmed_fee_msat,
                 ..
             } => {
                 info!(
@@ -287,12 +468,28 @@ impl EventHandler {
                         String::new()
                     }
                 );
+                if counterparty_skimmed_fee_msat > 0
+                    && !self
+                        .skimmed_fee_agreed(
+                            &payment_hash,
+                            amount_msat,
+                            counterparty_skimmed_fee_msat,
+                        )
+                        .await?
+                {
+                    self.channel_manager.fail_htlc_backwards(&payment_hash);
+                    return Ok(());
+                }
                 match purpose {
                     PaymentPurpose::InvoicePayment {
                         payment_preimage, ..
                     } => {
                         if let Some(payment_preimage) = payment_preimage {
-                            self.channel_manager.claim_funds(payment_preimage);
+                            self.claim_invoice_payment(payment_hash, payment_preimage)
+                                .await?;
+                        } else {
+                            // Only hold invoices are created without the preimage.
+                            self.hold_payment(payment_hash, claim_deadline).await?;
                         }
                     }
                     PaymentPurpose::SpontaneousPayment(preimage) => {
@@ -330,6 +527,14 @@ impl EventHandler {
                     .persist_payment(&payment)
                     .await
                     .context("Failed to persist payment")?;
+                self.ldk_database
+                    .persist_invoice_payment(&payment_hash, amount_msat)
+                    .await
+                    .context("Failed to update invoice")?;
+                self.publish(StreamEvent::PaymentClaimed {
+                    payment_hash: hex::encode(payment_hash.0),
+                    amount_msat,
+                });
             }
             Event::PaymentSent {
                 payment_id,
@@ -351,21 +556,19 @@ impl EventHandler {
                         "".to_string()
                     },
                 );
+                self.publish(StreamEvent::PaymentSent {
+                    payment_id: payment_id.map(|id| hex::encode(id.0)),
+                    payment_hash: hex::encode(payment_hash.0),
+                    fee_msat: fee_paid_msat,
+                });
                 let payment_id = payment_id.context(format!(
                     "Failed to update payment with hash {}",
                     hex::encode(payment_hash.0)
                 ))?;
-                let (mut payment, respond) = self
-                    .async_api_requests
-                    .payments
-                    .get(&payment_id)
-                    .await
-                    .context(format!(
-                        "Can't find payment for {}",
-                        hex::encode(payment_id.0)
-                    ))?;
-                payment.succeeded(payment_hash, payment_preimage, fee_paid_msat);
-                respond(Ok(payment));
+                self.update_payment(payment_id, |payment| {
+                    payment.succeeded(payment_hash, payment_preimage, fee_paid_msat)
+                })
+                .await?;
             }
             Event::PaymentPathSuccessful {
                 payment_id,
@@ -380,6 +583,7 @@ impl EventHandler {
                         .map(|h| format!(" and hash {}", hex::encode(h.0)))
                         .unwrap_or_default()
                 );
+                self.persist_payment_attempt(PaymentAttempt::succeeded(payment_id, path));
             }
             Event::PaymentPathFailed {
                 payment_id,
@@ -390,6 +594,15 @@ impl EventHandler {
                 short_channel_id,
                 ..
             } => {
+                if let Some(payment_id) = payment_id {
+                    self.persist_payment_attempt(PaymentAttempt::failed(
+                        payment_id,
+                        path.clone(),
+                        failure.clone(),
+                        short_channel_id,
+                        payment_failed_permanently,
+                    ));
+                }
                 match failure {
                     PathFailure::InitialSend { err } => warn!("{}", ldk_error(err)),
                     PathFailure::OnPath { network_update } => {
@@ -428,17 +641,13 @@ impl EventHandler {
                         .map(|r| format!(" for reason {r:?}"))
                         .unwrap_or_default()
                 );
-                let (mut payment, respond) = self
-                    .async_api_requests
-                    .payments
-                    .get(&payment_id)
-                    .await
-                    .context(format!(
-                        "Can't find payment for {}",
-                        hex::encode(payment_id.0)
-                    ))?;
-                payment.failed(reason);
-                respond(Ok(payment));
+                self.publish(StreamEvent::PaymentFailed {
+                    payment_id: hex::encode(payment_id.0),
+                    payment_hash: hex::encode(payment_hash.0),
+                    reason: reason.map(|r| format!("{r:?}")),
+                });
+                self.update_payment(payment_id, |payment| payment.failed(reason))
+                    .await?;
             }
             Event::PaymentForwarded {
                 prev_channel_id,
@@ -534,6 +743,12 @@ impl EventHandler {
                 info!(
                     "EVENT: Forwarded payment{id}{from_prev_str}{to_next_str} {amount_str},{fee_str} {from_onchain_str}",
                 );
+                self.publish(StreamEvent::PaymentForwarded {
+                    inbound_channel_id: prev_channel_id.map(|id| hex::encode(id.0)),
+                    outbound_channel_id: next_channel_id.map(|id| hex::encode(id.0)),
+                    amount_msat: outbound_amount_forwarded_msat,
+                    fee_msat: fee_earned_msat,
+                });
             }
             Event::ProbeSuccessful { .. } => {}
             Event::ProbeFailed { .. } => {}
@@ -558,6 +773,10 @@ impl EventHandler {
                     hex::encode(prev_channel_id.0),
                     htlc_destination_to_string(&failed_next_destination)
                 );
+                self.publish(StreamEvent::HtlcHandlingFailed {
+                    inbound_channel_id: hex::encode(prev_channel_id.0),
+                    destination: htlc_destination_to_string(&failed_next_destination),
+                });
             }
             Event::PendingHTLCsForwardable { time_forwardable } => {
                 let forwarding_channel_manager = self.channel_manager.clone();
@@ -577,32 +796,11 @@ impl EventHandler {
                     self.persist_spendable_output(spendable_output, channel_id.as_ref(), false)
                         .await;
                 }
-                let destination_address = self.wallet.new_internal_address()?;
-                let tx_feerate = self
-                    .bitcoind_client
-                    .get_est_sat_per_1000_weight(ConfirmationTarget::OnChainSweep);
-
-                let best_block_height = self.bitcoind_client.block_height().await?;
-                let spending_tx = self
-                    .keys_manager
-                    .spend_spendable_outputs(
-                        &outputs.iter().collect::<Vec<_>>()[..],
-                        Vec::new(),
-                        destination_address.script_pubkey(),
-                        tx_feerate,
-                        Some(LockTime::from_height(best_block_height as u32)?),
-                        &Secp256k1::new(),
-                    )
-                    .map_err(|()| anyhow!("Failed to build spending transaction"))?;
-                info!(
-                    "Sending spendable output to {}",
-                    destination_address.address
-                );
-                self.bitcoind_client.broadcast_transactions(&[&spending_tx]);
-                for spendable_output in outputs.iter() {
-                    self.persist_spendable_output(spendable_output, channel_id.as_ref(), true)
-                        .await;
-                }
+                self.publish(StreamEvent::SpendableOutputs {
+                    channel_id: channel_id.map(|id| hex::encode(id.0)),
+                    outputs: outputs.len(),
+                });
+                // The outputs are swept on the next block by the OutputSweeper.
             }
             Event::HTLCIntercepted {
                 intercept_id,
@@ -627,18 +825,59 @@ impl EventHandler {
                 }
             }
             Event::InvoiceRequestFailed { payment_id } => {
-                // XXX Handle it
-                warn!("Invoice request failed, payment id: {payment_id:}");
+                info!(
+                    "EVENT: Failed to request an invoice for payment with ID {}",
+                    hex::encode(payment_id.0)
+                );
+                self.update_payment(payment_id, |payment| payment.failed(None))
+                    .await?;
             }
-            Event::BumpTransaction(_) => unreachable!(),
-            Event::ConnectionNeeded { node_id, .. } => {
-                // XXX Handle it
-                warn!("Need to connect to node {node_id:} for onion message");
+            Event::BumpTransaction(event) => {
+                match &event {
+                    BumpTransactionEvent::ChannelClose {
+                        commitment_tx,
+                        package_target_feerate_sat_per_1000_weight,
+                        ..
+                    } => info!(
+                        "EVENT: Bumping commitment transaction {} to {package_target_feerate_sat_per_1000_weight} sat/kw",
+                        commitment_tx.txid()
+                    ),
+                    BumpTransactionEvent::HTLCResolution {
+                        target_feerate_sat_per_1000_weight,
+                        ..
+                    } => info!(
+                        "EVENT: Bumping HTLC resolution to {target_feerate_sat_per_1000_weight} sat/kw"
+                    ),
+                }
+                self.bump_tx_event_handler.handle_event(&event);
+            }
+            Event::ConnectionNeeded { node_id, addresses } => {
+                info!("EVENT: Connection needed to node {node_id} for onion message");
+                let peer_manager = self.peer_manager.clone();
+                let database = self.ldk_database.clone();
+                self.runtime_handle.spawn(async move {
+                    for address in addresses {
+                        match peer_manager
+                            .connect_peer(database.clone(), node_id, address.into())
+                            .await
+                        {
+                            Ok(()) => return,
+                            Err(e) => info!("Could not connect to {node_id}. {e}"),
+                        }
+                    }
+                    warn!("Could not connect to {node_id} for onion message");
+                });
             }
         };
         Ok(())
     }
 
+    /// Forward an event to any websocket subscribers.
+    fn publish(&self, event: StreamEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.event_sender.send(event);
+    }
+
     async fn persist_spendable_output(
         &self,
         spendable_output: &SpendableOutputDescriptor,
@@ -654,6 +893,183 @@ impl EventHandler {
         }
     }
 
+    /// Account a JIT channel becoming ready or closing. Channels that close before they are ready failed.
+    async fn update_jit_channel(
+        &self,
+        user_channel_id: u128,
+        channel_id: Option<ChannelId>,
+        status: JitChannelStatus,
+    ) {
+        let result = match self.ldk_database.fetch_jit_channel(user_channel_id).await {
+            Ok(Some(mut jit_channel)) => {
+                jit_channel.status = match (jit_channel.status, status) {
+                    (JitChannelStatus::Closed, _) => return,
+                    (JitChannelStatus::Ready, JitChannelStatus::Closed) => JitChannelStatus::Closed,
+                    (JitChannelStatus::Ready, _) => return,
+                    // A channel that closes before it is ready failed to open.
+                    (_, JitChannelStatus::Closed) => JitChannelStatus::Failed,
+                    (_, status) => status,
+                };
+                jit_channel.channel_id = channel_id.or(jit_channel.channel_id);
+                jit_channel.updated_at = microsecond_timestamp();
+                self.ldk_database.persist_jit_channel(&jit_channel).await
+            }
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log_error(&e)
+        }
+    }
+
+    /// Apply the outcome of an outbound payment. The payment is persisted here rather than by the
+    /// caller so that the result is kept even if nobody is waiting for it.
+    async fn update_payment(
+        &self,
+        payment_id: PaymentId,
+        update: impl FnOnce(&mut Payment),
+    ) -> Result<()> {
+        let (mut payment, respond) = match self.async_api_requests.payments.get(&payment_id).await {
+            Some((payment, respond)) => (payment, Some(respond)),
+            None => (
+                self.ldk_database
+                    .fetch_payment(&payment_id)
+                    .await?
+                    .context(format!(
+                        "Can't find payment for {}",
+                        hex::encode(payment_id.0)
+                    ))?,
+                None,
+            ),
+        };
+        update(&mut payment);
+        let result = self
+            .ldk_database
+            .persist_payment(&payment)
+            .await
+            .map(|_| payment);
+        match respond {
+            Some(respond) => respond(result),
+            None => {
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Claim a payment to one of our invoices, or fail it back if the invoice was canceled.
+    async fn claim_invoice_payment(
+        &self,
+        payment_hash: PaymentHash,
+        payment_preimage: PaymentPreimage,
+    ) -> Result<()> {
+        // LDK still knows the preimage of a canceled invoice, so late payments have to be failed here.
+        if let Some(InvoiceStatus::Canceled) = self
+            .ldk_database
+            .fetch_invoice_status(&payment_hash)
+            .await?
+        {
+            warn!(
+                "Failing payment with hash {} back, the invoice is canceled",
+                hex::encode(payment_hash.0)
+            );
+            self.channel_manager.fail_htlc_backwards(&payment_hash);
+        } else {
+            self.channel_manager.claim_funds(payment_preimage);
+        }
+        Ok(())
+    }
+
+    /// Keep a payment to a hold invoice pending until it is settled or canceled through the API.
+    async fn hold_payment(
+        &self,
+        payment_hash: PaymentHash,
+        claim_deadline: Option<u32>,
+    ) -> Result<()> {
+        match self
+            .ldk_database
+            .fetch_invoice_status(&payment_hash)
+            .await?
+        {
+            Some(InvoiceStatus::Open) | Some(InvoiceStatus::Held) => {
+                info!(
+                    "Holding payment with hash {} until it is settled or canceled",
+                    hex::encode(payment_hash.0)
+                );
+                self.ldk_database
+                    .update_invoice_status(&payment_hash, InvoiceStatus::Held, claim_deadline)
+                    .await
+            }
+            status => {
+                warn!(
+                    "Failing payment with hash {} back, the invoice is {}",
+                    hex::encode(payment_hash.0),
+                    status.map_or("unknown".to_string(), |s| s.to_string())
+                );
+                self.channel_manager.fail_htlc_backwards(&payment_hash);
+                Ok(())
+            }
+        }
+    }
+
+    /// The LSPs we bought JIT channels from skim their opening fee from the first payment through the channel,
+    /// so the channel accepts HTLCs below the invoice amount from the moment it is accepted.
+    fn accept_opening_fee(&self, channel_id: &ChannelId, lsp: &PublicKey) {
+        let Some(mut config) = self
+            .channel_manager
+            .list_channels_with_counterparty(lsp)
+            .into_iter()
+            .find(|c| c.channel_id == *channel_id)
+            .and_then(|c| c.config)
+        else {
+            return;
+        };
+        config.accept_underpaying_htlcs = true;
+        if let Err(e) = self
+            .channel_manager
+            .update_channel_config(lsp, &[*channel_id], &config)
+        {
+            error!(
+                "Failed to accept underpaying HTLCs on channel {}: {e:?}",
+                hex::encode(channel_id.0)
+            );
+        }
+    }
+
+    /// Whether the fee skimmed from a payment is at most the opening fee of its JIT invoice.
+    async fn skimmed_fee_agreed(
+        &self,
+        payment_hash: &PaymentHash,
+        amount_msat: u64,
+        skimmed_fee_msat: u64,
+    ) -> Result<bool> {
+        let Some(jit_invoice) = self.ldk_database.fetch_jit_invoice(payment_hash).await? else {
+            warn!(
+                "Failing payment with hash {} back, {skimmed_fee_msat} msat were skimmed without a JIT invoice",
+                hex::encode(payment_hash.0)
+            );
+            return Ok(false);
+        };
+        let opening_fee_msat = jit_invoice.opening_fee_msat(amount_msat + skimmed_fee_msat);
+        if skimmed_fee_msat > opening_fee_msat {
+            warn!(
+                "Failing payment with hash {} back, the LSP skimmed {skimmed_fee_msat} msat instead of {opening_fee_msat} msat",
+                hex::encode(payment_hash.0)
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn persist_payment_attempt(&self, attempt: PaymentAttempt) {
+        let database = self.ldk_database.clone();
+        self.runtime_handle.spawn(async move {
+            if let Err(e) = database.persist_payment_attempt(&attempt).await {
+                log_error(&e)
+            }
+        });
+    }
+
     fn persist_forward(&self, forward: Forward) {
         let database = self.ldk_database.clone();
         self.runtime_handle.spawn(async move {
@@ -1,24 +1,39 @@
 use anyhow::Result;
 use lightning::{
-    ln::{channelmanager::ChannelDetails, ChannelId},
-    routing::gossip::{ChannelInfo, NodeId, NodeInfo},
+    ln::{
+        channelmanager::{ChannelDetails, PaymentId},
+        ChannelId, PaymentHash, PaymentPreimage,
+    },
+    routing::{
+        gossip::{ChannelInfo, NodeId, NodeInfo},
+        router::Path,
+    },
     util::{config::UserConfig, indexed_map::IndexedMap},
 };
 
 use crate::{
     database::{
-        forward::{Forward, ForwardStatus, TotalForwards},
+        fee_history::ChannelFeeUpdate,
+        forward::{Forward, ForwardStatus, ForwardSummary, TimeBucket, TotalForwards},
         invoice::Invoice,
-        payment::{Payment, PaymentDirection},
-        ChannelRecord,
+        lsps1::Lsps1Order,
+        lsps2::{JitChannel, Lsps2Token},
+        offer::Offer,
+        payment::{Payment, PaymentAttempt, PaymentDirection},
+        rebalance::Rebalance,
+        sweep::OutputSweep,
+        ChannelRecord, Pagination,
     },
     MillisatAmount,
 };
 
-use crate::api::payloads::FeeRate;
+use super::Lsps1Options;
+use crate::api::payloads::{FeeRate, StreamEvent};
 use crate::api::SocketAddress;
 use async_trait::async_trait;
-use bitcoin::{secp256k1::PublicKey, Network, Transaction, Txid};
+use bitcoin::{secp256k1::PublicKey, Network, OutPoint, ScriptBuf, Transaction, Txid};
+use lightning_liquidity::lsps2::msgs::OpeningFeeParams;
+use time::OffsetDateTime;
 
 #[async_trait]
 pub trait LightningInterface: Send + Sync {
@@ -74,20 +89,21 @@ pub trait LightningInterface: Send + Sync {
 
     async fn disconnect_peer(&self, public_key: PublicKey) -> Result<()>;
 
-    async fn open_channel(
+    /// Open a channel to every target, all funded by the same transaction.
+    async fn open_channels(
         &self,
-        their_network_key: PublicKey,
-        channel_value_satoshis: u64,
-        push_msat: Option<u64>,
-        fee_rate: Option<FeeRate>,
-        override_config: Option<UserConfig>,
+        targets: Vec<ChannelTarget>,
+        funding: FundingOptions,
     ) -> Result<OpenChannelResult>;
 
+    /// Close a channel cooperatively, optionally paying our funds to the given script instead of the wallet.
     async fn close_channel(
         &self,
         channel_id: &ChannelId,
         counterparty_node_id: &PublicKey,
         fee_rate: Option<u32>,
+        max_initial_fee_rate: Option<u32>,
+        shutdown_script: Option<ScriptBuf>,
     ) -> Result<()>;
 
     async fn force_close_channel(
@@ -97,19 +113,43 @@ pub trait LightningInterface: Send + Sync {
         with_broadcast: bool,
     ) -> Result<()>;
 
+    /// Move liquidity from one of our channels to another by paying ourselves in a circle.
+    async fn rebalance(
+        &self,
+        outbound_channel_id: &ChannelId,
+        inbound_channel_id: &ChannelId,
+        amount: MillisatAmount,
+        max_fee_ppm: Option<u32>,
+    ) -> Result<Rebalance>;
+
     fn get_node(&self, node_id: &NodeId) -> Option<NodeInfo>;
 
-    fn nodes(&self) -> IndexedMap<NodeId, NodeInfo>;
+    /// Announced nodes of the network graph in node ID order, starting after the given node.
+    fn nodes(&self, after: Option<NodeId>, limit: Option<usize>) -> Vec<(NodeId, NodeInfo)>;
 
     fn get_channel(&self, channel_id: u64) -> Option<ChannelInfo>;
 
     fn channels(&self) -> IndexedMap<u64, ChannelInfo>;
 
+    /// The route the router would take for a payment, without sending it.
+    fn find_route(&self, query: RouteQuery) -> Result<Vec<PathEstimate>>;
+
     fn user_config(&self) -> UserConfig;
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment>;
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        options: PaymentOptions,
+    ) -> Result<Payment>;
 
-    async fn keysend_payment(&self, payee: NodeId, amount: MillisatAmount) -> Result<Payment>;
+    async fn keysend_payment(
+        &self,
+        payee: NodeId,
+        amount: MillisatAmount,
+        max_fee_msat: Option<MillisatAmount>,
+        non_blocking: bool,
+    ) -> Result<Payment>;
 
     async fn generate_invoice(
         &self,
@@ -117,30 +157,134 @@ pub trait LightningInterface: Send + Sync {
         amount: Option<u64>,
         description: String,
         expiry: Option<u32>,
+        // Creates a hold invoice, the payment waits for settle_invoice with the preimage.
+        payment_hash: Option<PaymentHash>,
     ) -> Result<Invoice>;
 
-    async fn list_invoices(&self, label: Option<String>) -> Result<Vec<Invoice>>;
+    /// Claim the held payment of a hold invoice.
+    async fn settle_invoice(&self, preimage: PaymentPreimage) -> Result<()>;
+
+    /// Fail the held payment of a hold invoice back to the payer and reject further payments to it.
+    async fn cancel_invoice(&self, payment_hash: PaymentHash) -> Result<()>;
+
+    async fn list_invoices(
+        &self,
+        label: Option<String>,
+        pagination: Pagination,
+    ) -> Result<Vec<Invoice>>;
+
+    /// Wait until the invoice with the label is paid, canceled or expired. None if there is no such invoice.
+    async fn wait_invoice(&self, label: String) -> Result<Option<Invoice>>;
+
+    /// Wait for the first invoice paid after the one with lastpay_index.
+    async fn wait_any_invoice(&self, lastpay_index: u64) -> Result<Invoice>;
 
     async fn list_payments(
         &self,
         bolt11: Option<Invoice>,
         direction: Option<PaymentDirection>,
+        pagination: Pagination,
     ) -> Result<Vec<Payment>>;
 
+    async fn get_payment(&self, payment_id: PaymentId) -> Result<Option<Payment>>;
+
+    async fn list_payment_attempts(&self, payment_id: PaymentId) -> Result<Vec<PaymentAttempt>>;
+
+    /// Receive node events as they happen, for streaming to API clients.
+    fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<StreamEvent>;
+
+    async fn create_offer(
+        &self,
+        label: Option<String>,
+        amount: Option<MillisatAmount>,
+        description: String,
+        expiry: Option<u64>,
+    ) -> Result<Offer>;
+
+    async fn pay_offer(
+        &self,
+        offer: Offer,
+        amount: Option<MillisatAmount>,
+        max_fee_msat: Option<MillisatAmount>,
+        payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment>;
+
+    async fn list_offers(&self, label: Option<String>) -> Result<Vec<Offer>>;
+
     async fn estimated_channel_liquidity_range(
         &self,
         scid: u64,
         target: &NodeId,
     ) -> Result<Option<(u64, u64)>>;
 
-    async fn fetch_forwards(&self, status: Option<ForwardStatus>) -> Result<Vec<Forward>>;
+    async fn fetch_forwards(
+        &self,
+        status: Option<ForwardStatus>,
+        pagination: Pagination,
+    ) -> Result<Vec<Forward>>;
 
     async fn fetch_total_forwards(&self) -> Result<TotalForwards>;
 
-    async fn channel_history(&self) -> Result<Vec<ChannelRecord>>;
+    /// Forwards grouped by period and channel pair, for the forwarding report.
+    async fn forward_summaries(
+        &self,
+        bucket: TimeBucket,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Result<Vec<ForwardSummary>>;
+
+    /// The on-chain fee we paid for a funding transaction, None if the counterparty funded it.
+    fn funding_fee(&self, txid: &Txid) -> Result<Option<u64>>;
+
+    /// The transaction that spent the funding output of a closed channel, None if it can't be found.
+    fn closing_transaction(&self, funding_txo: &OutPoint) -> Result<Option<Transaction>>;
+
+    async fn channel_history(&self, pagination: Pagination) -> Result<Vec<ChannelRecord>>;
+
+    /// Fee changes made by the automatic fee adjustment, optionally for a single channel.
+    async fn fee_history(&self, channel_id: Option<ChannelId>) -> Result<Vec<ChannelFeeUpdate>>;
+
+    /// Transactions sweeping the outputs of closed channels to the wallet, oldest first.
+    async fn list_sweeps(&self) -> Result<Vec<OutputSweep>>;
+
+    /// The tokens that LSPS2 clients can buy JIT channels with.
+    async fn list_lsps2_tokens(&self) -> Result<Vec<Lsps2Token>>;
+
+    /// Create a token or replace the fees of an existing one.
+    async fn persist_lsps2_token(&self, token: Lsps2Token) -> Result<()>;
+
+    /// Returns false if there is no such token.
+    async fn delete_lsps2_token(&self, token: &str) -> Result<bool>;
+
+    /// The JIT channels sold to LSPS2 clients, oldest first.
+    async fn list_jit_channels(&self) -> Result<Vec<JitChannel>>;
+
+    /// The channels ordered by LSPS1 clients, oldest first.
+    async fn list_lsps1_orders(&self) -> Result<Vec<Lsps1Order>>;
+
+    /// The channels a connected LSP sells to us.
+    async fn lsp_info(&self, lsp: PublicKey, token: Option<String>) -> Result<LspInfo>;
+
+    /// Order a channel from an LSP with LSPS1, it is opened when we pay the order.
+    async fn buy_channel(&self, lsp: PublicKey, order: ChannelOrder) -> Result<LspOrder>;
+
+    /// Like generate_invoice, paid through a JIT channel the LSP opens to us for the payment.
+    async fn generate_jit_invoice(
+        &self,
+        lsp: PublicKey,
+        token: Option<String>,
+        label: String,
+        amount: Option<u64>,
+        description: String,
+        expiry: Option<u32>,
+    ) -> Result<Invoice>;
 
     async fn scorer(&self) -> Result<Vec<u8>>;
 
+    /// The encrypted static channel backup of the current channels.
+    async fn static_channel_backup(&self) -> Result<Vec<u8>>;
+
     async fn update_channels(&self, channels: &[ChannelDetails]);
 }
 
@@ -168,8 +312,125 @@ impl ToString for PeerStatus {
     }
 }
 
+/// A channel to open with a peer.
+#[derive(Clone, Debug)]
+pub struct ChannelTarget {
+    pub node_id: PublicKey,
+    pub value_sats: u64,
+    pub push_msat: Option<u64>,
+    pub override_config: Option<UserConfig>,
+}
+
+/// How the wallet builds the funding transaction of new channels.
+#[derive(Clone, Debug, Default)]
+pub struct FundingOptions {
+    pub fee_rate: Option<FeeRate>,
+    /// Spend exactly these outputs instead of letting the wallet choose.
+    pub utxos: Vec<OutPoint>,
+    /// Minimum number of confirmations of the outputs the wallet chooses.
+    pub min_conf: Option<u8>,
+}
+
 pub struct OpenChannelResult {
     pub transaction: Transaction,
     pub txid: Txid,
-    pub channel_id: ChannelId,
+    /// The channels in the same order as the targets.
+    pub channel_ids: Vec<ChannelId>,
+}
+
+/// What an LSP sells, None for the protocols it does not support.
+pub struct LspInfo {
+    pub lsps1_options: Option<Lsps1Options>,
+    pub lsps2_opening_fees: Option<Vec<OpeningFeeParams>>,
+}
+
+/// A channel to buy from an LSP with LSPS1.
+#[derive(Clone, Debug)]
+pub struct ChannelOrder {
+    /// Our inbound liquidity.
+    pub lsp_balance_sat: u64,
+    /// Paid with the order and pushed to us.
+    pub client_balance_sat: u64,
+    /// How long the LSP keeps the channel open.
+    pub channel_expiry_blocks: u32,
+    pub announce_channel: bool,
+    pub token: Option<String>,
+}
+
+/// The order of a channel, as the LSP accepted it.
+pub struct LspOrder {
+    pub order_id: String,
+    pub order_state: String,
+    pub payment_state: String,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub fee_total_sat: u64,
+    pub order_total_sat: u64,
+    /// Pay either the invoice or the address before the order expires.
+    pub bolt11_invoice: String,
+    pub onchain_address: String,
+    pub expires_at: String,
+}
+
+/// A payment to find a route for.
+#[derive(Clone, Debug)]
+pub struct RouteQuery {
+    pub destination: PublicKey,
+    pub amount: MillisatAmount,
+    /// Limit on the total routing fees.
+    pub max_fee_msat: Option<MillisatAmount>,
+    /// CLTV expiry delta required by the destination.
+    pub final_cltv_expiry_delta: u32,
+    /// Channels and nodes the route must not go through.
+    pub exclude_channels: Vec<u64>,
+    pub exclude_nodes: Vec<PublicKey>,
+}
+
+/// A path of a route with the scorer's estimate of how likely each hop forwards the payment.
+pub struct PathEstimate {
+    pub path: Path,
+    /// None for the channels the scorer knows nothing about.
+    pub hop_probabilities: Vec<Option<f64>>,
+}
+
+impl PathEstimate {
+    /// The chance that every hop with an estimate forwards the payment.
+    pub fn success_probability(&self) -> Option<f64> {
+        self.hop_probabilities
+            .iter()
+            .flatten()
+            .fold(None, |probability, p| Some(probability.unwrap_or(1.0) * p))
+    }
+}
+
+/// Limits and overrides applied when paying an invoice.
+#[derive(Clone, Debug, Default)]
+pub struct PaymentOptions {
+    /// Amount to pay, required for invoices without an amount.
+    pub amount: Option<MillisatAmount>,
+    /// Absolute limit on the routing fees paid.
+    pub max_fee_msat: Option<MillisatAmount>,
+    /// Limit on the routing fees paid, in parts per million of the amount.
+    pub max_fee_ppm: Option<u32>,
+    /// Keep retrying failed paths for this long (seconds).
+    pub retry_for: Option<u64>,
+    /// Maximum number of paths the payment can be split over.
+    pub max_paths: Option<u8>,
+    /// Maximum total CLTV expiry delta of the route.
+    pub max_cltv_expiry_delta: Option<u32>,
+    /// Return as soon as the payment is sent instead of waiting for it to complete.
+    pub non_blocking: bool,
+}
+
+impl PaymentOptions {
+    /// The lower of the absolute and proportional fee limits, if any were set.
+    pub fn max_total_routing_fee_msat(&self, amount: MillisatAmount) -> Option<MillisatAmount> {
+        let ppm_fee = self
+            .max_fee_ppm
+            .map(|ppm| amount.saturating_mul(ppm as u64) / 1_000_000);
+        match (self.max_fee_msat, ppm_fee) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
 }
@@ -0,0 +1,323 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::secp256k1::PublicKey;
+use chrono::{DateTime, Utc};
+use lightning::sign::KeysManager;
+use lightning_liquidity::lsps2::client::LSPS2ClientHandler;
+use lightning_liquidity::lsps2::msgs::OpeningFeeParams;
+use log::{info, warn};
+use serde_json::{json, Value};
+use tokio::time::timeout;
+
+use crate::bitcoind::BitcoindClient;
+use crate::database::WalletDatabase;
+use crate::wallet::{Wallet, WalletInterface};
+use crate::MillisatAmount;
+
+use super::controller::AsyncAPIRequests;
+use super::lightning_interface::{ChannelOrder, LspInfo, LspOrder};
+use super::lsps1::{CreateOrderParams, Lsps1Options, OrderResponse, RpcError};
+use super::peer_manager::{KuutamoPeerManger, PeerManager};
+use super::{ldk_error, KuutamoCustomMessageHandler};
+
+// How long we wait for an LSP to answer a request.
+const LSP_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+// Opening fees that expire sooner are not used, the payer needs some time to pay the invoice.
+const MIN_OPENING_FEE_VALIDITY_SECS: i64 = 60;
+
+/// Buys inbound liquidity from LSPs, LSPS1 channels paid up front and LSPS2 JIT channels paid from the first payment.
+pub(crate) struct LspClient {
+    kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+    peer_manager: Arc<PeerManager>,
+    async_api_requests: Arc<AsyncAPIRequests>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+}
+
+/// The JIT channel an LSP sold us, invoices with its intercept scid in the route hint open it.
+pub(crate) struct JitChannelOffer {
+    pub opening_fee_params: OpeningFeeParams,
+    pub intercept_scid: u64,
+    pub cltv_expiry_delta: u32,
+}
+
+impl LspClient {
+    pub fn new(
+        kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+        peer_manager: Arc<PeerManager>,
+        async_api_requests: Arc<AsyncAPIRequests>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    ) -> LspClient {
+        LspClient {
+            kuutamo_handler,
+            peer_manager,
+            async_api_requests,
+            wallet,
+        }
+    }
+
+    /// What the LSP sells, an LSP may support only one of the protocols.
+    pub async fn get_info(&self, lsp: PublicKey, token: Option<String>) -> Result<LspInfo> {
+        self.check_connected(&lsp)?;
+        let (lsps1_options, lsps2_opening_fees) = tokio::join!(
+            self.lsps1_options(lsp, token.clone()),
+            self.lsps2_opening_fees(lsp, token)
+        );
+        if let (Err(lsps1_error), Err(lsps2_error)) = (&lsps1_options, &lsps2_opening_fees) {
+            bail!("LSP {lsp} sells no channels, LSPS1: {lsps1_error}, LSPS2: {lsps2_error}");
+        }
+        Ok(LspInfo {
+            lsps1_options: lsps1_options.ok(),
+            lsps2_opening_fees: lsps2_opening_fees.ok(),
+        })
+    }
+
+    /// Order a channel with LSPS1, the LSP opens it once the returned invoice or address is paid.
+    pub async fn buy_channel(&self, lsp: PublicKey, order: ChannelOrder) -> Result<LspOrder> {
+        self.check_connected(&lsp)?;
+        let options = self.lsps1_options(lsp, order.token.clone()).await?;
+        check_channel_order(&options, &order)?;
+        let params = CreateOrderParams {
+            lsp_balance_sat: order.lsp_balance_sat,
+            client_balance_sat: order.client_balance_sat,
+            required_channel_confirmations: options.min_required_channel_confirmations,
+            funding_confirms_within_blocks: options.min_funding_confirms_within_blocks,
+            channel_expiry_blocks: order.channel_expiry_blocks,
+            token: order.token,
+            refund_onchain_address: Some(self.wallet.new_external_address()?.address.to_string()),
+            announce_channel: order.announce_channel,
+        };
+        let response: OrderResponse = self
+            .lsps1_request(lsp, "lsps1.create_order", serde_json::to_value(params)?)
+            .await?;
+        info!("Ordered LSPS1 channel {} from {lsp}", response.order_id);
+        Ok(LspOrder {
+            order_id: response.order_id,
+            order_state: response.order_state,
+            payment_state: response.payment.bolt11.state,
+            lsp_balance_sat: response.lsp_balance_sat,
+            client_balance_sat: response.client_balance_sat,
+            fee_total_sat: response.payment.bolt11.fee_total_sat,
+            order_total_sat: response.payment.bolt11.order_total_sat,
+            bolt11_invoice: response.payment.bolt11.invoice,
+            onchain_address: response.payment.onchain.address,
+            expires_at: response.payment.bolt11.expires_at,
+        })
+    }
+
+    /// Buy a JIT channel with LSPS2 at the cheapest fees the LSP offers for the amount.
+    pub async fn buy_jit_channel(
+        &self,
+        lsp: PublicKey,
+        token: Option<String>,
+        amount_msat: Option<MillisatAmount>,
+    ) -> Result<JitChannelOffer> {
+        self.check_connected(&lsp)?;
+        let menu = self.lsps2_opening_fees(lsp, token).await?;
+        let opening_fee_params = cheapest_opening_fee_params(menu, amount_msat, Utc::now())
+            .with_context(|| format!("LSP {lsp} offers no JIT channel for this amount"))?;
+        let request_id = self
+            .lsps2_client()?
+            .select_opening_params(lsp, amount_msat, opening_fee_params.clone())
+            .map_err(ldk_error)?;
+        let receiver = self
+            .async_api_requests
+            .lsps2_invoice_params
+            .insert(request_id, ())
+            .await;
+        let (intercept_scid, cltv_expiry_delta) =
+            timeout(LSP_RESPONSE_TIMEOUT, receiver)
+                .await
+                .with_context(|| format!("LSP {lsp} did not sell the JIT channel in time"))??;
+        info!("Bought JIT channel with intercept scid {intercept_scid} from {lsp}");
+        Ok(JitChannelOffer {
+            opening_fee_params,
+            intercept_scid,
+            cltv_expiry_delta,
+        })
+    }
+
+    async fn lsps1_options(&self, lsp: PublicKey, token: Option<String>) -> Result<Lsps1Options> {
+        self.lsps1_request(lsp, "lsps1.get_info", json!({ "token": token }))
+            .await
+    }
+
+    async fn lsps1_request<T: serde::de::DeserializeOwned>(
+        &self,
+        lsp: PublicKey,
+        method: &str,
+        params: Value,
+    ) -> Result<T> {
+        let messages = &self.kuutamo_handler.lsps1_messages;
+        let receiver = messages.request(lsp, method, params);
+        let response = timeout(LSP_RESPONSE_TIMEOUT, receiver).await;
+        messages.forget_abandoned_requests();
+        let result = response
+            .with_context(|| format!("LSP {lsp} did not answer {method} in time"))??
+            .map_err(|e| rpc_error(method, e))?;
+        serde_json::from_value(result).with_context(|| format!("Invalid {method} response"))
+    }
+
+    async fn lsps2_opening_fees(
+        &self,
+        lsp: PublicKey,
+        token: Option<String>,
+    ) -> Result<Vec<OpeningFeeParams>> {
+        let request_id = self.lsps2_client()?.request_opening_params(lsp, token);
+        let receiver = self
+            .async_api_requests
+            .lsps2_opening_params
+            .insert(request_id, ())
+            .await;
+        timeout(LSP_RESPONSE_TIMEOUT, receiver)
+            .await
+            .with_context(|| format!("LSP {lsp} did not offer JIT channels in time"))?
+            .map_err(|e| anyhow!(e))
+    }
+
+    fn lsps2_client(&self) -> Result<&LSPS2ClientHandler<Arc<KeysManager>>> {
+        self.kuutamo_handler
+            .liquidity_manager
+            .lsps2_client_handler()
+            .context("LSPS2 client is not configured")
+    }
+
+    fn check_connected(&self, lsp: &PublicKey) -> Result<()> {
+        if !self.peer_manager.is_connected(lsp) {
+            bail!("LSP {lsp} is not connected");
+        }
+        Ok(())
+    }
+}
+
+fn rpc_error(method: &str, error: RpcError) -> anyhow::Error {
+    warn!("LSP rejected {method}: {error:?}");
+    match error.data {
+        Some(data) => anyhow!("{method} failed: {} ({data})", error.message),
+        None => anyhow!("{method} failed: {}", error.message),
+    }
+}
+
+/// Check an order against the options of the LSP before sending it.
+pub(crate) fn check_channel_order(options: &Lsps1Options, order: &ChannelOrder) -> Result<()> {
+    let channel_sat = order
+        .lsp_balance_sat
+        .checked_add(order.client_balance_sat)
+        .context("The channel size overflows")?;
+    if order.lsp_balance_sat < options.min_initial_lsp_balance_sat
+        || order.lsp_balance_sat > options.max_initial_lsp_balance_sat
+    {
+        bail!(
+            "The LSP balance must be between {} and {} sats",
+            options.min_initial_lsp_balance_sat,
+            options.max_initial_lsp_balance_sat
+        );
+    }
+    if order.client_balance_sat < options.min_initial_client_balance_sat
+        || order.client_balance_sat > options.max_initial_client_balance_sat
+    {
+        bail!(
+            "The client balance must be between {} and {} sats",
+            options.min_initial_client_balance_sat,
+            options.max_initial_client_balance_sat
+        );
+    }
+    if channel_sat < options.min_channel_balance_sat
+        || channel_sat > options.max_channel_balance_sat
+    {
+        bail!(
+            "The channel size must be between {} and {} sats",
+            options.min_channel_balance_sat,
+            options.max_channel_balance_sat
+        );
+    }
+    if order.channel_expiry_blocks > options.max_channel_expiry_blocks {
+        bail!(
+            "The channel can be leased for at most {} blocks",
+            options.max_channel_expiry_blocks
+        );
+    }
+    Ok(())
+}
+
+/// The offered fees that are cheapest for the payment, variable amounts compare the minimum fees first.
+pub(crate) fn cheapest_opening_fee_params(
+    menu: Vec<OpeningFeeParams>,
+    amount_msat: Option<MillisatAmount>,
+    now: DateTime<Utc>,
+) -> Option<OpeningFeeParams> {
+    let valid = menu.into_iter().filter(|params| {
+        params.valid_until.timestamp() > now.timestamp() + MIN_OPENING_FEE_VALIDITY_SECS
+            && amount_msat.map_or(true, |amount| {
+                amount >= params.min_payment_size_msat && amount <= params.max_payment_size_msat
+            })
+    });
+    match amount_msat {
+        Some(amount) => valid.min_by_key(|params| {
+            let proportional_fee =
+                (amount as u128 * params.proportional as u128 + 999_999) / 1_000_000;
+            params.min_fee_msat.max(proportional_fee as MillisatAmount)
+        }),
+        None => valid.min_by_key(|params| (params.min_fee_msat, params.proportional)),
+    }
+}
+
+#[test]
+fn test_check_channel_order() {
+    let options = Lsps1Options {
+        min_required_channel_confirmations: 0,
+        min_funding_confirms_within_blocks: 6,
+        supports_zero_channel_reserve: false,
+        max_channel_expiry_blocks: 13140,
+        min_initial_client_balance_sat: 0,
+        max_initial_client_balance_sat: 50_000,
+        min_initial_lsp_balance_sat: 0,
+        max_initial_lsp_balance_sat: 1_000_000,
+        min_channel_balance_sat: 100_000,
+        max_channel_balance_sat: 1_000_000,
+    };
+    let order = |lsp_balance_sat, client_balance_sat, channel_expiry_blocks| ChannelOrder {
+        lsp_balance_sat,
+        client_balance_sat,
+        channel_expiry_blocks,
+        announce_channel: false,
+        token: None,
+    };
+    assert!(check_channel_order(&options, &order(500_000, 0, 4320)).is_ok());
+    assert!(check_channel_order(&options, &order(1_000_001, 0, 4320)).is_err());
+    assert!(check_channel_order(&options, &order(500_000, 50_001, 4320)).is_err());
+    assert!(check_channel_order(&options, &order(99_999, 0, 4320)).is_err());
+    assert!(check_channel_order(&options, &order(990_000, 20_000, 4320)).is_err());
+    assert!(check_channel_order(&options, &order(500_000, 0, 13141)).is_err());
+    assert!(check_channel_order(&options, &order(u64::MAX, 1, 4320)).is_err());
+}
+
+#[test]
+fn test_cheapest_opening_fee_params() {
+    let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let params = |min_fee_msat, proportional, valid_for_secs| OpeningFeeParams {
+        min_fee_msat,
+        proportional,
+        valid_until: DateTime::from_timestamp(1_700_000_000 + valid_for_secs, 0).unwrap(),
+        min_lifetime: 4320,
+        max_client_to_self_delay: 3600,
+        min_payment_size_msat: 10_000,
+        max_payment_size_msat: 100_000_000,
+        promise: format!("{min_fee_msat}-{proportional}"),
+    };
+    let menu = vec![
+        params(2000, 10_000, 600),
+        params(10_000, 1000, 600),
+        params(1000, 1000, 30),
+    ];
+    // 2000 msat against 10000 msat, the last fees expire too soon
+    let cheapest = cheapest_opening_fee_params(menu.clone(), Some(100_000), now).unwrap();
+    assert_eq!(cheapest.min_fee_msat, 2000);
+    // 1000000 msat against 100000 msat
+    let cheapest = cheapest_opening_fee_params(menu.clone(), Some(100_000_000), now).unwrap();
+    assert_eq!(cheapest.min_fee_msat, 10_000);
+    let cheapest = cheapest_opening_fee_params(menu.clone(), None, now).unwrap();
+    assert_eq!(cheapest.min_fee_msat, 2000);
+    assert!(cheapest_opening_fee_params(menu, Some(5_000), now).is_none());
+}
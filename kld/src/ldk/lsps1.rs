@@ -0,0 +1,1027 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bdk::{LocalUtxo, TransactionDetails};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Address;
+use chrono::{DateTime, SecondsFormat};
+use lightning::ln::ChannelId;
+use lightning::sign::KeysManager;
+use log::{debug, info, warn};
+use rand::random;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+use crate::bitcoind::BitcoindClient;
+use crate::database::invoice::{Invoice, InvoiceStatus};
+use crate::database::lsps1::{Lsps1Order, OrderState, PaymentState};
+use crate::database::{microsecond_timestamp, LdkDatabase, WalletDatabase};
+use crate::log_error;
+use crate::logger::KldLogger;
+use crate::settings::Settings;
+use crate::wallet::{Wallet, WalletInterface};
+
+use super::controller::{AsyncAPIRequests, FundingBatch};
+use super::peer_manager::{KuutamoPeerManger, PeerManager};
+use super::{
+    ldk_error, sign_or_creation_error, ChannelManager, FundingOptions, KuutamoCustomMessageHandler,
+};
+
+const LSPS1_METHOD_PREFIX: &str = "lsps1.";
+const LIST_PROTOCOLS_METHOD: &str = "lsps0.list_protocols";
+
+// JSON-RPC error codes, the LSPS1 specific ones are positive.
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+const OPTION_MISMATCH: i32 = 100;
+// Implementation defined server error.
+const TOO_MANY_ORDERS: i32 = -32000;
+
+// The funding transaction is broadcast with the channel funding fee estimate, which aims at this many blocks.
+const FUNDING_CONFIRMS_WITHIN_BLOCKS: u16 = 6;
+const CHECK_ORDERS_INTERVAL: Duration = Duration::from_secs(30);
+const SECS_PER_BLOCK: i64 = 600;
+
+// A request when it has a method, otherwise a response with a result or an error.
+#[derive(Debug, Deserialize)]
+struct JsonRpcMessage {
+    id: String,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug)]
+pub(crate) struct JsonRpcRequest {
+    id: String,
+    method: String,
+    params: Value,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: &str) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    // Points the client to the parameter that we cannot accept.
+    fn property(code: i32, message: &str, property: &str) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: Some(json!({ "property": property })),
+        }
+    }
+}
+
+fn internal_error(e: anyhow::Error) -> RpcError {
+    log_error(&e);
+    RpcError::new(INTERNAL_ERROR, "Internal error")
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| internal_error(e.into()))
+}
+
+/// The LSPS1 messages are taken out of the LSPS0 messages before lightning-liquidity reads them,
+/// which only serves LSPS1 when built with a cfg flag. The messages we send wait here for the peer manager.
+pub(crate) struct Lsps1Messages {
+    // None when the service is disabled, lightning-liquidity gets every request then.
+    requests: Option<UnboundedSender<(PublicKey, JsonRpcRequest)>>,
+    // Answer to lsps0.list_protocols while the service is enabled, lightning-liquidity does not know LSPS1.
+    protocols: Vec<u16>,
+    // Our requests to LSPs that wait for an answer, by request id.
+    responses: Mutex<HashMap<String, (PublicKey, oneshot::Sender<Result<Value, RpcError>>)>>,
+    pending: Mutex<Vec<(PublicKey, String)>>,
+    process_msgs_callback: OnceLock<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl Lsps1Messages {
+    pub fn new(
+        requests: Option<UnboundedSender<(PublicKey, JsonRpcRequest)>>,
+        protocols: Vec<u16>,
+    ) -> Lsps1Messages {
+        Lsps1Messages {
+            requests,
+            protocols,
+            responses: Mutex::new(HashMap::new()),
+            pending: Mutex::new(vec![]),
+            process_msgs_callback: OnceLock::new(),
+        }
+    }
+
+    /// Returns false if the message is neither for the LSPS1 service nor an answer to our LSPS1 requests.
+    pub fn handle(&self, sender_node_id: &PublicKey, payload: &str) -> bool {
+        let Ok(message) = serde_json::from_str::<JsonRpcMessage>(payload) else {
+            return false;
+        };
+        let Some(method) = message.method else {
+            return self.handle_response(sender_node_id, message);
+        };
+        let Some(requests) = &self.requests else {
+            return false;
+        };
+        if method == LIST_PROTOCOLS_METHOD {
+            self.respond(
+                *sender_node_id,
+                &message.id,
+                Ok(json!({ "protocols": self.protocols })),
+            );
+            return true;
+        }
+        if !method.starts_with(LSPS1_METHOD_PREFIX) {
+            return false;
+        }
+        let request = JsonRpcRequest {
+            id: message.id,
+            method,
+            params: message.params,
+        };
+        if requests.send((*sender_node_id, request)).is_err() {
+            warn!("LSPS1 service stopped, dropping request from {sender_node_id}");
+        }
+        true
+    }
+
+    fn handle_response(&self, sender_node_id: &PublicKey, message: JsonRpcMessage) -> bool {
+        let mut responses = self.responses.lock().unwrap();
+        match responses.get(&message.id) {
+            Some((lsp, _)) if lsp == sender_node_id => {}
+            _ => return false,
+        }
+        let Some((_, sender)) = responses.remove(&message.id) else {
+            return false;
+        };
+        let result = match (message.result, message.error) {
+            (_, Some(error)) => Err(error),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(RpcError::new(INTERNAL_ERROR, "Empty response")),
+        };
+        if sender.send(result).is_err() {
+            debug!("LSPS1 response from {sender_node_id} arrived too late");
+        }
+        true
+    }
+
+    /// Send a request to an LSP, the answer is delivered to the receiver.
+    pub fn request(
+        &self,
+        lsp: PublicKey,
+        method: &str,
+        params: Value,
+    ) -> oneshot::Receiver<Result<Value, RpcError>> {
+        let id = hex::encode(random::<[u8; 16]>());
+        let (sender, receiver) = oneshot::channel();
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (lsp, sender));
+        self.send(
+            lsp,
+            json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+        );
+        receiver
+    }
+
+    /// Drop the requests whose receiver is gone, e.g. after the LSP did not answer in time.
+    pub fn forget_abandoned_requests(&self) {
+        self.responses
+            .lock()
+            .unwrap()
+            .retain(|_, (_, sender)| !sender.is_closed());
+    }
+
+    pub fn respond(&self, counterparty: PublicKey, id: &str, result: Result<Value, RpcError>) {
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        };
+        self.send(counterparty, response);
+    }
+
+    fn send(&self, node_id: PublicKey, message: Value) {
+        self.pending
+            .lock()
+            .unwrap()
+            .push((node_id, message.to_string()));
+        if let Some(process_msgs_callback) = self.process_msgs_callback.get() {
+            process_msgs_callback();
+        }
+    }
+
+    pub fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, String)> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+
+    pub fn set_process_msgs_callback(&self, callback: impl Fn() + Send + Sync + 'static) {
+        if self.process_msgs_callback.set(Box::new(callback)).is_err() {
+            warn!("LSPS1 process messages callback was already set");
+        }
+    }
+}
+
+// LSPS1 sends the amounts as strings.
+mod sat_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The channels that an LSP sells, the answer to lsps1.get_info.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lsps1Options {
+    pub min_required_channel_confirmations: u16,
+    pub min_funding_confirms_within_blocks: u16,
+    pub supports_zero_channel_reserve: bool,
+    pub max_channel_expiry_blocks: u32,
+    #[serde(with = "sat_string")]
+    pub min_initial_client_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub max_initial_client_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub min_initial_lsp_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub max_initial_lsp_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub min_channel_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub max_channel_balance_sat: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CreateOrderParams {
+    #[serde(with = "sat_string")]
+    pub lsp_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub client_balance_sat: u64,
+    pub required_channel_confirmations: u16,
+    pub funding_confirms_within_blocks: u16,
+    pub channel_expiry_blocks: u32,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub refund_onchain_address: Option<String>,
+    pub announce_channel: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetOrderParams {
+    order_id: String,
+}
+
+/// An order as the LSP reports it to the client.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct OrderResponse {
+    pub order_id: String,
+    #[serde(with = "sat_string")]
+    pub lsp_balance_sat: u64,
+    #[serde(with = "sat_string")]
+    pub client_balance_sat: u64,
+    pub required_channel_confirmations: u16,
+    pub funding_confirms_within_blocks: u16,
+    pub channel_expiry_blocks: u32,
+    pub token: String,
+    pub created_at: String,
+    pub announce_channel: bool,
+    pub order_state: String,
+    pub payment: OrderPayment,
+    pub channel: Option<OrderChannel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct OrderPayment {
+    pub bolt11: Bolt11Payment,
+    pub onchain: OnchainPayment,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Bolt11Payment {
+    pub state: String,
+    pub expires_at: String,
+    #[serde(with = "sat_string")]
+    pub fee_total_sat: u64,
+    #[serde(with = "sat_string")]
+    pub order_total_sat: u64,
+    pub invoice: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct OnchainPayment {
+    pub state: String,
+    pub expires_at: String,
+    #[serde(with = "sat_string")]
+    pub fee_total_sat: u64,
+    #[serde(with = "sat_string")]
+    pub order_total_sat: u64,
+    pub address: String,
+    pub min_onchain_payment_confirmations: Option<u16>,
+    pub min_fee_for_0conf: Option<u64>,
+    pub refund_onchain_address: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct OrderChannel {
+    pub funded_at: String,
+    pub funding_outpoint: String,
+    pub expires_at: String,
+}
+
+/// Sells channels to LSPS1 clients at the prices of the settings. The channel of an order is opened
+/// once its invoice is paid or its on-chain address received enough confirmed funds.
+pub(crate) struct Lsps1Service {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    channel_manager: Arc<ChannelManager>,
+    keys_manager: Arc<KeysManager>,
+    peer_manager: Arc<PeerManager>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+    async_api_requests: Arc<AsyncAPIRequests>,
+}
+
+impl Lsps1Service {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        channel_manager: Arc<ChannelManager>,
+        keys_manager: Arc<KeysManager>,
+        peer_manager: Arc<PeerManager>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+        kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+        async_api_requests: Arc<AsyncAPIRequests>,
+    ) -> Lsps1Service {
+        Lsps1Service {
+            settings,
+            database,
+            channel_manager,
+            keys_manager,
+            peer_manager,
+            wallet,
+            kuutamo_handler,
+            async_api_requests,
+        }
+    }
+
+    pub async fn run(self, mut requests: UnboundedReceiver<(PublicKey, JsonRpcRequest)>) {
+        let mut interval = tokio::time::interval(CHECK_ORDERS_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                request = requests.recv() => {
+                    let Some((counterparty, request)) = request else {
+                        return;
+                    };
+                    debug!("Response {} to {counterparty}", request.method);
+                    let result = self
+                        .handle_request(counterparty, &request.method, request.params)
+                        .await;
+                    self.kuutamo_handler
+                        .lsps1_messages
+                        .respond(counterparty, &request.id, result);
+                }
+                _ = interval.tick() => {
+                    if let Err(e) = self.check_orders().await {
+                        log_error(&e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_request(
+        &self,
+        counterparty: PublicKey,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "lsps1.get_info" => to_result(options(&self.settings)),
+            "lsps1.create_order" => {
+                let params: CreateOrderParams = serde_json::from_value(params)
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, &e.to_string()))?;
+                check_order(&self.settings, &params)?;
+                let orders = self
+                    .database
+                    .fetch_lsps1_orders(Some(OrderState::Created))
+                    .await
+                    .map_err(internal_error)?;
+                check_open_orders(&self.settings, &orders, &counterparty)?;
+                let order = self
+                    .create_order(counterparty, params)
+                    .await
+                    .map_err(internal_error)?;
+                to_result(OrderResponse::from(&order))
+            }
+            "lsps1.get_order" => {
+                let params: GetOrderParams = serde_json::from_value(params)
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, &e.to_string()))?;
+                match self
+                    .database
+                    .fetch_lsps1_order(&params.order_id)
+                    .await
+                    .map_err(internal_error)?
+                {
+                    // Clients only see their own orders.
+                    Some(order) if order.counterparty == counterparty => {
+                        to_result(OrderResponse::from(&order))
+                    }
+                    _ => Err(RpcError::property(
+                        INVALID_PARAMS,
+                        "Order not found",
+                        "order_id",
+                    )),
+                }
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+        }
+    }
+
+    async fn create_order(
+        &self,
+        counterparty: PublicKey,
+        params: CreateOrderParams,
+    ) -> Result<Lsps1Order> {
+        let order_id = hex::encode(random::<[u8; 16]>());
+        // Both were checked with the order.
+        let fee_total_sat = order_fee_sat(&self.settings, params.lsp_balance_sat)
+            .ok_or_else(|| anyhow!("Order fee overflows"))?;
+        let order_total_sat = order_total_sat(&self.settings, &params)
+            .ok_or_else(|| anyhow!("Order total overflows"))?;
+        let bolt11 = lightning_invoice::utils::create_invoice_from_channelmanager(
+            &self.channel_manager,
+            self.keys_manager.clone(),
+            KldLogger::global(),
+            self.settings.bitcoin_network.into(),
+            Some(order_total_sat * 1000),
+            format!("LSPS1 order {order_id}"),
+            self.settings.lsps1_order_expiry_secs,
+            None,
+        )
+        .map_err(sign_or_creation_error)?;
+        let invoice = Invoice::new(Some(format!("lsps1-{order_id}")), bolt11)?;
+        self.database.persist_invoice(&invoice).await?;
+        let onchain_address = self.wallet.new_external_address()?.address.to_string();
+
+        let timestamp = microsecond_timestamp();
+        let order = Lsps1Order {
+            order_id,
+            counterparty,
+            lsp_balance_sat: params.lsp_balance_sat,
+            client_balance_sat: params.client_balance_sat,
+            required_channel_confirmations: params.required_channel_confirmations,
+            funding_confirms_within_blocks: params.funding_confirms_within_blocks,
+            channel_expiry_blocks: params.channel_expiry_blocks,
+            token: params.token,
+            announce_channel: params.announce_channel,
+            fee_total_sat,
+            order_total_sat,
+            payment_hash: invoice.payment_hash,
+            bolt11_invoice: invoice.bolt11.to_string(),
+            onchain_address,
+            min_onchain_payment_confirmations: self
+                .settings
+                .lsps1_min_onchain_payment_confirmations,
+            refund_onchain_address: params.refund_onchain_address,
+            expires_at: timestamp
+                + time::Duration::seconds(self.settings.lsps1_order_expiry_secs as i64),
+            order_state: OrderState::Created,
+            payment_state: PaymentState::ExpectPayment,
+            user_channel_id: None,
+            funding_outpoint: None,
+            funded_at: None,
+            created_at: timestamp,
+            updated_at: timestamp,
+        };
+        self.database.persist_lsps1_order(&order).await?;
+        info!(
+            "Created LSPS1 order {} of {order_total_sat} sats for {counterparty}",
+            order.order_id
+        );
+        Ok(order)
+    }
+
+    /// Move the open orders forward: record payments, expire the unpaid ones and open the paid channels.
+    async fn check_orders(&self) -> Result<()> {
+        let orders = self
+            .database
+            .fetch_lsps1_orders(Some(OrderState::Created))
+            .await?;
+        if orders.is_empty() {
+            return Ok(());
+        }
+        // Scan the wallet once for the on-chain payments of all orders.
+        let utxos = self.wallet.list_utxos()?;
+        let height = self.channel_manager.current_best_block().height();
+        for mut order in orders {
+            // The channel is being funded.
+            if order.user_channel_id.is_some() {
+                continue;
+            }
+            if order.payment_state == PaymentState::ExpectPayment {
+                if self.is_paid(&order, &utxos, height).await? {
+                    info!("LSPS1 order {} is paid", order.order_id);
+                    order.payment_state = PaymentState::Paid;
+                } else if order.expires_at < OffsetDateTime::now_utc() {
+                    info!("LSPS1 order {} expired without payment", order.order_id);
+                    order.order_state = OrderState::Failed;
+                } else {
+                    continue;
+                }
+                order.updated_at = microsecond_timestamp();
+                self.database.persist_lsps1_order(&order).await?;
+            }
+            if order.order_state == OrderState::Created && order.payment_state == PaymentState::Paid
+            {
+                if let Err(e) = self.open_channel(order).await {
+                    log_error(&e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn is_paid(
+        &self,
+        order: &Lsps1Order,
+        utxos: &[(LocalUtxo, TransactionDetails)],
+        height: u32,
+    ) -> Result<bool> {
+        if self
+            .database
+            .fetch_invoice_status(&order.payment_hash)
+            .await?
+            == Some(InvoiceStatus::Settled)
+        {
+            return Ok(true);
+        }
+        let script_pubkey = Address::<NetworkUnchecked>::from_str(&order.onchain_address)?
+            .assume_checked()
+            .script_pubkey();
+        let received_sat: u64 = utxos
+            .iter()
+            .filter(|(utxo, details)| {
+                let confirmations = details
+                    .confirmation_time
+                    .as_ref()
+                    .map(|time| (height + 1).saturating_sub(time.height))
+                    .unwrap_or_default();
+                utxo.txout.script_pubkey == script_pubkey
+                    && confirmations >= order.min_onchain_payment_confirmations as u32
+            })
+            .map(|(utxo, _)| utxo.txout.value)
+            .sum();
+        Ok(received_sat >= order.order_total_sat)
+    }
+
+    async fn open_channel(&self, mut order: Lsps1Order) -> Result<()> {
+        if !self.peer_manager.is_connected(&order.counterparty) {
+            debug!(
+                "LSPS1 order {} waits for {} to connect",
+                order.order_id, order.counterparty
+            );
+            return Ok(());
+        }
+        let user_channel_id: u64 = random::<u64>() / 2; // To fit into the database INT
+        let mut config = *self.channel_manager.get_current_default_configuration();
+        config.channel_handshake_config.announced_channel = order.announce_channel;
+        let temporary_channel_id = self
+            .channel_manager
+            .create_channel(
+                order.counterparty,
+                order.lsp_balance_sat + order.client_balance_sat,
+                order.client_balance_sat * 1000,
+                user_channel_id as u128,
+                None,
+                Some(config),
+            )
+            .map_err(ldk_error)?;
+        info!(
+            "Opening channel {user_channel_id} of LSPS1 order {} with {}",
+            order.order_id, order.counterparty
+        );
+        order.user_channel_id = Some(user_channel_id);
+        order.updated_at = microsecond_timestamp();
+        self.database.persist_lsps1_order(&order).await?;
+
+        // Funding waits for the peer, the other orders and the LSPS1 requests must not wait with it.
+        let batch = Arc::new(FundingBatch::new(
+            FundingOptions::default(),
+            vec![(user_channel_id, temporary_channel_id, order.counterparty)],
+        ));
+        let receiver = self
+            .async_api_requests
+            .funding_transactions
+            .insert(user_channel_id, batch)
+            .await;
+        let database = self.database.clone();
+        let channel_manager = self.channel_manager.clone();
+        tokio::spawn(async move {
+            let order_id = order.order_id.clone();
+            let result = match receiver.await {
+                Ok(Ok(transaction)) => {
+                    order_funded(
+                        &database,
+                        &channel_manager,
+                        order,
+                        &temporary_channel_id,
+                        transaction.txid(),
+                    )
+                    .await
+                }
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(anyhow!(e)),
+            };
+            if let Err(e) = result {
+                warn!("Channel of LSPS1 order {order_id} failed to open: {e}");
+                order_failed(&database, &order_id).await;
+            }
+        });
+        Ok(())
+    }
+}
+
+async fn order_funded(
+    database: &LdkDatabase,
+    channel_manager: &ChannelManager,
+    mut order: Lsps1Order,
+    temporary_channel_id: &ChannelId,
+    txid: bitcoin::Txid,
+) -> Result<()> {
+    database
+        .persist_initializing_channel(
+            temporary_channel_id,
+            order.announce_channel,
+            &order.counterparty,
+            &txid,
+        )
+        .await?;
+    let funding_txo = channel_manager
+        .list_channels()
+        .into_iter()
+        .find(|channel| Some(channel.user_channel_id as u64) == order.user_channel_id)
+        .and_then(|channel| channel.funding_txo);
+    order.funding_outpoint = Some(match funding_txo {
+        Some(outpoint) => format!("{}:{}", outpoint.txid, outpoint.index),
+        None => txid.to_string(),
+    });
+    order.order_state = OrderState::Completed;
+    order.funded_at = Some(microsecond_timestamp());
+    order.updated_at = microsecond_timestamp();
+    database.persist_lsps1_order(&order).await?;
+    info!("LSPS1 order {} is completed", order.order_id);
+    Ok(())
+}
+
+// The payment stays recorded, it is up to the operator to refund it.
+async fn order_failed(database: &LdkDatabase, order_id: &str) {
+    match database.fetch_lsps1_order(order_id).await {
+        Ok(Some(mut order)) => {
+            order.order_state = OrderState::Failed;
+            order.updated_at = microsecond_timestamp();
+            if let Err(e) = database.persist_lsps1_order(&order).await {
+                log_error(&e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log_error(&e),
+    }
+}
+
+/// The LSPS protocols that the settings enable, lightning-liquidity always serves LSPS2 to clients with a token.
+pub(crate) fn supported_protocols(settings: &Settings) -> Vec<u16> {
+    let mut protocols = vec![];
+    if settings.lsps1_enable {
+        protocols.push(1);
+    }
+    protocols.push(2);
+    protocols
+}
+
+/// The channels that clients can order, returned by lsps1.get_info.
+pub(crate) fn options(settings: &Settings) -> Lsps1Options {
+    Lsps1Options {
+        min_required_channel_confirmations: 0,
+        min_funding_confirms_within_blocks: FUNDING_CONFIRMS_WITHIN_BLOCKS,
+        supports_zero_channel_reserve: false,
+        max_channel_expiry_blocks: settings.lsps1_max_channel_expiry_blocks,
+        min_initial_client_balance_sat: 0,
+        max_initial_client_balance_sat: settings.lsps1_max_client_balance_sats,
+        min_initial_lsp_balance_sat: 0,
+        max_initial_lsp_balance_sat: settings.lsps1_max_channel_sats,
+        min_channel_balance_sat: settings.lsps1_min_channel_sats,
+        max_channel_balance_sat: settings.lsps1_max_channel_sats,
+    }
+}
+
+/// Reject the orders outside of the options, naming the first option they do not match.
+pub(crate) fn check_order(settings: &Settings, params: &CreateOrderParams) -> Result<(), RpcError> {
+    let mismatch = |property| RpcError::property(OPTION_MISMATCH, "Option mismatch", property);
+    if params.client_balance_sat > settings.lsps1_max_client_balance_sats {
+        return Err(mismatch("client_balance_sat"));
+    }
+    let channel_sats = params
+        .lsp_balance_sat
+        .checked_add(params.client_balance_sat)
+        .ok_or_else(|| mismatch("lsp_balance_sat"))?;
+    if channel_sats < settings.lsps1_min_channel_sats
+        || channel_sats > settings.lsps1_max_channel_sats
+    {
+        return Err(mismatch("lsp_balance_sat"));
+    }
+    if params.funding_confirms_within_blocks < FUNDING_CONFIRMS_WITHIN_BLOCKS {
+        return Err(mismatch("funding_confirms_within_blocks"));
+    }
+    if params.channel_expiry_blocks > settings.lsps1_max_channel_expiry_blocks {
+        return Err(mismatch("channel_expiry_blocks"));
+    }
+    // The invoice of the order is in milli satoshis.
+    if order_total_sat(settings, params)
+        .and_then(|total| total.checked_mul(1000))
+        .is_none()
+    {
+        return Err(mismatch("lsp_balance_sat"));
+    }
+    if let Some(address) = &params.refund_onchain_address {
+        if Address::<NetworkUnchecked>::from_str(address)
+            .ok()
+            .and_then(|address| address.require_network(settings.bitcoin_network).ok())
+            .is_none()
+        {
+            return Err(RpcError::property(
+                INVALID_PARAMS,
+                "Invalid address",
+                "refund_onchain_address",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Refuse new orders while the client, or all clients together, have too many orders waiting for payment.
+fn check_open_orders(
+    settings: &Settings,
+    orders: &[Lsps1Order],
+    counterparty: &PublicKey,
+) -> Result<(), RpcError> {
+    let open_orders: Vec<_> = orders
+        .iter()
+        .filter(|order| {
+            order.order_state == OrderState::Created
+                && order.payment_state == PaymentState::ExpectPayment
+        })
+        .collect();
+    if open_orders.len() >= settings.lsps1_max_open_orders
+        || open_orders
+            .iter()
+            .filter(|order| order.counterparty == *counterparty)
+            .count()
+            >= settings.lsps1_max_open_orders_per_client
+    {
+        return Err(RpcError::new(TOO_MANY_ORDERS, "Too many unpaid orders"));
+    }
+    Ok(())
+}
+
+/// The base fee and the proportional fee of the liquidity on our side, the client balance is paid on top.
+/// None if it overflows.
+pub(crate) fn order_fee_sat(settings: &Settings, lsp_balance_sat: u64) -> Option<u64> {
+    lsp_balance_sat
+        .checked_mul(settings.lsps1_fee_ppm as u64)
+        .map(|fee| fee / 1_000_000)?
+        .checked_add(settings.lsps1_base_fee_sats)
+}
+
+/// The fee and the client balance that the client pays for the order, None if it overflows.
+fn order_total_sat(settings: &Settings, params: &CreateOrderParams) -> Option<u64> {
+    order_fee_sat(settings, params.lsp_balance_sat)?.checked_add(params.client_balance_sat)
+}
+
+impl From<&Lsps1Order> for OrderResponse {
+    fn from(order: &Lsps1Order) -> Self {
+        let order_state = match order.order_state {
+            OrderState::Created => "CREATED",
+            OrderState::Completed => "COMPLETED",
+            OrderState::Failed => "FAILED",
+        };
+        let payment_state = match order.payment_state {
+            PaymentState::ExpectPayment => "EXPECT_PAYMENT",
+            PaymentState::Paid => "PAID",
+            PaymentState::Refunded => "REFUNDED",
+        };
+        let channel = order.funded_at.map(|funded_at| OrderChannel {
+            funded_at: iso8601(funded_at),
+            funding_outpoint: order.funding_outpoint.clone().unwrap_or_default(),
+            expires_at: iso8601(
+                funded_at
+                    + time::Duration::seconds(order.channel_expiry_blocks as i64 * SECS_PER_BLOCK),
+            ),
+        });
+        OrderResponse {
+            order_id: order.order_id.clone(),
+            lsp_balance_sat: order.lsp_balance_sat,
+            client_balance_sat: order.client_balance_sat,
+            required_channel_confirmations: order.required_channel_confirmations,
+            funding_confirms_within_blocks: order.funding_confirms_within_blocks,
+            channel_expiry_blocks: order.channel_expiry_blocks,
+            token: order.token.clone().unwrap_or_default(),
+            created_at: iso8601(order.created_at),
+            announce_channel: order.announce_channel,
+            order_state: order_state.to_string(),
+            payment: OrderPayment {
+                bolt11: Bolt11Payment {
+                    state: payment_state.to_string(),
+                    expires_at: iso8601(order.expires_at),
+                    fee_total_sat: order.fee_total_sat,
+                    order_total_sat: order.order_total_sat,
+                    invoice: order.bolt11_invoice.clone(),
+                },
+                onchain: OnchainPayment {
+                    state: payment_state.to_string(),
+                    expires_at: iso8601(order.expires_at),
+                    fee_total_sat: order.fee_total_sat,
+                    order_total_sat: order.order_total_sat,
+                    address: order.onchain_address.clone(),
+                    min_onchain_payment_confirmations: Some(
+                        order.min_onchain_payment_confirmations,
+                    ),
+                    min_fee_for_0conf: None,
+                    refund_onchain_address: order.refund_onchain_address.clone(),
+                },
+            },
+            channel,
+        }
+    }
+}
+
+fn iso8601(time: OffsetDateTime) -> String {
+    DateTime::from_timestamp(time.unix_timestamp(), time.nanosecond())
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[test]
+fn test_check_order() {
+    let mut settings = Settings::default();
+    settings.lsps1_max_client_balance_sats = 50_000;
+    let params = |lsp_balance_sat, client_balance_sat, channel_expiry_blocks| {
+        serde_json::from_value::<CreateOrderParams>(json!({
+            "lsp_balance_sat": lsp_balance_sat,
+            "client_balance_sat": client_balance_sat,
+            "required_channel_confirmations": 0,
+            "funding_confirms_within_blocks": 6,
+            "channel_expiry_blocks": channel_expiry_blocks,
+            "token": "",
+            "refund_onchain_address": null,
+            "announce_channel": false,
+        }))
+        .unwrap()
+    };
+    assert_eq!(
+        check_order(&settings, &params("1000000", "0", 1000)),
+        Ok(())
+    );
+    assert_eq!(
+        check_order(&settings, &params("1000000", "50001", 1000)),
+        Err(RpcError::property(
+            OPTION_MISMATCH,
+            "Option mismatch",
+            "client_balance_sat"
+        ))
+    );
+    assert_eq!(
+        check_order(&settings, &params("99999", "0", 1000)),
+        Err(RpcError::property(
+            OPTION_MISMATCH,
+            "Option mismatch",
+            "lsp_balance_sat"
+        ))
+    );
+    assert_eq!(
+        check_order(&settings, &params("1000000", "0", 13141)),
+        Err(RpcError::property(
+            OPTION_MISMATCH,
+            "Option mismatch",
+            "channel_expiry_blocks"
+        ))
+    );
+    assert_eq!(
+        check_order(&settings, &params(u64::MAX.to_string().as_str(), "1", 1000)),
+        Err(RpcError::property(
+            OPTION_MISMATCH,
+            "Option mismatch",
+            "lsp_balance_sat"
+        ))
+    );
+    settings.lsps1_max_channel_sats = u64::MAX;
+    settings.lsps1_fee_ppm = 0;
+    assert_eq!(
+        check_order(
+            &settings,
+            &params((u64::MAX / 100).to_string().as_str(), "0", 1000)
+        ),
+        Err(RpcError::property(
+            OPTION_MISMATCH,
+            "Option mismatch",
+            "lsp_balance_sat"
+        ))
+    );
+}
+
+#[test]
+fn test_check_open_orders() {
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use lightning::ln::PaymentHash;
+
+    let mut settings = Settings::default();
+    settings.lsps1_max_open_orders_per_client = 2;
+    settings.lsps1_max_open_orders = 3;
+    let client = |byte| {
+        PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[byte; 32]).unwrap(),
+        )
+    };
+    let order = |counterparty, payment_state| Lsps1Order {
+        order_id: hex::encode(random::<[u8; 16]>()),
+        counterparty,
+        lsp_balance_sat: 1_000_000,
+        client_balance_sat: 0,
+        required_channel_confirmations: 0,
+        funding_confirms_within_blocks: 6,
+        channel_expiry_blocks: 1000,
+        token: None,
+        announce_channel: false,
+        fee_total_sat: 12_000,
+        order_total_sat: 12_000,
+        payment_hash: PaymentHash(random()),
+        bolt11_invoice: String::new(),
+        onchain_address: String::new(),
+        min_onchain_payment_confirmations: 1,
+        refund_onchain_address: None,
+        expires_at: OffsetDateTime::now_utc(),
+        order_state: OrderState::Created,
+        payment_state,
+        user_channel_id: None,
+        funding_outpoint: None,
+        funded_at: None,
+        created_at: OffsetDateTime::now_utc(),
+        updated_at: OffsetDateTime::now_utc(),
+    };
+    let too_many = Err(RpcError::new(TOO_MANY_ORDERS, "Too many unpaid orders"));
+    let mut orders = vec![
+        order(client(1), PaymentState::ExpectPayment),
+        order(client(1), PaymentState::Paid),
+    ];
+    assert_eq!(check_open_orders(&settings, &orders, &client(1)), Ok(()));
+    orders.push(order(client(1), PaymentState::ExpectPayment));
+    assert_eq!(check_open_orders(&settings, &orders, &client(1)), too_many);
+    assert_eq!(check_open_orders(&settings, &orders, &client(2)), Ok(()));
+    orders.push(order(client(2), PaymentState::ExpectPayment));
+    assert_eq!(check_open_orders(&settings, &orders, &client(3)), too_many);
+}
+
+#[test]
+fn test_supported_protocols() {
+    let mut settings = Settings::default();
+    assert_eq!(supported_protocols(&settings), vec![2]);
+    settings.lsps1_enable = true;
+    assert_eq!(supported_protocols(&settings), vec![1, 2]);
+}
+
+#[test]
+fn test_order_fee_sat() {
+    let settings = Settings::default();
+    assert_eq!(order_fee_sat(&settings, 0), Some(2000));
+    assert_eq!(order_fee_sat(&settings, 1_000_000), Some(12_000));
+    assert_eq!(order_fee_sat(&settings, u64::MAX), None);
+}
@@ -0,0 +1,392 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use bitcoin::secp256k1::PublicKey;
+use chrono::DateTime;
+use lightning_liquidity::events::Event::{LSPS2Client, LSPS2Service};
+use lightning_liquidity::lsps2::event::{LSPS2ClientEvent, LSPS2ServiceEvent};
+use lightning_liquidity::lsps2::msgs::{OpeningFeeParams, RawOpeningFeeParams};
+use lightning_liquidity::lsps2::service::LSPS2ServiceHandler;
+use log::{debug, info, warn};
+use rand::random;
+use time::OffsetDateTime;
+
+use crate::database::lsps2::{
+    JitChannel, JitChannelStatus, Lsps2Offer, Lsps2Token, JIT_USER_CHANNEL_ID_START,
+};
+use crate::database::{microsecond_timestamp, LdkDatabase};
+use crate::log_error;
+use crate::settings::Settings;
+
+use super::controller::{AsyncAPIRequests, FundingBatch};
+use super::{ldk_error, ChannelManager, FundingOptions, KuutamoCustomMessageHandler};
+
+// Based on Bolt#11 we use 9 for cltv_expiry_delta of the invoices paying into JIT channels.
+const CLTV_EXPIRY_DELTA: u32 = 9;
+
+/// Sells JIT channels to the LSPS2 clients that present a token from the database, at the fees of the token.
+/// It also handles all the LSPS2 events, including the answers for our client.
+pub(crate) struct Lsps2Service {
+    settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
+    channel_manager: Arc<ChannelManager>,
+    kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+    async_api_requests: Arc<AsyncAPIRequests>,
+}
+
+impl Lsps2Service {
+    pub fn new(
+        settings: Arc<Settings>,
+        database: Arc<LdkDatabase>,
+        channel_manager: Arc<ChannelManager>,
+        kuutamo_handler: Arc<KuutamoCustomMessageHandler>,
+        async_api_requests: Arc<AsyncAPIRequests>,
+    ) -> Lsps2Service {
+        Lsps2Service {
+            settings,
+            database,
+            channel_manager,
+            kuutamo_handler,
+            async_api_requests,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let result = match self
+                .kuutamo_handler
+                .liquidity_manager
+                .next_event_async()
+                .await
+            {
+                LSPS2Service(LSPS2ServiceEvent::GetInfo {
+                    request_id,
+                    counterparty_node_id,
+                    token,
+                }) => {
+                    debug!("Response LSPS2 GetInfo to {counterparty_node_id}");
+                    match self.offer_fees(counterparty_node_id, token).await {
+                        Ok(Some(params)) => self
+                            .handler()
+                            .opening_fee_params_generated(
+                                &counterparty_node_id,
+                                request_id,
+                                vec![params],
+                            )
+                            .map_err(ldk_error),
+                        Ok(None) => {
+                            info!(
+                                "LSPS2 client {counterparty_node_id} did not provide a valid token"
+                            );
+                            self.handler()
+                                .invalid_token_provided(&counterparty_node_id, request_id)
+                                .map_err(ldk_error)
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                LSPS2Service(LSPS2ServiceEvent::BuyRequest {
+                    request_id,
+                    counterparty_node_id,
+                    opening_fee_params,
+                    payment_size_msat,
+                }) => {
+                    debug!("Response LSPS2 BuyRequest to {counterparty_node_id}");
+                    match self
+                        .sell_channel(counterparty_node_id, &opening_fee_params, payment_size_msat)
+                        .await
+                    {
+                        Ok(Some(jit_channel)) => self
+                            .handler()
+                            .invoice_parameters_generated(
+                                &counterparty_node_id,
+                                request_id,
+                                jit_channel.intercept_scid,
+                                CLTV_EXPIRY_DELTA,
+                                // client_trusts_lsp
+                                true,
+                                jit_channel.user_channel_id,
+                            )
+                            .map_err(ldk_error),
+                        Ok(None) => Ok(()),
+                        Err(e) => Err(e),
+                    }
+                }
+                LSPS2Service(LSPS2ServiceEvent::OpenChannel {
+                    their_network_key,
+                    amt_to_forward_msat,
+                    opening_fee_msat,
+                    user_channel_id,
+                    intercept_scid,
+                }) => {
+                    self.open_channel(
+                        their_network_key,
+                        amt_to_forward_msat,
+                        opening_fee_msat,
+                        user_channel_id,
+                        intercept_scid,
+                    )
+                    .await
+                }
+                // The answers to our own requests as a client, see LspClient.
+                LSPS2Client(LSPS2ClientEvent::OpeningParametersReady {
+                    request_id,
+                    opening_fee_params_menu,
+                    ..
+                }) => {
+                    self.async_api_requests
+                        .lsps2_opening_params
+                        .respond(&request_id, opening_fee_params_menu)
+                        .await;
+                    Ok(())
+                }
+                LSPS2Client(LSPS2ClientEvent::InvoiceParametersReady {
+                    request_id,
+                    intercept_scid,
+                    cltv_expiry_delta,
+                    ..
+                }) => {
+                    self.async_api_requests
+                        .lsps2_invoice_params
+                        .respond(&request_id, (intercept_scid, cltv_expiry_delta))
+                        .await;
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                log_error(&e);
+            }
+        }
+    }
+
+    fn handler(&self) -> &LSPS2ServiceHandler<Arc<ChannelManager>> {
+        self.kuutamo_handler
+            .liquidity_manager
+            .lsps2_service_handler()
+            .expect("lsps2 handler should be set")
+    }
+
+    /// The fees of the token, None if the client has no token or it is not in the database.
+    async fn offer_fees(
+        &self,
+        counterparty_node_id: PublicKey,
+        token: Option<String>,
+    ) -> Result<Option<RawOpeningFeeParams>> {
+        let Some(token) = token else {
+            return Ok(None);
+        };
+        let Some(token) = self.database.fetch_lsps2_token(&token).await? else {
+            return Ok(None);
+        };
+        let params = opening_fee_params(&token, &self.settings, SystemTime::now());
+        // The channel the client buys with these fees is accounted to the token, also after a restart.
+        self.database
+            .persist_lsps2_offer(&Lsps2Offer {
+                counterparty: counterparty_node_id,
+                token: token.token,
+                min_fee_msat: params.min_fee_msat,
+                proportional: params.proportional,
+                valid_until: OffsetDateTime::from_unix_timestamp(params.valid_until.timestamp())?,
+            })
+            .await?;
+        Ok(Some(params))
+    }
+
+    /// Record the JIT channel the client buys, None if the payment is outside of the offered limits.
+    async fn sell_channel(
+        &self,
+        counterparty_node_id: PublicKey,
+        opening_fee_params: &OpeningFeeParams,
+        payment_size_msat: Option<u64>,
+    ) -> Result<Option<JitChannel>> {
+        if let Some(payment_size_msat) = payment_size_msat {
+            if payment_size_msat < opening_fee_params.min_payment_size_msat
+                || payment_size_msat > opening_fee_params.max_payment_size_msat
+            {
+                // Swallow the request, there is no error response for it.
+                warn!("LSPS2 client {counterparty_node_id} wants a JIT channel for {payment_size_msat} msat outside of the offered limits");
+                return Ok(None);
+            }
+        }
+        let valid_until =
+            OffsetDateTime::from_unix_timestamp(opening_fee_params.valid_until.timestamp())?;
+        let token = self
+            .database
+            .fetch_lsps2_offers(&counterparty_node_id, &valid_until)
+            .await?
+            .into_iter()
+            .find(|offer| {
+                offer.min_fee_msat == opening_fee_params.min_fee_msat
+                    && offer.proportional == opening_fee_params.proportional
+            })
+            .map(|offer| offer.token);
+        let jit_channel = JitChannel::new(
+            JIT_USER_CHANNEL_ID_START + (random::<u64>() / 2) as u128,
+            token,
+            counterparty_node_id,
+            self.channel_manager.get_intercept_scid(),
+            payment_size_msat,
+        );
+        self.database.persist_jit_channel(&jit_channel).await?;
+        info!(
+            "Sold JIT channel {} to {counterparty_node_id}",
+            jit_channel.user_channel_id
+        );
+        Ok(Some(jit_channel))
+    }
+
+    async fn open_channel(
+        &self,
+        their_network_key: PublicKey,
+        amt_to_forward_msat: u64,
+        opening_fee_msat: u64,
+        user_channel_id: u128,
+        intercept_scid: u64,
+    ) -> Result<()> {
+        let mut jit_channel = match self.database.fetch_jit_channel(user_channel_id).await? {
+            Some(jit_channel) => jit_channel,
+            None => JitChannel::new(
+                user_channel_id,
+                None,
+                their_network_key,
+                intercept_scid,
+                None,
+            ),
+        };
+        let channel_value_sats = channel_value_sats(
+            amt_to_forward_msat,
+            self.settings.lsps2_channel_over_provisioning_ppm,
+        );
+        info!("Opening JIT channel {user_channel_id} of {channel_value_sats} sats with {their_network_key}");
+        jit_channel.opening_fee_msat = Some(opening_fee_msat);
+        jit_channel.channel_value_sats = Some(channel_value_sats);
+        jit_channel.updated_at = microsecond_timestamp();
+
+        let mut config = *self.channel_manager.get_current_default_configuration();
+        config.channel_handshake_config.announced_channel = false;
+        let temporary_channel_id = match self
+            .channel_manager
+            .create_channel(
+                their_network_key,
+                channel_value_sats,
+                0,
+                user_channel_id,
+                None,
+                Some(config),
+            )
+            .map_err(ldk_error)
+        {
+            Ok(channel_id) => channel_id,
+            Err(e) => {
+                jit_channel.status = JitChannelStatus::Failed;
+                self.database.persist_jit_channel(&jit_channel).await?;
+                return Err(e);
+            }
+        };
+        jit_channel.status = JitChannelStatus::Opening;
+        self.database.persist_jit_channel(&jit_channel).await?;
+
+        // The channel is funded by the event handler like any other, waiting for it must not block the LSPS2 events.
+        let batch = Arc::new(FundingBatch::new(
+            FundingOptions::default(),
+            vec![(
+                user_channel_id as u64,
+                temporary_channel_id,
+                their_network_key,
+            )],
+        ));
+        let receiver = self
+            .async_api_requests
+            .funding_transactions
+            .insert(user_channel_id as u64, batch)
+            .await;
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            let result = match receiver.await {
+                Ok(Ok(transaction)) => {
+                    database
+                        .persist_initializing_channel(
+                            &temporary_channel_id,
+                            false,
+                            &their_network_key,
+                            &transaction.txid(),
+                        )
+                        .await
+                }
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(anyhow!(e)),
+            };
+            if let Err(e) = result {
+                warn!("JIT channel {user_channel_id} failed to open: {e}");
+                jit_channel_failed(&database, user_channel_id).await;
+            }
+        });
+        Ok(())
+    }
+}
+
+async fn jit_channel_failed(database: &LdkDatabase, user_channel_id: u128) {
+    match database.fetch_jit_channel(user_channel_id).await {
+        Ok(Some(mut jit_channel)) => {
+            jit_channel.status = JitChannelStatus::Failed;
+            jit_channel.updated_at = microsecond_timestamp();
+            if let Err(e) = database.persist_jit_channel(&jit_channel).await {
+                log_error(&e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log_error(&e),
+    }
+}
+
+/// The fees offered to the clients with this token, they can be used to buy a channel until they expire.
+pub(crate) fn opening_fee_params(
+    token: &Lsps2Token,
+    settings: &Settings,
+    now: SystemTime,
+) -> RawOpeningFeeParams {
+    let now = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    RawOpeningFeeParams {
+        min_fee_msat: token.min_fee_msat,
+        proportional: token.proportional,
+        valid_until: DateTime::from_timestamp((now + token.valid_for_secs as u64) as i64, 0)
+            .unwrap_or_default(),
+        min_lifetime: settings.lsps2_min_lifetime,
+        max_client_to_self_delay: settings.lsps2_max_client_to_self_delay,
+        min_payment_size_msat: token.min_payment_size_msat,
+        max_payment_size_msat: token.max_payment_size_msat,
+    }
+}
+
+/// Large enough to forward the payment and keep the reserve, with some capacity to spare for the next payments.
+pub(crate) fn channel_value_sats(amt_to_forward_msat: u64, over_provisioning_ppm: u32) -> u64 {
+    let amount_sats = (amt_to_forward_msat + 999) / 1000;
+    amount_sats + amount_sats * over_provisioning_ppm as u64 / 1_000_000
+}
+
+#[test]
+fn test_opening_fee_params() {
+    let token = Lsps2Token::new("token".to_string(), 2000, 1000, 600, 10_000, 5_000_000);
+    let settings = Settings::default();
+    let now = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    let params = opening_fee_params(&token, &settings, now);
+    assert_eq!(params.min_fee_msat, 2000);
+    assert_eq!(params.proportional, 1000);
+    assert_eq!(params.valid_until.timestamp(), 1_700_000_600);
+    assert_eq!(params.min_lifetime, 4320);
+    assert_eq!(params.max_client_to_self_delay, 3600);
+    assert_eq!(params.min_payment_size_msat, 10_000);
+    assert_eq!(params.max_payment_size_msat, 5_000_000);
+}
+
+#[test]
+fn test_channel_value_sats() {
+    assert_eq!(channel_value_sats(1_000_000, 0), 1000);
+    assert_eq!(channel_value_sats(1_000_001, 0), 1001);
+    assert_eq!(channel_value_sats(1_000_000, 100_000), 1100);
+}
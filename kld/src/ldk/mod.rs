@@ -1,25 +1,37 @@
+mod autofee;
+mod backup;
+mod channel_policy;
 pub mod channel_utils;
 pub mod controller;
 mod event_handler;
 pub mod lightning_interface;
+mod lsp_client;
+mod lsps1;
+mod lsps2;
 mod peer_manager;
+mod rapid_gossip_sync;
+mod sweeper;
 
 use std::sync::{Arc, RwLock};
 
-use crate::database::LdkDatabase;
+use crate::database::{LdkDatabase, WalletDatabase};
 use crate::logger::KldLogger;
 use anyhow::anyhow;
 use bitcoin::secp256k1::PublicKey;
 use lightning::ln::peer_handler::CustomMessageHandler;
 use lightning::{
     chain::{chainmonitor, Filter},
-    events::HTLCDestination,
+    events::{
+        bump_transaction::{self, BumpTransactionEventHandler},
+        HTLCDestination, PathFailure,
+    },
     ln::{
         channelmanager::{PaymentSendFailure, RetryableSendFailure, SimpleArcChannelManager},
         features::{InitFeatures, NodeFeatures},
         msgs::{DecodeError, LightningError},
         wire::CustomMessageReader,
     },
+    offers::parse::Bolt12SemanticError,
     onion_message::messenger::SimpleArcOnionMessenger,
     routing::{
         gossip,
@@ -32,10 +44,16 @@ use lightning::{
 use lightning_invoice::SignOrCreationError;
 
 pub use controller::Controller;
-pub use lightning_interface::{LightningInterface, OpenChannelResult, Peer, PeerStatus};
+pub use lightning_interface::{
+    ChannelOrder, ChannelTarget, FundingOptions, LightningInterface, LspInfo, LspOrder,
+    OpenChannelResult, PathEstimate, PaymentOptions, Peer, PeerStatus, RouteQuery,
+};
 use log::warn;
+use lsps1::Lsps1Messages;
+pub use lsps1::Lsps1Options;
 
 use crate::bitcoind::BitcoindClient;
+use crate::wallet::Wallet;
 
 /// The minimum feerate we are allowed to send, as specify by LDK (sats/kwu).
 pub static MIN_FEERATE: u32 = 2000;
@@ -59,10 +77,13 @@ pub(crate) type LiquidityManager = lightning_liquidity::LiquidityManager<
 
 pub(crate) struct KuutamoCustomMessageHandler {
     liquidity_manager: LiquidityManager,
+    lsps1_messages: Lsps1Messages,
 }
 
+type RawLspsMessage = <LiquidityManager as CustomMessageReader>::CustomMessage;
+
 impl lightning::ln::wire::CustomMessageReader for KuutamoCustomMessageHandler {
-    type CustomMessage = <LiquidityManager as CustomMessageReader>::CustomMessage;
+    type CustomMessage = RawLspsMessage;
     fn read<RD: lightning::io::Read>(
         &self,
         message_type: u16,
@@ -78,12 +99,22 @@ impl CustomMessageHandler for KuutamoCustomMessageHandler {
         msg: Self::CustomMessage,
         sender_node_id: &PublicKey,
     ) -> Result<(), LightningError> {
+        if self.lsps1_messages.handle(sender_node_id, &msg.payload) {
+            return Ok(());
+        }
         self.liquidity_manager
             .handle_custom_message(msg, sender_node_id)
     }
 
     fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, Self::CustomMessage)> {
-        self.liquidity_manager.get_and_clear_pending_msg()
+        let mut messages = self.liquidity_manager.get_and_clear_pending_msg();
+        messages.extend(
+            self.lsps1_messages
+                .get_and_clear_pending_msg()
+                .into_iter()
+                .map(|(node_id, payload)| (node_id, RawLspsMessage { payload })),
+        );
+        messages
     }
 
     fn provided_node_features(&self) -> NodeFeatures {
@@ -103,6 +134,13 @@ pub(crate) type OnionMessenger =
 
 pub type Scorer = ProbabilisticScorer<Arc<NetworkGraph>, Arc<KldLogger>>;
 
+pub(crate) type BumpTxEventHandler = BumpTransactionEventHandler<
+    Arc<BitcoindClient>,
+    Arc<bump_transaction::Wallet<Arc<Wallet<WalletDatabase, BitcoindClient>>, Arc<KldLogger>>>,
+    Arc<KeysManager>,
+    Arc<KldLogger>,
+>;
+
 pub(crate) type KldRouter = DefaultRouter<
     Arc<NetworkGraph>,
     Arc<KldLogger>,
@@ -155,6 +193,10 @@ pub fn sign_or_creation_error(error: SignOrCreationError) -> anyhow::Error {
     }
 }
 
+pub fn bolt12_semantic_error(error: Bolt12SemanticError) -> anyhow::Error {
+    anyhow!("Offer error: {error:?}")
+}
+
 pub fn payment_send_failure(error: PaymentSendFailure) -> anyhow::Error {
     match error {
         PaymentSendFailure::ParameterError(api_error) => ldk_error(api_error),
@@ -223,3 +265,17 @@ pub fn htlc_destination_to_string(destination: &HTLCDestination) -> String {
         }
     }
 }
+
+pub fn path_failure_to_string(failure: &PathFailure) -> String {
+    match failure {
+        PathFailure::InitialSend { err } => {
+            format!("Initial send failed. {}", ldk_error(err.clone()))
+        }
+        PathFailure::OnPath {
+            network_update: Some(update),
+        } => format!("Failed on path with network update {update:?}"),
+        PathFailure::OnPath {
+            network_update: None,
+        } => "Failed on path".to_string(),
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::info;
+
+use crate::logger::KldLogger;
+
+use super::NetworkGraph;
+
+pub(crate) type RapidGossipSync =
+    lightning_rapid_gossip_sync::RapidGossipSync<Arc<NetworkGraph>, Arc<KldLogger>>;
+
+// Snapshots are large for the first sync, give slow connections some time.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Where the Rapid Gossip Sync snapshots come from.
+#[derive(Debug, PartialEq)]
+pub(crate) enum SnapshotSource {
+    /// A server that serves the gossip since a timestamp at <url>/<timestamp>.
+    Url(String),
+    /// A snapshot made elsewhere, it is only applied once at startup.
+    File(PathBuf),
+}
+
+impl SnapshotSource {
+    pub fn parse(source: &str) -> SnapshotSource {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            SnapshotSource::Url(source.trim_end_matches('/').to_string())
+        } else {
+            SnapshotSource::File(PathBuf::from(source))
+        }
+    }
+}
+
+/// Loads Rapid Gossip Sync snapshots into the network graph, so that a new node can route
+/// payments before the P2P gossip fills the graph.
+pub(crate) struct GossipSnapshots {
+    source: SnapshotSource,
+    rapid_gossip_sync: Arc<RapidGossipSync>,
+    network_graph: Arc<NetworkGraph>,
+    client: reqwest::Client,
+}
+
+impl GossipSnapshots {
+    pub fn new(
+        source: SnapshotSource,
+        rapid_gossip_sync: Arc<RapidGossipSync>,
+        network_graph: Arc<NetworkGraph>,
+    ) -> Result<GossipSnapshots> {
+        Ok(GossipSnapshots {
+            source,
+            rapid_gossip_sync,
+            network_graph,
+            client: reqwest::Client::builder()
+                .timeout(DOWNLOAD_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    /// True if the source has more snapshots after the first one.
+    pub fn is_refreshed(&self) -> bool {
+        matches!(self.source, SnapshotSource::Url(_))
+    }
+
+    /// Apply the gossip since the last sync.
+    pub async fn sync(&self) -> Result<()> {
+        // The timestamp is persisted together with the graph, so the graph always has the gossip before it.
+        // If the graph was lost the timestamp is gone too and the whole gossip is fetched again.
+        let last_sync_timestamp = self
+            .network_graph
+            .get_last_rapid_gossip_sync_timestamp()
+            .unwrap_or_default();
+        let snapshot = match &self.source {
+            SnapshotSource::Url(url) => self
+                .client
+                .get(snapshot_url(url, last_sync_timestamp))
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec(),
+            SnapshotSource::File(path) => tokio::fs::read(path).await?,
+        };
+        let timestamp = self
+            .rapid_gossip_sync
+            .update_network_graph(&snapshot)
+            .map_err(|e| anyhow!("Invalid gossip snapshot: {e:?}"))?;
+        let graph = self.network_graph.read_only();
+        info!(
+            "Rapid gossip sync at {timestamp}, the network graph has {} nodes and {} channels",
+            graph.nodes().len(),
+            graph.channels().len()
+        );
+        Ok(())
+    }
+}
+
+fn snapshot_url(url: &str, last_sync_timestamp: u32) -> String {
+    format!("{url}/{last_sync_timestamp}")
+}
+
+#[test]
+fn test_snapshot_source() {
+    assert_eq!(
+        SnapshotSource::Url("https://rapidsync.lightningdevkit.org/snapshot".to_string()),
+        SnapshotSource::parse("https://rapidsync.lightningdevkit.org/snapshot/")
+    );
+    assert_eq!(
+        SnapshotSource::File(PathBuf::from("/var/lib/kld/snapshot.bin")),
+        SnapshotSource::parse("/var/lib/kld/snapshot.bin")
+    );
+    assert_eq!(
+        "https://rapidsync.lightningdevkit.org/snapshot/1700000000",
+        snapshot_url(
+            "https://rapidsync.lightningdevkit.org/snapshot",
+            1_700_000_000
+        )
+    );
+}
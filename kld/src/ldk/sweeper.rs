@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::OutPoint;
+use lightning::chain::chaininterface::{
+    ConfirmationTarget, FeeEstimator, FEERATE_FLOOR_SATS_PER_KW,
+};
+use lightning::chain::channelmonitor::ANTI_REORG_DELAY;
+use lightning::sign::{KeysManager, SpendableOutputDescriptor};
+use log::{info, warn};
+
+use crate::bitcoind::BitcoindClient;
+use crate::database::sweep::{OutputSweep, SweepStatus};
+use crate::database::{LdkDatabase, WalletDatabase};
+use crate::wallet::{Wallet, WalletInterface};
+
+// A replacement has to pay at least the default incremental relay fee (1 sat/vbyte) more than the original.
+const INCREMENTAL_RELAY_FEE: u32 = 253;
+// Bumping stops once the fee rate would exceed this multiple of the current estimate.
+const MAX_FEE_MULTIPLE: u32 = 4;
+
+/// Sends the outputs that LDK hands us after channels close back to the on-chain wallet.
+/// Every output that is not spent yet goes into a single sweep transaction. On each new block the
+/// sweep is re-broadcast, or replaced with a higher fee while it is unconfirmed. The outputs are only
+/// marked as spent once the sweep is buried too deep to be reorged out.
+pub(crate) struct OutputSweeper {
+    database: Arc<LdkDatabase>,
+    keys_manager: Arc<KeysManager>,
+    bitcoind_client: Arc<BitcoindClient>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+}
+
+impl OutputSweeper {
+    pub fn new(
+        database: Arc<LdkDatabase>,
+        keys_manager: Arc<KeysManager>,
+        bitcoind_client: Arc<BitcoindClient>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    ) -> OutputSweeper {
+        OutputSweeper {
+            database,
+            keys_manager,
+            bitcoind_client,
+            wallet,
+        }
+    }
+
+    pub async fn on_new_block(&self, height: u32) -> Result<()> {
+        let mut to_sweep: Vec<SpendableOutputDescriptor> = self
+            .database
+            .fetch_spendable_outputs()
+            .await?
+            .into_iter()
+            .filter(|output| !output.is_spent)
+            .map(|output| output.descriptor)
+            .collect();
+        if to_sweep.is_empty() {
+            return Ok(());
+        }
+        let unspent: HashSet<OutPoint> = to_sweep.iter().map(outpoint).collect();
+
+        // The latest sweep that is not mined yet, any earlier ones are double spent by it.
+        let mut active = None;
+        for mut sweep in self.database.fetch_output_sweeps(None).await? {
+            if sweep.status == SweepStatus::Confirmed
+                || !sweep.outputs().iter().any(|o| unspent.contains(o))
+            {
+                continue;
+            }
+            // The sweep pays to our wallet so the wallet knows when it is mined.
+            let confirmation_height = self.wallet.confirmation_height(&sweep.txid)?;
+            let Some(mined_at) = confirmation_height else {
+                if sweep.confirmation_height.take().is_some() {
+                    warn!("Sweep {} was reorged out", sweep.txid);
+                    self.database.persist_output_sweep(&sweep).await?;
+                }
+                if sweep.status == SweepStatus::Pending {
+                    active = Some(sweep);
+                }
+                continue;
+            };
+            let outputs = sweep.outputs();
+            to_sweep.retain(|descriptor| !outputs.contains(&outpoint(descriptor)));
+            sweep.confirmation_height = Some(mined_at);
+            if height + 1 >= mined_at + ANTI_REORG_DELAY {
+                info!("Sweep {} is confirmed", sweep.txid);
+                self.database.mark_spendable_outputs_spent(&outputs).await?;
+                sweep.status = SweepStatus::Confirmed;
+            }
+            self.database.persist_output_sweep(&sweep).await?;
+        }
+        if to_sweep.is_empty() {
+            return Ok(());
+        }
+
+        let estimate = self
+            .bitcoind_client
+            .get_est_sat_per_1000_weight(ConfirmationTarget::OnChainSweep)
+            .max(FEERATE_FLOOR_SATS_PER_KW);
+        let fee_rate = match &active {
+            None => estimate,
+            Some(sweep) => {
+                let outputs: HashSet<OutPoint> = sweep.outputs().into_iter().collect();
+                let outputs_changed = to_sweep
+                    .iter()
+                    .any(|descriptor| !outputs.contains(&outpoint(descriptor)));
+                let bumped = (sweep.broadcast_height < height)
+                    .then(|| bump_fee_rate(sweep.fee_rate, estimate))
+                    .flatten();
+                match bumped {
+                    Some(fee_rate) => fee_rate,
+                    // New outputs can only be added by replacing the sweep.
+                    None if outputs_changed => sweep.fee_rate + INCREMENTAL_RELAY_FEE,
+                    None => {
+                        if let Err(e) = self
+                            .bitcoind_client
+                            .send_transaction(&sweep.transaction)
+                            .await
+                        {
+                            warn!("Failed to re-broadcast sweep {}: {e}", sweep.txid);
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        let destination_address = self.wallet.new_internal_address()?;
+        let transaction = self
+            .keys_manager
+            .spend_spendable_outputs(
+                &to_sweep.iter().collect::<Vec<_>>()[..],
+                Vec::new(),
+                destination_address.script_pubkey(),
+                fee_rate,
+                Some(LockTime::from_height(height)?),
+                &Secp256k1::new(),
+            )
+            .map_err(|()| anyhow!("Failed to build sweep transaction"))?;
+        self.bitcoind_client.send_transaction(&transaction).await?;
+        info!(
+            "Sweeping {} spendable outputs to {} at {fee_rate} sats per kw in {}",
+            to_sweep.len(),
+            destination_address.address,
+            transaction.txid()
+        );
+        if let Some(mut replaced) = active {
+            replaced.status = SweepStatus::Replaced;
+            self.database.persist_output_sweep(&replaced).await?;
+        }
+        self.database
+            .persist_output_sweep(&OutputSweep::new(transaction, fee_rate, height))
+            .await
+    }
+}
+
+fn outpoint(descriptor: &SpendableOutputDescriptor) -> OutPoint {
+    match descriptor {
+        SpendableOutputDescriptor::StaticOutput { outpoint, .. } => {
+            outpoint.into_bitcoin_outpoint()
+        }
+        SpendableOutputDescriptor::DelayedPaymentOutput(descriptor) => {
+            descriptor.outpoint.into_bitcoin_outpoint()
+        }
+        SpendableOutputDescriptor::StaticPaymentOutput(descriptor) => {
+            descriptor.outpoint.into_bitcoin_outpoint()
+        }
+    }
+}
+
+/// The fee rate to replace an unconfirmed sweep with, or None if it already pays as much as we are willing to.
+fn bump_fee_rate(previous: u32, estimate: u32) -> Option<u32> {
+    let bumped = estimate.max(previous + (previous / 4).max(INCREMENTAL_RELAY_FEE));
+    (bumped <= estimate * MAX_FEE_MULTIPLE).then_some(bumped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::bump_fee_rate;
+
+    #[test]
+    fn test_bump_fee_rate() {
+        assert_eq!(Some(2000), bump_fee_rate(500, 2000));
+        assert_eq!(Some(1253), bump_fee_rate(1000, 1000));
+        assert_eq!(Some(2500), bump_fee_rate(2000, 1000));
+        assert_eq!(None, bump_fee_rate(3500, 1000));
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use lightning::chain::chaininterface::ConfirmationTarget;
+
+/// Every target LDK asks fee rates for.
+pub const CONFIRMATION_TARGETS: [ConfirmationTarget; 6] = [
+    ConfirmationTarget::OnChainSweep,
+    ConfirmationTarget::MinAllowedAnchorChannelRemoteFee,
+    ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee,
+    ConfirmationTarget::AnchorChannelFee,
+    ConfirmationTarget::NonAnchorChannelFee,
+    ConfirmationTarget::ChannelCloseMinimum,
+];
+
+/// Where fee rate estimates come from. Sources are tried in the configured order until one succeeds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FeeSource {
+    /// estimatesmartfee of the connected bitcoind.
+    Bitcoind,
+    /// The fee-estimates endpoint of an esplora compatible API.
+    Esplora,
+    /// The configured static fee rates, this never fails.
+    Static,
+}
+
+impl fmt::Display for FeeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeeSource::Bitcoind => f.write_str("bitcoind"),
+            FeeSource::Esplora => f.write_str("esplora"),
+            FeeSource::Static => f.write_str("static"),
+        }
+    }
+}
+
+impl FromStr for FeeSource {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<FeeSource, Self::Err> {
+        match input {
+            "bitcoind" => Ok(FeeSource::Bitcoind),
+            "esplora" => Ok(FeeSource::Esplora),
+            "static" => Ok(FeeSource::Static),
+            _ => Err("not a valid value, must be one of: bitcoind, esplora or static"),
+        }
+    }
+}
+
+/// A fee rate in sats per 1000 weight for one confirmation target, written as `target=fee_rate`
+/// with the target in snake case, e.g. `on_chain_sweep=5000`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TargetFeeRate {
+    pub target: ConfirmationTarget,
+    pub fee_rate: u32,
+}
+
+impl FromStr for TargetFeeRate {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<TargetFeeRate, Self::Err> {
+        let Some((target, fee_rate)) = input.split_once('=') else {
+            bail!("{input} is not of the form target=fee_rate")
+        };
+        let target = CONFIRMATION_TARGETS
+            .into_iter()
+            .find(|t| target_name(t) == target)
+            .ok_or_else(|| anyhow!("{target} is not a confirmation target"))?;
+        Ok(TargetFeeRate {
+            target,
+            fee_rate: fee_rate.parse()?,
+        })
+    }
+}
+
+pub fn target_name(target: &ConfirmationTarget) -> &'static str {
+    match target {
+        ConfirmationTarget::OnChainSweep => "on_chain_sweep",
+        ConfirmationTarget::MinAllowedAnchorChannelRemoteFee => {
+            "min_allowed_anchor_channel_remote_fee"
+        }
+        ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee => {
+            "min_allowed_non_anchor_channel_remote_fee"
+        }
+        ConfirmationTarget::AnchorChannelFee => "anchor_channel_fee",
+        ConfirmationTarget::NonAnchorChannelFee => "non_anchor_channel_fee",
+        ConfirmationTarget::ChannelCloseMinimum => "channel_close_minimum",
+    }
+}
@@ -1,9 +1,11 @@
 mod bitcoin_network;
+mod fee_estimation;
 
 use crate::api::SocketAddress;
 pub use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::PublicKey;
 use clap::{builder::OsStr, Parser};
+pub use fee_estimation::{target_name, FeeSource, TargetFeeRate, CONFIRMATION_TARGETS};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -85,6 +87,152 @@ pub struct Settings {
     #[arg(long, value_delimiter = ',', env = "KLD_PROBE_TARGETS")]
     pub probe_targets: Vec<PublicKey>,
 
+    /// Evaluate every inbound channel against the inbound channel policy instead of accepting all of them.
+    #[arg(long, env = "KLD_MANUALLY_ACCEPT_INBOUND_CHANNELS")]
+    pub manually_accept_inbound_channels: bool,
+    /// The smallest inbound channel in satoshis that will be accepted.
+    #[arg(long, default_value = "0", env = "KLD_INBOUND_CHANNEL_MIN_SATS")]
+    pub inbound_channel_min_sats: u64,
+    /// The largest inbound channel in satoshis that will be accepted.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_MAX_SATS")]
+    pub inbound_channel_max_sats: Option<u64>,
+    /// Only accept inbound channels from these nodes. Empty allows every node that is not denied.
+    #[arg(long, value_delimiter = ',', env = "KLD_INBOUND_CHANNEL_ALLOW_LIST")]
+    pub inbound_channel_allow_list: Vec<PublicKey>,
+    /// Never accept inbound channels from these nodes.
+    #[arg(long, value_delimiter = ',', env = "KLD_INBOUND_CHANNEL_DENY_LIST")]
+    pub inbound_channel_deny_list: Vec<PublicKey>,
+    /// Reject inbound channels that the peer does not want to announce.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_REQUIRE_ANNOUNCED")]
+    pub inbound_channel_require_announced: bool,
+    /// The number of channels a single peer can have waiting for confirmation before further inbound channels are rejected.
+    #[arg(long, env = "KLD_INBOUND_CHANNEL_MAX_PENDING_PER_PEER")]
+    pub inbound_channel_max_pending_per_peer: Option<usize>,
+    /// Peers whose inbound channels are accepted without waiting for confirmations.
+    #[arg(long, value_delimiter = ',', env = "KLD_TRUSTED_ZERO_CONF_PEERS")]
+    pub trusted_zero_conf_peers: Vec<PublicKey>,
+
+    /// The time interval in seconds to adjust channel forwarding fees, 0 will disable the feature
+    #[arg(long, default_value = "0", env = "KLD_AUTOFEE_INTERVAL")]
+    pub autofee_interval: u64,
+    /// The lowest proportional fee in millionths that automatic adjustment will set
+    #[arg(long, default_value = "1", env = "KLD_AUTOFEE_MIN_PPM")]
+    pub autofee_min_ppm: u32,
+    /// The highest proportional fee in millionths that automatic adjustment will set
+    #[arg(long, default_value = "2000", env = "KLD_AUTOFEE_MAX_PPM")]
+    pub autofee_max_ppm: u32,
+    /// How many hours of forwards count towards the demand of a channel
+    #[arg(long, default_value = "24", env = "KLD_AUTOFEE_LOOKBACK_HOURS")]
+    pub autofee_lookback_hours: u64,
+    /// The highest routing fee in millionths of the amount that a rebalance pays when the request sets no limit
+    #[arg(long, default_value = "1000", env = "KLD_REBALANCE_MAX_FEE_PPM")]
+    pub rebalance_max_fee_ppm: u32,
+
+    /// Sources of fee rate estimates, each one is tried in order until one succeeds
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "bitcoind,static",
+        env = "KLD_FEE_SOURCES"
+    )]
+    pub fee_sources: Vec<FeeSource>,
+    /// Base url of an esplora compatible API for the esplora fee source, e.g. https://mempool.space/api
+    #[arg(long, env = "KLD_FEE_ESPLORA_URL")]
+    pub fee_esplora_url: Option<String>,
+    /// The time interval in seconds to refresh fee rate estimates
+    #[arg(long, default_value = "60", env = "KLD_FEE_POLL_INTERVAL")]
+    pub fee_poll_interval: u64,
+    /// Fee rates in sats per 1000 weight used by the static fee source, as target=fee_rate
+    #[arg(long, value_delimiter = ',', env = "KLD_FEE_STATIC_RATES")]
+    pub fee_static_rates: Vec<TargetFeeRate>,
+    /// The lowest fee rates in sats per 1000 weight to use for confirmation targets, as target=fee_rate
+    #[arg(long, value_delimiter = ',', env = "KLD_FEE_MIN_RATES")]
+    pub fee_min_rates: Vec<TargetFeeRate>,
+    /// The highest fee rates in sats per 1000 weight to use for confirmation targets, as target=fee_rate
+    #[arg(long, value_delimiter = ',', env = "KLD_FEE_MAX_RATES")]
+    pub fee_max_rates: Vec<TargetFeeRate>,
+
+    /// Load the network graph from Rapid Gossip Sync snapshots, from a server url like
+    /// https://rapidsync.lightningdevkit.org/snapshot or once from a snapshot file. P2P gossip keeps updating the graph.
+    #[arg(long, env = "KLD_RGS_SOURCE")]
+    pub rgs_source: Option<String>,
+    /// The time interval in seconds to fetch new snapshots from the Rapid Gossip Sync server
+    #[arg(long, default_value = "3600", env = "KLD_RGS_INTERVAL")]
+    pub rgs_interval: u64,
+
+    /// Advertise the LSPS2 JIT channel service in the node features. Clients still need a token to buy channels.
+    #[arg(long, env = "KLD_LSPS2_ADVERTISE_SERVICE")]
+    pub lsps2_advertise_service: bool,
+    /// The number of blocks that JIT channels are promised to stay open for
+    #[arg(long, default_value = "4320", env = "KLD_LSPS2_MIN_LIFETIME")]
+    pub lsps2_min_lifetime: u32,
+    /// The largest to_self_delay in blocks that the clients of JIT channels can ask for
+    #[arg(
+        long,
+        default_value = "3600",
+        env = "KLD_LSPS2_MAX_CLIENT_TO_SELF_DELAY"
+    )]
+    pub lsps2_max_client_to_self_delay: u32,
+    /// Capacity added to JIT channels on top of the payment that opens them, in millionths of the payment
+    #[arg(
+        long,
+        default_value = "100000",
+        env = "KLD_LSPS2_CHANNEL_OVER_PROVISIONING_PPM"
+    )]
+    pub lsps2_channel_over_provisioning_ppm: u32,
+
+    /// Sell channels to LSPS1 clients that pay for them up front, with an invoice or on-chain.
+    #[arg(long, env = "KLD_LSPS1_ENABLE")]
+    pub lsps1_enable: bool,
+    /// The fee in satoshis of every LSPS1 channel, on top of the proportional fee
+    #[arg(long, default_value = "2000", env = "KLD_LSPS1_BASE_FEE_SATS")]
+    pub lsps1_base_fee_sats: u64,
+    /// The fee of LSPS1 channels in millionths of the balance on our side of the channel
+    #[arg(long, default_value = "10000", env = "KLD_LSPS1_FEE_PPM")]
+    pub lsps1_fee_ppm: u32,
+    /// The smallest LSPS1 channel in satoshis
+    #[arg(long, default_value = "100000", env = "KLD_LSPS1_MIN_CHANNEL_SATS")]
+    pub lsps1_min_channel_sats: u64,
+    /// The largest LSPS1 channel in satoshis
+    #[arg(long, default_value = "16777215", env = "KLD_LSPS1_MAX_CHANNEL_SATS")]
+    pub lsps1_max_channel_sats: u64,
+    /// The largest balance in satoshis that LSPS1 clients can buy on their side of the channel
+    #[arg(long, default_value = "0", env = "KLD_LSPS1_MAX_CLIENT_BALANCE_SATS")]
+    pub lsps1_max_client_balance_sats: u64,
+    /// The largest number of blocks that LSPS1 channels can be promised to stay open for
+    #[arg(
+        long,
+        default_value = "13140",
+        env = "KLD_LSPS1_MAX_CHANNEL_EXPIRY_BLOCKS"
+    )]
+    pub lsps1_max_channel_expiry_blocks: u32,
+    /// The time in seconds that LSPS1 orders can be paid for
+    #[arg(long, default_value = "3600", env = "KLD_LSPS1_ORDER_EXPIRY_SECS")]
+    pub lsps1_order_expiry_secs: u32,
+    /// The confirmations of on-chain payments before the channel of an LSPS1 order is opened
+    #[arg(
+        long,
+        default_value = "1",
+        env = "KLD_LSPS1_MIN_ONCHAIN_PAYMENT_CONFIRMATIONS"
+    )]
+    pub lsps1_min_onchain_payment_confirmations: u16,
+    /// The most unpaid LSPS1 orders that a client can have at a time
+    #[arg(
+        long,
+        default_value = "3",
+        env = "KLD_LSPS1_MAX_OPEN_ORDERS_PER_CLIENT"
+    )]
+    pub lsps1_max_open_orders_per_client: usize,
+    /// The most unpaid LSPS1 orders of all clients together
+    #[arg(long, default_value = "100", env = "KLD_LSPS1_MAX_OPEN_ORDERS")]
+    pub lsps1_max_open_orders: usize,
+
+    /// Start in recovery mode with this channel backup after the database was lost. The peers of the
+    /// channels in the backup are asked to force close them, the outputs paid to our static payment key
+    /// have to be swept manually.
+    #[arg(long, env = "KLD_RECOVER_FROM_BACKUP")]
+    pub recover_from_backup: Option<String>,
+
     /// The graceful period in seconds when a shutdown signal is received
     #[arg(long, default_value = "5", env = "KLD_SHUTDOWN_GRACEFUL_SEC")]
     pub shutdown_graceful_sec: u64,
@@ -104,8 +252,11 @@ impl Default for Settings {
 
 #[cfg(test)]
 mod test {
-    use crate::settings::Settings;
+    use crate::settings::{FeeSource, Settings, TargetFeeRate};
+    use clap::Parser;
+    use lightning::chain::chaininterface::ConfirmationTarget;
     use std::env::set_var;
+    use std::str::FromStr;
 
     #[test]
     pub fn test_parse_settings() {
@@ -113,4 +264,34 @@ mod test {
         let settings = Settings::load();
         assert_eq!(settings.public_addresses.len(), 2);
     }
+
+    #[test]
+    pub fn test_parse_fee_settings() {
+        let settings = Settings::parse_from([
+            "kld",
+            "--fee-sources",
+            "esplora,static",
+            "--fee-min-rates",
+            "on_chain_sweep=1000,channel_close_minimum=300",
+        ]);
+        assert_eq!(
+            vec![FeeSource::Esplora, FeeSource::Static],
+            settings.fee_sources
+        );
+        assert_eq!(
+            vec![
+                TargetFeeRate {
+                    target: ConfirmationTarget::OnChainSweep,
+                    fee_rate: 1000
+                },
+                TargetFeeRate {
+                    target: ConfirmationTarget::ChannelCloseMinimum,
+                    fee_rate: 300
+                }
+            ],
+            settings.fee_min_rates
+        );
+        assert!(TargetFeeRate::from_str("on_chain_sweep").is_err());
+        assert!(TargetFeeRate::from_str("fast=1000").is_err());
+    }
 }
@@ -1,23 +1,28 @@
 use std::{
+    collections::HashSet,
     sync::{Arc, Mutex, OnceLock},
     time::Duration,
 };
 
+use crate::ldk::FundingOptions;
 use crate::settings::Settings;
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use bdk::{
     bitcoin::bip32::ExtendedPrivKey,
-    blockchain::{log_progress, ElectrumBlockchain, GetHeight},
+    blockchain::{log_progress, ElectrumBlockchain, GetHeight, GetTx},
     database::{BatchDatabase, BatchOperations, Database},
-    electrum_client::Client,
+    electrum_client::{Client, ElectrumApi},
     template::Bip84,
     wallet::AddressInfo,
     Balance, FeeRate, KeychainKind, LocalUtxo, SignOptions, SyncOptions, TransactionDetails,
 };
 use bitcoin::address::NetworkUnchecked;
-use bitcoin::{Address, OutPoint, Script, Transaction};
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::PartiallySignedTransaction;
+use bitcoin::{Address, OutPoint, ScriptBuf, Transaction, Txid, WPubkeyHash};
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::events::bump_transaction::{Utxo, WalletSource};
 use lightning_block_sync::BlockSource;
 use log::{error, info, warn};
 
@@ -140,6 +145,115 @@ impl<
         }
         Ok(result)
     }
+
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>> {
+        let mut transactions = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?
+            .list_transactions(false)?;
+        transactions.sort_by_key(|tx| {
+            (
+                tx.confirmation_time
+                    .as_ref()
+                    .map_or(u32::MAX, |time| time.height),
+                tx.txid,
+            )
+        });
+        Ok(transactions)
+    }
+
+    fn list_used_addresses(&self) -> Result<Vec<AddressInfo>> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?;
+        let mut seen = HashSet::new();
+        let mut addresses = vec![];
+        for tx in wallet.list_transactions(true)? {
+            let Some(transaction) = tx.transaction else {
+                continue;
+            };
+            for output in transaction.output {
+                let Some((keychain, index)) = wallet
+                    .database()
+                    .get_path_from_script_pubkey(&output.script_pubkey)?
+                else {
+                    continue;
+                };
+                if seen.insert(output.script_pubkey.clone()) {
+                    addresses.push(AddressInfo {
+                        address: Address::from_script(&output.script_pubkey, self.network)?,
+                        index,
+                        keychain,
+                    });
+                }
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+/// Lets LDK spend our on-chain funds when it needs to bump anchor channel transactions.
+impl<
+        D: Database + BatchDatabase + BatchOperations + Send + 'static,
+        B: BlockSource + FeeEstimator + Service,
+    > WalletSource for Wallet<D, B>
+{
+    fn list_confirmed_utxos(&self) -> std::result::Result<Vec<Utxo>, ()> {
+        let wallet = self.wallet.lock().map_err(|_| ())?;
+        let utxos = wallet.list_unspent().map_err(|e| error!("{e}"))?;
+        let mut confirmed = vec![];
+        for utxo in utxos {
+            let is_confirmed = wallet
+                .get_tx(&utxo.outpoint.txid, false)
+                .map_err(|e| error!("{e}"))?
+                .is_some_and(|tx| tx.confirmation_time.is_some());
+            // Bip84 wallets only hold P2WPKH outputs.
+            if !is_confirmed || !utxo.txout.script_pubkey.is_v0_p2wpkh() {
+                continue;
+            }
+            let pubkey_hash = WPubkeyHash::from_slice(&utxo.txout.script_pubkey.as_bytes()[2..])
+                .map_err(|e| error!("{e}"))?;
+            confirmed.push(Utxo::new_v0_p2wpkh(
+                utxo.outpoint,
+                utxo.txout.value,
+                &pubkey_hash,
+            ));
+        }
+        Ok(confirmed)
+    }
+
+    fn get_change_script(&self) -> std::result::Result<ScriptBuf, ()> {
+        let address = self
+            .wallet
+            .lock()
+            .map_err(|_| ())?
+            .get_internal_address(bdk::wallet::AddressIndex::New)
+            .map_err(|e| error!("{e}"))?;
+        Ok(address.script_pubkey())
+    }
+
+    fn sign_tx(&self, tx: Transaction) -> std::result::Result<Transaction, ()> {
+        let wallet = self.wallet.lock().map_err(|_| ())?;
+        let utxos = wallet.list_unspent().map_err(|e| error!("{e}"))?;
+        let mut psbt =
+            PartiallySignedTransaction::from_unsigned_tx(tx).map_err(|e| error!("{e}"))?;
+        // Only our own inputs are signed here, LDK signs the anchor input afterwards.
+        for (input, psbt_input) in psbt.unsigned_tx.input.iter().zip(psbt.inputs.iter_mut()) {
+            if let Some(utxo) = utxos.iter().find(|u| u.outpoint == input.previous_output) {
+                psbt_input.witness_utxo = Some(utxo.txout.clone());
+            }
+        }
+        let sign_options = SignOptions {
+            trust_witness_utxo: true,
+            ..Default::default()
+        };
+        wallet
+            .sign(&mut psbt, sign_options)
+            .map_err(|e| error!("{e}"))?;
+        Ok(psbt.extract_tx())
+    }
 }
 
 impl<
@@ -228,19 +342,56 @@ impl<
         });
     }
 
+    /// Build and sign a transaction paying the funding outputs of one or more channels.
     pub fn fund_tx(
         &self,
-        output_script: &Script,
-        channel_value_satoshis: &u64,
-        fee_rate: crate::api::payloads::FeeRate,
+        outputs: &[(ScriptBuf, u64)],
+        funding: &FundingOptions,
     ) -> Result<Transaction> {
         let wallet = self.wallet.lock().unwrap();
 
-        let mut tx_builder = wallet.build_tx();
+        let mut unspendable = vec![];
+        if let Some(min_conf) = funding.min_conf {
+            let height = wallet
+                .database()
+                .get_sync_time()?
+                .map(|time| time.block_time.height)
+                .unwrap_or_default();
+            let confirmations = |outpoint: &OutPoint| -> Result<u32> {
+                Ok(wallet
+                    .get_tx(&outpoint.txid, false)?
+                    .and_then(|tx| tx.confirmation_time)
+                    .map(|time| height.saturating_sub(time.height) + 1)
+                    .unwrap_or_default())
+            };
+            if funding.utxos.is_empty() {
+                for utxo in wallet.list_unspent()? {
+                    if confirmations(&utxo.outpoint)? < min_conf as u32 {
+                        unspendable.push(utxo.outpoint);
+                    }
+                }
+            } else {
+                for outpoint in &funding.utxos {
+                    let count = confirmations(outpoint)?;
+                    if count < min_conf as u32 {
+                        bail!("Output {outpoint} has {count} confirmations, the minimum is {min_conf}");
+                    }
+                }
+            }
+        }
 
+        let mut tx_builder = wallet.build_tx();
+        for (output_script, value) in outputs {
+            tx_builder.add_recipient(output_script.clone(), *value);
+        }
+        if !funding.utxos.is_empty() {
+            tx_builder
+                .add_utxos(&funding.utxos)?
+                .manually_selected_only();
+        }
         tx_builder
-            .add_recipient(output_script.into(), *channel_value_satoshis)
-            .fee_rate(self.to_bdk_fee_rate(fee_rate))
+            .unspendable(unspendable)
+            .fee_rate(self.to_bdk_fee_rate(funding.fee_rate.clone().unwrap_or_default()))
             .enable_rbf();
 
         let (mut psbt, _tx_details) = tx_builder.finish()?;
@@ -251,6 +402,66 @@ impl<
         Ok(funding_tx)
     }
 
+    /// Height of the block that mined a transaction paying to or from the wallet, as far as the last sync knows.
+    pub fn confirmation_height(&self, txid: &Txid) -> Result<Option<u32>> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?;
+        Ok(wallet
+            .get_tx(txid, false)?
+            .and_then(|tx| tx.confirmation_time)
+            .map(|time| time.height))
+    }
+
+    /// The transaction that spent an output, from the wallet if it pays to us or else from the Electrum server.
+    pub fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>> {
+        let spends = |tx: &Transaction| {
+            tx.input
+                .iter()
+                .any(|input| input.previous_output == *outpoint)
+        };
+        let wallet_tx = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?
+            .list_transactions(true)?
+            .into_iter()
+            .filter_map(|tx| tx.transaction)
+            .find(spends);
+        if wallet_tx.is_some() {
+            return Ok(wallet_tx);
+        }
+        let Some(blockchain) = self.blockchain.get() else {
+            return Ok(None);
+        };
+        let Some(output) = blockchain
+            .get_tx(&outpoint.txid)?
+            .and_then(|tx| tx.output.get(outpoint.vout as usize).cloned())
+        else {
+            return Ok(None);
+        };
+        // The history of the output script holds the transaction that created it and the one that spent it.
+        for history in blockchain.script_get_history(&output.script_pubkey)? {
+            if history.tx_hash == outpoint.txid {
+                continue;
+            }
+            if let Some(tx) = blockchain.get_tx(&history.tx_hash)?.filter(spends) {
+                return Ok(Some(tx));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The fee of a transaction the wallet funded, None if the wallet doesn't know the transaction or its inputs.
+    pub fn transaction_fee(&self, txid: &Txid) -> Result<Option<u64>> {
+        let wallet = self
+            .wallet
+            .lock()
+            .map_err(|_| anyhow!("wallet lock is poisened"))?;
+        Ok(wallet.get_tx(txid, false)?.and_then(|tx| tx.fee))
+    }
+
     fn to_bdk_fee_rate(&self, fee_rate: crate::api::payloads::FeeRate) -> FeeRate {
         match fee_rate {
             crate::api::payloads::FeeRate::Urgent => FeeRate::from_sat_per_kwu(
@@ -281,10 +492,13 @@ mod test {
         sync::{Arc, Mutex, OnceLock},
     };
 
+    use crate::ldk::FundingOptions;
     use crate::settings::Settings;
     use anyhow::Result;
     use bdk::{database::MemoryDatabase, wallet::get_funded_wallet, Balance};
-    use bitcoin::Address;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::{Address, Transaction, TxIn, TxOut};
+    use lightning::events::bump_transaction::WalletSource;
     use test_utils::{TEST_ADDRESS, TEST_WPKH};
 
     use crate::{bitcoind::MockBitcoindClient, wallet::WalletInterface};
@@ -380,4 +594,84 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_fund_tx() -> Result<()> {
+        let (bdk_wallet, _, _) = get_funded_wallet(TEST_WPKH);
+        let outpoint = bdk_wallet.list_unspent()?[0].outpoint;
+        let wallet = Wallet {
+            bitcoind_client: Arc::new(MockBitcoindClient::default()),
+            wallet: Arc::new(Mutex::new(bdk_wallet)),
+            settings: Arc::new(Settings::default()),
+            blockchain: Arc::new(OnceLock::new()),
+            network: bitcoin::network::constants::Network::Testnet,
+        };
+        let outputs = vec![
+            (wallet.new_external_address()?.script_pubkey(), 20_000),
+            (wallet.new_internal_address()?.script_pubkey(), 10_000),
+        ];
+
+        let tx = wallet.fund_tx(
+            &outputs,
+            &FundingOptions {
+                utxos: vec![outpoint],
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            vec![outpoint],
+            tx.input
+                .iter()
+                .map(|i| i.previous_output)
+                .collect::<Vec<_>>()
+        );
+        for (output_script, value) in &outputs {
+            assert!(tx
+                .output
+                .iter()
+                .any(|o| &o.script_pubkey == output_script && o.value == *value));
+        }
+
+        let res = wallet.fund_tx(
+            &outputs,
+            &FundingOptions {
+                min_conf: Some(u8::MAX),
+                ..Default::default()
+            },
+        );
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_source_signs_own_inputs() -> Result<()> {
+        let (bdk_wallet, _, _) = get_funded_wallet(TEST_WPKH);
+        let outpoint = bdk_wallet.list_unspent()?[0].outpoint;
+        let wallet = Wallet {
+            bitcoind_client: Arc::new(MockBitcoindClient::default()),
+            wallet: Arc::new(Mutex::new(bdk_wallet)),
+            settings: Arc::new(Settings::default()),
+            blockchain: Arc::new(OnceLock::new()),
+            network: bitcoin::network::constants::Network::Testnet,
+        };
+
+        let change_script = wallet.get_change_script().unwrap();
+        assert!(change_script.is_v0_p2wpkh());
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: outpoint,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: change_script,
+            }],
+        };
+        let signed_tx = wallet.sign_tx(tx).unwrap();
+        assert!(!signed_tx.input[0].witness.is_empty());
+        Ok(())
+    }
 }
@@ -24,4 +24,10 @@ pub trait WalletInterface {
     fn new_internal_address(&self) -> Result<AddressInfo>;
 
     fn list_utxos(&self) -> Result<Vec<(LocalUtxo, TransactionDetails)>>;
+
+    /// Every transaction paying to or from the wallet, oldest first with the unconfirmed ones last.
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>>;
+
+    /// Addresses of the wallet that were paid to, with the keychain and index they were derived at.
+    fn list_used_addresses(&self) -> Result<Vec<AddressInfo>>;
 }
@@ -16,9 +16,11 @@ use kld::api::codegen::{
     post_v1_peer_connect_response::PostV1PeerConnectResponse,
 };
 use kld::api::payloads::{
-    FeeRatesResponse, FundChannelResponse, GenerateInvoiceResponse, GetInfo, Invoice, ListFunds,
-    NetworkChannel, NetworkNode, PaymentResponse, Peer, SetChannelFeeResponse, SignResponse,
-    WalletBalance, WalletTransferResponse,
+    BakeMacaroonResponse, FeeRatesResponse, FeeUpdate, ForwardingReport, FundChannelResponse,
+    GenerateInvoiceResponse, GetInfo, GetRouteResponse, Invoice, JitChannel, ListFunds, LspInfo,
+    LspOrder, Lsps1Order, Lsps2Token, NetworkChannel, NetworkNode, Offer, PaymentResponse,
+    PaymentStatusResponse, Peer, RebalanceResponse, RootKey, SetChannelFeeResponse, SignResponse,
+    Sweep, WalletBalance, WalletTransferResponse,
 };
 
 use super::rest::create_api_server;
@@ -72,6 +74,124 @@ async fn test_cli_list_funds() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_list_sweeps() -> Result<()> {
+    let output = run_cli("list-sweeps", &[]).await?;
+    let sweeps: Vec<Sweep> = deserialize(&output.stdout)?;
+    assert_eq!(1, sweeps.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_backup() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("channel_backup_{}.scb", std::process::id()));
+    run_cli("backup", &[path.to_str().unwrap()]).await?;
+    assert_eq!(vec![1, 2, 3], std::fs::read(&path)?);
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_bake_macaroon() -> Result<()> {
+    let output = run_cli(
+        "bake-macaroon",
+        &[
+            "--permissions",
+            "invoices:read,invoices:write",
+            "--max-amount-msat",
+            "1000",
+        ],
+    )
+    .await?;
+    let response: BakeMacaroonResponse = deserialize(&output.stdout)?;
+    assert_eq!(
+        vec!["invoices:read", "invoices:write"],
+        response.permissions
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_root_keys() -> Result<()> {
+    let output = run_cli("list-root-keys", &[]).await?;
+    let root_keys: Vec<RootKey> = deserialize(&output.stdout)?;
+    assert!(root_keys.iter().any(|key| key.admin));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_create_lsps2_token() -> Result<()> {
+    let output = run_cli(
+        "create-lsps2-token",
+        &[
+            "--token",
+            "shop",
+            "--min-fee-msat",
+            "2000",
+            "--proportional",
+            "1000",
+            "--max-payment-size-msat",
+            "5000000",
+        ],
+    )
+    .await?;
+    let token: Lsps2Token = deserialize(&output.stdout)?;
+    assert_eq!("shop", token.token);
+    assert_eq!(2000, token.min_fee_msat);
+    assert_eq!(600, token.valid_for_secs);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_jit_channels() -> Result<()> {
+    let output = run_cli("list-jit-channels", &[]).await?;
+    let channels: Vec<JitChannel> = deserialize(&output.stdout)?;
+    assert_eq!(1, channels.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_lsps1_orders() -> Result<()> {
+    let output = run_cli("list-lsps1-orders", &[]).await?;
+    let orders: Vec<Lsps1Order> = deserialize(&output.stdout)?;
+    assert_eq!(1, orders.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_lsp() -> Result<()> {
+    let output = run_cli("lsp", &["get-info", TEST_PUBLIC_KEY]).await?;
+    let info: LspInfo = deserialize(&output.stdout)?;
+    assert!(info.lsps1.is_some());
+    let output = run_cli("lsp", &["buy-channel", TEST_PUBLIC_KEY, "1000000"]).await?;
+    let order: LspOrder = deserialize(&output.stdout)?;
+    assert_eq!(1_000_000, order.lsp_balance_sat);
+    let output = run_cli(
+        "lsp",
+        &[
+            "jit-invoice",
+            TEST_PUBLIC_KEY,
+            "jit",
+            "jit invoice",
+            "--amount",
+            "100000",
+        ],
+    )
+    .await?;
+    let _: GenerateInvoiceResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_export() -> Result<()> {
+    let output = run_cli("export", &["transactions"]).await?;
+    let csv = String::from_utf8(output.stdout)?;
+    assert!(csv.starts_with("txid,confirmation_height,"));
+    let output = run_cli("export", &["payments", "--format", "jsonl"]).await?;
+    let _: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_list_peer_channels() -> Result<()> {
     let output = run_cli("list-peer-channels", &[]).await?;
@@ -119,6 +239,28 @@ async fn test_cli_open_channel() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_open_channel_batch() -> Result<()> {
+    let batch = format!("{TEST_PUBLIC_KEY}:500000");
+    let output = run_cli(
+        "open-channel",
+        &[
+            TEST_PUBLIC_KEY,
+            "1000",
+            "--min-conf",
+            "3",
+            "--batch",
+            &batch,
+            "--batch",
+            &batch,
+        ],
+    )
+    .await?;
+    let response: FundChannelResponse = deserialize(&output.stdout)?;
+    assert_eq!(3, response.channel_ids.len());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_set_channel_fee() -> Result<()> {
     let output = run_cli(
@@ -147,6 +289,19 @@ async fn test_cli_set_channel_fee_all() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_rebalance() -> Result<()> {
+    let scid = TEST_SHORT_CHANNEL_ID.to_string();
+    let output = run_cli(
+        "rebalance",
+        &[&scid, &scid, "100000", "--max-fee-ppm", "500"],
+    )
+    .await?;
+    let response: RebalanceResponse = deserialize(&output.stdout)?;
+    assert_eq!(100000, response.amount_msat);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_close_channel() -> Result<()> {
     let output = run_cli("close-channel", &[&TEST_SHORT_CHANNEL_ID.to_string()]).await?;
@@ -154,6 +309,23 @@ async fn test_cli_close_channel() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_close_channel_to_address() -> Result<()> {
+    let output = run_cli(
+        "close-channel",
+        &[
+            &TEST_SHORT_CHANNEL_ID.to_string(),
+            "--address",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "--max-fee-rate",
+            "1000",
+        ],
+    )
+    .await?;
+    assert!(output.stdout.is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_get_network_node() -> Result<()> {
     let output = run_cli("network-nodes", &["--id", TEST_PUBLIC_KEY]).await?;
@@ -194,6 +366,26 @@ async fn test_cli_fee_rates() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_get_route() -> Result<()> {
+    let output = run_cli(
+        "get-route",
+        &[
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "100000",
+            "--max-fee",
+            "5000",
+            "--exclude-channels",
+            "1,2",
+        ],
+    )
+    .await?;
+    let route: GetRouteResponse = deserialize(&output.stdout)?;
+    assert_eq!(1000, route.fee_msat);
+    assert_eq!(2, route.paths[0].hops.len());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_keysend() -> Result<()> {
     let output = run_cli("keysend", &[TEST_PUBLIC_KEY, "102000"]).await?;
@@ -201,6 +393,28 @@ async fn test_cli_keysend() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_settle_invoice() -> Result<()> {
+    let output = run_cli("settle-invoice", &[&"01".repeat(32)]).await?;
+    let _: () = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_wait_invoice() -> Result<()> {
+    let output = run_cli("wait-invoice", &["label"]).await?;
+    let _: Invoice = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_wait_any_invoice() -> Result<()> {
+    let output = run_cli("wait-any-invoice", &["--lastpay-index", "3"]).await?;
+    let invoice: Invoice = deserialize(&output.stdout)?;
+    assert_eq!(Some(4), invoice.pay_index);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_generate_invoice() -> Result<()> {
     let output = run_cli(
@@ -228,11 +442,63 @@ async fn test_cli_list_invoices() -> Result<()> {
 #[tokio::test]
 async fn test_cli_pay_invoice() -> Result<()> {
     let bolt11 = mock_lightning().invoice.bolt11.to_string();
-    let output = run_cli("pay-invoice", &[&bolt11, "-l", "a label"]).await?;
+    let output = run_cli(
+        "pay-invoice",
+        &[
+            &bolt11,
+            "-l",
+            "a label",
+            "--max-fee-msat",
+            "5000",
+            "--max-fee-ppm",
+            "1000",
+            "--retry-for",
+            "30",
+            "--max-paths",
+            "2",
+            "--max-cltv-expiry-delta",
+            "1008",
+        ],
+    )
+    .await?;
+    let _: PaymentResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_payment_status() -> Result<()> {
+    let id = hex::encode(mock_lightning().payment.id.0);
+    let output = run_cli("payment-status", &[&id]).await?;
+    let _: PaymentStatusResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_create_offer() -> Result<()> {
+    let output = run_cli(
+        "create-offer",
+        &["a description", "--amount", "1000", "--label", "a label"],
+    )
+    .await?;
+    let _: Offer = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_pay_offer() -> Result<()> {
+    let offer = mock_lightning().offer.bolt12.to_string();
+    let output = run_cli("pay-offer", &[&offer, "-l", "a label", "-p", "a note"]).await?;
     let _: PaymentResponse = deserialize(&output.stdout)?;
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_list_offers() -> Result<()> {
+    let output = run_cli("list-offers", &["--label", "a label"]).await?;
+    let _: Vec<Offer> = deserialize(&output.stdout)?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_list_payments() -> Result<()> {
     let output = run_cli(
@@ -271,18 +537,53 @@ async fn test_cli_get_fees() -> Result<()> {
 
 #[tokio::test]
 async fn test_cli_list_forwards() -> Result<()> {
-    let output = run_cli("list-forwards", &["--status", "settled"]).await?;
+    let output = run_cli(
+        "list-forwards",
+        &["--status", "settled", "--limit", "10", "--sort", "desc"],
+    )
+    .await?;
     let _: Vec<GetV1ChannelListForwardsResponseItem> = deserialize(&output.stdout)?;
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_forwarding_report() -> Result<()> {
+    let output = run_cli(
+        "forwarding-report",
+        &["--bucket", "month", "--from", "1600000000"],
+    )
+    .await?;
+    let report: ForwardingReport = deserialize(&output.stdout)?;
+    assert_eq!("month", report.bucket);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_channel_history() -> Result<()> {
-    let output = run_cli("list-channel-history", &[]).await?;
+    let output = run_cli(
+        "list-channel-history",
+        &[
+            "--offset",
+            "1",
+            "--from",
+            "1600000000",
+            "--to",
+            "2000000000",
+        ],
+    )
+    .await?;
     let _: Vec<GetV1ChannelHistoryResponseItem> = deserialize(&output.stdout)?;
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_fee_history() -> Result<()> {
+    let output = run_cli("fee-history", &[]).await?;
+    let response: Vec<FeeUpdate> = deserialize(&output.stdout)?;
+    assert_eq!(1, response.len());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_list_channels() -> Result<()> {
     let output = run_cli("list-channels", &[]).await?;
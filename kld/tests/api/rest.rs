@@ -2,11 +2,11 @@ use std::assert_eq;
 use std::net::SocketAddr;
 use std::sync::OnceLock;
 use std::thread::spawn;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs, sync::Arc};
 
 use anyhow::{Context, Result};
-use futures::FutureExt;
+use futures::{FutureExt, SinkExt, StreamExt};
 use hyper::Method;
 use kld::api::bind_api_server;
 use kld::api::codegen::get_kld_channel_response::GetKldChannelResponseItem;
@@ -33,8 +33,11 @@ use kld::database::payment::PaymentStatus;
 use kld::logger::KldLogger;
 use kld::settings::Settings;
 use lightning::events::ClosureReason;
+use reqwest::header;
+use reqwest::Client;
 use reqwest::RequestBuilder;
 use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use test_utils::ports::get_available_port;
 use test_utils::{
@@ -43,18 +46,27 @@ use test_utils::{
 };
 
 use kld::api::payloads::{
-    ChannelFee, ChannelState, FeeRate, FeeRatesResponse, FundChannel, FundChannelResponse,
-    GenerateInvoice, GenerateInvoiceResponse, GetInfo, Invoice, InvoiceStatus, KeysendRequest,
-    ListFunds, NetworkChannel, NetworkNode, OutputStatus, PayInvoice, PaymentResponse, Peer,
-    SetChannelFeeResponse, SignRequest, SignResponse, WalletBalance, WalletTransfer,
-    WalletTransferResponse,
+    BakeMacaroon, BakeMacaroonResponse, BatchChannel, BuyChannel, CancelInvoice, ChannelFee,
+    ChannelState, CloseChannel, CreateLsps2Token, CreateOffer, EventSubscription,
+    EventSubscriptionResponse, EventTopic, FeeRate, FeeRatesResponse, FeeUpdate, ForwardingReport,
+    FundChannel, FundChannelResponse, GenerateInvoice, GenerateInvoiceResponse, GenerateJitInvoice,
+    GetInfo, GetRouteQuery, GetRouteResponse, Invoice, InvoiceStatus, JitChannel, KeysendRequest,
+    ListFunds, LspInfo, LspOrder, Lsps1Order, Lsps2Token, NetworkChannel, NetworkNode, Offer,
+    OfferStatus, OutputStatus, PayInvoice, PayOffer, PaymentResponse, Peer, RebalanceChannel,
+    RebalanceResponse, RootKey, SetChannelFeeResponse, SettleInvoice, SignRequest, SignResponse,
+    StreamEvent, Sweep, WalletBalance, WalletTransfer, WalletTransferResponse,
 };
 use kld::api::routes;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::runtime::Runtime;
 use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 
 use crate::mocks::mock_bitcoind::MockBitcoind;
 use crate::mocks::mock_lightning::MockLightning;
+use crate::mocks::mock_macaroon_store::MockMacaroonKeyStore;
 use crate::mocks::mock_wallet::MockWallet;
 use crate::quit_signal;
 
@@ -65,6 +77,7 @@ pub async fn test_unauthorized() -> Result<()> {
         (Method::POST, routes::SIGN),
         (Method::POST, routes::OPEN_CHANNEL),
         (Method::POST, routes::SET_CHANNEL_FEE),
+        (Method::POST, routes::REBALANCE_CHANNEL),
         (Method::DELETE, routes::CLOSE_CHANNEL),
         (Method::DELETE, routes::FORCE_CLOSE_CHANNEL_WITH_BROADCAST),
         (
@@ -77,7 +90,21 @@ pub async fn test_unauthorized() -> Result<()> {
         (Method::DELETE, routes::DISCONNECT_PEER),
         (Method::POST, routes::KEYSEND),
         (Method::POST, routes::GENERATE_INVOICE),
+        (Method::POST, routes::SETTLE_INVOICE),
+        (Method::POST, routes::CANCEL_INVOICE),
         (Method::POST, routes::PAY_INVOICE),
+        (Method::POST, routes::CREATE_OFFER),
+        (Method::POST, routes::PAY_OFFER),
+        (Method::POST, routes::BAKE_MACAROON),
+        (Method::DELETE, routes::REVOKE_ROOT_KEY),
+        (Method::GET, routes::LIST_ROOT_KEYS),
+        (Method::GET, routes::BACKUP),
+        (Method::GET, routes::EXPORT),
+        (Method::GET, routes::LIST_LSPS2_TOKENS),
+        (Method::POST, routes::CREATE_LSPS2_TOKEN),
+        (Method::DELETE, routes::DELETE_LSPS2_TOKEN),
+        (Method::POST, routes::LSP_BUY_CHANNEL),
+        (Method::POST, routes::LSP_JIT_INVOICE),
     ];
     for (method, route) in &admin_functions {
         assert_eq!(
@@ -93,21 +120,32 @@ pub async fn test_unauthorized() -> Result<()> {
         (Method::GET, routes::GET_INFO),
         (Method::GET, routes::GET_BALANCE),
         (Method::GET, routes::LIST_FUNDS),
+        (Method::GET, routes::LIST_SWEEPS),
+        (Method::GET, routes::LIST_JIT_CHANNELS),
+        (Method::GET, routes::LIST_LSPS1_ORDERS),
+        (Method::GET, routes::LSP_GET_INFO),
         (Method::GET, routes::LIST_PEERS),
         (Method::GET, routes::LIST_NETWORK_NODE),
         (Method::GET, routes::LIST_NETWORK_NODES),
         (Method::GET, routes::LIST_NETWORK_CHANNEL),
         (Method::GET, routes::LIST_NETWORK_CHANNELS),
         (Method::GET, routes::FEE_RATES),
+        (Method::GET, routes::GET_ROUTE),
         (Method::GET, routes::LIST_INVOICES),
+        (Method::GET, routes::WAIT_INVOICE),
+        (Method::GET, routes::WAIT_ANY_INVOICE),
         (Method::GET, routes::LIST_PAYMENTS),
         (Method::GET, routes::ESTIMATE_CHANNEL_LIQUIDITY),
         (Method::GET, routes::LOCAL_REMOTE_BALANCE),
         (Method::GET, routes::GET_FEES),
         (Method::GET, routes::LIST_FORWARDS),
+        (Method::GET, routes::FORWARDING_REPORT),
         (Method::GET, routes::LIST_CHANNEL_HISTORY),
+        (Method::GET, routes::CHANNEL_FEE_HISTORY),
         (Method::GET, routes::LIST_PEER_CHANNELS),
         (Method::GET, routes::DECODE_INVOICE),
+        (Method::GET, routes::LIST_OFFERS),
+        (Method::GET, routes::PAYMENT_STATUS),
     ];
     readonly_functions.extend(admin_functions.into_iter());
     for (method, route) in readonly_functions {
@@ -123,6 +161,379 @@ pub async fn test_unauthorized() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bake_macaroon() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || {
+            BakeMacaroon {
+                permissions: vec!["invoices:read".to_string(), "invoices:write".to_string()],
+                ip_ranges: vec!["127.0.0.0/8".to_string()],
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let address = &context.settings.rest_api_address;
+    let client = https_client(Some(response.macaroon.into_bytes()))?;
+    let status = client
+        .get(format!("https://{address}{}", routes::LIST_INVOICES))
+        .send()
+        .await?
+        .status();
+    assert!(status.is_success());
+    let status = client
+        .get(format!("https://{address}{}", routes::LIST_PAYMENTS))
+        .send()
+        .await?
+        .status();
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+
+    let response: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || {
+            BakeMacaroon {
+                permissions: vec!["payments:send".to_string()],
+                max_amount_msat: Some(1000),
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let client = https_client(Some(response.macaroon.into_bytes()))?;
+    let keysend = |amount, max_fee_msat| KeysendRequest {
+        pubkey: TEST_PUBLIC_KEY.to_string(),
+        amount,
+        max_fee_msat,
+        ..Default::default()
+    };
+    let status = client
+        .post(format!("https://{address}{}", routes::KEYSEND))
+        .body(serde_json::to_string(&keysend(5000, Some(0)))?)
+        .send()
+        .await?
+        .status();
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+    // The routing fees count towards the limit, so they need a cap.
+    let status = client
+        .post(format!("https://{address}{}", routes::KEYSEND))
+        .body(serde_json::to_string(&keysend(1000, None))?)
+        .send()
+        .await?
+        .status();
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    let status = client
+        .post(format!("https://{address}{}", routes::KEYSEND))
+        .body(serde_json::to_string(&keysend(1000, Some(1)))?)
+        .send()
+        .await?
+        .status();
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+    let status = client
+        .post(format!("https://{address}{}", routes::KEYSEND))
+        .body(serde_json::to_string(&keysend(900, Some(100)))?)
+        .send()
+        .await?
+        .status();
+    assert!(status.is_success());
+
+    let response = admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || {
+        BakeMacaroon {
+            permissions: vec!["invoices:everything".to_string()],
+            ..Default::default()
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || {
+        BakeMacaroon {
+            root_key_id: Some(u64::MAX),
+            permissions: vec!["invoices:read".to_string()],
+            ..Default::default()
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_revoke_root_key() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: BakeMacaroonResponse =
+        admin_request_with_body(&context, Method::POST, routes::BAKE_MACAROON, || {
+            BakeMacaroon {
+                permissions: vec!["info:read".to_string()],
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let address = &context.settings.rest_api_address;
+    let client = https_client(Some(response.macaroon.into_bytes()))?;
+    assert!(client
+        .get(format!("https://{address}{}", routes::GET_INFO))
+        .send()
+        .await?
+        .status()
+        .is_success());
+
+    let route = routes::REVOKE_ROOT_KEY.replace(":id", &response.root_key_id.to_string());
+    let revoked: RootKey = admin_request(&context, Method::DELETE, &route)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(response.root_key_id, revoked.id);
+    assert!(revoked.revoked_at.is_some());
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        client
+            .get(format!("https://{address}{}", routes::GET_INFO))
+            .send()
+            .await?
+            .status()
+    );
+
+    let root_keys: Vec<RootKey> = admin_request(&context, Method::GET, routes::LIST_ROOT_KEYS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let admin_key = root_keys.first().context("expected root key")?;
+    assert_eq!(0, admin_key.id);
+    assert!(admin_key.admin);
+    assert!(root_keys
+        .iter()
+        .any(|key| key.id == response.root_key_id && key.revoked_at.is_some()));
+
+    let route = routes::REVOKE_ROOT_KEY.replace(":id", "999999");
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        admin_request(&context, Method::DELETE, &route)?
+            .send()
+            .await?
+            .status()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lsps2_tokens() -> Result<()> {
+    let context = create_api_server().await?;
+    let tokens: Vec<Lsps2Token> = admin_request(&context, Method::GET, routes::LIST_LSPS2_TOKENS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let token = tokens.first().context("expected token")?;
+    assert_eq!("kuutamo", token.token);
+    assert_eq!(2000, token.min_fee_msat);
+    assert_eq!(1000, token.proportional);
+    assert_eq!(5_000_000, token.max_payment_size_msat);
+
+    let created: Lsps2Token =
+        admin_request_with_body(&context, Method::POST, routes::CREATE_LSPS2_TOKEN, || {
+            CreateLsps2Token {
+                min_fee_msat: 1000,
+                proportional: 500,
+                max_payment_size_msat: 1_000_000,
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(32, created.token.len());
+    assert_eq!(600, created.valid_for_secs);
+    assert_eq!(500, created.proportional);
+
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        admin_request_with_body(&context, Method::POST, routes::CREATE_LSPS2_TOKEN, || {
+            CreateLsps2Token {
+                min_payment_size_msat: 2000,
+                max_payment_size_msat: 1000,
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .status()
+    );
+
+    let route = routes::DELETE_LSPS2_TOKEN.replace(":token", "kuutamo");
+    assert!(admin_request(&context, Method::DELETE, &route)?
+        .send()
+        .await?
+        .status()
+        .is_success());
+    let route = routes::DELETE_LSPS2_TOKEN.replace(":token", "unknown");
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        admin_request(&context, Method::DELETE, &route)?
+            .send()
+            .await?
+            .status()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_jit_channels() -> Result<()> {
+    let context = create_api_server().await?;
+    let channels: Vec<JitChannel> =
+        readonly_request(&context, Method::GET, routes::LIST_JIT_CHANNELS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let channel = channels.first().context("expected JIT channel")?;
+    assert_eq!("9223372036854775809", channel.user_channel_id);
+    assert_eq!(Some("kuutamo".to_string()), channel.token);
+    assert_eq!(TEST_PUBLIC_KEY, channel.counterparty);
+    assert_eq!(TEST_SHORT_CHANNEL_ID, channel.intercept_scid);
+    assert_eq!(Some(2000), channel.opening_fee_msat);
+    assert_eq!(Some(1100), channel.channel_value_sats);
+    assert_eq!("ready", channel.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_lsps1_orders() -> Result<()> {
+    let context = create_api_server().await?;
+    let orders: Vec<Lsps1Order> =
+        readonly_request(&context, Method::GET, routes::LIST_LSPS1_ORDERS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let order = orders.first().context("expected LSPS1 order")?;
+    assert_eq!("a1b2c3", order.order_id);
+    assert_eq!(TEST_PUBLIC_KEY, order.counterparty);
+    assert_eq!(1_000_000, order.lsp_balance_sat);
+    assert_eq!(12_000, order.order_total_sat);
+    assert_eq!("completed", order.order_state);
+    assert_eq!("paid", order.payment_state);
+    assert_eq!(Some(format!("{TEST_TX_ID}:0")), order.funding_outpoint);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lsp_client() -> Result<()> {
+    let context = create_api_server().await?;
+    let info: LspInfo = readonly_request(&context, Method::GET, routes::LSP_GET_INFO)?
+        .query(&[("nodeId", TEST_PUBLIC_KEY)])
+        .send()
+        .await?
+        .json()
+        .await?;
+    let options = info.lsps1.context("expected LSPS1 options")?;
+    assert_eq!(100_000, options.min_channel_balance_sat);
+    let fees = info.lsps2_opening_fees.context("expected LSPS2 fees")?;
+    assert_eq!(2000, fees[0].min_fee_msat);
+    assert_eq!(1_700_000_600, fees[0].valid_until);
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        readonly_request(&context, Method::GET, routes::LSP_GET_INFO)?
+            .query(&[("nodeId", "x")])
+            .send()
+            .await?
+            .status()
+    );
+
+    let order: LspOrder =
+        admin_request_with_body(&context, Method::POST, routes::LSP_BUY_CHANNEL, || {
+            BuyChannel {
+                node_id: TEST_PUBLIC_KEY.to_string(),
+                lsp_balance_sat: 1_000_000,
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!("d4e5f6", order.order_id);
+    assert_eq!(1_000_000, order.lsp_balance_sat);
+    assert_eq!(TEST_ADDRESS, order.onchain_address);
+
+    let invoice: GenerateInvoiceResponse =
+        admin_request_with_body(&context, Method::POST, routes::LSP_JIT_INVOICE, || {
+            GenerateJitInvoice {
+                node_id: TEST_PUBLIC_KEY.to_string(),
+                amount: Some(100_000),
+                label: "jit".to_string(),
+                description: "jit invoice".to_string(),
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(64, invoice.payment_hash.len());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lsp_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let order = BuyChannel {
+        node_id: TEST_PUBLIC_KEY.to_string(),
+        lsp_balance_sat: 1_000_000,
+        client_balance_sat: 100,
+        ..Default::default()
+    };
+    let route = routes::LSP_BUY_CHANNEL;
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "lsps:write", 99_999, route, &order).await?
+    );
+    assert!(
+        limited_request(&context, "lsps:write", 100_000, route, &order)
+            .await?
+            .is_success()
+    );
+
+    let invoice = GenerateJitInvoice {
+        node_id: TEST_PUBLIC_KEY.to_string(),
+        amount: Some(100_000),
+        label: "limited jit".to_string(),
+        description: "jit invoice".to_string(),
+        ..Default::default()
+    };
+    let route = routes::LSP_JIT_INVOICE;
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "lsps:write", 99_999, route, &invoice).await?
+    );
+    assert!(
+        limited_request(&context, "lsps:write", 100_000, route, &invoice)
+            .await?
+            .is_success()
+    );
+    // Without an amount the opening fee has no bound.
+    let invoice = GenerateJitInvoice {
+        amount: None,
+        ..invoice
+    };
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "lsps:write", 100_000, route, &invoice).await?
+    );
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_not_found() -> Result<()> {
     let context = create_api_server().await?;
@@ -234,6 +645,176 @@ async fn test_list_funds_readonly() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_backup_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let backup = admin_request(&context, Method::GET, routes::BACKUP)?
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    assert_eq!(vec![1, 2, 3], backup.to_vec());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_sweeps_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let sweeps: Vec<Sweep> = readonly_request(&context, Method::GET, routes::LIST_SWEEPS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    let sweep = sweeps.first().context("Missing sweep")?;
+    assert_eq!(TEST_TX_ID, sweep.txid);
+    assert_eq!("pending", sweep.status);
+    assert!(!sweep.outputs.is_empty());
+    assert_eq!(2000, sweep.fee_rate);
+    assert_eq!(800000, sweep.broadcast_height);
+    assert_eq!(None, sweep.confirmation_height);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_csv() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(
+        &context,
+        Method::GET,
+        &routes::EXPORT.replace(":kind", "transactions"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(
+        Some("text/csv"),
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    );
+    let csv = response.text().await?;
+    let mut lines = csv.lines();
+    assert_eq!(
+        Some("txid,confirmation_height,confirmation_time,received_sat,sent_sat,fee_sat,net_sat"),
+        lines.next()
+    );
+    assert_eq!(
+        Some(format!("{TEST_TX_ID},600000,23293219,10000,1200,20,8800").as_str()),
+        lines.next()
+    );
+    assert_eq!(None, lines.next());
+
+    let csv = admin_request(
+        &context,
+        Method::GET,
+        &routes::EXPORT.replace(":kind", "channels"),
+    )?
+    .send()
+    .await?
+    .text()
+    .await?;
+    let channel = csv.lines().nth(1).context("expected channel")?;
+    assert!(channel.starts_with(&format!(
+        "{},{TEST_PUBLIC_KEY},true,",
+        hex::encode([1u8; 32])
+    )));
+    assert!(channel.contains(&format!("{TEST_TX_ID}:2,1500,")));
+    // The closing transaction follows the closure reason.
+    assert!(channel.contains(&format!(",{TEST_TX_ID},")));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_jsonl() -> Result<()> {
+    let context = create_api_server().await?;
+    let jsonl = admin_request(
+        &context,
+        Method::GET,
+        &format!(
+            "{}?format=jsonl",
+            routes::EXPORT.replace(":kind", "forwards")
+        ),
+    )?
+    .send()
+    .await?
+    .text()
+    .await?;
+    let forward: serde_json::Value =
+        serde_json::from_str(jsonl.lines().next().context("expected forward")?)?;
+    assert_eq!("succeeded", forward["status"]);
+    assert_eq!(hex::encode([3u8; 32]), forward["in_channel"]);
+    assert_eq!(5000000, forward["amount_msat"]);
+    assert_eq!(3000, forward["fee_msat"]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_labels() -> Result<()> {
+    let context = create_api_server().await?;
+    let jsonl = admin_request(
+        &context,
+        Method::GET,
+        &routes::EXPORT.replace(":kind", "labels"),
+    )?
+    .send()
+    .await?
+    .text()
+    .await?;
+    let labels = jsonl
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let tx = labels
+        .iter()
+        .find(|label| label["type"] == "tx")
+        .context("expected tx label")?;
+    assert_eq!(TEST_TX_ID, tx["ref"]);
+    assert!(tx["label"]
+        .as_str()
+        .context("expected label")?
+        .contains(&format!("Channel open with {TEST_PUBLIC_KEY}")));
+    let output = labels
+        .iter()
+        .find(|label| label["type"] == "output")
+        .context("expected output label")?;
+    assert_eq!(format!("{TEST_TX_ID}:2"), output["ref"]);
+    assert!(tx["label"]
+        .as_str()
+        .context("expected label")?
+        .contains(&format!("Channel close with {TEST_PUBLIC_KEY}")));
+    let address = labels
+        .iter()
+        .find(|label| label["type"] == "addr")
+        .context("expected address label")?;
+    assert_eq!(TEST_ADDRESS, address["ref"]);
+    assert_eq!("Receive address 0", address["label"]);
+
+    let response = admin_request(
+        &context,
+        Method::GET,
+        &format!("{}?format=csv", routes::EXPORT.replace(":kind", "labels")),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_unknown_kind() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(
+        &context,
+        Method::GET,
+        &routes::EXPORT.replace(":kind", "everything"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_list_channels_readonly() -> Result<()> {
     let context = create_api_server().await?;
@@ -321,6 +902,76 @@ async fn test_open_channel_admin() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_batch() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: FundChannelResponse =
+        admin_request_with_body(&context, Method::POST, routes::OPEN_CHANNEL, || {
+            FundChannel {
+                utxos: vec![format!("{TEST_TX_ID}:1")],
+                batch: vec![BatchChannel {
+                    id: TEST_PUBLIC_KEY.to_string(),
+                    satoshis: "500000".to_string(),
+                    ..Default::default()
+                }],
+                ..fund_channel_request()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(TEST_TX_ID, response.txid);
+    assert_eq!(2, response.channel_ids.len());
+    assert_eq!(response.channel_id, response.channel_ids[0]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = fund_channel_request();
+    let route = routes::OPEN_CHANNEL;
+    // The pushed amount counts towards the limit.
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "channels:write", 2_100_000_000, route, &request).await?
+    );
+    assert!(
+        limited_request(&context, "channels:write", 2_100_010_000, route, &request)
+            .await?
+            .is_success()
+    );
+    let batch = FundChannel {
+        batch: vec![BatchChannel {
+            id: TEST_PUBLIC_KEY.to_string(),
+            satoshis: "500000".to_string(),
+            ..Default::default()
+        }],
+        ..fund_channel_request()
+    };
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "channels:write", 2_100_010_000, route, &batch).await?
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_invalid_utxo() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(&context, Method::POST, routes::OPEN_CHANNEL, || {
+        FundChannel {
+            utxos: vec!["not an outpoint".to_string()],
+            ..fund_channel_request()
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_set_channel_fee_admin() -> Result<()> {
     let context = create_api_server().await?;
@@ -368,6 +1019,72 @@ async fn test_set_all_channel_fees_admin() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rebalance_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: RebalanceResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::REBALANCE_CHANNEL,
+        rebalance_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+
+    let channel_id = hex::encode(mock_lightning().channel.channel_id.0);
+    assert_eq!(channel_id, response.outbound_channel_id);
+    assert_eq!(channel_id, response.inbound_channel_id);
+    assert_eq!(rebalance_request().amount, response.amount_msat);
+    assert_eq!(Some(10), response.fee_msat);
+    assert_eq!(PaymentStatus::Succeeded.to_string(), response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rebalance_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = rebalance_request();
+    let route = routes::REBALANCE_CHANNEL;
+    // The maximum routing fee counts towards the limit.
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "channels:write", 100_049, route, &request).await?
+    );
+    assert!(
+        limited_request(&context, "channels:write", 100_050, route, &request)
+            .await?
+            .is_success()
+    );
+    let request = RebalanceChannel {
+        max_fee_ppm: None,
+        ..rebalance_request()
+    };
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        limited_request(&context, "channels:write", 1_000_000, route, &request).await?
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rebalance_unknown_channel() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = RebalanceChannel {
+        inbound_channel_id: "123".to_string(),
+        ..rebalance_request()
+    };
+    let result =
+        admin_request_with_body(&context, Method::POST, routes::REBALANCE_CHANNEL, || {
+            request.clone()
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::NOT_FOUND, result.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_close_channel_admin() -> Result<()> {
     let context = create_api_server().await?;
@@ -382,6 +1099,81 @@ async fn test_close_channel_admin() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_channel_to_address() -> Result<()> {
+    let context = create_api_server().await?;
+    let result = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::CLOSE_CHANNEL_WITH_FEE
+            .replace(":id", &TEST_SHORT_CHANNEL_ID.to_string())
+            .replace(":fee_rate", "500"),
+    )?
+    .query(&CloseChannel {
+        address: Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+        max_initial_fee_rate: Some(1000),
+    })
+    .send()
+    .await?;
+    assert!(result.status().is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_channel_to_address_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let address = &context.settings.rest_api_address;
+    let route = routes::CLOSE_CHANNEL.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string());
+    let close = CloseChannel {
+        address: Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+        ..Default::default()
+    };
+    // Closing to an address spends the channel balance.
+    let status = limited_client(&context, "channels:write", 99_999)
+        .await?
+        .delete(format!("https://{address}{route}"))
+        .query(&close)
+        .send()
+        .await?
+        .status();
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+    let status = limited_client(&context, "channels:write", 100_000)
+        .await?
+        .delete(format!("https://{address}{route}"))
+        .query(&close)
+        .send()
+        .await?
+        .status();
+    assert!(status.is_success());
+    // Closing to the node wallet keeps the funds in the node.
+    let status = limited_client(&context, "channels:write", 0)
+        .await?
+        .delete(format!("https://{address}{route}"))
+        .send()
+        .await?
+        .status();
+    assert!(status.is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_channel_to_address_on_wrong_network() -> Result<()> {
+    let context = create_api_server().await?;
+    let result = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::CLOSE_CHANNEL.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string()),
+    )?
+    .query(&CloseChannel {
+        address: Some(TEST_ADDRESS.to_string()),
+        ..Default::default()
+    })
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, result.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_force_close_channel_with_broadcast_admin() -> Result<()> {
     let context = create_api_server().await?;
@@ -542,6 +1334,31 @@ async fn test_list_network_nodes_readonly() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_nodes_after() -> Result<()> {
+    let context = create_api_server().await?;
+    let nodes: Vec<NetworkNode> = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?after={TEST_PUBLIC_KEY}", routes::LIST_NETWORK_NODES),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert!(nodes.is_empty());
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?after=abc", routes::LIST_NETWORK_NODES),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_list_network_channel_readonly() -> Result<()> {
     let context = create_api_server().await?;
@@ -631,32 +1448,236 @@ async fn test_fee_rates() -> Result<()> {
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_generate_invoice() -> Result<()> {
+async fn test_get_route() -> Result<()> {
+    let context = create_api_server().await?;
+    let destination = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    let route: GetRouteResponse = readonly_request(&context, Method::GET, routes::GET_ROUTE)?
+        .query(&GetRouteQuery {
+            destination: destination.to_string(),
+            amount_msat: 100_000,
+            cltv: Some(144),
+            exclude_channels: Some("1,2".to_string()),
+            ..Default::default()
+        })
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(100_000, route.amount_msat);
+    assert_eq!(1000, route.fee_msat);
+    assert_eq!(Some(0.5), route.probability);
+    let path = &route.paths[0];
+    assert_eq!(184, path.total_cltv_expiry_delta);
+    assert_eq!(TEST_SHORT_CHANNEL_ID, path.hops[0].short_channel_id);
+    assert_eq!(101_000, path.hops[0].amount_msat);
+    assert_eq!(1000, path.hops[0].fee_msat);
+    assert_eq!(Some(TEST_ALIAS.to_string()), path.hops[0].alias);
+    assert_eq!(destination, path.hops[1].node_id);
+    assert_eq!(100_000, path.hops[1].amount_msat);
+    assert_eq!(0, path.hops[1].fee_msat);
+    assert_eq!(Some(0.5), path.hops[1].probability);
+
+    let bad_queries = [
+        GetRouteQuery {
+            destination: TEST_PUBLIC_KEY.to_string(),
+            amount_msat: 100_000,
+            ..Default::default()
+        },
+        GetRouteQuery {
+            destination: destination.to_string(),
+            amount_msat: 100_000,
+            exclude_nodes: Some(destination.to_string()),
+            ..Default::default()
+        },
+        GetRouteQuery {
+            destination: destination.to_string(),
+            amount_msat: 100_000,
+            exclude_channels: Some("x".to_string()),
+            ..Default::default()
+        },
+    ];
+    for query in bad_queries {
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            readonly_request(&context, Method::GET, routes::GET_ROUTE)?
+                .query(&query)
+                .send()
+                .await?
+                .status()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_invoice() -> Result<()> {
+    let context = create_api_server().await?;
+    let expiry = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+    let invoice_request = GenerateInvoice {
+        amount: 400004,
+        label: "test label".to_string(),
+        description: "test description".to_string(),
+        expiry: Some(expiry),
+        private: None,
+        fallbacks: None,
+        preimage: None,
+        payment_hash: None,
+    };
+    let response: GenerateInvoiceResponse =
+        admin_request_with_body(&context, Method::POST, routes::GENERATE_INVOICE, || {
+            invoice_request.clone()
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let bolt11 = response
+        .bolt11
+        .parse::<lightning_invoice::SignedRawBolt11Invoice>()
+        .unwrap();
+    assert_eq!(bolt11.to_string(), response.payment_hash);
+    assert!(response.expires_at > expiry);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wait_invoice_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice: Invoice = readonly_request(
+        &context,
+        Method::GET,
+        &routes::WAIT_INVOICE.replace(":label", "label"),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(InvoiceStatus::Paid, invoice.status);
+    assert_eq!(Some(200000), invoice.amount_received_msat);
+    assert!(invoice.paid_at.is_some());
+    assert_eq!(Some(1), invoice.pay_index);
+
+    let status = readonly_request(
+        &context,
+        Method::GET,
+        &routes::WAIT_INVOICE.replace(":label", "unknown"),
+    )?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::NOT_FOUND, status);
+
+    let status = readonly_request(
+        &context,
+        Method::GET,
+        &format!(
+            "{}?timeout=10",
+            routes::WAIT_INVOICE.replace(":label", "duplicate")
+        ),
+    )?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wait_any_invoice_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice: Invoice = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?lastpay_index=4&timeout=10", routes::WAIT_ANY_INVOICE),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(InvoiceStatus::Paid, invoice.status);
+    assert_eq!(Some(5), invoice.pay_index);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_hold_invoice() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice_request = GenerateInvoice {
+        amount: 400004,
+        label: "hold invoice".to_string(),
+        description: "test description".to_string(),
+        payment_hash: Some("02".repeat(32)),
+        ..Default::default()
+    };
+    let status = admin_request_with_body(&context, Method::POST, routes::GENERATE_INVOICE, || {
+        invoice_request.clone()
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::OK, status);
+
+    let invoice_request = GenerateInvoice {
+        payment_hash: Some("abc".to_string()),
+        ..invoice_request
+    };
+    let status = admin_request_with_body(&context, Method::POST, routes::GENERATE_INVOICE, || {
+        invoice_request.clone()
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_settle_invoice_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let expiry = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
-    let invoice_request = GenerateInvoice {
-        amount: 400004,
-        label: "test label".to_string(),
-        description: "test description".to_string(),
-        expiry: Some(expiry),
-        private: None,
-        fallbacks: None,
-        preimage: None,
-    };
-    let response: GenerateInvoiceResponse =
-        admin_request_with_body(&context, Method::POST, routes::GENERATE_INVOICE, || {
-            invoice_request.clone()
-        })?
-        .send()
-        .await?
-        .json()
-        .await?;
-    let bolt11 = response
-        .bolt11
-        .parse::<lightning_invoice::SignedRawBolt11Invoice>()
-        .unwrap();
-    assert_eq!(bolt11.to_string(), response.payment_hash);
-    assert!(response.expires_at > expiry);
+    let status = admin_request_with_body(&context, Method::POST, routes::SETTLE_INVOICE, || {
+        SettleInvoice {
+            preimage: "01".repeat(32),
+        }
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::OK, status);
+
+    let status = admin_request_with_body(&context, Method::POST, routes::SETTLE_INVOICE, || {
+        SettleInvoice {
+            preimage: "02".repeat(32),
+        }
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cancel_invoice_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let status = admin_request_with_body(&context, Method::POST, routes::CANCEL_INVOICE, || {
+        CancelInvoice {
+            payment_hash: hex::encode(mock_lightning().invoice.payment_hash.0),
+        }
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::OK, status);
+
+    let status = admin_request_with_body(&context, Method::POST, routes::CANCEL_INVOICE, || {
+        CancelInvoice {
+            payment_hash: "03".repeat(32),
+        }
+    })?
+    .send()
+    .await?
+    .status();
+    assert_eq!(StatusCode::BAD_REQUEST, status);
     Ok(())
 }
 
@@ -725,6 +1746,10 @@ async fn test_pay_invoice() -> Result<()> {
     let request = PayInvoice {
         label: Some("test label".to_string()),
         invoice: invoice.to_string(),
+        max_fee_ppm: Some(5000),
+        retry_for: Some(30),
+        max_paths: Some(3),
+        ..Default::default()
     };
     let response: PaymentResponse =
         admin_request_with_body(&context, Method::POST, routes::PAY_INVOICE, || request)?
@@ -746,6 +1771,226 @@ async fn test_pay_invoice() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_invoice_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = |max_fee_msat, max_fee_ppm| PayInvoice {
+        invoice: mock_lightning().invoice.bolt11.to_string(),
+        max_fee_msat,
+        max_fee_ppm,
+        ..Default::default()
+    };
+    let route = routes::PAY_INVOICE;
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        limited_request(
+            &context,
+            "payments:send",
+            300_000,
+            route,
+            &request(None, None)
+        )
+        .await?
+    );
+    // The lower of the fee caps counts towards the limit.
+    let request = request(Some(1000), Some(10_000));
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "payments:send", 200_999, route, &request).await?
+    );
+    assert!(
+        limited_request(&context, "payments:send", 201_000, route, &request)
+            .await?
+            .is_success()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_invoice_max_paths_zero() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice = &mock_lightning().invoice.bolt11;
+    let request = PayInvoice {
+        invoice: invoice.to_string(),
+        max_paths: Some(0),
+        ..Default::default()
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::PAY_INVOICE, || request)?
+            .send()
+            .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_invoice_below_invoice_amount() -> Result<()> {
+    let context = create_api_server().await?;
+    let invoice = &mock_lightning().invoice.bolt11;
+    let request = PayInvoice {
+        invoice: invoice.to_string(),
+        amount: Some(199999),
+        ..Default::default()
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::PAY_INVOICE, || request)?
+            .send()
+            .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payment_status() -> Result<()> {
+    let context = create_api_server().await?;
+    let payment = &mock_lightning().payment;
+    let response: PaymentStatusResponse = readonly_request(
+        &context,
+        Method::GET,
+        &routes::PAYMENT_STATUS.replace(":id", &hex::encode(payment.id.0)),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(hex::encode(payment.id.0), response.id);
+    assert_eq!(payment.status.to_string(), response.status);
+    assert_eq!(payment.amount, response.amount_msat);
+    assert_eq!(2, response.attempts.len());
+    let failed = response.attempts.first().context("expected attempt")?;
+    assert_eq!("failed", failed.status);
+    assert_eq!(Some(TEST_SHORT_CHANNEL_ID), failed.failed_short_channel_id);
+    assert!(failed.failure.is_some());
+    let hop = failed.hops.first().context("expected hop")?;
+    assert_eq!(TEST_PUBLIC_KEY, hop.node_id);
+    assert_eq!(TEST_SHORT_CHANNEL_ID, hop.short_channel_id);
+    assert_eq!("succeeded", response.attempts[1].status);
+    assert!(response.attempts[1].failure.is_none());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payment_status_not_found() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::PAYMENT_STATUS.replace(":id", &hex::encode([9u8; 32])),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_create_offer() -> Result<()> {
+    let context = create_api_server().await?;
+    let offer = &mock_lightning().offer;
+    let request = CreateOffer {
+        amount: Some(200000),
+        label: Some("label".to_string()),
+        description: "test offer description".to_string(),
+        expiry: None,
+    };
+    let response: Offer =
+        admin_request_with_body(&context, Method::POST, routes::CREATE_OFFER, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(hex::encode(offer.id), response.id);
+    assert_eq!(offer.bolt12.to_string(), response.bolt12);
+    assert_eq!(Some(200000), response.amount_msat);
+    assert_eq!(OfferStatus::Active, response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_offer() -> Result<()> {
+    let context = create_api_server().await?;
+    let offer = &mock_lightning().offer;
+    let request = PayOffer {
+        offer: offer.bolt12.to_string(),
+        label: Some("test label".to_string()),
+        ..Default::default()
+    };
+    let response: PaymentResponse =
+        admin_request_with_body(&context, Method::POST, routes::PAY_OFFER, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(TEST_PUBLIC_KEY, response.destination);
+    assert_eq!(64, response.payment_hash.len());
+    assert_eq!(64, response.payment_preimage.len());
+    assert_eq!(Some(200000), response.amount_msat);
+    assert_eq!(200000, response.amount_sent_msat);
+    assert_eq!("succeeded", response.status);
+
+    // Paying more than the offer asks for reports the requested amount.
+    let request = PayOffer {
+        offer: offer.bolt12.to_string(),
+        amount: Some(250000),
+        ..Default::default()
+    };
+    let response: PaymentResponse =
+        admin_request_with_body(&context, Method::POST, routes::PAY_OFFER, || request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(Some(250000), response.amount_msat);
+    assert_eq!(250000, response.amount_sent_msat);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_offer_amount_limit() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = PayOffer {
+        offer: mock_lightning().offer.bolt12.to_string(),
+        ..Default::default()
+    };
+    let route = routes::PAY_OFFER;
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        limited_request(&context, "payments:send", 300_000, route, &request).await?
+    );
+    let request = PayOffer {
+        max_fee_msat: Some(1000),
+        ..request
+    };
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        limited_request(&context, "payments:send", 200_999, route, &request).await?
+    );
+    assert!(
+        limited_request(&context, "payments:send", 201_000, route, &request)
+            .await?
+            .is_success()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_offers() -> Result<()> {
+    let context = create_api_server().await?;
+    let offer = &mock_lightning().offer;
+    let response: Vec<Offer> = readonly_request(&context, Method::GET, routes::LIST_OFFERS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let offer_response = response.first().context("expected offer")?;
+    assert_eq!(offer.label, offer_response.label);
+    assert_eq!(offer.bolt12.to_string(), offer_response.bolt12);
+    assert_eq!("test offer description", offer_response.description);
+    assert_eq!(Some(200000), offer_response.amount_msat);
+    assert_eq!(None, offer_response.expires_at);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_keysend_admin() -> Result<()> {
     let context = create_api_server().await?;
@@ -849,6 +2094,89 @@ async fn test_fetch_forwards() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fetch_forwards_paged() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: Vec<GetV1ChannelListForwardsResponseItem> = readonly_request(
+        &context,
+        Method::GET,
+        &format!(
+            "{}?offset=0&limit=10&from=1600000000&to=2000000000&sort=desc",
+            routes::LIST_FORWARDS
+        ),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(1, response.len());
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?from=2000000000&to=1600000000", routes::LIST_FORWARDS),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?sort=sideways", routes::LIST_FORWARDS),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forwarding_report() -> Result<()> {
+    let context = create_api_server().await?;
+    let report: ForwardingReport = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?bucket=week", routes::FORWARDING_REPORT),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!("week", report.bucket);
+    let period = report.periods.first().context("expected period")?;
+    assert_eq!(1, period.forwards);
+    assert_eq!(5000000, period.volume_msat);
+    assert_eq!(3000, period.fees_msat);
+    assert_eq!(Some(&2), period.failures.get("NextHopChannel"));
+
+    // Least profitable first: the channel we paid to open, then the inbound and outbound channel of the forward.
+    assert_eq!(3, report.channels.len());
+    let opened = &report.channels[0];
+    assert_eq!(hex::encode([1u8; 32]), opened.channel_id);
+    assert_eq!(Some(1500), opened.open_cost_sat);
+    assert_eq!(-1500000, opened.profit_msat);
+    assert_eq!(Some(TEST_PUBLIC_KEY.to_string()), opened.counterparty);
+    let inbound = &report.channels[1];
+    assert_eq!(5000000, inbound.volume_in_msat);
+    assert_eq!(2, inbound.failed_in);
+    let outbound = &report.channels[2];
+    assert_eq!(5000000, outbound.volume_out_msat);
+    assert_eq!(3000, outbound.fees_earned_msat);
+    assert_eq!(2, outbound.failed_out);
+    assert_eq!(3000, outbound.profit_msat);
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?bucket=year", routes::FORWARDING_REPORT),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_channel_history() -> Result<()> {
     let context = create_api_server().await?;
@@ -886,6 +2214,30 @@ async fn test_channel_history() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fee_history() -> Result<()> {
+    let context = create_api_server().await?;
+    let channel_id = hex::encode(mock_lightning().channel.channel_id.0);
+    let response: Vec<FeeUpdate> =
+        readonly_request(&context, Method::GET, routes::CHANNEL_FEE_HISTORY)?
+            .query(&[("channel_id", &channel_id)])
+            .send()
+            .await?
+            .json()
+            .await?;
+    let update = response.first().context("expected fee update")?;
+    assert_eq!(channel_id, update.channel_id);
+    assert_eq!(1000, update.previous_ppm);
+    assert_eq!(1500, update.ppm);
+
+    let response = readonly_request(&context, Method::GET, routes::CHANNEL_FEE_HISTORY)?
+        .query(&[("channel_id", "xyz")])
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_decode_invoice() -> Result<()> {
     let context = create_api_server().await?;
@@ -915,6 +2267,40 @@ async fn test_decode_invoice() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_websocket_events() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(&context, Method::GET, routes::WEBSOCKET)?
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_VERSION, "13")
+        .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+        .send()
+        .await?;
+    assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+    let mut socket =
+        WebSocketStream::from_raw_socket(response.upgrade().await?, Role::Client, None).await;
+
+    let subscription = EventSubscription {
+        subscribe: vec![EventTopic::Blocks],
+        unsubscribe: vec![],
+    };
+    socket
+        .send(Message::Text(serde_json::to_string(&subscription)?))
+        .await?;
+    let response: EventSubscriptionResponse = next_websocket_message(&mut socket).await?;
+    assert_eq!(vec![EventTopic::Blocks], response.topics);
+
+    let event = StreamEvent::NewBlock {
+        height: 800000,
+        hash: "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5".to_string(),
+    };
+    mock_lightning().event_sender.send(event.clone())?;
+    let received: StreamEvent = next_websocket_message(&mut socket).await?;
+    assert_eq!(event, received);
+    Ok(())
+}
+
 fn withdraw_request() -> WalletTransfer {
     WalletTransfer {
         address: TEST_ADDRESS.to_string(),
@@ -937,6 +2323,7 @@ fn fund_channel_request() -> FundChannel {
         compact_lease: None,
         min_conf: Some(5),
         utxos: vec![],
+        batch: vec![],
     }
 }
 
@@ -948,6 +2335,15 @@ fn set_channel_fee_request() -> ChannelFee {
     }
 }
 
+fn rebalance_request() -> RebalanceChannel {
+    RebalanceChannel {
+        outbound_channel_id: TEST_SHORT_CHANNEL_ID.to_string(),
+        inbound_channel_id: TEST_SHORT_CHANNEL_ID.to_string(),
+        amount: 100000,
+        max_fee_ppm: Some(500),
+    }
+}
+
 fn keysend_request() -> KeysendRequest {
     KeysendRequest {
         pubkey: TEST_PUBLIC_KEY.to_string(),
@@ -957,6 +2353,8 @@ fn keysend_request() -> KeysendRequest {
         retry_for: None,
         maxdelay: None,
         exemptfee: None,
+        max_fee_msat: None,
+        non_blocking: None,
     }
 }
 
@@ -1000,8 +2398,13 @@ pub async fn create_api_server() -> Result<Arc<TestContext>> {
     settings.rest_api_address = rest_api_address.clone();
     let certs_dir = settings.certs_dir.clone();
     let macaroon_auth = Arc::new(
-        MacaroonAuth::init(&[0u8; 32], &settings.data_dir)
-            .context("cannot initialize macaroon auth")?,
+        MacaroonAuth::init(
+            &[0u8; 32],
+            &settings.data_dir,
+            Arc::new(MockMacaroonKeyStore::default()),
+        )
+        .await
+        .context("cannot initialize macaroon auth")?,
     );
     let admin_macaroon = admin_macaroon(&settings)?;
     let readonly_macaroon = readonly_macaroon(&settings)?;
@@ -1071,6 +2474,23 @@ fn unauthorized_request(
     Ok(https_client(None)?.request(method, format!("https://{address}{route}")))
 }
 
+async fn next_websocket_message<T: DeserializeOwned, S>(
+    socket: &mut WebSocketStream<S>,
+) -> Result<T>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Skip the pings and read the next text message from the server.
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(10), socket.next())
+            .await?
+            .context("websocket closed")??;
+        if let Message::Text(text) = message {
+            return Ok(serde_json::from_str(&text)?);
+        }
+    }
+}
+
 fn admin_request(context: &TestContext, method: Method, route: &str) -> Result<RequestBuilder> {
     let address = &context.settings.rest_api_address;
     Ok(https_client(Some(context.admin_macaroon.clone()))?
@@ -1087,6 +2507,44 @@ fn admin_request_with_body<T: Serialize, F: FnOnce() -> T>(
     Ok(admin_request(context, method, route)?.body(body))
 }
 
+// POSTs the body with a macaroon limited to the permission and amount.
+async fn limited_request<T: Serialize>(
+    context: &TestContext,
+    permission: &str,
+    max_amount_msat: u64,
+    route: &str,
+    body: &T,
+) -> Result<StatusCode> {
+    let address = &context.settings.rest_api_address;
+    Ok(limited_client(context, permission, max_amount_msat)
+        .await?
+        .post(format!("https://{address}{route}"))
+        .body(serde_json::to_string(body)?)
+        .send()
+        .await?
+        .status())
+}
+
+async fn limited_client(
+    context: &TestContext,
+    permission: &str,
+    max_amount_msat: u64,
+) -> Result<Client> {
+    let response: BakeMacaroonResponse =
+        admin_request_with_body(context, Method::POST, routes::BAKE_MACAROON, || {
+            BakeMacaroon {
+                permissions: vec![permission.to_string()],
+                max_amount_msat: Some(max_amount_msat),
+                ..Default::default()
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    https_client(Some(response.macaroon.into_bytes()))
+}
+
 fn readonly_request(context: &TestContext, method: Method, route: &str) -> Result<RequestBuilder> {
     let address = &context.settings.rest_api_address;
     Ok(https_client(Some(context.readonly_macaroon.clone()))?
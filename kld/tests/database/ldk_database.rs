@@ -3,16 +3,27 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
+use bitcoin::absolute::LockTime;
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::{sha256, Hash};
-use bitcoin::secp256k1::{Secp256k1, SecretKey};
-use bitcoin::{Network, TxOut, Txid};
-use kld::database::forward::{Forward, ForwardStatus};
-use kld::database::invoice::Invoice;
-use kld::database::payment::{Payment, PaymentDirection};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::{Network, Transaction, TxIn, TxOut, Txid};
+use kld::database::channel_rejection::ChannelRejection;
+use kld::database::fee_history::ChannelFeeUpdate;
+use kld::database::forward::{Forward, ForwardStatus, TimeBucket};
+use kld::database::invoice::{Invoice, InvoiceStatus};
+use kld::database::lsps1::{Lsps1Order, OrderState, PaymentState};
+use kld::database::lsps2::{
+    JitChannel, JitChannelStatus, JitInvoice, Lsps2Offer, Lsps2Token, JIT_USER_CHANNEL_ID_START,
+};
+use kld::database::macaroon::{MacaroonKeyStore, MacaroonRootKey};
+use kld::database::offer::Offer;
+use kld::database::payment::{Payment, PaymentAttempt, PaymentDirection, PaymentStatus};
 use kld::database::peer::Peer;
-use kld::database::ChannelRecord;
+use kld::database::rebalance::Rebalance;
+use kld::database::sweep::{OutputSweep, SweepStatus};
 use kld::database::LdkDatabase;
+use kld::database::{microsecond_timestamp, ChannelRecord, Pagination};
 use kld::ldk::Scorer;
 
 use kld::logger::KldLogger;
@@ -21,16 +32,17 @@ use lightning::chain::chainmonitor::ChainMonitor;
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::Filter;
 
-use lightning::events::ClosureReason;
+use lightning::events::{ClosureReason, PathFailure};
 use lightning::ln::channelmanager::{
     ChannelCounterparty, ChannelDetails, CounterpartyForwardingInfo,
 };
-use lightning::ln::features::{ChannelTypeFeatures, InitFeatures};
+use lightning::ln::features::{ChannelFeatures, ChannelTypeFeatures, InitFeatures, NodeFeatures};
 use lightning::ln::msgs::SocketAddress;
 use lightning::ln::ChannelId;
 use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
+use lightning::offers::offer::OfferBuilder;
 use lightning::routing::gossip::NetworkGraph;
-use lightning::routing::router::DefaultRouter;
+use lightning::routing::router::{DefaultRouter, Path, RouteHop};
 use lightning::routing::scoring::{
     ProbabilisticScorer, ProbabilisticScoringDecayParameters, ProbabilisticScoringFeeParameters,
 };
@@ -42,6 +54,7 @@ use test_utils::{
     init_db_test_context, poll, random_public_key, TempDir, TEST_PRIVATE_KEY, TEST_PUBLIC_KEY,
     TEST_TX_ID,
 };
+use time::OffsetDateTime;
 
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_peers() -> Result<()> {
@@ -105,7 +118,9 @@ pub async fn test_forwards() -> Result<()> {
     assert_eq!(amount, total.amount);
     assert_eq!(fee, total.fee);
 
-    let forwards = database.fetch_forwards(None).await?;
+    let forwards = database
+        .fetch_forwards(None, &Pagination::default())
+        .await?;
     assert_eq!(
         forwards.first().context("expected success forward")?,
         &forward_success
@@ -116,10 +131,347 @@ pub async fn test_forwards() -> Result<()> {
     );
 
     let forwards = database
-        .fetch_forwards(Some(ForwardStatus::Succeeded))
+        .fetch_forwards(Some(ForwardStatus::Succeeded), &Pagination::default())
         .await?;
     assert_eq!(1, forwards.len());
 
+    let newest = Pagination {
+        limit: Some(1),
+        descending: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![forward_fail.clone()],
+        database.fetch_forwards(None, &newest).await?
+    );
+    let second = Pagination {
+        offset: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![forward_fail.clone()],
+        database.fetch_forwards(None, &second).await?
+    );
+    let before_failure = Pagination {
+        to: Some(forward_fail.timestamp),
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![forward_success.clone()],
+        database.fetch_forwards(None, &before_failure).await?
+    );
+
+    let summaries = database
+        .fetch_forward_summaries(TimeBucket::Day, None, None)
+        .await?;
+    let succeeded = summaries
+        .iter()
+        .find(|s| s.succeeded > 0)
+        .context("expected summary of the success")?;
+    assert_eq!(
+        ChannelId::from_bytes([0u8; 32]),
+        succeeded.inbound_channel_id
+    );
+    assert_eq!(
+        Some(ChannelId::from_bytes([1u8; 32])),
+        succeeded.outbound_channel_id
+    );
+    assert_eq!((amount, fee), (succeeded.amount, succeeded.fee));
+    let failed = summaries
+        .iter()
+        .find(|s| !s.failures.is_empty())
+        .context("expected summary of the failure")?;
+    assert_eq!(ChannelId::from_bytes([3u8; 32]), failed.inbound_channel_id);
+    assert_eq!(Some(&1), failed.failures.get("FailedPayment"));
+    assert!(database
+        .fetch_forward_summaries(
+            TimeBucket::Month,
+            Some(forward_fail.timestamp + Duration::from_secs(1)),
+            None
+        )
+        .await?
+        .is_empty());
+
+    let forwarded_amounts = database
+        .fetch_forwarded_amounts(forward_success.timestamp - Duration::from_secs(60))
+        .await?;
+    assert_eq!(
+        Some(&amount),
+        forwarded_amounts.get(&ChannelId::from_bytes([1u8; 32]))
+    );
+    assert_eq!(1, forwarded_amounts.len());
+    assert!(database
+        .fetch_forwarded_amounts(forward_success.timestamp + Duration::from_secs(60))
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_rebalances() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let mut rebalance = Rebalance::new(
+        Payment::new_id(),
+        ChannelId::from_bytes([4u8; 32]),
+        ChannelId::from_bytes([5u8; 32]),
+        100000,
+    );
+    database.persist_rebalance(&rebalance).await?;
+    assert_eq!(vec![rebalance.clone()], database.fetch_rebalances().await?);
+
+    rebalance.status = PaymentStatus::Succeeded;
+    rebalance.fee = Some(120);
+    database.persist_rebalance(&rebalance).await?;
+    assert_eq!(vec![rebalance], database.fetch_rebalances().await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_fee_history() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let first_update =
+        ChannelFeeUpdate::new(ChannelId::from_bytes([6u8; 32]), 0, 500, 1000, 0.5, 0);
+    database.persist_channel_fee_update(&first_update).await?;
+    let second_update = ChannelFeeUpdate::new(
+        ChannelId::from_bytes([7u8; 32]),
+        100,
+        1200,
+        1000,
+        0.1,
+        250000,
+    );
+    database.persist_channel_fee_update(&second_update).await?;
+
+    assert_eq!(
+        vec![first_update.clone(), second_update.clone()],
+        database.fetch_channel_fee_history(None).await?
+    );
+    assert_eq!(
+        vec![second_update],
+        database
+            .fetch_channel_fee_history(Some(ChannelId::from_bytes([7u8; 32])))
+            .await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_rejections() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let rejection = ChannelRejection::new(
+        ChannelId::from_bytes([2u8; 32]),
+        random_public_key(),
+        100000,
+        0,
+        "Peer is on the deny list".to_string(),
+    );
+    database.persist_channel_rejection(&rejection).await?;
+
+    let rejections = database.fetch_channel_rejections().await?;
+    assert_eq!(vec![rejection], rejections);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_macaroon_root_keys() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let admin_key = MacaroonRootKey::new(0, true);
+    let mut root_key = MacaroonRootKey::new(1, false);
+    database.persist_macaroon_root_key(&admin_key).await?;
+    database.persist_macaroon_root_key(&root_key).await?;
+    assert_eq!(
+        vec![admin_key.clone(), root_key.clone()],
+        database.fetch_macaroon_root_keys().await?
+    );
+
+    root_key.revoked_at = Some(microsecond_timestamp());
+    database.persist_macaroon_root_key(&root_key).await?;
+    assert_eq!(
+        vec![admin_key, root_key],
+        database.fetch_macaroon_root_keys().await?
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_lsps2() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let mut token = Lsps2Token::new("kuutamo".to_string(), 2000, 1000, 600, 10_000, 5_000_000);
+    database.persist_lsps2_token(&token).await?;
+    token.proportional = 2000;
+    database.persist_lsps2_token(&token).await?;
+    assert_eq!(
+        Some(token.clone()),
+        database.fetch_lsps2_token("kuutamo").await?
+    );
+    assert_eq!(vec![token], database.fetch_lsps2_tokens().await?);
+    assert!(database.delete_lsps2_token("kuutamo").await?);
+    assert!(!database.delete_lsps2_token("kuutamo").await?);
+    assert!(database.fetch_lsps2_tokens().await?.is_empty());
+
+    let counterparty = PublicKey::from_str(TEST_PUBLIC_KEY)?;
+    let valid_until = OffsetDateTime::from_unix_timestamp(4_000_000_000)?;
+    let expired = Lsps2Offer {
+        counterparty,
+        token: "expired".to_string(),
+        min_fee_msat: 2000,
+        proportional: 1000,
+        valid_until: OffsetDateTime::from_unix_timestamp(1_000_000_000)?,
+    };
+    database.persist_lsps2_offer(&expired).await?;
+    let offer = Lsps2Offer {
+        token: "kuutamo".to_string(),
+        valid_until,
+        ..expired.clone()
+    };
+    database.persist_lsps2_offer(&offer).await?;
+    assert_eq!(
+        vec![offer],
+        database
+            .fetch_lsps2_offers(&counterparty, &valid_until)
+            .await?
+    );
+    assert!(database
+        .fetch_lsps2_offers(&counterparty, &expired.valid_until)
+        .await?
+        .is_empty());
+
+    let user_channel_id = JIT_USER_CHANNEL_ID_START + 5;
+    let mut channel = JitChannel::new(
+        user_channel_id,
+        Some("kuutamo".to_string()),
+        PublicKey::from_str(TEST_PUBLIC_KEY)?,
+        u64::MAX - 1,
+        None,
+    );
+    database.persist_jit_channel(&channel).await?;
+    assert_eq!(
+        Some(channel.clone()),
+        database.fetch_jit_channel(user_channel_id).await?
+    );
+
+    channel.opening_fee_msat = Some(2000);
+    channel.channel_value_sats = Some(1100);
+    channel.channel_id = Some(ChannelId::from_bytes([1u8; 32]));
+    channel.status = JitChannelStatus::Ready;
+    database.persist_jit_channel(&channel).await?;
+    assert_eq!(vec![channel.clone()], database.fetch_jit_channels().await?);
+    channel.status = JitChannelStatus::Closed;
+    database.persist_jit_channel(&channel).await?;
+    assert_eq!(vec![channel], database.fetch_jit_channels().await?);
+
+    let lsp = PublicKey::from_str(TEST_PUBLIC_KEY)?;
+    assert_eq!(0, database.open_jit_invoice_count(&lsp).await?);
+    let private_key = SecretKey::from_slice(&TEST_PRIVATE_KEY)?;
+    let bolt11 = InvoiceBuilder::new(Currency::Regtest)
+        .description("jit".into())
+        .payment_hash(sha256::Hash::from_slice(&[2u8; 32])?)
+        .payment_secret(PaymentSecret([2u8; 32]))
+        .current_timestamp()
+        .expiry_time(Duration::from_secs(3600))
+        .min_final_cltv_expiry_delta(144)
+        .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &private_key))?;
+    let mut invoice = Invoice::new(Some("jit".to_string()), bolt11)?;
+    database.persist_invoice(&invoice).await?;
+    let jit_invoice = JitInvoice::new(PaymentHash([2u8; 32]), lsp, u64::MAX - 1, 2000, 1000);
+    database.persist_jit_invoice(&jit_invoice).await?;
+    assert_eq!(
+        Some(jit_invoice),
+        database.fetch_jit_invoice(&PaymentHash([2u8; 32])).await?
+    );
+    assert_eq!(
+        None,
+        database.fetch_jit_invoice(&PaymentHash([3u8; 32])).await?
+    );
+    assert_eq!(1, database.open_jit_invoice_count(&lsp).await?);
+    invoice.status = InvoiceStatus::Canceled;
+    database.persist_invoice(&invoice).await?;
+    assert_eq!(0, database.open_jit_invoice_count(&lsp).await?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_lsps1_orders() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let timestamp = microsecond_timestamp();
+    let mut order = Lsps1Order {
+        order_id: "a1b2c3".to_string(),
+        counterparty: PublicKey::from_str(TEST_PUBLIC_KEY)?,
+        lsp_balance_sat: 1_000_000,
+        client_balance_sat: 10_000,
+        required_channel_confirmations: 0,
+        funding_confirms_within_blocks: 6,
+        channel_expiry_blocks: 4320,
+        token: Some("kuutamo".to_string()),
+        announce_channel: true,
+        fee_total_sat: 12_000,
+        order_total_sat: 22_000,
+        payment_hash: PaymentHash([1u8; 32]),
+        bolt11_invoice: "lnbcrt220u1".to_string(),
+        onchain_address: TEST_ADDRESS.to_string(),
+        min_onchain_payment_confirmations: 1,
+        refund_onchain_address: None,
+        expires_at: timestamp,
+        order_state: OrderState::Created,
+        payment_state: PaymentState::ExpectPayment,
+        user_channel_id: None,
+        funding_outpoint: None,
+        funded_at: None,
+        created_at: timestamp,
+        updated_at: timestamp,
+    };
+    database.persist_lsps1_order(&order).await?;
+    assert_eq!(
+        Some(order.clone()),
+        database.fetch_lsps1_order("a1b2c3").await?
+    );
+    assert_eq!(None, database.fetch_lsps1_order("x").await?);
+
+    order.payment_state = PaymentState::Paid;
+    order.order_state = OrderState::Completed;
+    order.user_channel_id = Some(5);
+    order.funding_outpoint = Some(format!("{TEST_TX_ID}:0"));
+    order.funded_at = Some(microsecond_timestamp());
+    database.persist_lsps1_order(&order).await?;
+    assert!(database
+        .fetch_lsps1_orders(Some(OrderState::Created))
+        .await?
+        .is_empty());
+    assert_eq!(
+        vec![order.clone()],
+        database
+            .fetch_lsps1_orders(Some(OrderState::Completed))
+            .await?
+    );
+    assert_eq!(vec![order], database.fetch_lsps1_orders(None).await?);
     Ok(())
 }
 
@@ -149,29 +501,76 @@ pub async fn test_invoice_payments() -> Result<()> {
     database.persist_invoice(&invoice).await?;
 
     let result = database
-        .fetch_invoices(Some(label.clone()))
+        .fetch_invoices(Some(label.clone()), &Pagination::default())
         .await?
         .into_iter()
         .last()
         .context("expected invoice")?;
     assert_eq!(result, invoice);
 
+    database
+        .update_invoice_status(&invoice.payment_hash, InvoiceStatus::Held, Some(800_000))
+        .await?;
+    assert_eq!(
+        Some(InvoiceStatus::Held),
+        database.fetch_invoice_status(&invoice.payment_hash).await?
+    );
+    assert_eq!(
+        vec![(invoice.payment_hash, Some(800_000))],
+        database.fetch_held_invoices().await?
+    );
+
+    assert_eq!(None, database.fetch_next_paid_invoice(0).await?);
+    database
+        .persist_invoice_payment(&invoice.payment_hash, 1000)
+        .await?;
+    // Replayed claim events don't pay the invoice twice.
+    database
+        .persist_invoice_payment(&invoice.payment_hash, 1000)
+        .await?;
+    let paid = database
+        .fetch_next_paid_invoice(0)
+        .await?
+        .context("expected paid invoice")?;
+    assert_eq!(InvoiceStatus::Settled, paid.status);
+    assert_eq!(Some(1000), paid.amount_received);
+    assert_eq!(Some(1), paid.pay_index);
+    assert!(paid.paid_at.is_some());
+    assert!(database.fetch_held_invoices().await?.is_empty());
+    assert_eq!(None, database.fetch_next_paid_invoice(1).await?);
+
     let mut payment = Payment::of_invoice_outbound(&invoice, Some("label".to_string()));
     database.persist_payment(&payment).await?;
 
     let result = database
-        .fetch_invoices(Some(label.clone()))
+        .fetch_invoices(Some(label.clone()), &Pagination::default())
         .await?
         .into_iter()
         .last()
         .context("expected invoice")?;
     assert_eq!(1, result.payments.len());
 
-    let result = database.fetch_invoices(None).await?;
+    let result = database
+        .fetch_invoices(None, &Pagination::default())
+        .await?;
     assert_eq!(1, result.len());
 
+    // The limit counts invoices, not their payments.
+    let first = Pagination {
+        limit: Some(1),
+        ..Default::default()
+    };
+    let result = database.fetch_invoices(None, &first).await?;
+    assert_eq!(1, result.len());
+    assert_eq!(1, result[0].payments.len());
+    let later = Pagination {
+        from: Some(OffsetDateTime::from(invoice.timestamp) + Duration::from_secs(1)),
+        ..Default::default()
+    };
+    assert!(database.fetch_invoices(None, &later).await?.is_empty());
+
     let stored_payments = database
-        .fetch_payments(None, None)
+        .fetch_payments(None, None, &Pagination::default())
         .await?
         .into_iter()
         .find(|p| p.id == payment.id)
@@ -182,7 +581,11 @@ pub async fn test_invoice_payments() -> Result<()> {
     database.persist_payment(&payment).await?;
 
     let stored_payments = database
-        .fetch_payments(payment.hash, Some(PaymentDirection::Outbound))
+        .fetch_payments(
+            payment.hash,
+            Some(PaymentDirection::Outbound),
+            &Pagination::default(),
+        )
         .await?;
     assert_eq!(1, stored_payments.len());
     assert_eq!(
@@ -193,6 +596,90 @@ pub async fn test_invoice_payments() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_payment_attempts() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let payment = Payment::spontaneous_outbound(Payment::new_id(), 1000);
+    database.persist_payment(&payment).await?;
+    assert_eq!(
+        Some(payment.clone()),
+        database.fetch_payment(&payment.id).await?
+    );
+    assert_eq!(None, database.fetch_payment(&Payment::new_id()).await?);
+
+    let path = Path {
+        hops: vec![RouteHop {
+            pubkey: PublicKey::from_str(TEST_PUBLIC_KEY)?,
+            node_features: NodeFeatures::empty(),
+            short_channel_id: 42,
+            channel_features: ChannelFeatures::empty(),
+            fee_msat: 1000,
+            cltv_expiry_delta: 40,
+            maybe_announced_channel: true,
+        }],
+        blinded_tail: None,
+    };
+    let failed = PaymentAttempt::failed(
+        payment.id,
+        path.clone(),
+        PathFailure::OnPath {
+            network_update: None,
+        },
+        Some(42),
+        false,
+    );
+    database.persist_payment_attempt(&failed).await?;
+    let succeeded = PaymentAttempt::succeeded(payment.id, path);
+    database.persist_payment_attempt(&succeeded).await?;
+
+    let attempts = database.fetch_payment_attempts(&payment.id).await?;
+    assert_eq!(vec![failed, succeeded], attempts);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_offers() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let bolt12 = OfferBuilder::new("test".into(), PublicKey::from_str(TEST_PUBLIC_KEY)?)
+        .amount_msats(1000)
+        .absolute_expiry(Duration::from_secs(4102444800))
+        .build()
+        .map_err(|e| anyhow!("{e:?}"))?;
+    let label = "test label".to_owned();
+    let offer = Offer::new(Some(label.clone()), bolt12);
+    database.persist_offer(&offer).await?;
+
+    let bolt12 = OfferBuilder::new("other".into(), PublicKey::from_str(TEST_PUBLIC_KEY)?)
+        .build()
+        .map_err(|e| anyhow!("{e:?}"))?;
+    database.persist_offer(&Offer::new(None, bolt12)).await?;
+
+    let result = database
+        .fetch_offers(Some(label))
+        .await?
+        .into_iter()
+        .last()
+        .context("expected offer")?;
+    assert_eq!(result, offer);
+    assert_eq!(result.bolt12.to_string(), offer.bolt12.to_string());
+    assert_eq!(Some(1000), result.amount);
+    assert_eq!(Some(4102444800), result.expiry);
+
+    let result = database.fetch_offers(None).await?;
+    assert_eq!(2, result.len());
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_network_graph() -> Result<()> {
     KldLogger::init("test", log::LevelFilter::Debug);
@@ -343,6 +830,63 @@ pub async fn test_spendable_outputs() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_output_sweeps() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (settings, _cockroach, durable_connection) = init_db_test_context(&temp_dir).await?;
+
+    let database = LdkDatabase::new(settings.into(), durable_connection.into());
+
+    let outpoint = OutPoint {
+        txid: Txid::from_str(TEST_TX_ID)?,
+        index: 1,
+    };
+    let descriptor = SpendableOutputDescriptor::StaticOutput {
+        outpoint,
+        output: TxOut::default(),
+        channel_keys_id: None,
+    };
+    database
+        .persist_spendable_output(&descriptor, None, false)
+        .await?;
+
+    let transaction = Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint.into_bitcoin_outpoint(),
+            ..Default::default()
+        }],
+        output: vec![TxOut::default()],
+    };
+    let mut sweep = OutputSweep::new(transaction, 1000, 800_000);
+    database.persist_output_sweep(&sweep).await?;
+    assert_eq!(
+        vec![sweep.clone()],
+        database
+            .fetch_output_sweeps(Some(SweepStatus::Pending))
+            .await?
+    );
+
+    sweep.confirmation_height = Some(800_001);
+    sweep.status = SweepStatus::Confirmed;
+    database.persist_output_sweep(&sweep).await?;
+    database
+        .mark_spendable_outputs_spent(&sweep.outputs())
+        .await?;
+    assert!(database
+        .fetch_output_sweeps(Some(SweepStatus::Pending))
+        .await?
+        .is_empty());
+    assert_eq!(vec![sweep], database.fetch_output_sweeps(None).await?);
+    assert!(database
+        .fetch_spendable_outputs()
+        .await?
+        .iter()
+        .all(|output| output.is_spent));
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_channels() -> Result<()> {
     let temp_dir = TempDir::new()?;
@@ -355,7 +899,7 @@ pub async fn test_channels() -> Result<()> {
     type_features.set_scid_privacy_required();
     let mut initializing_channel_id = ChannelId::from_bytes([0; 32]);
     let mut channel_id = ChannelId::from_bytes([1; 32]);
-    let counterparty = bitcoin::secp256k1::PublicKey::from_str(TEST_PUBLIC_KEY)?;
+    let counterparty = PublicKey::from_str(TEST_PUBLIC_KEY)?;
     let txid = Txid::from_raw_hash(bitcoin_hashes::sha256d::Hash::from_slice(
         &Vec::<u8>::from_hex(TEST_TX_ID)?[..],
     )?);
@@ -413,7 +957,9 @@ pub async fn test_channels() -> Result<()> {
         .await?;
     let mut channels = database.fetch_channels().await?;
     assert_eq!(0, channels.len());
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(0, channels.len());
 
     database.persist_channel(&channel).await?;
@@ -426,7 +972,9 @@ pub async fn test_channels() -> Result<()> {
     } = channels.first().context("expected channel")?;
     assert_eq!(*detail, Some(channel.clone()));
     assert!(closure_reason.is_none());
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(0, channels.len());
 
     channel.is_usable = false;
@@ -436,7 +984,9 @@ pub async fn test_channels() -> Result<()> {
         .await?;
     channels = database.fetch_channels().await?;
     assert_eq!(1, channels.len());
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(1, channels.len());
     let ChannelRecord {
         open_timestamp,
@@ -454,7 +1004,9 @@ pub async fn test_channels() -> Result<()> {
     //
     initializing_channel_id = ChannelId::from_bytes([2; 32]);
     channel_id = ChannelId::from_bytes([3; 32]);
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     let previous_channel_num = channels.len();
     database
         .persist_initializing_channel(&initializing_channel_id, true, &counterparty, &txid)
@@ -466,7 +1018,9 @@ pub async fn test_channels() -> Result<()> {
             None::<&str>,
         )
         .await?;
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(previous_channel_num, channels.len());
     channels = database.fetch_channels().await?;
     assert_eq!(previous_channel_num, channels.len());
@@ -474,7 +1028,9 @@ pub async fn test_channels() -> Result<()> {
     database
         .create_channel(&channel_id, true, &counterparty)
         .await?;
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(previous_channel_num, channels.len());
     channels = database.fetch_channels().await?;
     assert_eq!(previous_channel_num + 1, channels.len());
@@ -491,7 +1047,9 @@ pub async fn test_channels() -> Result<()> {
         .close_channel(&channel_id, format!("{reason}"))
         .await?;
     // NOTE channel_history is not hanndle any channel without details
-    channels = database.fetch_channel_history().await?;
+    channels = database
+        .fetch_channel_history(&Pagination::default())
+        .await?;
     assert_eq!(previous_channel_num, channels.len());
     channels = database.fetch_channels().await?;
     assert_eq!(previous_channel_num + 1, channels.len());
@@ -1,52 +1,70 @@
 use std::{
     net::{SocketAddrV4, SocketAddrV6},
     str::FromStr,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use bitcoin::{
     consensus::deserialize,
     hashes::{hex::FromHex, sha256, Hash},
     secp256k1::{PublicKey, Secp256k1, SecretKey},
-    Network, Txid,
+    Network, ScriptBuf, Transaction, Txid,
 };
-use kld::api::payloads::FeeRate;
+use kld::api::payloads::StreamEvent;
 use kld::{
     api::SocketAddress,
     database::{
-        forward::{Forward, ForwardStatus, TotalForwards},
-        microsecond_timestamp, ChannelRecord,
+        forward::{Forward, ForwardStatus, ForwardSummary, TimeBucket, TotalForwards},
+        microsecond_timestamp, ChannelRecord, Pagination,
     },
 };
 use kld::{
     database::{
-        invoice::Invoice,
-        payment::{Payment, PaymentDirection},
+        fee_history::ChannelFeeUpdate,
+        invoice::{Invoice, InvoiceStatus},
+        lsps1::{Lsps1Order, OrderState, PaymentState},
+        lsps2::{JitChannel, JitChannelStatus, Lsps2Token, JIT_USER_CHANNEL_ID_START},
+        offer::Offer,
+        payment::{Payment, PaymentAttempt, PaymentDirection, PaymentStatus},
+        rebalance::Rebalance,
+        sweep::OutputSweep,
+    },
+    ldk::{
+        ChannelOrder, ChannelTarget, FundingOptions, LightningInterface, LspInfo, LspOrder,
+        Lsps1Options, OpenChannelResult, PathEstimate, PaymentOptions, Peer, PeerStatus,
+        RouteQuery,
     },
-    ldk::{LightningInterface, OpenChannelResult, Peer, PeerStatus},
     MillisatAmount,
 };
 use lightning::{
     chain::transaction::OutPoint,
-    events::ClosureReason,
+    events::{ClosureReason, PathFailure},
     ln::{
-        channelmanager::{ChannelCounterparty, ChannelDetails},
-        features::{ChannelTypeFeatures, Features, InitFeatures},
-        ChannelId, PaymentPreimage, PaymentSecret,
+        channelmanager::{ChannelCounterparty, ChannelDetails, PaymentId},
+        features::{ChannelFeatures, ChannelTypeFeatures, Features, InitFeatures, NodeFeatures},
+        ChannelId, PaymentHash, PaymentPreimage, PaymentSecret,
+    },
+    offers::offer::OfferBuilder,
+    routing::{
+        gossip::{ChannelInfo, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
+        router::{Path, RouteHop},
     },
-    routing::gossip::{ChannelInfo, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
     util::{
         config::{ChannelConfig, UserConfig},
         indexed_map::IndexedMap,
     },
 };
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
 
 use lightning_invoice::{Currency, InvoiceBuilder};
+use lightning_liquidity::lsps2::msgs::OpeningFeeParams;
 
 use test_utils::{
-    TEST_ALIAS, TEST_PRIVATE_KEY, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX, TEST_TX_ID,
+    TEST_ADDRESS, TEST_ALIAS, TEST_PRIVATE_KEY, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX,
+    TEST_TX_ID,
 };
 
 pub struct MockLightning {
@@ -59,7 +77,9 @@ pub struct MockLightning {
     pub ipv4_address: SocketAddress,
     pub invoice: Invoice,
     pub payment: Payment,
+    pub offer: Offer,
     pub forward: Forward,
+    pub event_sender: broadcast::Sender<StreamEvent>,
 }
 
 impl Default for MockLightning {
@@ -127,6 +147,11 @@ impl Default for MockLightning {
         let invoice =
             kld::database::invoice::Invoice::new(Some("label".to_string()), invoice).unwrap();
         let payment = Payment::of_invoice_outbound(&invoice, Some("label".to_string()));
+        let offer = OfferBuilder::new("test offer description".to_owned(), public_key)
+            .amount_msats(200000)
+            .build()
+            .unwrap();
+        let offer = Offer::new(Some("label".to_string()), offer);
         let forward = Forward::success(
             ChannelId::from_bytes([3u8; 32]),
             ChannelId::from_bytes([4u8; 32]),
@@ -144,7 +169,9 @@ impl Default for MockLightning {
             ipv4_address: socket_addr.into(),
             invoice,
             payment,
+            offer,
             forward,
+            event_sender: broadcast::channel(16).0,
         }
     }
 }
@@ -236,20 +263,19 @@ impl LightningInterface for MockLightning {
         vec![addr1.into(), addr2.into()]
     }
 
-    async fn open_channel(
+    async fn open_channels(
         &self,
-        _their_network_key: PublicKey,
-        _channel_value_satoshis: u64,
-        _push_msat: Option<u64>,
-        _fee_rate: Option<FeeRate>,
-        _override_config: Option<UserConfig>,
+        targets: Vec<ChannelTarget>,
+        _funding: FundingOptions,
     ) -> Result<OpenChannelResult> {
         let transaction = deserialize::<bitcoin::Transaction>(&Vec::<u8>::from_hex(TEST_TX)?)?;
         let txid = transaction.txid();
         Ok(OpenChannelResult {
             transaction,
             txid,
-            channel_id: ChannelId::from_bytes([1u8; 32]),
+            channel_ids: (1..=targets.len())
+                .map(|i| ChannelId::from_bytes([i as u8; 32]))
+                .collect(),
         })
     }
 
@@ -279,6 +305,8 @@ impl LightningInterface for MockLightning {
         _channel_id: &ChannelId,
         _counterparty_node_id: &PublicKey,
         _fee_rate: Option<u32>,
+        _max_initial_fee_rate: Option<u32>,
+        _shutdown_script: Option<ScriptBuf>,
     ) -> Result<()> {
         Ok(())
     }
@@ -292,6 +320,24 @@ impl LightningInterface for MockLightning {
         Ok(())
     }
 
+    async fn rebalance(
+        &self,
+        outbound_channel_id: &ChannelId,
+        inbound_channel_id: &ChannelId,
+        amount: MillisatAmount,
+        _max_fee_ppm: Option<u32>,
+    ) -> Result<Rebalance> {
+        let mut rebalance = Rebalance::new(
+            self.payment.id,
+            *outbound_channel_id,
+            *inbound_channel_id,
+            amount,
+        );
+        rebalance.status = PaymentStatus::Succeeded;
+        rebalance.fee = Some(10);
+        Ok(rebalance)
+    }
+
     fn get_node(&self, _node_id: &NodeId) -> Option<NodeInfo> {
         let mut alias = [0u8; 32];
         alias[..TEST_ALIAS.len()].copy_from_slice(TEST_ALIAS.as_bytes());
@@ -308,11 +354,12 @@ impl LightningInterface for MockLightning {
         })
     }
 
-    fn nodes(&self) -> IndexedMap<NodeId, NodeInfo> {
-        let mut nodes = IndexedMap::new();
+    fn nodes(&self, after: Option<NodeId>, limit: Option<usize>) -> Vec<(NodeId, NodeInfo)> {
         let node_id = NodeId::from_pubkey(&self.public_key);
-        nodes.insert(node_id, self.get_node(&node_id).unwrap());
-        nodes
+        if after.is_some_and(|after| after >= node_id) || limit == Some(0) {
+            return vec![];
+        }
+        vec![(node_id, self.get_node(&node_id).unwrap())]
     }
 
     fn get_channel(&self, _channel_id: u64) -> Option<ChannelInfo> {
@@ -323,6 +370,33 @@ impl LightningInterface for MockLightning {
         IndexedMap::new()
     }
 
+    fn find_route(&self, query: RouteQuery) -> Result<Vec<PathEstimate>> {
+        let hop = |pubkey, short_channel_id, fee_msat, cltv_expiry_delta| RouteHop {
+            pubkey,
+            node_features: NodeFeatures::empty(),
+            short_channel_id,
+            channel_features: ChannelFeatures::empty(),
+            fee_msat,
+            cltv_expiry_delta,
+            maybe_announced_channel: true,
+        };
+        Ok(vec![PathEstimate {
+            path: Path {
+                hops: vec![
+                    hop(self.public_key, TEST_SHORT_CHANNEL_ID, 1000, 40),
+                    hop(
+                        query.destination,
+                        TEST_SHORT_CHANNEL_ID + 1,
+                        query.amount,
+                        query.final_cltv_expiry_delta,
+                    ),
+                ],
+                blinded_tail: None,
+            },
+            hop_probabilities: vec![Some(1.0), Some(0.5)],
+        }])
+    }
+
     fn user_config(&self) -> UserConfig {
         UserConfig::default()
     }
@@ -333,12 +407,35 @@ impl LightningInterface for MockLightning {
         _amount: Option<u64>,
         _description: String,
         _expiry: Option<u32>,
+        _payment_hash: Option<PaymentHash>,
     ) -> Result<Invoice> {
         Ok(self.invoice.clone())
     }
 
-    async fn pay_invoice(&self, invoice: Invoice, label: Option<String>) -> Result<Payment> {
+    async fn settle_invoice(&self, preimage: PaymentPreimage) -> Result<()> {
+        if preimage.0 != [1u8; 32] {
+            bail!("Invoice is open, only held invoices can be settled")
+        }
+        Ok(())
+    }
+
+    async fn cancel_invoice(&self, payment_hash: PaymentHash) -> Result<()> {
+        if payment_hash != self.invoice.payment_hash {
+            bail!("No invoice with this payment hash")
+        }
+        Ok(())
+    }
+
+    async fn pay_invoice(
+        &self,
+        invoice: Invoice,
+        label: Option<String>,
+        options: PaymentOptions,
+    ) -> Result<Payment> {
         let mut payment = Payment::of_invoice_outbound(&invoice, label);
+        if let Some(amount) = options.amount {
+            payment.amount = amount;
+        }
         payment.succeeded(invoice.payment_hash, PaymentPreimage([1u8; 32]), Some(2323));
         Ok(payment)
     }
@@ -347,18 +444,122 @@ impl LightningInterface for MockLightning {
         &self,
         _bolt11: Option<Invoice>,
         _direction: Option<PaymentDirection>,
+        _pagination: Pagination,
     ) -> Result<Vec<Payment>> {
         Ok(vec![self.payment.clone()])
     }
 
-    async fn list_invoices(&self, _label: Option<String>) -> Result<Vec<Invoice>> {
+    async fn list_invoices(
+        &self,
+        label: Option<String>,
+        _pagination: Pagination,
+    ) -> Result<Vec<Invoice>> {
+        if label.as_deref() == Some("duplicate") {
+            return Ok(vec![self.invoice.clone(), self.invoice.clone()]);
+        }
         Ok(vec![self.invoice.clone()])
     }
 
-    async fn keysend_payment(&self, _payee: NodeId, _amount: MillisatAmount) -> Result<Payment> {
+    async fn wait_invoice(&self, label: String) -> Result<Option<Invoice>> {
+        if self.invoice.label.as_ref() != Some(&label) {
+            return Ok(None);
+        }
+        Ok(Some(Invoice {
+            status: InvoiceStatus::Settled,
+            amount_received: self.invoice.amount,
+            paid_at: Some(SystemTime::now()),
+            pay_index: Some(1),
+            ..self.invoice.clone()
+        }))
+    }
+
+    async fn wait_any_invoice(&self, lastpay_index: u64) -> Result<Invoice> {
+        Ok(Invoice {
+            status: InvoiceStatus::Settled,
+            amount_received: self.invoice.amount,
+            paid_at: Some(SystemTime::now()),
+            pay_index: Some(lastpay_index + 1),
+            ..self.invoice.clone()
+        })
+    }
+
+    async fn create_offer(
+        &self,
+        _label: Option<String>,
+        _amount: Option<MillisatAmount>,
+        _description: String,
+        _expiry: Option<u64>,
+    ) -> Result<Offer> {
+        Ok(self.offer.clone())
+    }
+
+    async fn pay_offer(
+        &self,
+        _offer: Offer,
+        amount: Option<MillisatAmount>,
+        _max_fee_msat: Option<MillisatAmount>,
+        _payer_note: Option<String>,
+        label: Option<String>,
+    ) -> Result<Payment> {
+        let mut payment =
+            Payment::of_offer_outbound(Payment::new_id(), amount.unwrap_or(200000), label);
+        payment.succeeded(
+            PaymentHash([1u8; 32]),
+            PaymentPreimage([2u8; 32]),
+            Some(2323),
+        );
+        Ok(payment)
+    }
+
+    async fn list_offers(&self, _label: Option<String>) -> Result<Vec<Offer>> {
+        Ok(vec![self.offer.clone()])
+    }
+
+    async fn keysend_payment(
+        &self,
+        _payee: NodeId,
+        _amount: MillisatAmount,
+        _max_fee_msat: Option<MillisatAmount>,
+        _non_blocking: bool,
+    ) -> Result<Payment> {
         Ok(self.payment.clone())
     }
 
+    async fn get_payment(&self, payment_id: PaymentId) -> Result<Option<Payment>> {
+        Ok(Some(self.payment.clone()).filter(|p| p.id == payment_id))
+    }
+
+    async fn list_payment_attempts(&self, payment_id: PaymentId) -> Result<Vec<PaymentAttempt>> {
+        let path = Path {
+            hops: vec![RouteHop {
+                pubkey: self.public_key,
+                node_features: NodeFeatures::empty(),
+                short_channel_id: TEST_SHORT_CHANNEL_ID,
+                channel_features: ChannelFeatures::empty(),
+                fee_msat: 1000,
+                cltv_expiry_delta: 40,
+                maybe_announced_channel: true,
+            }],
+            blinded_tail: None,
+        };
+        Ok(vec![
+            PaymentAttempt::failed(
+                payment_id,
+                path.clone(),
+                PathFailure::OnPath {
+                    network_update: None,
+                },
+                Some(TEST_SHORT_CHANNEL_ID),
+                false,
+            ),
+            PaymentAttempt::succeeded(payment_id, path),
+        ])
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<StreamEvent> {
+        self.event_sender.subscribe()
+    }
+
     async fn estimated_channel_liquidity_range(
         &self,
         _scid: u64,
@@ -375,11 +576,43 @@ impl LightningInterface for MockLightning {
         })
     }
 
-    async fn fetch_forwards(&self, _status: Option<ForwardStatus>) -> Result<Vec<Forward>> {
+    async fn fetch_forwards(
+        &self,
+        _status: Option<ForwardStatus>,
+        _pagination: Pagination,
+    ) -> Result<Vec<Forward>> {
         Ok(vec![self.forward.clone()])
     }
 
-    async fn channel_history(&self) -> Result<Vec<ChannelRecord>> {
+    async fn forward_summaries(
+        &self,
+        _bucket: TimeBucket,
+        _from: Option<OffsetDateTime>,
+        _to: Option<OffsetDateTime>,
+    ) -> Result<Vec<ForwardSummary>> {
+        let mut summary = ForwardSummary::new(
+            self.forward.timestamp,
+            self.forward.inbound_channel_id,
+            self.forward.outbound_channel_id,
+        );
+        summary.succeeded = 1;
+        summary.amount = self.forward.amount.context("expected amount")?;
+        summary.fee = self.forward.fee.context("expected fee")?;
+        summary.failures.insert("NextHopChannel".to_string(), 2);
+        Ok(vec![summary])
+    }
+
+    fn funding_fee(&self, _txid: &Txid) -> Result<Option<u64>> {
+        Ok(Some(1500))
+    }
+
+    fn closing_transaction(&self, _funding_txo: &bitcoin::OutPoint) -> Result<Option<Transaction>> {
+        Ok(Some(deserialize::<Transaction>(&Vec::<u8>::from_hex(
+            TEST_TX,
+        )?)?))
+    }
+
+    async fn channel_history(&self, _pagination: Pagination) -> Result<Vec<ChannelRecord>> {
         Ok(vec![ChannelRecord {
             channel_id: self.channel.channel_id.to_string(),
             counterparty: self.channel.counterparty.node_id.to_string(),
@@ -390,9 +623,154 @@ impl LightningInterface for MockLightning {
         }])
     }
 
+    async fn fee_history(&self, channel_id: Option<ChannelId>) -> Result<Vec<ChannelFeeUpdate>> {
+        if channel_id.is_some_and(|id| id != self.channel.channel_id) {
+            return Ok(vec![]);
+        }
+        Ok(vec![ChannelFeeUpdate::new(
+            self.channel.channel_id,
+            1000,
+            1500,
+            0,
+            0.3,
+            50000,
+        )])
+    }
+
+    async fn list_sweeps(&self) -> Result<Vec<OutputSweep>> {
+        let transaction = deserialize::<bitcoin::Transaction>(&Vec::<u8>::from_hex(TEST_TX)?)?;
+        Ok(vec![OutputSweep::new(transaction, 2000, 800_000)])
+    }
+
+    async fn list_lsps2_tokens(&self) -> Result<Vec<Lsps2Token>> {
+        Ok(vec![Lsps2Token::new(
+            "kuutamo".to_string(),
+            2000,
+            1000,
+            600,
+            10_000,
+            5_000_000,
+        )])
+    }
+
+    async fn persist_lsps2_token(&self, _token: Lsps2Token) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete_lsps2_token(&self, token: &str) -> Result<bool> {
+        Ok(token == "kuutamo")
+    }
+
+    async fn list_jit_channels(&self) -> Result<Vec<JitChannel>> {
+        let mut channel = JitChannel::new(
+            JIT_USER_CHANNEL_ID_START + 1,
+            Some("kuutamo".to_string()),
+            self.public_key,
+            TEST_SHORT_CHANNEL_ID,
+            Some(1_000_000),
+        );
+        channel.opening_fee_msat = Some(2000);
+        channel.channel_value_sats = Some(1100);
+        channel.channel_id = Some(self.channel.channel_id);
+        channel.status = JitChannelStatus::Ready;
+        Ok(vec![channel])
+    }
+
+    async fn list_lsps1_orders(&self) -> Result<Vec<Lsps1Order>> {
+        let timestamp = microsecond_timestamp();
+        Ok(vec![Lsps1Order {
+            order_id: "a1b2c3".to_string(),
+            counterparty: self.public_key,
+            lsp_balance_sat: 1_000_000,
+            client_balance_sat: 0,
+            required_channel_confirmations: 0,
+            funding_confirms_within_blocks: 6,
+            channel_expiry_blocks: 4320,
+            token: None,
+            announce_channel: false,
+            fee_total_sat: 12_000,
+            order_total_sat: 12_000,
+            payment_hash: self.invoice.payment_hash,
+            bolt11_invoice: self.invoice.bolt11.to_string(),
+            onchain_address: TEST_ADDRESS.to_string(),
+            min_onchain_payment_confirmations: 1,
+            refund_onchain_address: None,
+            expires_at: timestamp,
+            order_state: OrderState::Completed,
+            payment_state: PaymentState::Paid,
+            user_channel_id: Some(1),
+            funding_outpoint: Some(format!("{TEST_TX_ID}:0")),
+            funded_at: Some(timestamp),
+            created_at: timestamp,
+            updated_at: timestamp,
+        }])
+    }
+
+    async fn lsp_info(&self, lsp: PublicKey, _token: Option<String>) -> Result<LspInfo> {
+        if lsp != self.public_key {
+            bail!("LSP {lsp} is not connected")
+        }
+        Ok(LspInfo {
+            lsps1_options: Some(Lsps1Options {
+                min_required_channel_confirmations: 0,
+                min_funding_confirms_within_blocks: 6,
+                supports_zero_channel_reserve: false,
+                max_channel_expiry_blocks: 13140,
+                min_initial_client_balance_sat: 0,
+                max_initial_client_balance_sat: 0,
+                min_initial_lsp_balance_sat: 0,
+                max_initial_lsp_balance_sat: 16_777_215,
+                min_channel_balance_sat: 100_000,
+                max_channel_balance_sat: 16_777_215,
+            }),
+            lsps2_opening_fees: Some(vec![OpeningFeeParams {
+                min_fee_msat: 2000,
+                proportional: 1000,
+                valid_until: chrono::DateTime::from_timestamp(1_700_000_600, 0)
+                    .context("invalid timestamp")?,
+                min_lifetime: 4320,
+                max_client_to_self_delay: 3600,
+                min_payment_size_msat: 10_000,
+                max_payment_size_msat: 5_000_000,
+                promise: "promise".to_string(),
+            }]),
+        })
+    }
+
+    async fn buy_channel(&self, _lsp: PublicKey, order: ChannelOrder) -> Result<LspOrder> {
+        Ok(LspOrder {
+            order_id: "d4e5f6".to_string(),
+            order_state: "CREATED".to_string(),
+            payment_state: "EXPECT_PAYMENT".to_string(),
+            lsp_balance_sat: order.lsp_balance_sat,
+            client_balance_sat: order.client_balance_sat,
+            fee_total_sat: 12_000,
+            order_total_sat: 12_000 + order.client_balance_sat,
+            bolt11_invoice: self.invoice.bolt11.to_string(),
+            onchain_address: TEST_ADDRESS.to_string(),
+            expires_at: "2023-11-14T22:13:20.000Z".to_string(),
+        })
+    }
+
+    async fn generate_jit_invoice(
+        &self,
+        _lsp: PublicKey,
+        _token: Option<String>,
+        _label: String,
+        _amount: Option<u64>,
+        _description: String,
+        _expiry: Option<u32>,
+    ) -> Result<Invoice> {
+        Ok(self.invoice.clone())
+    }
+
     async fn scorer(&self) -> Result<Vec<u8>> {
         Ok(Vec::new())
     }
 
+    async fn static_channel_backup(&self) -> Result<Vec<u8>> {
+        Ok(vec![1, 2, 3])
+    }
+
     async fn update_channels(&self, _channels: &[ChannelDetails]) {}
 }
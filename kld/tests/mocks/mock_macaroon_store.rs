@@ -0,0 +1,24 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use kld::database::macaroon::{MacaroonKeyStore, MacaroonRootKey};
+
+#[derive(Default)]
+pub struct MockMacaroonKeyStore {
+    root_keys: Mutex<Vec<MacaroonRootKey>>,
+}
+
+#[async_trait]
+impl MacaroonKeyStore for MockMacaroonKeyStore {
+    async fn fetch_macaroon_root_keys(&self) -> Result<Vec<MacaroonRootKey>> {
+        Ok(self.root_keys.lock().unwrap().clone())
+    }
+
+    async fn persist_macaroon_root_key(&self, root_key: &MacaroonRootKey) -> Result<()> {
+        let mut root_keys = self.root_keys.lock().unwrap();
+        root_keys.retain(|key| key.id != root_key.id);
+        root_keys.push(root_key.clone());
+        Ok(())
+    }
+}
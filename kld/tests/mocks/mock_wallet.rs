@@ -55,6 +55,25 @@ impl WalletInterface for MockWallet {
         })
     }
 
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>> {
+        Ok(vec![TransactionDetails {
+            transaction: None,
+            txid: self.transaction.txid(),
+            received: 10000,
+            sent: 1200,
+            fee: Some(20),
+            confirmation_time: BlockTime::new(Some(600000), Some(23293219)),
+        }])
+    }
+
+    fn list_used_addresses(&self) -> Result<Vec<AddressInfo>> {
+        Ok(vec![AddressInfo {
+            address: Address::from_str(TEST_ADDRESS)?.assume_checked(),
+            index: 0,
+            keychain: KeychainKind::External,
+        }])
+    }
+
     fn list_utxos(&self) -> Result<Vec<(LocalUtxo, TransactionDetails)>> {
         let details = TransactionDetails {
             transaction: Some(self.transaction.clone()),
@@ -1,3 +1,4 @@
 pub mod mock_bitcoind;
 pub mod mock_lightning;
+pub mod mock_macaroon_store;
 pub mod mock_wallet;
@@ -5,8 +5,9 @@ use anyhow::{Context, Result};
 use bitcoin::Address;
 use hyper::Method;
 use kld::api::payloads::{
-    FundChannel, FundChannelResponse, GenerateInvoice, GenerateInvoiceResponse, GetInfo, Invoice,
-    KeysendRequest, PayInvoice, PaymentResponse, WalletBalance,
+    CancelInvoice, FundChannel, FundChannelResponse, GenerateInvoice, GenerateInvoiceResponse,
+    GetInfo, Invoice, KeysendRequest, PayInvoice, PaymentResponse, PaymentStatusResponse,
+    WalletBalance,
 };
 use kld::api::routes;
 use kld::{
@@ -72,6 +73,13 @@ pub async fn test_start() -> Result<()> {
     bitcoin
         .generate_blocks(1, &bitcoin::Address::from_str(&address.address)?, false)
         .await?;
+    // kld_1 needs confirmed funds to accept an anchor channel.
+    let address_1: GetV1NewaddrResponse = kld_1
+        .call_rest_api(Method::GET, routes::NEW_ADDR, ())
+        .await?;
+    bitcoin
+        .generate_blocks(1, &bitcoin::Address::from_str(&address_1.address)?, false)
+        .await?;
     bitcoin
         .generate_blocks(
             100, // Coinbase not spendable for 100 blocks.
@@ -177,6 +185,14 @@ pub async fn test_start() -> Result<()> {
         keysend_response.status,
         PaymentStatus::Succeeded.to_string()
     );
+    let payment_status: PaymentStatusResponse = kld_0
+        .call_rest_api(
+            Method::GET,
+            &routes::PAYMENT_STATUS.replace(":id", &keysend_response.payment_id),
+            (),
+        )
+        .await?;
+    assert_eq!(payment_status.status, PaymentStatus::Succeeded.to_string());
 
     let generate_invoice = GenerateInvoice {
         amount: invoice_amount_msat,
@@ -190,6 +206,7 @@ pub async fn test_start() -> Result<()> {
     let pay_invoice = PayInvoice {
         label: Some("payment".to_string()),
         invoice: invoice.bolt11,
+        ..Default::default()
     };
     let payment: PaymentResponse = kld_0
         .call_rest_api(Method::POST, routes::PAY_INVOICE, pay_invoice)
@@ -201,6 +218,35 @@ pub async fn test_start() -> Result<()> {
         .await?;
     assert_eq!(1, invoices.len());
 
+    // A canceled invoice must not be paid even though the node still knows its preimage.
+    let generate_invoice = GenerateInvoice {
+        amount: invoice_amount_msat,
+        label: "canceled".to_string(),
+        description: "description".to_string(),
+        ..Default::default()
+    };
+    let invoice: GenerateInvoiceResponse = kld_1
+        .call_rest_api(Method::POST, routes::GENERATE_INVOICE, generate_invoice)
+        .await?;
+    kld_1
+        .call_rest_api::<(), CancelInvoice>(
+            Method::POST,
+            routes::CANCEL_INVOICE,
+            CancelInvoice {
+                payment_hash: invoice.payment_hash,
+            },
+        )
+        .await?;
+    let pay_invoice = PayInvoice {
+        label: Some("canceled payment".to_string()),
+        invoice: invoice.bolt11,
+        ..Default::default()
+    };
+    let payment: PaymentResponse = kld_0
+        .call_rest_api(Method::POST, routes::PAY_INVOICE, pay_invoice)
+        .await?;
+    assert_eq!(payment.status, PaymentStatus::RecipientRejected.to_string());
+
     kld_0
         .call_rest_api(
             Method::DELETE,
@@ -238,7 +238,7 @@ impl App {
                                 });
                                 action_tx.send(Action::ExitCmdMode)?;
                             }
-                            Cmd::PeerCont | Cmd::ChanOpen => {
+                            Cmd::PeerCont | Cmd::ChanOpen | Cmd::ChanRbal => {
                                 thread::spawn(move || {
                                     log::trace!("query for {trigger_time:}");
                                     let output = query::post(auth, uri, input);
@@ -256,6 +256,42 @@ impl App {
                                 });
                                 action_tx.send(Action::ExitCmdMode)?;
                             }
+                            Cmd::NetwGrte | Cmd::ChanFeeh => {
+                                thread::spawn(move || {
+                                    log::trace!("query for {trigger_time:}");
+                                    let output = query::get(auth, &input);
+                                    match pool.get() {
+                                        Ok(conn) => {
+                                            if let Err(e) = conn.execute("UPDATE history SET output = ? WHERE timestamp == ?;", [&output, &trigger_time.to_string()]) {
+                                                log::error!("Fail to update query result for {trigger_time:}: {}", e);
+                                            }
+                                        }
+                                        Err(e) => log::error!(
+                                            "Fail to get db connection for {trigger_time:}: {}",
+                                            e
+                                        ),
+                                    }
+                                });
+                                action_tx.send(Action::ExitCmdMode)?;
+                            }
+                            Cmd::ChanClos => {
+                                thread::spawn(move || {
+                                    log::trace!("query for {trigger_time:}");
+                                    let output = query::delete(auth, input);
+                                    match pool.get() {
+                                        Ok(conn) => {
+                                            if let Err(e) = conn.execute("UPDATE history SET output = ? WHERE timestamp == ?;", [&output, &trigger_time.to_string()]) {
+                                                log::error!("Fail to update query result for {trigger_time:}: {}", e);
+                                            }
+                                        }
+                                        Err(e) => log::error!(
+                                            "Fail to get db connection for {trigger_time:}: {}",
+                                            e
+                                        ),
+                                    }
+                                });
+                                action_tx.send(Action::ExitCmdMode)?;
+                            }
                             _ => {}
                         }
                     }
@@ -1,5 +1,6 @@
 use color_eyre::eyre::Result;
-use kld::api::payloads::FundChannel;
+use kld::api::payloads::{FundChannel, RebalanceChannel};
+use kld::api::routes;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use ratatui::{prelude::*, widgets::*};
@@ -7,13 +8,33 @@ use serde_json::json;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::action::Action;
-use crate::components::command::parsers::parse_channel_details;
+use crate::components::command::parsers::{
+    parse_channel_details, parse_fee_history, parse_route, FEE_HISTORY_COLUMNS,
+};
 use crate::components::command::Cmd;
 use crate::components::{Component, Frame};
 use crate::keybinding::{KeyBindingHelps, KeyBindings};
 use crate::mode::Mode;
 use crate::utils::{ts_to_string, WORD_BINDINGS};
 
+/// The inputs of the route query, with their titles and query keys
+const ROUTE_INPUTS: [(&str, &str); 6] = [
+    ("Destination", "destination"),
+    ("Amount (msats)", "amountMsat"),
+    ("Max Fee (msats)", "maxFeeMsat"),
+    ("CLTV Expiry", "cltv"),
+    ("Exclude Channels", "excludeChannels"),
+    ("Exclude Nodes", "excludeNodes"),
+];
+
+/// The titles of the rebalance inputs
+const REBALANCE_INPUTS: [&str; 4] = [
+    "Outbound Channel",
+    "Inbound Channel",
+    "Amount (msats)",
+    "Max Fee (ppm)",
+];
+
 pub struct CmdDetails {
     command_tx: Option<UnboundedSender<Action>>,
     display: bool,
@@ -132,6 +153,18 @@ impl Component for CmdDetails {
                     Cmd::ChanOpen => {
                         self.inputs = vec![String::new(), String::new()];
                     }
+                    Cmd::ChanClos => {
+                        self.inputs = vec![String::new(), String::new(), String::new()];
+                    }
+                    Cmd::NetwGrte => {
+                        self.inputs = vec![String::new(); ROUTE_INPUTS.len()];
+                    }
+                    Cmd::ChanRbal => {
+                        self.inputs = vec![String::new(); REBALANCE_INPUTS.len()];
+                    }
+                    Cmd::ChanFeeh => {
+                        self.inputs = vec![String::new()];
+                    }
                     Cmd::ChanList => {
                         self.index = 0;
                         self.length = 0;
@@ -166,6 +199,7 @@ impl Component for CmdDetails {
                             close_to: None,
                             request_amt: None,
                             compact_lease: None,
+                            batch: Vec::new(),
                         };
                         match serde_json::to_string(&fund_channel) {
                             Ok(payload) => Some(Action::Execute(Cmd::ChanOpen, payload)),
@@ -175,6 +209,52 @@ impl Component for CmdDetails {
                             }
                         }
                     }
+                    Cmd::ChanClos => {
+                        let mut query = url::form_urlencoded::Serializer::new(String::new());
+                        if !self.inputs[1].is_empty() {
+                            query.append_pair("address", &self.inputs[1]);
+                        }
+                        if !self.inputs[2].is_empty() {
+                            query.append_pair("max_fee_rate", &self.inputs[2]);
+                        }
+                        Some(Action::Execute(
+                            Cmd::ChanClos,
+                            format!(
+                                "{}?{}",
+                                routes::CLOSE_CHANNEL.replace(":id", &self.inputs[0]),
+                                query.finish()
+                            ),
+                        ))
+                    }
+                    Cmd::ChanRbal => match self.rebalance_payload() {
+                        Ok(payload) => Some(Action::Execute(Cmd::ChanRbal, payload)),
+                        Err(e) => {
+                            self.error_msg = Some(format!("{e}"));
+                            None
+                        }
+                    },
+                    Cmd::ChanFeeh => {
+                        let mut query = url::form_urlencoded::Serializer::new(String::new());
+                        if !self.inputs[0].is_empty() {
+                            query.append_pair("channel_id", &self.inputs[0]);
+                        }
+                        Some(Action::Execute(
+                            Cmd::ChanFeeh,
+                            format!("{}?{}", routes::CHANNEL_FEE_HISTORY, query.finish()),
+                        ))
+                    }
+                    Cmd::NetwGrte => {
+                        let mut query = url::form_urlencoded::Serializer::new(String::new());
+                        for ((_, key), value) in ROUTE_INPUTS.iter().zip(self.inputs.iter()) {
+                            if !value.is_empty() {
+                                query.append_pair(key, value);
+                            }
+                        }
+                        Some(Action::Execute(
+                            Cmd::NetwGrte,
+                            format!("{}?{}", routes::GET_ROUTE, query.finish()),
+                        ))
+                    }
                     _ => None,
                 };
                 self.inputs = Vec::new();
@@ -184,8 +264,11 @@ impl Component for CmdDetails {
                 if let Some(on_focus) = self.on_focus {
                     let mut new_focus = on_focus + 1;
                     match self.selected_command {
-                        Cmd::PeerCont => new_focus = 0,
+                        Cmd::PeerCont | Cmd::ChanFeeh => new_focus = 0,
                         Cmd::ChanOpen if new_focus > 1 => new_focus = 0,
+                        Cmd::ChanClos if new_focus > 2 => new_focus = 0,
+                        Cmd::NetwGrte if new_focus >= ROUTE_INPUTS.len() => new_focus = 0,
+                        Cmd::ChanRbal if new_focus >= REBALANCE_INPUTS.len() => new_focus = 0,
                         _ => {}
                     }
                     self.on_focus = Some(new_focus);
@@ -224,7 +307,11 @@ impl Component for CmdDetails {
                 Cmd::NodeInfo => self.node_info(f, size),
                 Cmd::ChanOpen => self.channel_open(f, size),
                 Cmd::ChanList => self.channel_list(f, size),
+                Cmd::ChanClos => self.channel_close(f, size),
+                Cmd::ChanRbal => self.channel_rebalance(f, size),
+                Cmd::ChanFeeh => self.channel_fee_history(f, size),
                 Cmd::PeerCont => self.peer_connect(f, size),
+                Cmd::NetwGrte => self.network_route(f, size),
                 _ => {
                     let text = Text::from(Line::from(
                         WORD_BINDINGS
@@ -471,6 +558,160 @@ impl CmdDetails {
         );
         f.render_widget(amt_input, chunks[2]);
     }
+    fn channel_close(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(area);
+        self.draw_intro(f, chunks[0]);
+        if let Some(ref err_msg) = self.error_msg {
+            self.show_error_msg(f, chunks[4], err_msg.to_string());
+        } else {
+            self.show_last_result(f, chunks[4], Cmd::ChanClos);
+        }
+
+        for (i, title) in ["Channel ID", "Address", "Max Fee Rate"].iter().enumerate() {
+            let input = Paragraph::new(
+                self.inputs
+                    .get(i)
+                    .map(|s| s.to_string())
+                    .unwrap_or_default(),
+            )
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(if self.on_focus == Some(i) {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    })
+                    .title(WORD_BINDINGS.get(title)),
+            );
+            f.render_widget(input, chunks[i + 1]);
+        }
+    }
+    fn rebalance_payload(&self) -> Result<String> {
+        let rebalance = RebalanceChannel {
+            outbound_channel_id: self.inputs[0].clone(),
+            inbound_channel_id: self.inputs[1].clone(),
+            amount: self.inputs[2].parse()?,
+            max_fee_ppm: if self.inputs[3].is_empty() {
+                None
+            } else {
+                Some(self.inputs[3].parse()?)
+            },
+        };
+        Ok(serde_json::to_string(&rebalance)?)
+    }
+    fn channel_rebalance(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let mut constraints = vec![Constraint::Length(3); REBALANCE_INPUTS.len() + 1];
+        constraints.push(Constraint::Min(0));
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+        self.draw_intro(f, chunks[0]);
+        let result_area = chunks[REBALANCE_INPUTS.len() + 1];
+        if let Some(ref err_msg) = self.error_msg {
+            self.show_error_msg(f, result_area, err_msg.to_string());
+        } else {
+            self.show_last_result(f, result_area, Cmd::ChanRbal);
+        }
+
+        for (i, title) in REBALANCE_INPUTS.iter().enumerate() {
+            let input = Paragraph::new(
+                self.inputs
+                    .get(i)
+                    .map(|s| s.to_string())
+                    .unwrap_or_default(),
+            )
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(if self.on_focus == Some(i) {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    })
+                    .title(WORD_BINDINGS.get(title)),
+            );
+            f.render_widget(input, chunks[i + 1]);
+        }
+    }
+    fn channel_fee_history(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(area);
+        self.draw_intro(f, chunks[0]);
+
+        let input = Paragraph::new(
+            self.inputs
+                .first()
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(if self.on_focus.is_some() {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                })
+                .title(WORD_BINDINGS.get("Channel ID")),
+        );
+        f.render_widget(input, chunks[1]);
+
+        if let Some(ref err_msg) = self.error_msg {
+            self.show_error_msg(f, chunks[2], err_msg.to_string());
+            return;
+        }
+        let (last_query_time, info) = self.last_result(Cmd::ChanFeeh);
+        // The last result starts with the query, the response follows on the next line.
+        let rows = info
+            .split_once('\n')
+            .and_then(|(_, response)| parse_fee_history(response).ok());
+        match (last_query_time, rows) {
+            (Some(last_query_time), Some(rows)) => {
+                let widths = [
+                    Constraint::Length(24),
+                    Constraint::Min(16),
+                    Constraint::Length(14),
+                    Constraint::Length(18),
+                    Constraint::Length(16),
+                    Constraint::Length(18),
+                ];
+                let header = Row::new(FEE_HISTORY_COLUMNS.map(|title| WORD_BINDINGS.get(title)))
+                    .style(Style::default().bold());
+                let table = Table::new(rows, widths).header(header).block(
+                    Block::default()
+                        .title(
+                            block::title::Title::from(format!(
+                                "{}{}",
+                                WORD_BINDINGS.get("Query at "),
+                                ts_to_string(last_query_time)
+                            ))
+                            .position(block::title::Position::Top)
+                            .alignment(Alignment::Right),
+                        )
+                        .borders(Borders::ALL),
+                );
+                f.render_widget(table, chunks[2]);
+            }
+            _ => self.show_last_result(f, chunks[2], Cmd::ChanFeeh),
+        }
+    }
     fn channel_list(&mut self, f: &mut Frame<'_>, area: Rect) {
         let (last_query_time, info) = self.last_result(Cmd::ChanList);
         if let Some(last_query_time) = last_query_time {
@@ -525,4 +766,64 @@ impl CmdDetails {
             );
         }
     }
+    fn network_route(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let mut constraints = vec![Constraint::Length(3); ROUTE_INPUTS.len() + 1];
+        constraints.push(Constraint::Min(0));
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+        self.draw_intro(f, chunks[0]);
+
+        for (i, (title, _)) in ROUTE_INPUTS.iter().enumerate() {
+            let input = Paragraph::new(
+                self.inputs
+                    .get(i)
+                    .map(|s| s.to_string())
+                    .unwrap_or_default(),
+            )
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(if self.on_focus == Some(i) {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    })
+                    .title(WORD_BINDINGS.get(title)),
+            );
+            f.render_widget(input, chunks[i + 1]);
+        }
+
+        let result_area = chunks[ROUTE_INPUTS.len() + 1];
+        if let Some(ref err_msg) = self.error_msg {
+            self.show_error_msg(f, result_area, err_msg.to_string());
+            return;
+        }
+        let (last_query_time, info) = self.last_result(Cmd::NetwGrte);
+        // The last result starts with the query, the response follows on the next line.
+        let rows = info
+            .split_once('\n')
+            .and_then(|(_, response)| parse_route(response).ok());
+        match (last_query_time, rows) {
+            (Some(last_query_time), Some(rows)) => {
+                let widths = [Constraint::Length(30), Constraint::Max(f.size().width - 30)];
+                let table = Table::new(rows, widths).block(
+                    Block::default()
+                        .title(
+                            block::title::Title::from(format!(
+                                "{}{}",
+                                WORD_BINDINGS.get("Query at "),
+                                ts_to_string(last_query_time)
+                            ))
+                            .position(block::title::Position::Top)
+                            .alignment(Alignment::Right),
+                        )
+                        .borders(Borders::ALL),
+                );
+                f.render_widget(table, result_area);
+            }
+            _ => self.show_last_result(f, result_area, Cmd::NetwGrte),
+        }
+    }
 }
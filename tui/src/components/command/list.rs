@@ -33,6 +33,7 @@ impl Default for CmdList {
                 (WORD_BINDINGS.get("Network"), None),
                 (WORD_BINDINGS.get("list nodes"), Some(Cmd::NetwLsnd)),
                 (WORD_BINDINGS.get("fee rates"), Some(Cmd::NetwFeer)),
+                (WORD_BINDINGS.get("get route"), Some(Cmd::NetwGrte)),
                 (WORD_BINDINGS.get("Peers"), None),
                 (WORD_BINDINGS.get("list"), Some(Cmd::PeerList)),
                 (WORD_BINDINGS.get("connect"), Some(Cmd::PeerCont)),
@@ -49,8 +50,10 @@ impl Default for CmdList {
                 (WORD_BINDINGS.get("list"), Some(Cmd::ChanList)),
                 (WORD_BINDINGS.get("open"), Some(Cmd::ChanOpen)),
                 (WORD_BINDINGS.get("set fee"), Some(Cmd::ChanSetf)),
+                (WORD_BINDINGS.get("rebalance"), Some(Cmd::ChanRbal)),
                 (WORD_BINDINGS.get("close"), Some(Cmd::ChanClos)),
                 (WORD_BINDINGS.get("history"), Some(Cmd::ChanHist)),
+                (WORD_BINDINGS.get("fee history"), Some(Cmd::ChanFeeh)),
                 (WORD_BINDINGS.get("balance"), Some(Cmd::ChanBala)),
                 (WORD_BINDINGS.get("list forwards"), Some(Cmd::ChanLsfd)),
             ],
@@ -10,15 +10,18 @@ pub enum Cmd {
     AppInfo,
     ChanBala,
     ChanClos,
+    ChanFeeh,
     ChanHist,
     ChanList,
     ChanLsfd,
     ChanOpen,
+    ChanRbal,
     ChanSetf,
     InvoDeco,
     InvoGene,
     InvoList,
     NetwFeer,
+    NetwGrte,
     NetwLsnd,
     NodeEslq,
     NodeFees,
@@ -38,15 +41,18 @@ impl Cmd {
         match self {
             Cmd::ChanBala => Some(routes::LOCAL_REMOTE_BALANCE),
             Cmd::ChanClos => Some(routes::CLOSE_CHANNEL),
+            Cmd::ChanFeeh => Some(routes::CHANNEL_FEE_HISTORY),
             Cmd::ChanHist => Some(routes::LIST_CHANNEL_HISTORY),
             Cmd::ChanList => Some(routes::LIST_CHANNELS),
             Cmd::ChanLsfd => Some(routes::LIST_FORWARDS),
             Cmd::ChanOpen => Some(routes::OPEN_CHANNEL),
+            Cmd::ChanRbal => Some(routes::REBALANCE_CHANNEL),
             Cmd::ChanSetf => Some(routes::SET_CHANNEL_FEE),
             Cmd::InvoDeco => Some(routes::DECODE_INVOICE),
             Cmd::InvoGene => Some(routes::GENERATE_INVOICE),
             Cmd::InvoList => Some(routes::LIST_INVOICES),
             Cmd::NetwFeer => Some(routes::FEE_RATES),
+            Cmd::NetwGrte => Some(routes::GET_ROUTE),
             Cmd::NetwLsnd => Some(routes::LIST_NETWORK_NODES),
             Cmd::NodeEslq => Some(routes::ESTIMATE_CHANNEL_LIQUIDITY),
             Cmd::NodeFees => Some(routes::GET_FEES),
@@ -1,9 +1,110 @@
 use color_eyre::eyre::Result;
 use kld::api::codegen::get_kld_channel_response::GetKldChannelResponseItem;
+use kld::api::payloads::{FeeUpdate, GetRouteResponse};
 use ratatui::{prelude::*, widgets::*};
 
 use crate::utils::{ts_to_string, WORD_BINDINGS};
 
+fn probability_to_string(probability: Option<f64>) -> String {
+    probability
+        .map(|p| format!("{:.1}%", p * 100.0))
+        .unwrap_or(WORD_BINDINGS.get("unknown").into())
+}
+
+/// The column titles of the fee history table
+pub const FEE_HISTORY_COLUMNS: [&str; 6] = [
+    "Time",
+    "Channel ID",
+    "Fee (ppm)",
+    "Base Fee (msats)",
+    "Outbound Ratio",
+    "Forwarded (msats)",
+];
+
+pub fn parse_fee_history<'a>(input: impl std::convert::AsRef<str>) -> Result<Vec<Row<'a>>> {
+    let updates: Vec<FeeUpdate> = serde_json::from_str(input.as_ref())?;
+    Ok(updates
+        .into_iter()
+        .map(|update| {
+            Row::new(vec![
+                ts_to_string(update.timestamp),
+                update.channel_id,
+                format!("{} -> {}", update.previous_ppm, update.ppm),
+                update.base_msat.to_string(),
+                format!("{:.1}%", update.outbound_ratio * 100.0),
+                update.forwarded_msat.to_string(),
+            ])
+        })
+        .collect())
+}
+
+pub fn parse_route<'a>(input: impl std::convert::AsRef<str>) -> Result<Vec<Row<'a>>> {
+    let route: GetRouteResponse = serde_json::from_str(input.as_ref())?;
+
+    let mut output = vec![
+        Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Amount"))).style(Style::default().bold()),
+            Cell::from(Text::from(format!("{} msats", route.amount_msat))),
+        ]),
+        Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Fee"))).style(Style::default().bold()),
+            Cell::from(Text::from(format!("{} msats", route.fee_msat))),
+        ]),
+        Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Probability"))).style(Style::default().bold()),
+            Cell::from(Text::from(probability_to_string(route.probability))),
+        ]),
+    ];
+    for (i, path) in route.paths.into_iter().enumerate() {
+        output.push(Row::new(vec![""]));
+        output.push(Row::new(vec![Cell::from(Text::from(format!(
+            "{} {}",
+            WORD_BINDINGS.get("Path"),
+            i + 1
+        )))
+        .style(Style::default().bold())]));
+        output.push(Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Amount"))).style(Style::default().bold()),
+            Cell::from(Text::from(format!("{} msats", path.amount_msat))),
+        ]));
+        output.push(Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Fee"))).style(Style::default().bold()),
+            Cell::from(Text::from(format!("{} msats", path.fee_msat))),
+        ]));
+        output.push(Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("CLTV Expiry"))).style(Style::default().bold()),
+            Cell::from(Text::from(format!(
+                "{} blocks",
+                path.total_cltv_expiry_delta
+            ))),
+        ]));
+        output.push(Row::new(vec![
+            Cell::from(Text::from(WORD_BINDINGS.get("Probability"))).style(Style::default().bold()),
+            Cell::from(Text::from(probability_to_string(path.probability))),
+        ]));
+        for (n, hop) in path.hops.into_iter().enumerate() {
+            output.push(Row::new(vec![
+                Cell::from(Text::from(format!(
+                    "{} {}",
+                    WORD_BINDINGS.get("Hop"),
+                    n + 1
+                )))
+                .style(Style::default().bold()),
+                Cell::from(Text::from(format!(
+                    "{} -> {}: {} msats, {} msats fee, {} blocks, {}",
+                    hop.short_channel_id,
+                    hop.alias.unwrap_or(hop.node_id),
+                    hop.amount_msat,
+                    hop.fee_msat,
+                    hop.cltv_expiry_delta,
+                    probability_to_string(hop.probability)
+                ))),
+            ]));
+        }
+    }
+    Ok(output)
+}
+
 pub fn parse_channel_details<'a>(
     input: impl std::convert::AsRef<str>,
 ) -> Result<Vec<Vec<Row<'a>>>> {
@@ -1,6 +1,6 @@
 use crate::ConnectionAuth;
 
-pub fn get(auth: ConnectionAuth, uri: &'static str) -> String {
+pub fn get(auth: ConnectionAuth, uri: &str) -> String {
     let client = reqwest::blocking::ClientBuilder::new()
         .add_root_certificate(reqwest::Certificate::from_pem(&auth.pem).unwrap())
         .build()
@@ -49,3 +49,32 @@ pub fn post(auth: ConnectionAuth, uri: &'static str, payload: String) -> String
         Err(request_error) => request_error.to_string(),
     }
 }
+
+pub fn delete(auth: ConnectionAuth, path: String) -> String {
+    let client = reqwest::blocking::ClientBuilder::new()
+        .add_root_certificate(reqwest::Certificate::from_pem(&auth.pem).unwrap())
+        .build()
+        .unwrap();
+    let request = client
+        .delete(
+            auth.url
+                .join(&path)
+                .expect("delete should be correct")
+                .as_str(),
+        )
+        .header("Macaroon", auth.macaroon)
+        .send();
+
+    match request {
+        Ok(response) => {
+            let status = response.status();
+            let data = response.text().unwrap();
+            if status.is_success() {
+                data.to_string()
+            } else {
+                format!("{}{}", status, data)
+            }
+        }
+        Err(request_error) => request_error.to_string(),
+    }
+}
@@ -0,0 +1,30 @@
+use crate::components::command::parsers::parse_fee_history;
+
+#[test]
+fn test_parse_fee_history() {
+    let response = r#"[
+  {
+    "id": "6c6bd4a4-9d5e-4c8e-9a44-3c0a0e5c5c1e",
+    "channelId": "0101010101010101010101010101010101010101010101010101010101010101",
+    "previousPpm": 100,
+    "ppm": 250,
+    "baseMsat": 1000,
+    "outboundRatio": 0.25,
+    "forwardedMsat": 500000,
+    "timestamp": 1700000000
+  },
+  {
+    "id": "0c1f3a4e-2b6d-4f7e-8a9b-1c2d3e4f5a6b",
+    "channelId": "0202020202020202020202020202020202020202020202020202020202020202",
+    "previousPpm": 250,
+    "ppm": 200,
+    "baseMsat": 1000,
+    "outboundRatio": 0.5,
+    "forwardedMsat": 0,
+    "timestamp": 1700003600
+  }
+]"#;
+    let rows = parse_fee_history(response).expect("parse fee history should work");
+    assert_eq!(rows.len(), 2);
+    assert!(parse_fee_history("{}").is_err());
+}
@@ -1 +1,3 @@
 mod channel_details;
+mod fee_history;
+mod route;
@@ -0,0 +1,39 @@
+use crate::components::command::parsers::parse_route;
+
+#[test]
+fn test_parse_route() {
+    let response = r#"{
+  "amountMsat": 100000,
+  "feeMsat": 1000,
+  "probability": 0.5,
+  "paths": [
+    {
+      "amountMsat": 100000,
+      "feeMsat": 1000,
+      "totalCltvExpiryDelta": 184,
+      "probability": 0.5,
+      "hops": [
+        {
+          "nodeId": "03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad",
+          "alias": "test node",
+          "shortChannelId": 72623859790382856,
+          "amountMsat": 101000,
+          "feeMsat": 1000,
+          "cltvExpiryDelta": 40,
+          "probability": 1.0
+        },
+        {
+          "nodeId": "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+          "shortChannelId": 72623859790382857,
+          "amountMsat": 100000,
+          "feeMsat": 0,
+          "cltvExpiryDelta": 144
+        }
+      ]
+    }
+  ]
+}"#;
+    let rows = parse_route(response).expect("parse route should work");
+    // Route summary, then a blank line, the path summary and one row per hop.
+    assert_eq!(rows.len(), 3 + 6 + 2);
+}